@@ -0,0 +1,154 @@
+//! Civil calendar helpers for IC timestamps
+//!
+//! Timestamps are nanoseconds since the Unix epoch (UTC). Days are counted
+//! from 1970-01-01, and weekdays are numbered from Monday = 0.
+
+use crate::types::Timestamp;
+
+pub const NANOS_PER_SECOND: u64 = 1_000_000_000;
+pub const NANOS_PER_DAY: u64 = 86_400 * NANOS_PER_SECOND;
+
+/// Day number (days since 1970-01-01) of a timestamp
+pub fn day_of(timestamp: Timestamp) -> i64 {
+    (timestamp / NANOS_PER_DAY) as i64
+}
+
+/// Day number of a civil date (proleptic Gregorian calendar)
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400;
+    let m = month as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Civil date (year, month, day) of a day number
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = if z >= 0 { z } else { z - 146_096 } / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Weekday of a day number (Monday = 0 ... Sunday = 6)
+pub fn weekday_of_day(days: i64) -> u32 {
+    // 1970-01-01 was a Thursday
+    (days + 3).rem_euclid(7) as u32
+}
+
+/// Number of days in a month
+pub fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Day number of a `YYYY-MM-DD` date, or None if it is not a valid date
+pub fn parse_ymd(value: &str) -> Option<i64> {
+    let parts: Vec<&str> = value.split('-').collect();
+    let [year, month, day] = parts[..] else {
+        return None;
+    };
+    if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return None;
+    }
+    let year: i64 = year.parse().ok()?;
+    let month: u32 = month.parse().ok()?;
+    let day: u32 = day.parse().ok()?;
+    if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+        return None;
+    }
+    Some(days_from_civil(year, month, day))
+}
+
+/// Human-readable UTC date and time, e.g. "2024-01-31 09:30 UTC"
+pub fn format_utc(timestamp: Timestamp) -> String {
+    let (year, month, day) = civil_from_days(day_of(timestamp));
+    let seconds = (timestamp % NANOS_PER_DAY) / NANOS_PER_SECOND;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60
+    )
+}
+
+/// iCalendar UTC date-time, e.g. "20240131T093000Z"
+pub fn format_ics(timestamp: Timestamp) -> String {
+    let (year, month, day) = civil_from_days(day_of(timestamp));
+    let seconds = (timestamp % NANOS_PER_DAY) / NANOS_PER_SECOND;
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_epoch_round_trip() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+    }
+
+    #[test]
+    fn test_civil_round_trip_across_leap_years() {
+        for days in [-1, 59, 365, 11_016, 18_321, 19_782, 20_000, 40_000] {
+            let (y, m, d) = civil_from_days(days);
+            assert_eq!(days_from_civil(y, m, d), days);
+        }
+        assert_eq!(civil_from_days(days_from_civil(2024, 2, 29)), (2024, 2, 29));
+    }
+
+    #[test]
+    fn test_weekday() {
+        // 1970-01-01 was a Thursday, 2024-01-01 a Monday
+        assert_eq!(weekday_of_day(0), 3);
+        assert_eq!(weekday_of_day(days_from_civil(2024, 1, 1)), 0);
+    }
+
+    #[test]
+    fn test_format_utc() {
+        let ts = days_from_civil(2024, 1, 31) as u64 * NANOS_PER_DAY + (9 * 3600 + 30 * 60) * NANOS_PER_SECOND;
+        assert_eq!(format_utc(ts), "2024-01-31 09:30 UTC");
+        assert_eq!(format_ics(ts), "20240131T093000Z");
+    }
+
+    #[test]
+    fn test_days_in_month() {
+        assert_eq!(days_in_month(2024, 2), 29);
+        assert_eq!(days_in_month(2023, 2), 28);
+        assert_eq!(days_in_month(1900, 2), 28);
+        assert_eq!(days_in_month(2000, 2), 29);
+        assert_eq!(days_in_month(2024, 4), 30);
+    }
+
+    #[test]
+    fn test_parse_ymd() {
+        assert_eq!(parse_ymd("2024-02-29"), Some(days_from_civil(2024, 2, 29)));
+        assert_eq!(parse_ymd("2023-02-29"), None);
+        assert_eq!(parse_ymd("2024-2-01"), None);
+        assert_eq!(parse_ymd("24-02-01"), None);
+        assert_eq!(parse_ymd("today"), None);
+    }
+}
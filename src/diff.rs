@@ -0,0 +1,244 @@
+//! Line-based diffs and delta encoding for document versions
+//!
+//! Texts are split into lines that keep their `\n`, so joining the lines
+//! reproduces the text exactly (including a missing final newline). The edit
+//! script comes from Myers' O(ND) algorithm after trimming the common prefix
+//! and suffix; edits larger than `MAX_EDIT_DISTANCE` fall back to replacing
+//! the whole changed region, which is still correct but not minimal.
+
+use crate::types::DeltaOp;
+
+/// Largest edit distance searched for a minimal diff
+const MAX_EDIT_DISTANCE: usize = 2000;
+
+/// One line of a diff
+#[derive(Clone, Debug, PartialEq)]
+pub enum LineChange<'a> {
+    Unchanged(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Lines of a text, each keeping its trailing newline
+pub fn split_lines(text: &str) -> Vec<&str> {
+    text.split_inclusive('\n').collect()
+}
+
+/// Line diff turning `old` into `new`
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<LineChange<'a>> {
+    let a = split_lines(old);
+    let b = split_lines(new);
+
+    let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (a_mid, b_mid) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    let mut changes: Vec<LineChange> = a[..prefix].iter().map(|l| LineChange::Unchanged(l)).collect();
+    match myers(a_mid, b_mid) {
+        Some(middle) => changes.extend(middle),
+        None => {
+            changes.extend(a_mid.iter().map(|l| LineChange::Removed(l)));
+            changes.extend(b_mid.iter().map(|l| LineChange::Added(l)));
+        }
+    }
+    changes.extend(a[a.len() - suffix..].iter().map(|l| LineChange::Unchanged(l)));
+    changes
+}
+
+/// Minimal edit script between two line slices, or None when the edit
+/// distance exceeds `MAX_EDIT_DISTANCE`
+fn myers<'a>(a: &[&'a str], b: &[&'a str]) -> Option<Vec<LineChange<'a>>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (n + m) as usize;
+    // Diagonals beyond the search limit are never visited
+    let limit = max.min(MAX_EDIT_DISTANCE);
+    let offset = limit as isize + 1;
+    let mut v = vec![0isize; 2 * limit + 3];
+    // trace[d] holds V for diagonals -d..=d before step d
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    'search: for d in 0..=max as isize {
+        if d as usize > MAX_EDIT_DISTANCE {
+            return None;
+        }
+        trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let i = (offset + k) as usize;
+            let mut x = if k == -d || (k != d && v[i - 1] < v[i + 1]) { v[i + 1] } else { v[i - 1] + 1 };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[i] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    let mut changes = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        if d == 0 {
+            while x > 0 {
+                changes.push(LineChange::Unchanged(a[(x - 1) as usize]));
+                x -= 1;
+            }
+            break;
+        }
+        let get = |k: isize| v[(k + d) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && get(k - 1) < get(k + 1)) { k + 1 } else { k - 1 };
+        let prev_x = get(prev_k);
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            changes.push(LineChange::Unchanged(a[(x - 1) as usize]));
+            x -= 1;
+            y -= 1;
+        }
+        if x == prev_x {
+            changes.push(LineChange::Added(b[(y - 1) as usize]));
+        } else {
+            changes.push(LineChange::Removed(a[(x - 1) as usize]));
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+
+    changes.reverse();
+    Some(changes)
+}
+
+/// Delta that rebuilds the old text of a diff from its new text
+pub fn encode_reverse_delta(changes: &[LineChange]) -> Vec<DeltaOp> {
+    delta_from_changes(changes.iter().map(|change| match *change {
+        LineChange::Unchanged(line) => LineChange::Unchanged(line),
+        LineChange::Removed(line) => LineChange::Added(line),
+        LineChange::Added(line) => LineChange::Removed(line),
+    }))
+}
+
+/// Delta applying a line diff to its old text
+fn delta_from_changes<'a>(changes: impl IntoIterator<Item = LineChange<'a>>) -> Vec<DeltaOp> {
+    let mut ops: Vec<DeltaOp> = Vec::new();
+    for change in changes {
+        match (change, ops.last_mut()) {
+            (LineChange::Unchanged(_), Some(DeltaOp::Copy(count))) => *count += 1,
+            (LineChange::Unchanged(_), _) => ops.push(DeltaOp::Copy(1)),
+            (LineChange::Removed(_), Some(DeltaOp::Skip(count))) => *count += 1,
+            (LineChange::Removed(_), _) => ops.push(DeltaOp::Skip(1)),
+            (LineChange::Added(line), Some(DeltaOp::Insert(text))) => text.push_str(line),
+            (LineChange::Added(line), _) => ops.push(DeltaOp::Insert(line.to_string())),
+        }
+    }
+    ops
+}
+
+/// Apply a delta produced by `encode_reverse_delta` to its base text
+pub fn apply_delta(base: &str, delta: &[DeltaOp]) -> Result<String, String> {
+    let lines = split_lines(base);
+    let mut position = 0usize;
+    let mut text = String::with_capacity(base.len());
+
+    for op in delta {
+        match op {
+            DeltaOp::Copy(count) | DeltaOp::Skip(count) => {
+                let end = position + *count as usize;
+                if end > lines.len() {
+                    return Err("Delta does not match its base text".to_string());
+                }
+                if let DeltaOp::Copy(_) = op {
+                    lines[position..end].iter().for_each(|line| text.push_str(line));
+                }
+                position = end;
+            }
+            DeltaOp::Insert(inserted) => text.push_str(inserted),
+        }
+    }
+    if position != lines.len() {
+        return Err("Delta does not match its base text".to_string());
+    }
+
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Delta that rebuilds `target` from `base`
+    fn encode_delta(base: &str, target: &str) -> Vec<DeltaOp> {
+        delta_from_changes(diff_lines(base, target))
+    }
+
+    fn render(changes: &[LineChange]) -> String {
+        changes
+            .iter()
+            .map(|change| match change {
+                LineChange::Unchanged(l) => format!(" {}", l),
+                LineChange::Removed(l) => format!("-{}", l),
+                LineChange::Added(l) => format!("+{}", l),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_lines_is_minimal() {
+        let old = "a\nb\nc\nd\n";
+        let new = "a\nc\nd\ne\n";
+        assert_eq!(render(&diff_lines(old, new)), " a\n-b\n c\n d\n+e\n");
+        assert_eq!(render(&diff_lines("", "x\n")), "+x\n");
+        assert_eq!(render(&diff_lines("x", "x\n")), "-x+x\n");
+    }
+
+    #[test]
+    fn test_delta_round_trips() {
+        let cases = [
+            ("", ""),
+            ("", "new\ntext"),
+            ("# Title\n\nbody\n", "# Title\n\nedited body\nmore\n"),
+            ("one\ntwo\nthree", "zero\none\nthree\nfour"),
+            ("same\n", "same\n"),
+            ("drop\nall\n", ""),
+        ];
+        for (base, target) in cases {
+            let delta = encode_delta(base, target);
+            assert_eq!(apply_delta(base, &delta).unwrap(), target, "{:?} -> {:?}", base, target);
+        }
+    }
+
+    #[test]
+    fn test_delta_is_compact() {
+        let base: String = (0..1000).map(|i| format!("line {}\n", i)).collect();
+        let target = base.replace("line 500\n", "line five hundred\n");
+        assert_eq!(
+            encode_delta(&base, &target),
+            vec![
+                DeltaOp::Copy(500),
+                DeltaOp::Skip(1),
+                DeltaOp::Insert("line five hundred\n".to_string()),
+                DeltaOp::Copy(499),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reverse_delta_matches_encode_delta() {
+        let (old, new) = ("a\nb\nc\nd\n", "a\nc\nd\ne\nf\n");
+        let delta = encode_reverse_delta(&diff_lines(old, new));
+        assert_eq!(apply_delta(new, &delta).unwrap(), old);
+    }
+
+    #[test]
+    fn test_apply_delta_rejects_wrong_base() {
+        let delta = encode_delta("a\nb\n", "a\n");
+        assert!(apply_delta("a\n", &delta).is_err());
+    }
+}
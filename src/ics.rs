@@ -0,0 +1,303 @@
+//! iCalendar (RFC 5545) export for captures
+//!
+//! Calendar captures become VEVENTs and every other dated capture a VTODO.
+//! The latest occurrence of a recurring series carries an RRULE so calendar
+//! apps can expand the rest of the series themselves.
+
+use crate::datetime::format_ics;
+use crate::types::{
+    Capture, CaptureStatus, CaptureType, Priority, Recurrence, RecurrenceFrequency, Weekday,
+};
+
+const PRODUCT_ID: &str = "-//FounderyOS//Core Canister//EN";
+const MAX_LINE_OCTETS: usize = 75;
+
+/// Render dated captures as an iCalendar document. Captures without a
+/// start_date or due_date are skipped.
+pub fn export_calendar(name: &str, captures: &[&Capture]) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:{}", PRODUCT_ID),
+        "CALSCALE:GREGORIAN".to_string(),
+        format!("X-WR-CALNAME:{}", escape_text(name)),
+    ];
+
+    for capture in captures {
+        lines.extend(component_lines(capture));
+    }
+
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut out = String::new();
+    for line in lines {
+        out.push_str(&fold_line(&line));
+        out.push_str("\r\n");
+    }
+    out
+}
+
+/// VEVENT or VTODO lines for a capture (empty if it has no dates)
+fn component_lines(capture: &Capture) -> Vec<String> {
+    let start = capture.fields.start_date;
+    let due = capture.fields.due_date;
+    if start.is_none() && due.is_none() {
+        return vec![];
+    }
+
+    let is_event = capture.capture_type == CaptureType::Calendar;
+    let component = if is_event { "VEVENT" } else { "VTODO" };
+
+    let mut lines = vec![
+        format!("BEGIN:{}", component),
+        format!("UID:capture-{}@foundery-os", capture.id),
+        format!("DTSTAMP:{}", format_ics(capture.updated_at)),
+        format!("CREATED:{}", format_ics(capture.created_at)),
+        format!("LAST-MODIFIED:{}", format_ics(capture.updated_at)),
+        format!("SUMMARY:{}", escape_text(&capture.title)),
+    ];
+
+    // RRULE is only valid alongside DTSTART
+    let has_dtstart = is_event || start.is_some();
+    if is_event {
+        let dtstart = start.or(due).unwrap_or_default();
+        lines.push(format!("DTSTART:{}", format_ics(dtstart)));
+        if let Some(end) = due.filter(|end| *end > dtstart) {
+            lines.push(format!("DTEND:{}", format_ics(end)));
+        }
+    } else {
+        if let Some(start) = start {
+            lines.push(format!("DTSTART:{}", format_ics(start)));
+        }
+        if let Some(due) = due {
+            lines.push(format!("DUE:{}", format_ics(due)));
+        }
+    }
+
+    if let Some(description) = &capture.description {
+        lines.push(format!("DESCRIPTION:{}", escape_text(description)));
+    }
+    if !capture.fields.labels.is_empty() {
+        let labels: Vec<String> = capture.fields.labels.iter().map(|l| escape_text(l)).collect();
+        lines.push(format!("CATEGORIES:{}", labels.join(",")));
+    }
+    lines.push(format!("PRIORITY:{}", ics_priority(&capture.priority)));
+    lines.push(format!("STATUS:{}", ics_status(&capture.status, is_event)));
+
+    // Only the latest occurrence of a series repeats
+    let series_head = capture.recurrence.as_ref().filter(|r| r.next_occurrence_id.is_none());
+    if let Some(recurrence) = series_head.filter(|_| has_dtstart) {
+        if let Some(rrule) = rrule(recurrence) {
+            lines.push(format!("RRULE:{}", rrule));
+        }
+    }
+
+    lines.push(format!("END:{}", component));
+    lines
+}
+
+/// RRULE value for the remainder of a series, starting at this occurrence
+fn rrule(recurrence: &Recurrence) -> Option<String> {
+    let rule = &recurrence.rule;
+    let frequency = match rule.frequency {
+        RecurrenceFrequency::Daily => "DAILY",
+        RecurrenceFrequency::Weekly => "WEEKLY",
+        RecurrenceFrequency::Monthly => "MONTHLY",
+    };
+
+    let mut parts = vec![format!("FREQ={}", frequency)];
+    if rule.interval > 1 {
+        parts.push(format!("INTERVAL={}", rule.interval));
+    }
+    if !rule.by_day.is_empty() {
+        let days: Vec<&str> = rule.by_day.iter().map(ics_weekday).collect();
+        parts.push(format!("BYDAY={}", days.join(",")));
+    }
+    if let Some(count) = rule.count {
+        // Occurrences already created are exported individually
+        let remaining = count.checked_sub(recurrence.occurrence)?;
+        if remaining == 0 {
+            return None;
+        }
+        parts.push(format!("COUNT={}", remaining + 1));
+    }
+    if let Some(until) = rule.until {
+        parts.push(format!("UNTIL={}", format_ics(until)));
+    }
+
+    Some(parts.join(";"))
+}
+
+fn ics_weekday(day: &Weekday) -> &'static str {
+    match day {
+        Weekday::Monday => "MO",
+        Weekday::Tuesday => "TU",
+        Weekday::Wednesday => "WE",
+        Weekday::Thursday => "TH",
+        Weekday::Friday => "FR",
+        Weekday::Saturday => "SA",
+        Weekday::Sunday => "SU",
+    }
+}
+
+fn ics_priority(priority: &Priority) -> u8 {
+    match priority {
+        Priority::Critical => 1,
+        Priority::High => 3,
+        Priority::Medium => 5,
+        Priority::Low => 9,
+    }
+}
+
+fn ics_status(status: &CaptureStatus, is_event: bool) -> &'static str {
+    match (status, is_event) {
+        (CaptureStatus::Cancelled, _) => "CANCELLED",
+        (_, true) => "CONFIRMED",
+        (CaptureStatus::Completed, false) => "COMPLETED",
+        (CaptureStatus::InProgress, false) => "IN-PROCESS",
+        (_, false) => "NEEDS-ACTION",
+    }
+}
+
+/// Escape a TEXT property value
+fn escape_text(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            ';' => out.push_str("\\;"),
+            ',' => out.push_str("\\,"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Fold a content line at 75 octets without splitting UTF-8 characters
+fn fold_line(line: &str) -> String {
+    let mut out = String::with_capacity(line.len() + line.len() / MAX_LINE_OCTETS * 3);
+    let mut octets = 0;
+
+    for c in line.chars() {
+        let len = c.len_utf8();
+        if octets + len > MAX_LINE_OCTETS {
+            out.push_str("\r\n ");
+            // The leading space counts towards the continuation line
+            octets = 1;
+        }
+        out.push(c);
+        octets += len;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DynamicFields, RecurrenceRule};
+    use candid::Principal;
+
+    fn capture(capture_type: CaptureType, start: Option<u64>, due: Option<u64>) -> Capture {
+        Capture {
+            id: 7,
+            owner: Principal::anonymous(),
+            capture_type,
+            subtype: None,
+            title: "Standup".to_string(),
+            description: Some("Daily sync; bring notes".to_string()),
+            content: None,
+            priority: Priority::High,
+            status: CaptureStatus::Draft,
+            fields: DynamicFields {
+                start_date: start,
+                due_date: due,
+                ..Default::default()
+            },
+            rank: None,
+            recurrence: None,
+            checklist: None,
+            conversions: None,
+            template: None,
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_calendar_capture_exports_vevent() {
+        let event = capture(CaptureType::Calendar, Some(1_704_099_600_000_000_000), Some(1_704_101_400_000_000_000));
+        let ics = export_calendar("Work", &[&event]);
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert!(ics.contains("BEGIN:VEVENT\r\n"));
+        assert!(ics.contains("DTSTART:20240101T090000Z\r\n"));
+        assert!(ics.contains("DTEND:20240101T093000Z\r\n"));
+        assert!(ics.contains("DESCRIPTION:Daily sync\\; bring notes\r\n"));
+    }
+
+    #[test]
+    fn test_task_exports_vtodo_and_undated_are_skipped() {
+        let task = capture(CaptureType::Task, None, Some(1_704_099_600_000_000_000));
+        let undated = capture(CaptureType::Task, None, None);
+        let ics = export_calendar("Work", &[&task, &undated]);
+
+        assert_eq!(ics.matches("BEGIN:VTODO").count(), 1);
+        assert!(ics.contains("DUE:20240101T090000Z\r\n"));
+        assert!(ics.contains("STATUS:NEEDS-ACTION\r\n"));
+    }
+
+    #[test]
+    fn test_series_head_exports_rrule() {
+        let mut event = capture(CaptureType::Calendar, Some(1_704_099_600_000_000_000), None);
+        event.recurrence = Some(Recurrence {
+            rule: RecurrenceRule {
+                frequency: RecurrenceFrequency::Weekly,
+                interval: 2,
+                by_day: vec![Weekday::Monday, Weekday::Thursday],
+                count: Some(10),
+                until: None,
+            },
+            series_id: 1,
+            dtstart: 0,
+            occurrence: 3,
+            next_occurrence_id: None,
+        });
+
+        let ics = export_calendar("Work", &[&event]);
+        assert!(ics.contains("RRULE:FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH;COUNT=8\r\n"));
+    }
+
+    #[test]
+    fn test_rrule_requires_dtstart() {
+        let mut task = capture(CaptureType::Task, None, Some(1_704_099_600_000_000_000));
+        task.recurrence = Some(Recurrence {
+            rule: RecurrenceRule {
+                frequency: RecurrenceFrequency::Daily,
+                interval: 1,
+                by_day: vec![],
+                count: None,
+                until: None,
+            },
+            series_id: 7,
+            dtstart: 0,
+            occurrence: 1,
+            next_occurrence_id: None,
+        });
+        assert!(!export_calendar("Work", &[&task]).contains("RRULE"));
+
+        task.fields.start_date = Some(1_704_096_000_000_000_000);
+        assert!(export_calendar("Work", &[&task]).contains("RRULE:FREQ=DAILY\r\n"));
+    }
+
+    #[test]
+    fn test_long_lines_are_folded() {
+        let folded = fold_line(&"x".repeat(200));
+        for line in folded.split("\r\n") {
+            assert!(line.len() <= MAX_LINE_OCTETS);
+        }
+        assert_eq!(folded.replace("\r\n ", ""), "x".repeat(200));
+    }
+}
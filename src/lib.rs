@@ -1,6 +1,15 @@
 mod authorization;
+mod datetime;
+mod diff;
 mod discussion;
+mod ics;
+mod ot;
+mod query;
+mod rank;
+mod recurrence;
+mod scheduler;
 mod state;
+mod template;
 mod types;
 
 use candid::Principal;
@@ -62,6 +71,8 @@ fn post_upgrade() {
 
     STATE.with(|state| {
         *state.borrow_mut() = restored_state;
+        // The global timer does not survive upgrades
+        scheduler::arm(state.borrow().next_scheduled_job());
     });
 
     ic_cdk::println!("===========================================");
@@ -176,7 +187,7 @@ fn create_capture(request: CreateCaptureRequest) -> Result<Capture, String> {
 
     let capture = STATE.with(|state| {
         state.borrow_mut().create_capture(owner, request)
-    });
+    })?;
 
     ic_cdk::println!("Created capture {} for {}", capture.id, owner);
     Ok(capture)
@@ -219,7 +230,6 @@ fn update_capture(request: UpdateCaptureRequest) -> Result<Capture, String> {
         drop(s);
 
         state.borrow_mut().update_capture(request)
-            .ok_or_else(|| "Failed to update capture".to_string())
     })
 }
 
@@ -243,6 +253,66 @@ fn delete_capture(id: CaptureId) -> Result<Capture, String> {
     })
 }
 
+/// Set or clear a capture's recurrence rule
+#[update]
+fn set_capture_recurrence(id: CaptureId, rule: Option<RecurrenceRule>) -> Result<Capture, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| {
+        {
+            let s = state.borrow();
+            let capture = s.get_capture(id)
+                .ok_or_else(|| "Capture not found".to_string())?;
+
+            if capture.owner != caller {
+                return Err("Not authorized to update this capture".to_string());
+            }
+        }
+
+        state.borrow_mut().set_capture_recurrence(id, rule)
+    })
+}
+
+/// Change a capture's type and subtype, remapping incompatible fields
+#[update]
+fn convert_capture(request: ConvertCaptureRequest) -> Result<Capture, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| {
+        {
+            let s = state.borrow();
+            let capture = s.get_capture(request.id)
+                .ok_or_else(|| "Capture not found".to_string())?;
+
+            if capture.owner != caller {
+                return Err("Not authorized to update this capture".to_string());
+            }
+        }
+
+        state.borrow_mut().convert_capture(request)
+    })
+}
+
+/// Promote an idea to a project with a task scaffold
+#[update]
+fn promote_idea(request: PromoteIdeaRequest) -> Result<PromoteIdeaResult, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| {
+        {
+            let s = state.borrow();
+            let capture = s.get_capture(request.idea_id)
+                .ok_or_else(|| "Capture not found".to_string())?;
+
+            if capture.owner != caller {
+                return Err("Not authorized to update this capture".to_string());
+            }
+        }
+
+        state.borrow_mut().promote_idea(&UserRef::Principal(caller), request)
+    })
+}
+
 #[query]
 fn get_my_captures(
     filter: Option<CaptureFilter>,
@@ -267,6 +337,137 @@ fn get_my_captures(
     })
 }
 
+/// Search the caller's captures with the query language, e.g.
+/// `type:task status:(active|inprogress) due<2026-11-01 "search text"`
+#[query]
+fn query_captures(query: String, pagination: Option<PaginationParams>) -> Result<PaginatedResponse<Capture>, String> {
+    let caller = require_authenticated()?;
+    let now = ic_cdk::api::time();
+
+    STATE.with(|state| {
+        state.borrow().query_captures(&UserRef::Principal(caller), &query, pagination.unwrap_or_default(), now)
+    })
+}
+
+/// Check a capture query without running it; errors carry the byte offset of the problem
+#[query]
+fn validate_capture_query(query: String) -> Result<(), QueryError> {
+    query::parse_query(&query).map(|_| ())
+}
+
+/// Get a capture and its descendants (via fields.parent_id) in depth-first
+/// order, each with its estimate and progress roll-up
+#[query]
+fn get_capture_tree(root_id: CaptureId) -> Result<Vec<CaptureTreeNode>, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| state.borrow().get_capture_tree(&UserRef::Principal(caller), root_id))
+}
+
+// =============================================================================
+// Checklist API
+// =============================================================================
+
+/// Add an item to a capture's checklist, at `position` or at the end
+#[update]
+fn add_checklist_item(capture_id: CaptureId, text: String, position: Option<u32>) -> Result<Capture, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| {
+        {
+            let s = state.borrow();
+            let capture = s.get_capture(capture_id)
+                .ok_or_else(|| "Capture not found".to_string())?;
+
+            if capture.owner != caller {
+                return Err("Not authorized to update this capture".to_string());
+            }
+        }
+
+        state.borrow_mut().add_checklist_item(capture_id, text, position)
+    })
+}
+
+/// Check or uncheck a checklist item
+#[update]
+fn check_checklist_item(capture_id: CaptureId, item_id: ChecklistItemId, checked: bool) -> Result<Capture, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| {
+        {
+            let s = state.borrow();
+            let capture = s.get_capture(capture_id)
+                .ok_or_else(|| "Capture not found".to_string())?;
+
+            if capture.owner != caller {
+                return Err("Not authorized to update this capture".to_string());
+            }
+        }
+
+        state.borrow_mut().check_checklist_item(capture_id, item_id, checked)
+    })
+}
+
+/// Move a checklist item to a new position
+#[update]
+fn move_checklist_item(capture_id: CaptureId, item_id: ChecklistItemId, position: u32) -> Result<Capture, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| {
+        {
+            let s = state.borrow();
+            let capture = s.get_capture(capture_id)
+                .ok_or_else(|| "Capture not found".to_string())?;
+
+            if capture.owner != caller {
+                return Err("Not authorized to update this capture".to_string());
+            }
+        }
+
+        state.borrow_mut().move_checklist_item(capture_id, item_id, position)
+    })
+}
+
+/// Remove an item from a capture's checklist
+#[update]
+fn remove_checklist_item(capture_id: CaptureId, item_id: ChecklistItemId) -> Result<Capture, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| {
+        {
+            let s = state.borrow();
+            let capture = s.get_capture(capture_id)
+                .ok_or_else(|| "Capture not found".to_string())?;
+
+            if capture.owner != caller {
+                return Err("Not authorized to update this capture".to_string());
+            }
+        }
+
+        state.borrow_mut().remove_checklist_item(capture_id, item_id)
+    })
+}
+
+/// Promote a checklist item to a child task of the capture (returns the new task)
+#[update]
+fn promote_checklist_item(capture_id: CaptureId, item_id: ChecklistItemId) -> Result<Capture, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| {
+        {
+            let s = state.borrow();
+            let capture = s.get_capture(capture_id)
+                .ok_or_else(|| "Capture not found".to_string())?;
+
+            if capture.owner != caller {
+                return Err("Not authorized to update this capture".to_string());
+            }
+        }
+
+        state.borrow_mut().promote_checklist_item(capture_id, item_id)
+    })
+}
+
 // =============================================================================
 // Sprint API
 // =============================================================================
@@ -277,7 +478,7 @@ fn create_sprint(request: CreateSprintRequest) -> Result<Sprint, String> {
 
     let sprint = STATE.with(|state| {
         state.borrow_mut().create_sprint(owner, request)
-    });
+    })?;
 
     ic_cdk::println!("Created sprint {} for {}", sprint.id, owner);
     Ok(sprint)
@@ -372,7 +573,6 @@ fn update_sprint(id: SprintId, request: UpdateSprintRequest) -> Result<Sprint, S
         drop(s);
 
         state.borrow_mut().update_sprint(id, request)
-            .ok_or_else(|| "Failed to update sprint".to_string())
     })
 }
 
@@ -397,26 +597,104 @@ fn delete_sprint(id: SprintId) -> Result<Sprint, String> {
 }
 
 // =============================================================================
-// Workspace API
+// Backlog API
+// =============================================================================
+
+/// Get the caller's backlog: open captures without a sprint, in rank order
+#[query]
+fn get_backlog(pagination: Option<PaginationParams>) -> PaginatedResponse<Capture> {
+    let caller = ic_cdk::caller();
+    if caller == Principal::anonymous() {
+        return PaginatedResponse {
+            items: vec![],
+            total: 0,
+            offset: 0,
+            limit: 50,
+        };
+    }
+
+    STATE.with(|state| state.borrow().get_backlog(caller, pagination.unwrap_or_default()))
+}
+
+/// Get a sprint's captures in rank order.
+/// Only returns captures if caller owns the sprint.
+#[query]
+fn get_sprint_captures(sprint_id: SprintId) -> Vec<Capture> {
+    let caller = ic_cdk::caller();
+
+    STATE.with(|state| {
+        let s = state.borrow();
+        match s.get_sprint(sprint_id) {
+            Some(sprint) if caller != Principal::anonymous() && sprint.owner == caller => {
+                s.get_sprint_captures(sprint_id)
+            }
+            _ => vec![],
+        }
+    })
+}
+
+/// Move a capture within its backlog or sprint.
+/// `before` is the capture that should directly precede it, `after` the one that should follow it.
+#[update]
+fn reorder_capture(
+    id: CaptureId,
+    before: Option<CaptureId>,
+    after: Option<CaptureId>,
+) -> Result<Capture, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| {
+        {
+            let s = state.borrow();
+            let capture = s.get_capture(id)
+                .ok_or_else(|| "Capture not found".to_string())?;
+
+            if capture.owner != caller {
+                return Err("Not authorized to reorder this capture".to_string());
+            }
+        }
+
+        state.borrow_mut().reorder_capture(id, before, after)
+    })
+}
+
+// =============================================================================
+// Board API
 // =============================================================================
 
+/// Create a Kanban board over one of the caller's workspaces or sprints
 #[update]
-fn create_workspace(request: CreateWorkspaceRequest) -> Result<Workspace, String> {
+fn create_board(request: CreateBoardRequest) -> Result<Board, String> {
     let owner = require_authenticated()?;
 
-    let workspace = STATE.with(|state| {
-        state.borrow_mut().create_workspace(owner, request)
-    });
+    let board = STATE.with(|state| {
+        {
+            let s = state.borrow();
+            let scope_owner = match request.scope {
+                BoardScope::Workspace(id) => s.get_workspace(id)
+                    .map(|w| w.owner)
+                    .ok_or_else(|| "Workspace not found".to_string())?,
+                BoardScope::Sprint(id) => s.get_sprint(id)
+                    .map(|sprint| sprint.owner)
+                    .ok_or_else(|| "Sprint not found".to_string())?,
+            };
+
+            if scope_owner != owner {
+                return Err("Not authorized to create a board here".to_string());
+            }
+        }
 
-    ic_cdk::println!("Created workspace {} for {}", workspace.id, owner);
-    Ok(workspace)
+        state.borrow_mut().create_board(owner, request)
+    })?;
+
+    ic_cdk::println!("Created board {} for {}", board.id, owner);
+    Ok(board)
 }
 
-/// Get a workspace by ID.
-/// Only returns the workspace if caller is the owner (AC-5.6.12.3).
-/// @see FOS-5.6.12 - User Data Backend Authorization
+/// Get a board with its columns and cards.
+/// Only returns the board if caller is the owner.
 #[query]
-fn get_workspace(id: WorkspaceId) -> Option<Workspace> {
+fn get_board(id: BoardId) -> Option<BoardView> {
     let caller = ic_cdk::caller();
     if caller == Principal::anonymous() {
         return None;
@@ -424,392 +702,1631 @@ fn get_workspace(id: WorkspaceId) -> Option<Workspace> {
 
     STATE.with(|state| {
         let s = state.borrow();
-        if let Some(workspace) = s.get_workspace(id) {
-            // Only return if caller owns the workspace
-            if workspace.owner == caller {
-                return Some(workspace.clone());
-            }
+        match s.get_board(id) {
+            Some(board) if board.owner == caller => s.get_board_view(id),
+            _ => None,
         }
-        None
     })
 }
 
 #[query]
-fn get_my_workspaces() -> Vec<Workspace> {
+fn get_my_boards() -> Vec<Board> {
     let caller = ic_cdk::caller();
     if caller == Principal::anonymous() {
         return vec![];
     }
 
-    STATE.with(|state| state.borrow().get_user_workspaces(caller))
+    STATE.with(|state| state.borrow().get_user_boards(caller))
 }
 
 #[update]
-fn update_workspace(id: WorkspaceId, request: UpdateWorkspaceRequest) -> Result<Workspace, String> {
+fn update_board(id: BoardId, request: UpdateBoardRequest) -> Result<Board, String> {
     let caller = require_authenticated()?;
 
     STATE.with(|state| {
         let s = state.borrow();
-        let workspace = s.get_workspace(id)
-            .ok_or_else(|| "Workspace not found".to_string())?;
+        let board = s.get_board(id)
+            .ok_or_else(|| "Board not found".to_string())?;
 
-        if workspace.owner != caller {
-            return Err("Not authorized to update this workspace".to_string());
+        if board.owner != caller {
+            return Err("Not authorized to update this board".to_string());
         }
         drop(s);
 
-        state.borrow_mut().update_workspace(id, request)
-            .ok_or_else(|| "Failed to update workspace".to_string())
+        state.borrow_mut().update_board(id, request)
     })
 }
 
 #[update]
-fn delete_workspace(id: WorkspaceId) -> Result<Workspace, String> {
+fn delete_board(id: BoardId) -> Result<Board, String> {
     let caller = require_authenticated()?;
 
     STATE.with(|state| {
         {
             let s = state.borrow();
-            let workspace = s.get_workspace(id)
-                .ok_or_else(|| "Workspace not found".to_string())?;
+            let board = s.get_board(id)
+                .ok_or_else(|| "Board not found".to_string())?;
 
-            if workspace.owner != caller {
-                return Err("Not authorized to delete this workspace".to_string());
+            if board.owner != caller {
+                return Err("Not authorized to delete this board".to_string());
             }
         }
 
-        state.borrow_mut().delete_workspace(id)
-            .ok_or_else(|| "Failed to delete workspace".to_string())
+        state.borrow_mut().delete_board(id)
+            .ok_or_else(|| "Failed to delete board".to_string())
     })
 }
 
-// =============================================================================
-// Document API
-// =============================================================================
-
+/// Move a card to a column and position on a board.
+/// Updates the capture's status (or labels) and rank in one call; rejected if the
+/// target column is at its WIP limit.
 #[update]
-fn create_document(request: CreateDocumentRequest) -> Result<Document, String> {
-    let owner = require_authenticated()?;
+fn move_card(request: MoveCardRequest) -> Result<Capture, String> {
+    let caller = require_authenticated()?;
 
-    // Verify owner owns the workspace
     STATE.with(|state| {
-        let s = state.borrow();
-        let workspace = s.get_workspace(request.workspace_id)
-            .ok_or_else(|| "Workspace not found".to_string())?;
+        {
+            let s = state.borrow();
+            let board = s.get_board(request.board_id)
+                .ok_or_else(|| "Board not found".to_string())?;
 
-        if workspace.owner != owner {
-            return Err("Not authorized to create documents in this workspace".to_string());
+            if board.owner != caller {
+                return Err("Not authorized to move cards on this board".to_string());
+            }
         }
 
-        drop(s);
-        state.borrow_mut().create_document(owner, request)
+        state.borrow_mut().move_card(request)
     })
 }
 
-/// Get a document by ID.
-/// Only returns the document if caller is the owner (AC-5.6.12.2).
-/// @see FOS-5.6.12 - User Data Backend Authorization
+// =============================================================================
+// Dashboard API
+// =============================================================================
+
+/// Get the caller's home screen dashboard: overdue and upcoming work, in-progress
+/// and blocked captures, and active sprint progress.
+/// `now_hint` overrides the reference time (defaults to the canister clock).
 #[query]
-fn get_document(id: DocumentId) -> Option<Document> {
-    let caller = ic_cdk::caller();
-    if caller == Principal::anonymous() {
-        return None;
-    }
+fn get_my_dashboard(now_hint: Option<Timestamp>) -> Result<Dashboard, String> {
+    let caller = require_authenticated()?;
+    let now = now_hint.unwrap_or_else(ic_cdk::api::time);
 
-    STATE.with(|state| {
-        let s = state.borrow();
-        if let Some(document) = s.get_document(id) {
-            // Only return if caller owns the document
+    Ok(STATE.with(|state| state.borrow().get_dashboard(&UserRef::Principal(caller), now)))
+}
+
+// =============================================================================
+// Calendar API
+// =============================================================================
+
+/// Get the caller's calendar for a time range, including projected occurrences
+/// of recurring captures
+#[query]
+fn get_calendar(range: CalendarRange) -> Result<Vec<CalendarEntry>, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| state.borrow().get_calendar(&UserRef::Principal(caller), range))
+}
+
+/// Generate an unguessable calendar feed token
+async fn new_calendar_feed_token() -> Result<String, String> {
+    let (bytes,) = ic_cdk::api::management_canister::main::raw_rand()
+        .await
+        .map_err(|(code, msg)| format!("Failed to generate feed token: {:?} {}", code, msg))?;
+    Ok(hex::encode(bytes))
+}
+
+/// Create (or rotate) the caller's ICS calendar feed.
+/// Returns the feed path to subscribe to through the HTTP gateway.
+#[update]
+async fn create_calendar_feed() -> Result<String, String> {
+    let caller = require_authenticated()?;
+    let token = new_calendar_feed_token().await?;
+
+    STATE.with(|state| {
+        state.borrow_mut().set_calendar_feed(UserRef::Principal(caller), token.clone())
+    });
+
+    Ok(format!("/calendar/{}.ics", token))
+}
+
+/// Revoke the caller's calendar feed
+#[update]
+fn revoke_calendar_feed() -> Result<bool, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| Ok(state.borrow_mut().revoke_calendar_feed(&UserRef::Principal(caller))))
+}
+
+// =============================================================================
+// HTTP Gateway
+// =============================================================================
+
+/// Serve calendar feeds at `/calendar/<token>.ics`
+#[query]
+fn http_request(request: HttpRequest) -> HttpResponse {
+    let path = request.url.split('?').next().unwrap_or_default();
+
+    let token = path
+        .strip_prefix("/calendar/")
+        .and_then(|rest| rest.strip_suffix(".ics"));
+
+    let calendar = match (request.method.as_str(), token) {
+        ("GET", Some(token)) => STATE.with(|state| state.borrow().export_calendar_feed(token)),
+        _ => None,
+    };
+
+    match calendar {
+        Some(body) => HttpResponse {
+            status_code: 200,
+            headers: vec![
+                ("Content-Type".to_string(), "text/calendar; charset=utf-8".to_string()),
+                ("Cache-Control".to_string(), "no-store".to_string()),
+            ],
+            body: body.into_bytes(),
+        },
+        None => HttpResponse {
+            status_code: 404,
+            headers: vec![("Content-Type".to_string(), "text/plain".to_string())],
+            body: b"Not found".to_vec(),
+        },
+    }
+}
+
+// =============================================================================
+// Reminder & Notification API
+// =============================================================================
+
+/// Add a reminder to one of the caller's captures
+#[update]
+fn create_reminder(request: CreateReminderRequest) -> Result<Reminder, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| {
+        {
+            let s = state.borrow();
+            let capture = s.get_capture(request.capture_id)
+                .ok_or_else(|| "Capture not found".to_string())?;
+
+            if capture.owner != caller {
+                return Err("Not authorized to add reminders to this capture".to_string());
+            }
+        }
+
+        state.borrow_mut().create_reminder(request)
+    })
+}
+
+/// Get a capture's reminders.
+/// Only returns reminders if caller owns the capture.
+#[query]
+fn get_capture_reminders(capture_id: CaptureId) -> Vec<Reminder> {
+    let caller = ic_cdk::caller();
+
+    STATE.with(|state| {
+        let s = state.borrow();
+        match s.get_capture(capture_id) {
+            Some(capture) if caller != Principal::anonymous() && capture.owner == caller => {
+                s.get_capture_reminders(capture_id)
+            }
+            _ => vec![],
+        }
+    })
+}
+
+#[update]
+fn delete_reminder(id: ReminderId) -> Result<Reminder, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| {
+        {
+            let s = state.borrow();
+            let reminder = s.get_reminder(id)
+                .ok_or_else(|| "Reminder not found".to_string())?;
+            let owned = s.get_capture(reminder.capture_id)
+                .map(|c| c.owner == caller)
+                .unwrap_or(false);
+
+            if !owned {
+                return Err("Not authorized to delete this reminder".to_string());
+            }
+        }
+
+        state.borrow_mut().delete_reminder(id)
+            .ok_or_else(|| "Failed to delete reminder".to_string())
+    })
+}
+
+/// Get the caller's notifications, newest first
+#[query]
+fn get_my_notifications(
+    unread_only: Option<bool>,
+    pagination: Option<PaginationParams>,
+) -> PaginatedResponse<Notification> {
+    let caller = ic_cdk::caller();
+    if caller == Principal::anonymous() {
+        return PaginatedResponse {
+            items: vec![],
+            total: 0,
+            offset: 0,
+            limit: 50,
+        };
+    }
+
+    STATE.with(|state| {
+        state.borrow().get_notifications(
+            &UserRef::Principal(caller),
+            unread_only.unwrap_or(false),
+            pagination.unwrap_or_default(),
+        )
+    })
+}
+
+/// Mark notifications as read (all of the caller's when `ids` is empty).
+/// Returns the number of notifications marked.
+#[update]
+fn mark_read(ids: Vec<NotificationId>) -> Result<u64, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| {
+        Ok(state.borrow_mut().mark_notifications_read(&UserRef::Principal(caller), ids))
+    })
+}
+
+// =============================================================================
+// Time Tracking API
+// =============================================================================
+
+/// Start a timer on one of the caller's captures (one running timer per user)
+#[update]
+fn start_timer(capture_id: CaptureId, note: Option<String>) -> Result<TimeEntry, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| {
+        {
+            let s = state.borrow();
+            let capture = s.get_capture(capture_id)
+                .ok_or_else(|| "Capture not found".to_string())?;
+
+            if capture.owner != caller {
+                return Err("Not authorized to log time on this capture".to_string());
+            }
+        }
+
+        state.borrow_mut().start_timer(UserRef::Principal(caller), capture_id, note)
+    })
+}
+
+/// Stop the caller's running timer
+#[update]
+fn stop_timer() -> Result<TimeEntry, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| state.borrow_mut().stop_timer(&UserRef::Principal(caller)))
+}
+
+/// Get the caller's running timer, if any
+#[query]
+fn get_running_timer() -> Result<Option<TimeEntry>, String> {
+    let caller = require_authenticated()?;
+
+    Ok(STATE.with(|state| state.borrow().get_running_timer(&UserRef::Principal(caller))))
+}
+
+/// Log time on one of the caller's captures manually
+#[update]
+fn add_time_entry(request: CreateTimeEntryRequest) -> Result<TimeEntry, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| {
+        {
+            let s = state.borrow();
+            let capture = s.get_capture(request.capture_id)
+                .ok_or_else(|| "Capture not found".to_string())?;
+
+            if capture.owner != caller {
+                return Err("Not authorized to log time on this capture".to_string());
+            }
+        }
+
+        state.borrow_mut().add_time_entry(UserRef::Principal(caller), request)
+    })
+}
+
+/// Delete one of the caller's time entries
+#[update]
+fn delete_time_entry(id: TimeEntryId) -> Result<TimeEntry, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| {
+        {
+            let s = state.borrow();
+            let entry = s.get_time_entry(id)
+                .ok_or_else(|| "Time entry not found".to_string())?;
+
+            if entry.user != UserRef::Principal(caller) {
+                return Err("Not authorized to delete this time entry".to_string());
+            }
+        }
+
+        state.borrow_mut().delete_time_entry(id)
+            .ok_or_else(|| "Failed to delete time entry".to_string())
+    })
+}
+
+/// Get the time logged against a capture
+#[query]
+fn get_capture_time(capture_id: CaptureId) -> Result<TimeReport, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| {
+        let s = state.borrow();
+        let capture = s.get_capture(capture_id)
+            .ok_or_else(|| "Capture not found".to_string())?;
+
+        if capture.owner != caller {
+            return Err("Not authorized to view this capture".to_string());
+        }
+
+        Ok(s.get_capture_time(capture_id))
+    })
+}
+
+/// Get the time logged against the captures in a sprint
+#[query]
+fn get_sprint_time(sprint_id: SprintId) -> Result<TimeReport, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| {
+        let s = state.borrow();
+        let sprint = s.get_sprint(sprint_id)
+            .ok_or_else(|| "Sprint not found".to_string())?;
+
+        if sprint.owner != caller {
+            return Err("Not authorized to view this sprint".to_string());
+        }
+
+        Ok(s.get_sprint_time(sprint_id))
+    })
+}
+
+/// Get the time the caller logged within a range
+#[query]
+fn get_time_report(range: CalendarRange) -> Result<TimeReport, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| state.borrow().get_time_report(&UserRef::Principal(caller), range))
+}
+
+/// Compare completed points with logged time over the caller's completed sprints
+#[query]
+fn get_estimate_accuracy() -> Result<EstimateAccuracyReport, String> {
+    let caller = require_authenticated()?;
+
+    Ok(STATE.with(|state| state.borrow().get_estimate_accuracy(&UserRef::Principal(caller))))
+}
+
+// =============================================================================
+// Comment & Activity API
+// =============================================================================
+
+/// Comment on a capture or document
+#[update]
+fn add_thread_comment(request: AddThreadCommentRequest) -> Result<ThreadComment, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| state.borrow_mut().add_thread_comment(UserRef::Principal(caller), request))
+}
+
+/// Retract one of the caller's comments
+#[update]
+fn retract_thread_comment(id: ThreadCommentId) -> Result<ThreadComment, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| state.borrow_mut().retract_thread_comment(&UserRef::Principal(caller), id))
+}
+
+/// Get the comments on a capture or document, oldest first
+#[query]
+fn get_thread_comments(target: CommentTarget, pagination: PaginationParams) -> Result<PaginatedResponse<ThreadComment>, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| state.borrow().get_thread_comments(&UserRef::Principal(caller), &target, pagination))
+}
+
+/// Get the merged comment and change stream of a capture or document, newest first
+#[query]
+fn get_activity_stream(target: CommentTarget, pagination: PaginationParams) -> Result<PaginatedResponse<ActivityItem>, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| state.borrow().get_activity_stream(&UserRef::Principal(caller), &target, pagination))
+}
+
+// =============================================================================
+// Mention & Assignment API
+// =============================================================================
+
+/// Claim a handle used for @mentions and assignment. Returns the normalized handle.
+#[update]
+fn set_handle(handle: String) -> Result<String, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| state.borrow_mut().set_handle(UserRef::Principal(caller), handle))
+}
+
+/// Get the caller's handle
+#[query]
+fn get_my_handle() -> Option<String> {
+    let caller = ic_cdk::caller();
+
+    STATE.with(|state| state.borrow().get_handle(&UserRef::Principal(caller)))
+}
+
+/// Get the captures assigned to the caller across all owners, soonest due first
+#[query]
+fn get_assigned_to_me(
+    include_closed: Option<bool>,
+    pagination: Option<PaginationParams>,
+) -> Result<PaginatedResponse<Capture>, String> {
+    let caller = require_authenticated()?;
+
+    Ok(STATE.with(|state| {
+        state.borrow().get_assigned_captures(
+            &UserRef::Principal(caller),
+            include_closed.unwrap_or(false),
+            pagination.unwrap_or_default(),
+        )
+    }))
+}
+
+// =============================================================================
+// Saved View API
+// =============================================================================
+
+/// Save a named view (filter, sort, grouping and columns)
+#[update]
+fn create_saved_view(request: CreateSavedViewRequest) -> Result<SavedView, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| state.borrow_mut().create_saved_view(UserRef::Principal(caller), request))
+}
+
+/// Update one of the caller's saved views
+#[update]
+fn update_saved_view(request: UpdateSavedViewRequest) -> Result<SavedView, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| state.borrow_mut().update_saved_view(&UserRef::Principal(caller), request))
+}
+
+/// Delete one of the caller's saved views
+#[update]
+fn delete_saved_view(id: SavedViewId) -> Result<SavedView, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| state.borrow_mut().delete_saved_view(&UserRef::Principal(caller), id))
+}
+
+/// Get the caller's own and shared saved views, optionally only those pinned to a workspace
+#[query]
+fn get_my_saved_views(workspace_id: Option<WorkspaceId>) -> Result<Vec<SavedView>, String> {
+    let caller = require_authenticated()?;
+
+    Ok(STATE.with(|state| state.borrow().get_saved_views(&UserRef::Principal(caller), workspace_id)))
+}
+
+/// Run a saved view; date-relative filters are evaluated at call time
+#[query]
+fn run_saved_view(id: SavedViewId, pagination: Option<PaginationParams>) -> Result<SavedViewResult, String> {
+    let caller = require_authenticated()?;
+    let now = ic_cdk::api::time();
+
+    STATE.with(|state| {
+        state.borrow().run_saved_view(&UserRef::Principal(caller), id, pagination.unwrap_or_default(), now)
+    })
+}
+
+// =============================================================================
+// Custom Field API
+// =============================================================================
+
+/// Define a typed custom field for the caller's captures, or for one of their workspaces
+#[update]
+fn create_custom_field(request: CreateCustomFieldRequest) -> Result<CustomFieldDefinition, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| state.borrow_mut().create_custom_field(UserRef::Principal(caller), request))
+}
+
+/// Update one of the caller's custom field definitions
+#[update]
+fn update_custom_field(request: UpdateCustomFieldRequest) -> Result<CustomFieldDefinition, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| state.borrow_mut().update_custom_field(&UserRef::Principal(caller), request))
+}
+
+/// Delete one of the caller's custom field definitions; stored values are kept
+#[update]
+fn delete_custom_field(id: CustomFieldId) -> Result<CustomFieldDefinition, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| state.borrow_mut().delete_custom_field(&UserRef::Principal(caller), id))
+}
+
+/// Get the custom fields that apply to the caller's captures, optionally within a workspace
+#[query]
+fn get_custom_fields(workspace_id: Option<WorkspaceId>) -> Result<Vec<CustomFieldDefinition>, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| state.borrow().get_custom_fields(&UserRef::Principal(caller), workspace_id))
+}
+
+// =============================================================================
+// Link API
+// =============================================================================
+
+/// Get the caller's documents and captures that link to a document or capture
+/// via `[[Title]]` or `#capture-<id>`
+#[query]
+fn get_backlinks(entity: CommentTarget) -> Result<Vec<Backlink>, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| state.borrow().get_backlinks(&UserRef::Principal(caller), entity))
+}
+
+// =============================================================================
+// Workspace API
+// =============================================================================
+
+#[update]
+fn create_workspace(request: CreateWorkspaceRequest) -> Result<Workspace, String> {
+    let owner = require_authenticated()?;
+
+    let workspace = STATE.with(|state| {
+        state.borrow_mut().create_workspace(owner, request)
+    });
+
+    ic_cdk::println!("Created workspace {} for {}", workspace.id, owner);
+    Ok(workspace)
+}
+
+/// Get a workspace by ID.
+/// Only returns the workspace if caller is the owner (AC-5.6.12.3).
+/// @see FOS-5.6.12 - User Data Backend Authorization
+#[query]
+fn get_workspace(id: WorkspaceId) -> Option<Workspace> {
+    let caller = ic_cdk::caller();
+    if caller == Principal::anonymous() {
+        return None;
+    }
+
+    STATE.with(|state| {
+        let s = state.borrow();
+        if let Some(workspace) = s.get_workspace(id) {
+            // Only return if caller owns the workspace
+            if workspace.owner == caller {
+                return Some(workspace.clone());
+            }
+        }
+        None
+    })
+}
+
+#[query]
+fn get_my_workspaces() -> Vec<Workspace> {
+    let caller = ic_cdk::caller();
+    if caller == Principal::anonymous() {
+        return vec![];
+    }
+
+    STATE.with(|state| state.borrow().get_user_workspaces(caller))
+}
+
+#[update]
+fn update_workspace(id: WorkspaceId, request: UpdateWorkspaceRequest) -> Result<Workspace, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| {
+        let s = state.borrow();
+        let workspace = s.get_workspace(id)
+            .ok_or_else(|| "Workspace not found".to_string())?;
+
+        if workspace.owner != caller {
+            return Err("Not authorized to update this workspace".to_string());
+        }
+        drop(s);
+
+        state.borrow_mut().update_workspace(id, request)
+            .ok_or_else(|| "Failed to update workspace".to_string())
+    })
+}
+
+#[update]
+fn delete_workspace(id: WorkspaceId) -> Result<Workspace, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| {
+        {
+            let s = state.borrow();
+            let workspace = s.get_workspace(id)
+                .ok_or_else(|| "Workspace not found".to_string())?;
+
+            if workspace.owner != caller {
+                return Err("Not authorized to delete this workspace".to_string());
+            }
+        }
+
+        state.borrow_mut().delete_workspace(id)
+            .ok_or_else(|| "Failed to delete workspace".to_string())
+    })
+}
+
+// =============================================================================
+// Document API
+// =============================================================================
+
+#[update]
+fn create_document(request: CreateDocumentRequest) -> Result<Document, String> {
+    let owner = require_authenticated()?;
+
+    // Verify owner owns the workspace
+    STATE.with(|state| {
+        let s = state.borrow();
+        let workspace = s.get_workspace(request.workspace_id)
+            .ok_or_else(|| "Workspace not found".to_string())?;
+
+        if workspace.owner != owner {
+            return Err("Not authorized to create documents in this workspace".to_string());
+        }
+
+        drop(s);
+        state.borrow_mut().create_document(owner, request)
+    })
+}
+
+/// Get a document by ID.
+/// Only returns the document if caller is the owner (AC-5.6.12.2).
+/// @see FOS-5.6.12 - User Data Backend Authorization
+#[query]
+fn get_document(id: DocumentId) -> Option<Document> {
+    let caller = ic_cdk::caller();
+    if caller == Principal::anonymous() {
+        return None;
+    }
+
+    STATE.with(|state| {
+        let s = state.borrow();
+        if let Some(document) = s.get_document(id) {
+            // Only return if caller owns the document
             if document.owner == caller {
                 return Some(document.clone());
             }
         }
-        None
+        None
+    })
+}
+
+#[update]
+fn update_document(
+    id: DocumentId,
+    title: Option<String>,
+    content: Option<String>,
+) -> Result<Document, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| {
+        {
+            let s = state.borrow();
+            let doc = s.get_document(id)
+                .ok_or_else(|| "Document not found".to_string())?;
+
+            if doc.owner != caller {
+                return Err("Not authorized to update this document".to_string());
+            }
+        }
+
+        state.borrow_mut().update_document(id, UserRef::Principal(caller), title, content)
+    })
+}
+
+#[query]
+fn get_workspace_documents(workspace_id: WorkspaceId) -> Vec<Document> {
+    let caller = ic_cdk::caller();
+
+    STATE.with(|state| {
+        let s = state.borrow();
+
+        // Check if caller owns the workspace
+        if let Some(workspace) = s.get_workspace(workspace_id) {
+            if workspace.owner == caller {
+                return s.get_workspace_documents(workspace_id);
+            }
+        }
+
+        vec![]
+    })
+}
+
+#[update]
+fn delete_document(id: DocumentId) -> Result<Document, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| {
+        {
+            let s = state.borrow();
+            let doc = s.get_document(id)
+                .ok_or_else(|| "Document not found".to_string())?;
+
+            if doc.owner != caller {
+                return Err("Not authorized to delete this document".to_string());
+            }
+        }
+
+        state.borrow_mut().delete_document(id)
+            .ok_or_else(|| "Failed to delete document".to_string())
+    })
+}
+
+/// Move a document and its descendants to a workspace (None keeps the current
+/// one), under a parent (None for top level), at a position among its siblings
+/// (None appends)
+#[update]
+fn move_document(
+    id: DocumentId,
+    workspace_id: Option<WorkspaceId>,
+    parent_id: Option<DocumentId>,
+    position: Option<u32>,
+) -> Result<Document, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| state.borrow_mut().move_document(caller, id, workspace_id, parent_id, position))
+}
+
+/// Get a workspace's documents as a depth-first tree listing
+#[query]
+fn get_document_tree(workspace_id: WorkspaceId) -> Result<Vec<DocumentTreeEntry>, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| state.borrow().get_document_tree(caller, workspace_id))
+}
+
+/// Copy a document next to the original, optionally with its descendants
+#[update]
+fn duplicate_document(id: DocumentId, include_children: bool) -> Result<Document, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| state.borrow_mut().duplicate_document(caller, id, include_children))
+}
+
+/// List a document's saved versions, newest first
+#[query]
+fn get_document_versions(id: DocumentId) -> Result<Vec<DocumentVersion>, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| state.borrow().get_document_versions(caller, id))
+}
+
+/// Line-based diff between two versions of a document, optionally with only
+/// `context` unchanged lines around each change
+#[query]
+fn diff_document_versions(
+    id: DocumentId,
+    from_version: DocumentVersionNumber,
+    to_version: DocumentVersionNumber,
+    context: Option<u32>,
+) -> Result<DocumentDiff, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| state.borrow().diff_document_versions(caller, id, from_version, to_version, context))
+}
+
+/// Restore a document to a version (saved as a new version)
+#[update]
+fn restore_document_version(id: DocumentId, version: DocumentVersionNumber) -> Result<Document, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| state.borrow_mut().restore_document_version(caller, id, version))
+}
+
+/// Apply positional edits made against a base version; concurrent patches
+/// saved since then are transformed over
+#[update]
+fn apply_document_patch(request: ApplyDocumentPatchRequest) -> Result<DocumentPatch, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| state.borrow_mut().apply_document_patch(caller, request))
+}
+
+/// Poll for the patches saved after a version, oldest first
+#[query]
+fn get_document_patches(id: DocumentId, since_version: DocumentVersionNumber) -> Result<Vec<DocumentPatch>, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| state.borrow().get_document_patches(caller, id, since_version))
+}
+
+/// Start a chunked upload that replaces a document's content
+#[update]
+fn begin_document_upload(document_id: DocumentId, total_size: u64) -> Result<UploadSession, String> {
+    let caller = require_authenticated()?;
+    let now = ic_cdk::api::time();
+
+    STATE.with(|state| state.borrow_mut().begin_document_upload(caller, document_id, total_size, now))
+}
+
+/// Append a chunk to an upload; `offset` must equal the bytes received so far
+#[update]
+fn append_document_chunk(session_id: UploadSessionId, offset: u64, data: Vec<u8>) -> Result<UploadSession, String> {
+    let caller = require_authenticated()?;
+    let now = ic_cdk::api::time();
+
+    STATE.with(|state| state.borrow_mut().append_document_chunk(caller, session_id, offset, data, now))
+}
+
+/// Verify an upload's SHA-256 (hex) and save it as the document's content
+#[update]
+fn commit_document_upload(session_id: UploadSessionId, sha256: String) -> Result<Document, String> {
+    let caller = require_authenticated()?;
+    let now = ic_cdk::api::time();
+
+    STATE.with(|state| state.borrow_mut().commit_document_upload(caller, session_id, &sha256, now))
+}
+
+/// Discard an upload
+#[update]
+fn abort_document_upload(session_id: UploadSessionId) -> Result<(), String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| state.borrow_mut().abort_document_upload(caller, session_id))
+}
+
+/// Read a byte range of a document's content
+#[query]
+fn read_document_content(id: DocumentId, offset: u64, length: u64) -> Result<DocumentContentChunk, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| state.borrow().read_document_content(caller, id, offset, length))
+}
+
+// =============================================================================
+// Template API
+// =============================================================================
+
+#[update]
+fn create_template(request: CreateTemplateRequest) -> Result<Template, String> {
+    let owner = require_authenticated()?;
+
+    let template = STATE.with(|state| {
+        state.borrow_mut().create_template(owner, request)
+    })?;
+
+    ic_cdk::println!("Created template {} for {}", template.id, owner);
+    Ok(template)
+}
+
+/// Get a template by ID.
+/// Returns the draft if caller is the owner, or the latest published version
+/// if the template is public.
+/// @see FOS-5.6.12 - User Data Backend Authorization
+#[query]
+fn get_template(id: TemplateId) -> Option<Template> {
+    let caller = ic_cdk::caller();
+    let viewer = (caller != Principal::anonymous()).then_some(UserRef::Principal(caller));
+
+    STATE.with(|state| state.borrow().view_template(id, viewer.as_ref()))
+}
+
+#[query]
+fn get_my_templates() -> Vec<Template> {
+    let caller = ic_cdk::caller();
+    if caller == Principal::anonymous() {
+        return vec![];
+    }
+
+    STATE.with(|state| state.borrow().get_user_templates(caller))
+}
+
+#[query]
+fn get_public_templates() -> Vec<Template> {
+    STATE.with(|state| state.borrow().get_public_templates())
+}
+
+/// Search the public template gallery; sorted by usage unless requested otherwise
+#[query]
+fn search_public_templates(
+    query: Option<TemplateGalleryQuery>,
+    pagination: Option<PaginationParams>,
+) -> PaginatedResponse<TemplateGalleryEntry> {
+    STATE.with(|state| {
+        state.borrow().search_public_templates(query.unwrap_or_default(), pagination.unwrap_or_default())
+    })
+}
+
+#[update]
+fn update_template(id: TemplateId, request: UpdateTemplateRequest) -> Result<Template, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| {
+        let s = state.borrow();
+        let template = s.get_template(id)
+            .ok_or_else(|| "Template not found".to_string())?;
+
+        if template.owner != caller {
+            return Err("Not authorized to update this template".to_string());
+        }
+        drop(s);
+
+        state.borrow_mut().update_template(id, request)
+    })
+}
+
+#[update]
+fn delete_template(id: TemplateId) -> Result<Template, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| {
+        {
+            let s = state.borrow();
+            let template = s.get_template(id)
+                .ok_or_else(|| "Template not found".to_string())?;
+
+            if template.owner != caller {
+                return Err("Not authorized to delete this template".to_string());
+            }
+        }
+
+        state.borrow_mut().delete_template(id)
+            .ok_or_else(|| "Failed to delete template".to_string())
+    })
+}
+
+/// Create a document, capture or scaffold bundle from a template, rendering
+/// its variables.
+/// The template must be the caller's own or public.
+#[update]
+fn instantiate_template(request: InstantiateTemplateRequest) -> Result<TemplateInstance, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| {
+        state.borrow_mut().instantiate_template(&UserRef::Principal(caller), request)
+    })
+}
+
+/// Publish the caller's template draft as a new immutable version
+#[update]
+fn publish_template(id: TemplateId, changelog: Option<String>) -> Result<TemplateVersion, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| {
+        state.borrow_mut().publish_template(&UserRef::Principal(caller), id, changelog)
+    })
+}
+
+#[query]
+fn get_template_versions(id: TemplateId) -> Result<Vec<TemplateVersion>, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| {
+        state.borrow().get_template_versions(&UserRef::Principal(caller), id)
+    })
+}
+
+/// Copy an own or public template into a new private template
+#[update]
+fn fork_template(id: TemplateId, version: Option<TemplateVersionNumber>) -> Result<Template, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| {
+        state.borrow_mut().fork_template(&UserRef::Principal(caller), id, version)
+    })
+}
+
+/// Withdraw the caller's template from the public gallery
+#[update]
+fn unpublish_template(id: TemplateId) -> Result<Template, String> {
+    let caller = require_authenticated()?;
+
+    STATE.with(|state| {
+        state.borrow_mut().unpublish_template(&UserRef::Principal(caller), id)
+    })
+}
+
+/// Feature or hide a template in the gallery (controllers only)
+#[update]
+async fn moderate_template(id: TemplateId, request: ModerateTemplateRequest) -> Result<(), String> {
+    require_controller().await?;
+
+    STATE.with(|state| state.borrow_mut().moderate_template(id, request))
+}
+
+// =============================================================================
+// Token-Based API (Session Authentication via auth-service)
+// These endpoints accept an access_token for session-based authentication.
+// Use these from the frontend when authenticating via email/password or OAuth.
+// =============================================================================
+
+/// Create a capture using session-based authentication
+#[update]
+async fn create_capture_with_token(access_token: String, request: CreateCaptureRequest) -> Result<Capture, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
+
+    let capture = STATE.with(|state| {
+        state.borrow_mut().create_capture_for_user_id(&user_id, request)
+    })?;
+
+    ic_cdk::println!("Created capture {} for user_id {}", capture.id, user_id);
+    Ok(capture)
+}
+
+/// Update a capture using session-based authentication
+#[update]
+async fn update_capture_with_token(access_token: String, request: UpdateCaptureRequest) -> Result<Capture, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
+
+    STATE.with(|state| {
+        // Verify ownership
+        if !state.borrow().is_capture_owned_by_user_id(request.id, &user_id) {
+            return Err("Not authorized to update this capture".to_string());
+        }
+
+        state.borrow_mut().update_capture(request)
+    })
+}
+
+/// Delete a capture using session-based authentication
+#[update]
+async fn delete_capture_with_token(access_token: String, id: CaptureId) -> Result<Capture, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
+
+    STATE.with(|state| {
+        state.borrow_mut().delete_capture_by_user_id(id, &user_id)
+            .ok_or_else(|| "Capture not found or not authorized".to_string())
+    })
+}
+
+/// Get captures for the authenticated user (session-based)
+#[update]
+async fn get_my_captures_with_token(
+    access_token: String,
+    filter: Option<CaptureFilter>,
+    pagination: Option<PaginationParams>,
+) -> Result<PaginatedResponse<Capture>, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
+
+    Ok(STATE.with(|state| {
+        state.borrow().get_user_id_captures(
+            &user_id,
+            filter,
+            pagination.unwrap_or_default(),
+        )
+    }))
+}
+
+/// Search captures with the query language using session-based authentication
+#[update]
+async fn query_captures_with_token(
+    access_token: String,
+    query: String,
+    pagination: Option<PaginationParams>,
+) -> Result<PaginatedResponse<Capture>, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
+    let now = ic_cdk::api::time();
+
+    STATE.with(|state| {
+        state.borrow().query_captures(&UserRef::UserId(user_id), &query, pagination.unwrap_or_default(), now)
+    })
+}
+
+/// Convert a capture's type using session-based authentication
+#[update]
+async fn convert_capture_with_token(access_token: String, request: ConvertCaptureRequest) -> Result<Capture, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
+
+    STATE.with(|state| {
+        if !state.borrow().is_capture_owned_by_user_id(request.id, &user_id) {
+            return Err("Not authorized to update this capture".to_string());
+        }
+
+        state.borrow_mut().convert_capture(request)
+    })
+}
+
+/// Promote an idea to a project using session-based authentication
+#[update]
+async fn promote_idea_with_token(access_token: String, request: PromoteIdeaRequest) -> Result<PromoteIdeaResult, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
+
+    STATE.with(|state| {
+        if !state.borrow().is_capture_owned_by_user_id(request.idea_id, &user_id) {
+            return Err("Not authorized to update this capture".to_string());
+        }
+
+        state.borrow_mut().promote_idea(&UserRef::UserId(user_id), request)
+    })
+}
+
+/// Get a capture tree with roll-ups using session-based authentication
+#[update]
+async fn get_capture_tree_with_token(access_token: String, root_id: CaptureId) -> Result<Vec<CaptureTreeNode>, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
+
+    STATE.with(|state| state.borrow().get_capture_tree(&UserRef::UserId(user_id), root_id))
+}
+
+/// Add a checklist item using session-based authentication
+#[update]
+async fn add_checklist_item_with_token(access_token: String, capture_id: CaptureId, text: String, position: Option<u32>) -> Result<Capture, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
+
+    STATE.with(|state| {
+        if !state.borrow().is_capture_owned_by_user_id(capture_id, &user_id) {
+            return Err("Not authorized to update this capture".to_string());
+        }
+
+        state.borrow_mut().add_checklist_item(capture_id, text, position)
+    })
+}
+
+/// Check or uncheck a checklist item using session-based authentication
+#[update]
+async fn check_checklist_item_with_token(access_token: String, capture_id: CaptureId, item_id: ChecklistItemId, checked: bool) -> Result<Capture, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
+
+    STATE.with(|state| {
+        if !state.borrow().is_capture_owned_by_user_id(capture_id, &user_id) {
+            return Err("Not authorized to update this capture".to_string());
+        }
+
+        state.borrow_mut().check_checklist_item(capture_id, item_id, checked)
+    })
+}
+
+/// Move a checklist item using session-based authentication
+#[update]
+async fn move_checklist_item_with_token(access_token: String, capture_id: CaptureId, item_id: ChecklistItemId, position: u32) -> Result<Capture, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
+
+    STATE.with(|state| {
+        if !state.borrow().is_capture_owned_by_user_id(capture_id, &user_id) {
+            return Err("Not authorized to update this capture".to_string());
+        }
+
+        state.borrow_mut().move_checklist_item(capture_id, item_id, position)
+    })
+}
+
+/// Remove a checklist item using session-based authentication
+#[update]
+async fn remove_checklist_item_with_token(access_token: String, capture_id: CaptureId, item_id: ChecklistItemId) -> Result<Capture, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
+
+    STATE.with(|state| {
+        if !state.borrow().is_capture_owned_by_user_id(capture_id, &user_id) {
+            return Err("Not authorized to update this capture".to_string());
+        }
+
+        state.borrow_mut().remove_checklist_item(capture_id, item_id)
+    })
+}
+
+/// Promote a checklist item to a child task using session-based authentication
+#[update]
+async fn promote_checklist_item_with_token(access_token: String, capture_id: CaptureId, item_id: ChecklistItemId) -> Result<Capture, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
+
+    STATE.with(|state| {
+        if !state.borrow().is_capture_owned_by_user_id(capture_id, &user_id) {
+            return Err("Not authorized to update this capture".to_string());
+        }
+
+        state.borrow_mut().promote_checklist_item(capture_id, item_id)
     })
 }
 
+/// Create a sprint using session-based authentication
+#[update]
+async fn create_sprint_with_token(access_token: String, request: CreateSprintRequest) -> Result<Sprint, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
+
+    let sprint = STATE.with(|state| {
+        state.borrow_mut().create_sprint_for_user_id(&user_id, request)
+    })?;
+
+    ic_cdk::println!("Created sprint {} for user_id {}", sprint.id, user_id);
+    Ok(sprint)
+}
+
+/// Get sprints for the authenticated user (session-based)
+#[update]
+async fn get_my_sprints_with_token(access_token: String) -> Result<Vec<Sprint>, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
+
+    Ok(STATE.with(|state| {
+        state.borrow().get_user_id_sprints(&user_id)
+    }))
+}
+
+/// Create a workspace using session-based authentication
+#[update]
+async fn create_workspace_with_token(access_token: String, request: CreateWorkspaceRequest) -> Result<Workspace, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
+
+    let workspace = STATE.with(|state| {
+        state.borrow_mut().create_workspace_for_user_id(&user_id, request)
+    });
+
+    ic_cdk::println!("Created workspace {} for user_id {}", workspace.id, user_id);
+    Ok(workspace)
+}
+
+/// Get workspaces for the authenticated user (session-based)
+#[update]
+async fn get_my_workspaces_with_token(access_token: String) -> Result<Vec<Workspace>, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
+
+    Ok(STATE.with(|state| {
+        state.borrow().get_user_id_workspaces(&user_id)
+    }))
+}
+
+/// Create a template using session-based authentication
+#[update]
+async fn create_template_with_token(access_token: String, request: CreateTemplateRequest) -> Result<Template, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
+
+    let template = STATE.with(|state| {
+        state.borrow_mut().create_template_for_user_id(&user_id, request)
+    })?;
+
+    ic_cdk::println!("Created template {} for user_id {}", template.id, user_id);
+    Ok(template)
+}
+
+/// Get templates for the authenticated user (session-based)
+#[update]
+async fn get_my_templates_with_token(access_token: String) -> Result<Vec<Template>, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
+
+    Ok(STATE.with(|state| {
+        state.borrow().get_user_id_templates(&user_id)
+    }))
+}
+
+/// Create a capture or scaffold from a template using session-based authentication
 #[update]
-fn update_document(
-    id: DocumentId,
-    title: Option<String>,
-    content: Option<String>,
-) -> Result<Document, String> {
-    let caller = require_authenticated()?;
+async fn instantiate_template_with_token(access_token: String, request: InstantiateTemplateRequest) -> Result<TemplateInstance, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
 
     STATE.with(|state| {
-        {
-            let s = state.borrow();
-            let doc = s.get_document(id)
-                .ok_or_else(|| "Document not found".to_string())?;
+        state.borrow_mut().instantiate_template(&UserRef::UserId(user_id), request)
+    })
+}
 
-            if doc.owner != caller {
-                return Err("Not authorized to update this document".to_string());
-            }
-        }
+/// Publish a template draft using session-based authentication
+#[update]
+async fn publish_template_with_token(
+    access_token: String,
+    id: TemplateId,
+    changelog: Option<String>,
+) -> Result<TemplateVersion, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
 
-        state.borrow_mut().update_document(id, title, content)
-            .ok_or_else(|| "Failed to update document".to_string())
+    STATE.with(|state| {
+        state.borrow_mut().publish_template(&UserRef::UserId(user_id), id, changelog)
     })
 }
 
-#[query]
-fn get_workspace_documents(workspace_id: WorkspaceId) -> Vec<Document> {
-    let caller = ic_cdk::caller();
+/// Get a template's published versions using session-based authentication
+#[update]
+async fn get_template_versions_with_token(access_token: String, id: TemplateId) -> Result<Vec<TemplateVersion>, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
 
     STATE.with(|state| {
-        let s = state.borrow();
+        state.borrow().get_template_versions(&UserRef::UserId(user_id), id)
+    })
+}
 
-        // Check if caller owns the workspace
-        if let Some(workspace) = s.get_workspace(workspace_id) {
-            if workspace.owner == caller {
-                return s.get_workspace_documents(workspace_id);
-            }
-        }
+/// Fork a template using session-based authentication
+#[update]
+async fn fork_template_with_token(
+    access_token: String,
+    id: TemplateId,
+    version: Option<TemplateVersionNumber>,
+) -> Result<Template, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
 
-        vec![]
+    STATE.with(|state| {
+        state.borrow_mut().fork_template(&UserRef::UserId(user_id), id, version)
     })
 }
 
+/// Unpublish a template using session-based authentication
 #[update]
-fn delete_document(id: DocumentId) -> Result<Document, String> {
-    let caller = require_authenticated()?;
+async fn unpublish_template_with_token(access_token: String, id: TemplateId) -> Result<Template, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
 
     STATE.with(|state| {
-        {
-            let s = state.borrow();
-            let doc = s.get_document(id)
-                .ok_or_else(|| "Document not found".to_string())?;
+        state.borrow_mut().unpublish_template(&UserRef::UserId(user_id), id)
+    })
+}
 
-            if doc.owner != caller {
-                return Err("Not authorized to delete this document".to_string());
-            }
+/// Add a reminder to a capture using session-based authentication
+#[update]
+async fn create_reminder_with_token(access_token: String, request: CreateReminderRequest) -> Result<Reminder, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
+
+    STATE.with(|state| {
+        // Verify ownership
+        if !state.borrow().is_capture_owned_by_user_id(request.capture_id, &user_id) {
+            return Err("Not authorized to add reminders to this capture".to_string());
         }
 
-        state.borrow_mut().delete_document(id)
-            .ok_or_else(|| "Failed to delete document".to_string())
+        state.borrow_mut().create_reminder(request)
     })
 }
 
-// =============================================================================
-// Template API
-// =============================================================================
+/// Get notifications using session-based authentication
+#[update]
+async fn get_my_notifications_with_token(
+    access_token: String,
+    unread_only: Option<bool>,
+    pagination: Option<PaginationParams>,
+) -> Result<PaginatedResponse<Notification>, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
 
+    Ok(STATE.with(|state| {
+        state.borrow().get_notifications(
+            &UserRef::UserId(user_id),
+            unread_only.unwrap_or(false),
+            pagination.unwrap_or_default(),
+        )
+    }))
+}
+
+/// Mark notifications as read using session-based authentication
 #[update]
-fn create_template(request: CreateTemplateRequest) -> Result<Template, String> {
-    let owner = require_authenticated()?;
+async fn mark_read_with_token(access_token: String, ids: Vec<NotificationId>) -> Result<u64, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
 
-    let template = STATE.with(|state| {
-        state.borrow_mut().create_template(owner, request)
-    });
+    STATE.with(|state| {
+        Ok(state.borrow_mut().mark_notifications_read(&UserRef::UserId(user_id), ids))
+    })
+}
 
-    ic_cdk::println!("Created template {} for {}", template.id, owner);
-    Ok(template)
+/// Get the calendar for a time range using session-based authentication
+#[update]
+async fn get_calendar_with_token(access_token: String, range: CalendarRange) -> Result<Vec<CalendarEntry>, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
+
+    STATE.with(|state| state.borrow().get_calendar(&UserRef::UserId(user_id), range))
 }
 
-/// Get a template by ID.
-/// Returns the template if caller is the owner OR if the template is public.
-/// @see FOS-5.6.12 - User Data Backend Authorization
-#[query]
-fn get_template(id: TemplateId) -> Option<Template> {
-    let caller = ic_cdk::caller();
+/// Create (or rotate) the ICS calendar feed using session-based authentication
+#[update]
+async fn create_calendar_feed_with_token(access_token: String) -> Result<String, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
+    let token = new_calendar_feed_token().await?;
 
     STATE.with(|state| {
-        let s = state.borrow();
-        if let Some(template) = s.get_template(id) {
-            // Allow access if template is public OR if caller owns it
-            if template.is_public || (caller != Principal::anonymous() && template.owner == caller) {
-                return Some(template.clone());
-            }
-        }
-        None
-    })
+        state.borrow_mut().set_calendar_feed(UserRef::UserId(user_id), token.clone())
+    });
+
+    Ok(format!("/calendar/{}.ics", token))
 }
 
-#[query]
-fn get_my_templates() -> Vec<Template> {
-    let caller = ic_cdk::caller();
-    if caller == Principal::anonymous() {
-        return vec![];
-    }
+/// Revoke the calendar feed using session-based authentication
+#[update]
+async fn revoke_calendar_feed_with_token(access_token: String) -> Result<bool, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
 
-    STATE.with(|state| state.borrow().get_user_templates(caller))
+    STATE.with(|state| Ok(state.borrow_mut().revoke_calendar_feed(&UserRef::UserId(user_id))))
 }
 
-#[query]
-fn get_public_templates() -> Vec<Template> {
-    STATE.with(|state| state.borrow().get_public_templates())
+/// Get the dashboard using session-based authentication
+#[update]
+async fn get_my_dashboard_with_token(access_token: String, now_hint: Option<Timestamp>) -> Result<Dashboard, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
+    let now = now_hint.unwrap_or_else(ic_cdk::api::time);
+
+    Ok(STATE.with(|state| state.borrow().get_dashboard(&UserRef::UserId(user_id), now)))
 }
 
+/// Start a timer using session-based authentication
 #[update]
-fn update_template(id: TemplateId, request: UpdateTemplateRequest) -> Result<Template, String> {
-    let caller = require_authenticated()?;
+async fn start_timer_with_token(access_token: String, capture_id: CaptureId, note: Option<String>) -> Result<TimeEntry, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
 
     STATE.with(|state| {
-        let s = state.borrow();
-        let template = s.get_template(id)
-            .ok_or_else(|| "Template not found".to_string())?;
-
-        if template.owner != caller {
-            return Err("Not authorized to update this template".to_string());
+        if !state.borrow().is_capture_owned_by_user_id(capture_id, &user_id) {
+            return Err("Not authorized to log time on this capture".to_string());
         }
-        drop(s);
 
-        state.borrow_mut().update_template(id, request)
-            .ok_or_else(|| "Failed to update template".to_string())
+        state.borrow_mut().start_timer(UserRef::UserId(user_id), capture_id, note)
     })
 }
 
+/// Stop the running timer using session-based authentication
 #[update]
-fn delete_template(id: TemplateId) -> Result<Template, String> {
-    let caller = require_authenticated()?;
+async fn stop_timer_with_token(access_token: String) -> Result<TimeEntry, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
 
-    STATE.with(|state| {
-        {
-            let s = state.borrow();
-            let template = s.get_template(id)
-                .ok_or_else(|| "Template not found".to_string())?;
+    STATE.with(|state| state.borrow_mut().stop_timer(&UserRef::UserId(user_id)))
+}
 
-            if template.owner != caller {
-                return Err("Not authorized to delete this template".to_string());
-            }
+/// Log time manually using session-based authentication
+#[update]
+async fn add_time_entry_with_token(access_token: String, request: CreateTimeEntryRequest) -> Result<TimeEntry, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
+
+    STATE.with(|state| {
+        if !state.borrow().is_capture_owned_by_user_id(request.capture_id, &user_id) {
+            return Err("Not authorized to log time on this capture".to_string());
         }
 
-        state.borrow_mut().delete_template(id)
-            .ok_or_else(|| "Failed to delete template".to_string())
+        state.borrow_mut().add_time_entry(UserRef::UserId(user_id), request)
     })
 }
 
-// =============================================================================
-// Token-Based API (Session Authentication via auth-service)
-// These endpoints accept an access_token for session-based authentication.
-// Use these from the frontend when authenticating via email/password or OAuth.
-// =============================================================================
+/// Get the time logged within a range using session-based authentication
+#[update]
+async fn get_time_report_with_token(access_token: String, range: CalendarRange) -> Result<TimeReport, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
 
-/// Create a capture using session-based authentication
+    STATE.with(|state| state.borrow().get_time_report(&UserRef::UserId(user_id), range))
+}
+
+/// Get the estimate accuracy report using session-based authentication
 #[update]
-async fn create_capture_with_token(access_token: String, request: CreateCaptureRequest) -> Result<Capture, String> {
+async fn get_estimate_accuracy_with_token(access_token: String) -> Result<EstimateAccuracyReport, String> {
     let user_id = require_authenticated_with_token(&access_token).await?;
 
-    let capture = STATE.with(|state| {
-        state.borrow_mut().create_capture_for_user_id(&user_id, request)
-    });
+    Ok(STATE.with(|state| state.borrow().get_estimate_accuracy(&UserRef::UserId(user_id))))
+}
 
-    ic_cdk::println!("Created capture {} for user_id {}", capture.id, user_id);
-    Ok(capture)
+/// Comment on a capture using session-based authentication
+#[update]
+async fn add_thread_comment_with_token(access_token: String, request: AddThreadCommentRequest) -> Result<ThreadComment, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
+
+    STATE.with(|state| state.borrow_mut().add_thread_comment(UserRef::UserId(user_id), request))
 }
 
-/// Update a capture using session-based authentication
+/// Retract a comment using session-based authentication
 #[update]
-async fn update_capture_with_token(access_token: String, request: UpdateCaptureRequest) -> Result<Capture, String> {
+async fn retract_thread_comment_with_token(access_token: String, id: ThreadCommentId) -> Result<ThreadComment, String> {
     let user_id = require_authenticated_with_token(&access_token).await?;
 
-    STATE.with(|state| {
-        // Verify ownership
-        if !state.borrow().is_capture_owned_by_user_id(request.id, &user_id) {
-            return Err("Not authorized to update this capture".to_string());
-        }
+    STATE.with(|state| state.borrow_mut().retract_thread_comment(&UserRef::UserId(user_id), id))
+}
 
-        state.borrow_mut().update_capture(request)
-            .ok_or_else(|| "Failed to update capture".to_string())
-    })
+/// Get a capture's activity stream using session-based authentication
+#[update]
+async fn get_activity_stream_with_token(access_token: String, target: CommentTarget, pagination: PaginationParams) -> Result<PaginatedResponse<ActivityItem>, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
+
+    STATE.with(|state| state.borrow().get_activity_stream(&UserRef::UserId(user_id), &target, pagination))
 }
 
-/// Delete a capture using session-based authentication
+/// Claim a handle using session-based authentication
 #[update]
-async fn delete_capture_with_token(access_token: String, id: CaptureId) -> Result<Capture, String> {
+async fn set_handle_with_token(access_token: String, handle: String) -> Result<String, String> {
     let user_id = require_authenticated_with_token(&access_token).await?;
 
-    STATE.with(|state| {
-        state.borrow_mut().delete_capture_by_user_id(id, &user_id)
-            .ok_or_else(|| "Capture not found or not authorized".to_string())
-    })
+    STATE.with(|state| state.borrow_mut().set_handle(UserRef::UserId(user_id), handle))
 }
 
-/// Get captures for the authenticated user (session-based)
+/// Get assigned captures using session-based authentication
 #[update]
-async fn get_my_captures_with_token(
+async fn get_assigned_to_me_with_token(
     access_token: String,
-    filter: Option<CaptureFilter>,
+    include_closed: Option<bool>,
     pagination: Option<PaginationParams>,
 ) -> Result<PaginatedResponse<Capture>, String> {
     let user_id = require_authenticated_with_token(&access_token).await?;
 
     Ok(STATE.with(|state| {
-        state.borrow().get_user_id_captures(
-            &user_id,
-            filter,
+        state.borrow().get_assigned_captures(
+            &UserRef::UserId(user_id),
+            include_closed.unwrap_or(false),
             pagination.unwrap_or_default(),
         )
     }))
 }
 
-/// Create a sprint using session-based authentication
+/// Save a named view using session-based authentication
 #[update]
-async fn create_sprint_with_token(access_token: String, request: CreateSprintRequest) -> Result<Sprint, String> {
+async fn create_saved_view_with_token(access_token: String, request: CreateSavedViewRequest) -> Result<SavedView, String> {
     let user_id = require_authenticated_with_token(&access_token).await?;
 
-    let sprint = STATE.with(|state| {
-        state.borrow_mut().create_sprint_for_user_id(&user_id, request)
-    });
+    STATE.with(|state| state.borrow_mut().create_saved_view(UserRef::UserId(user_id), request))
+}
 
-    ic_cdk::println!("Created sprint {} for user_id {}", sprint.id, user_id);
-    Ok(sprint)
+/// Update a saved view using session-based authentication
+#[update]
+async fn update_saved_view_with_token(access_token: String, request: UpdateSavedViewRequest) -> Result<SavedView, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
+
+    STATE.with(|state| state.borrow_mut().update_saved_view(&UserRef::UserId(user_id), request))
 }
 
-/// Get sprints for the authenticated user (session-based)
+/// Delete a saved view using session-based authentication
 #[update]
-async fn get_my_sprints_with_token(access_token: String) -> Result<Vec<Sprint>, String> {
+async fn delete_saved_view_with_token(access_token: String, id: SavedViewId) -> Result<SavedView, String> {
     let user_id = require_authenticated_with_token(&access_token).await?;
 
-    Ok(STATE.with(|state| {
-        state.borrow().get_user_id_sprints(&user_id)
-    }))
+    STATE.with(|state| state.borrow_mut().delete_saved_view(&UserRef::UserId(user_id), id))
 }
 
-/// Create a workspace using session-based authentication
+/// Get saved views using session-based authentication
 #[update]
-async fn create_workspace_with_token(access_token: String, request: CreateWorkspaceRequest) -> Result<Workspace, String> {
+async fn get_my_saved_views_with_token(access_token: String, workspace_id: Option<WorkspaceId>) -> Result<Vec<SavedView>, String> {
     let user_id = require_authenticated_with_token(&access_token).await?;
 
-    let workspace = STATE.with(|state| {
-        state.borrow_mut().create_workspace_for_user_id(&user_id, request)
-    });
+    Ok(STATE.with(|state| state.borrow().get_saved_views(&UserRef::UserId(user_id), workspace_id)))
+}
 
-    ic_cdk::println!("Created workspace {} for user_id {}", workspace.id, user_id);
-    Ok(workspace)
+/// Run a saved view using session-based authentication
+#[update]
+async fn run_saved_view_with_token(
+    access_token: String,
+    id: SavedViewId,
+    pagination: Option<PaginationParams>,
+) -> Result<SavedViewResult, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
+    let now = ic_cdk::api::time();
+
+    STATE.with(|state| {
+        state.borrow().run_saved_view(&UserRef::UserId(user_id), id, pagination.unwrap_or_default(), now)
+    })
 }
 
-/// Get workspaces for the authenticated user (session-based)
+/// Define a custom field using session-based authentication
 #[update]
-async fn get_my_workspaces_with_token(access_token: String) -> Result<Vec<Workspace>, String> {
+async fn create_custom_field_with_token(
+    access_token: String,
+    request: CreateCustomFieldRequest,
+) -> Result<CustomFieldDefinition, String> {
     let user_id = require_authenticated_with_token(&access_token).await?;
 
-    Ok(STATE.with(|state| {
-        state.borrow().get_user_id_workspaces(&user_id)
-    }))
+    STATE.with(|state| state.borrow_mut().create_custom_field(UserRef::UserId(user_id), request))
 }
 
-/// Create a template using session-based authentication
+/// Update a custom field using session-based authentication
 #[update]
-async fn create_template_with_token(access_token: String, request: CreateTemplateRequest) -> Result<Template, String> {
+async fn update_custom_field_with_token(
+    access_token: String,
+    request: UpdateCustomFieldRequest,
+) -> Result<CustomFieldDefinition, String> {
     let user_id = require_authenticated_with_token(&access_token).await?;
 
-    let template = STATE.with(|state| {
-        state.borrow_mut().create_template_for_user_id(&user_id, request)
-    });
+    STATE.with(|state| state.borrow_mut().update_custom_field(&UserRef::UserId(user_id), request))
+}
 
-    ic_cdk::println!("Created template {} for user_id {}", template.id, user_id);
-    Ok(template)
+/// Delete a custom field using session-based authentication
+#[update]
+async fn delete_custom_field_with_token(access_token: String, id: CustomFieldId) -> Result<CustomFieldDefinition, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
+
+    STATE.with(|state| state.borrow_mut().delete_custom_field(&UserRef::UserId(user_id), id))
 }
 
-/// Get templates for the authenticated user (session-based)
+/// Get applicable custom fields using session-based authentication
 #[update]
-async fn get_my_templates_with_token(access_token: String) -> Result<Vec<Template>, String> {
+async fn get_custom_fields_with_token(
+    access_token: String,
+    workspace_id: Option<WorkspaceId>,
+) -> Result<Vec<CustomFieldDefinition>, String> {
     let user_id = require_authenticated_with_token(&access_token).await?;
 
-    Ok(STATE.with(|state| {
-        state.borrow().get_user_id_templates(&user_id)
-    }))
+    STATE.with(|state| state.borrow().get_custom_fields(&UserRef::UserId(user_id), workspace_id))
+}
+
+/// Get backlinks using session-based authentication
+#[update]
+async fn get_backlinks_with_token(access_token: String, entity: CommentTarget) -> Result<Vec<Backlink>, String> {
+    let user_id = require_authenticated_with_token(&access_token).await?;
+
+    STATE.with(|state| state.borrow().get_backlinks(&UserRef::UserId(user_id), entity))
 }
 
 // =============================================================================
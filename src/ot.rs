@@ -0,0 +1,362 @@
+//! Operational transformation for document patches
+//!
+//! Uses the retain/insert/delete model of ot.js: an operation walks the whole
+//! document, so composing and transforming are single linear passes. Clients
+//! send positional `PatchOp`s, which are folded into one operation here.
+//! Positions and lengths count Unicode scalar values (`char`s). When two
+//! concurrent inserts land at the same position, the transformed operation's
+//! text goes first.
+
+use crate::diff::LineChange;
+use crate::types::PatchOp;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Component {
+    Retain(usize),
+    Insert(String),
+    Delete(usize),
+}
+
+/// Edit spanning a whole document of `base_len` chars
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TextOperation {
+    components: Vec<Component>,
+    base_len: usize,
+    target_len: usize,
+}
+
+fn char_len(text: &str) -> usize {
+    text.chars().count()
+}
+
+/// Split text after `n` chars
+fn split_chars(text: &str, n: usize) -> (String, String) {
+    let at = text.char_indices().nth(n).map_or(text.len(), |(i, _)| i);
+    (text[..at].to_string(), text[at..].to_string())
+}
+
+/// What remains of a retain or delete after consuming `n` chars of it
+fn shrink(component: &Component, n: usize) -> Option<Component> {
+    match component {
+        Component::Retain(len) if *len > n => Some(Component::Retain(len - n)),
+        Component::Delete(len) if *len > n => Some(Component::Delete(len - n)),
+        _ => None,
+    }
+}
+
+impl TextOperation {
+    /// Length in chars of the text the operation applies to
+    pub fn base_len(&self) -> usize {
+        self.base_len
+    }
+
+    fn retain(&mut self, n: usize) {
+        if n == 0 {
+            return;
+        }
+        self.base_len += n;
+        self.target_len += n;
+        match self.components.last_mut() {
+            Some(Component::Retain(len)) => *len += n,
+            _ => self.components.push(Component::Retain(n)),
+        }
+    }
+
+    /// Inserts are kept before an adjacent delete so equal operations have
+    /// equal components
+    fn insert(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        self.target_len += char_len(text);
+        let count = self.components.len();
+        match self.components.last_mut() {
+            Some(Component::Insert(existing)) => existing.push_str(text),
+            Some(Component::Delete(_)) => match count.checked_sub(2).map(|i| &mut self.components[i]) {
+                Some(Component::Insert(existing)) => existing.push_str(text),
+                _ => self.components.insert(count - 1, Component::Insert(text.to_string())),
+            },
+            _ => self.components.push(Component::Insert(text.to_string())),
+        }
+    }
+
+    fn delete(&mut self, n: usize) {
+        if n == 0 {
+            return;
+        }
+        self.base_len += n;
+        match self.components.last_mut() {
+            Some(Component::Delete(len)) => *len += n,
+            _ => self.components.push(Component::Delete(n)),
+        }
+    }
+
+    /// Whether the operation leaves the text unchanged
+    pub fn is_noop(&self) -> bool {
+        self.components.iter().all(|c| matches!(c, Component::Retain(_)))
+    }
+
+    /// Apply the operation to a text of `base_len` chars
+    pub fn apply(&self, text: &str) -> Result<String, String> {
+        if char_len(text) != self.base_len {
+            return Err("Patch does not match the document length".to_string());
+        }
+        let mut chars = text.chars();
+        let mut result = String::with_capacity(text.len());
+        for component in &self.components {
+            match component {
+                Component::Retain(n) => result.extend(chars.by_ref().take(*n)),
+                Component::Insert(inserted) => result.push_str(inserted),
+                Component::Delete(n) => chars.by_ref().take(*n).for_each(drop),
+            }
+        }
+        Ok(result)
+    }
+
+    /// Operation for a sequence of positional ops, each relative to the text
+    /// left by the ones before it
+    pub fn from_patch(ops: &[PatchOp], base_len: usize) -> Result<Self, String> {
+        let mut operation = TextOperation::default();
+        operation.retain(base_len);
+
+        for op in ops {
+            let len = operation.target_len;
+            let mut step = TextOperation::default();
+            match op {
+                PatchOp::Insert { position, text } => {
+                    let position = *position as usize;
+                    if position > len {
+                        return Err(format!("Insert position {} is past the end of the document", position));
+                    }
+                    step.retain(position);
+                    step.insert(text);
+                    step.retain(len - position);
+                }
+                PatchOp::Delete { position, length } => {
+                    let (position, length) = (*position as usize, *length as usize);
+                    if position + length > len {
+                        return Err(format!("Delete at {} runs past the end of the document", position));
+                    }
+                    step.retain(position);
+                    step.delete(length);
+                    step.retain(len - position - length);
+                }
+            }
+            operation = operation.compose(&step)?;
+        }
+        Ok(operation)
+    }
+
+    /// Positional ops equivalent to the operation, applied in order
+    pub fn to_patch(&self) -> Vec<PatchOp> {
+        let mut position = 0usize;
+        let mut ops = Vec::new();
+        for component in &self.components {
+            match component {
+                Component::Retain(n) => position += n,
+                Component::Insert(text) => {
+                    ops.push(PatchOp::Insert { position: position as u32, text: text.clone() });
+                    position += char_len(text);
+                }
+                Component::Delete(n) => ops.push(PatchOp::Delete { position: position as u32, length: *n as u32 }),
+            }
+        }
+        ops
+    }
+
+    /// Operation turning the old text of a line diff into its new text
+    pub fn from_changes(changes: &[LineChange]) -> Self {
+        let mut operation = TextOperation::default();
+        for change in changes {
+            match *change {
+                LineChange::Unchanged(line) => operation.retain(char_len(line)),
+                LineChange::Removed(line) => operation.delete(char_len(line)),
+                LineChange::Added(line) => operation.insert(line),
+            }
+        }
+        operation
+    }
+
+    /// Single operation with the effect of `self` followed by `other`
+    pub fn compose(&self, other: &TextOperation) -> Result<TextOperation, String> {
+        if self.target_len != other.base_len {
+            return Err("Cannot compose operations of mismatched lengths".to_string());
+        }
+        let mut result = TextOperation::default();
+        let (mut first, mut second) = (self.components.iter().cloned(), other.components.iter().cloned());
+        let (mut a, mut b) = (first.next(), second.next());
+
+        loop {
+            match (&a, &b) {
+                (None, None) => break,
+                (Some(Component::Delete(n)), _) => {
+                    result.delete(*n);
+                    a = first.next();
+                }
+                (_, Some(Component::Insert(text))) => {
+                    result.insert(text);
+                    b = second.next();
+                }
+                (None, _) | (_, None) => return Err("Cannot compose operations of mismatched lengths".to_string()),
+                (Some(Component::Retain(n1)), Some(Component::Retain(n2))) => {
+                    let n = (*n1).min(*n2);
+                    result.retain(n);
+                    (a, b) = (shrink(a.as_ref().unwrap(), n), shrink(b.as_ref().unwrap(), n));
+                }
+                (Some(Component::Retain(n1)), Some(Component::Delete(n2))) => {
+                    let n = (*n1).min(*n2);
+                    result.delete(n);
+                    (a, b) = (shrink(a.as_ref().unwrap(), n), shrink(b.as_ref().unwrap(), n));
+                }
+                (Some(Component::Insert(text)), Some(Component::Retain(n2))) => {
+                    let n = char_len(text).min(*n2);
+                    let (kept, rest) = split_chars(text, n);
+                    result.insert(&kept);
+                    b = shrink(b.as_ref().unwrap(), n);
+                    a = (!rest.is_empty()).then_some(Component::Insert(rest));
+                }
+                (Some(Component::Insert(text)), Some(Component::Delete(n2))) => {
+                    let n = char_len(text).min(*n2);
+                    let (_, rest) = split_chars(text, n);
+                    b = shrink(b.as_ref().unwrap(), n);
+                    a = (!rest.is_empty()).then_some(Component::Insert(rest));
+                }
+            }
+            if a.is_none() {
+                a = first.next();
+            }
+            if b.is_none() {
+                b = second.next();
+            }
+        }
+        Ok(result)
+    }
+
+    /// Transform two concurrent operations on the same text into `(a', b')`
+    /// such that `a` then `b'` equals `b` then `a'`
+    pub fn transform(a: &TextOperation, b: &TextOperation) -> Result<(TextOperation, TextOperation), String> {
+        if a.base_len != b.base_len {
+            return Err("Cannot transform operations on different texts".to_string());
+        }
+        let (mut a_prime, mut b_prime) = (TextOperation::default(), TextOperation::default());
+        let (mut first, mut second) = (a.components.iter().cloned(), b.components.iter().cloned());
+        let (mut x, mut y) = (first.next(), second.next());
+
+        loop {
+            match (&x, &y) {
+                (None, None) => break,
+                (Some(Component::Insert(text)), _) => {
+                    a_prime.insert(text);
+                    b_prime.retain(char_len(text));
+                    x = first.next();
+                }
+                (_, Some(Component::Insert(text))) => {
+                    a_prime.retain(char_len(text));
+                    b_prime.insert(text);
+                    y = second.next();
+                }
+                (None, _) | (_, None) => return Err("Cannot transform operations on different texts".to_string()),
+                (Some(Component::Retain(n1)), Some(Component::Retain(n2))) => {
+                    let n = (*n1).min(*n2);
+                    a_prime.retain(n);
+                    b_prime.retain(n);
+                    (x, y) = (shrink(x.as_ref().unwrap(), n), shrink(y.as_ref().unwrap(), n));
+                }
+                (Some(Component::Delete(n1)), Some(Component::Delete(n2))) => {
+                    let n = (*n1).min(*n2);
+                    (x, y) = (shrink(x.as_ref().unwrap(), n), shrink(y.as_ref().unwrap(), n));
+                }
+                (Some(Component::Delete(n1)), Some(Component::Retain(n2))) => {
+                    let n = (*n1).min(*n2);
+                    a_prime.delete(n);
+                    (x, y) = (shrink(x.as_ref().unwrap(), n), shrink(y.as_ref().unwrap(), n));
+                }
+                (Some(Component::Retain(n1)), Some(Component::Delete(n2))) => {
+                    let n = (*n1).min(*n2);
+                    b_prime.delete(n);
+                    (x, y) = (shrink(x.as_ref().unwrap(), n), shrink(y.as_ref().unwrap(), n));
+                }
+            }
+            if x.is_none() {
+                x = first.next();
+            }
+            if y.is_none() {
+                y = second.next();
+            }
+        }
+        Ok((a_prime, b_prime))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::diff_lines;
+
+    fn insert(position: u32, text: &str) -> PatchOp {
+        PatchOp::Insert { position, text: text.to_string() }
+    }
+
+    fn delete(position: u32, length: u32) -> PatchOp {
+        PatchOp::Delete { position, length }
+    }
+
+    fn patch(ops: &[PatchOp], text: &str) -> TextOperation {
+        TextOperation::from_patch(ops, char_len(text)).unwrap()
+    }
+
+    #[test]
+    fn test_from_patch_applies_ops_in_order() {
+        let text = "hello world";
+        let op = patch(&[delete(0, 5), insert(0, "goodbye"), insert(13, "!")], text);
+        assert_eq!(op.apply(text).unwrap(), "goodbye world!");
+        assert!(TextOperation::from_patch(&[insert(12, "x")], 11).is_err());
+        assert!(TextOperation::from_patch(&[delete(10, 2)], 11).is_err());
+    }
+
+    #[test]
+    fn test_positions_count_chars() {
+        let text = "héllo 👋";
+        assert_eq!(patch(&[insert(7, "!"), delete(1, 1)], text).apply(text).unwrap(), "hllo 👋!");
+    }
+
+    #[test]
+    fn test_to_patch_round_trips() {
+        let text = "abcdef";
+        let op = patch(&[delete(1, 2), insert(3, "XY"), insert(0, ">")], text);
+        let ops = op.to_patch();
+        assert_eq!(patch(&ops, text), op);
+        assert_eq!(TextOperation::from_patch(&ops, 6).unwrap().apply(text).unwrap(), ">adeXYf");
+    }
+
+    #[test]
+    fn test_transform_converges() {
+        let text = "the quick brown fox";
+        let cases: [(&[PatchOp], &[PatchOp]); 5] = [
+            (&[insert(4, "very ")], &[insert(10, "dark ")]),
+            (&[delete(4, 6)], &[insert(8, "ck")]),
+            (&[delete(4, 10)], &[delete(10, 6)]),
+            (&[insert(0, "A")], &[insert(0, "B")]),
+            (&[delete(0, 19), insert(0, "new")], &[insert(19, "!"), delete(0, 3)]),
+        ];
+        for (a_ops, b_ops) in cases {
+            let (a, b) = (patch(a_ops, text), patch(b_ops, text));
+            let (a_prime, b_prime) = TextOperation::transform(&a, &b).unwrap();
+            let via_a = b_prime.apply(&a.apply(text).unwrap()).unwrap();
+            let via_b = a_prime.apply(&b.apply(text).unwrap()).unwrap();
+            assert_eq!(via_a, via_b, "{:?} / {:?}", a_ops, b_ops);
+        }
+
+        let (a, b) = (patch(&[insert(0, "A")], text), patch(&[insert(0, "B")], text));
+        let (a_prime, _) = TextOperation::transform(&a, &b).unwrap();
+        assert!(a_prime.apply(&b.apply(text).unwrap()).unwrap().starts_with("AB"));
+    }
+
+    #[test]
+    fn test_from_changes() {
+        let (old, new) = ("# Title\nbody\nend", "# Title\nnew body\nend\n");
+        let op = TextOperation::from_changes(&diff_lines(old, new));
+        assert_eq!(op.apply(old).unwrap(), new);
+        assert!(TextOperation::from_changes(&diff_lines(old, old)).is_noop());
+    }
+}
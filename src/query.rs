@@ -0,0 +1,499 @@
+//! Capture query language
+//!
+//! A query is a whitespace-separated list of terms that must all match:
+//!
+//! - `field:value` or `field:(a|b)`: equality, any of the listed values
+//! - `field<value`, `<=`, `>`, `>=`: comparisons on dates and estimates
+//! - `"quoted text"` or a bare word: case-insensitive search over title,
+//!   description and content
+//! - a leading `-` negates a term
+//!
+//! Fields are type, status, priority, label, sprint, workspace, parent,
+//! assignee, estimate, due, start, created and updated; `cf.<key>` refers to a
+//! custom field and compares by the field's kind. Dates are `YYYY-MM-DD`
+//! (a whole UTC day), `today` or an offset from now such as `+7d`, `-24h` or
+//! `+2w`. Errors carry the byte offset of the offending input.
+
+use crate::datetime::{day_of, parse_ymd, NANOS_PER_DAY, NANOS_PER_SECOND};
+use crate::types::{
+    CaptureId, CaptureStatus, CaptureType, DateField, Priority, QueryError, SprintId, Timestamp,
+    WorkspaceId,
+};
+
+/// Longest accepted query
+pub const MAX_QUERY_LEN: usize = 1000;
+
+/// Most terms accepted in one query
+pub const MAX_QUERY_TERMS: usize = 50;
+
+const NANOS_PER_HOUR: i64 = 3_600 * NANOS_PER_SECOND as i64;
+
+/// Comparison operator of a term (`:` is equality)
+pub use crate::types::FieldComparison as Comparison;
+
+/// Date operand, resolved against the time the query runs
+#[derive(Clone, Debug, PartialEq)]
+pub enum DateValue {
+    /// A UTC day (days since the Unix epoch)
+    Day(i64),
+    /// The UTC day containing now
+    Today,
+    /// Nanoseconds from now
+    Offset(i64),
+}
+
+impl DateValue {
+    /// Half-open span the value covers at `now`: whole days for dates, a single
+    /// instant for offsets
+    pub fn span(&self, now: Timestamp) -> (Timestamp, Timestamp) {
+        match self {
+            DateValue::Day(day) => day_span(*day),
+            DateValue::Today => day_span(day_of(now)),
+            DateValue::Offset(offset) => {
+                let at = (now as i128 + *offset as i128).clamp(0, u64::MAX as i128 - 1) as u64;
+                (at, at + 1)
+            }
+        }
+    }
+}
+
+/// Condition of a single term
+#[derive(Clone, Debug, PartialEq)]
+pub enum Predicate {
+    Type(Vec<CaptureType>),
+    Status(Vec<CaptureStatus>),
+    Priority(Vec<Priority>),
+    Label(Vec<String>),
+    Sprint(Vec<SprintId>),
+    Workspace(Vec<WorkspaceId>),
+    Parent(Vec<CaptureId>),
+    /// Handles or principals, resolved by the caller
+    Assignee(Vec<String>),
+    Estimate(Comparison, u32),
+    Date(DateField, Comparison, DateValue),
+    /// Custom field key, comparison and operands (any operand may match)
+    Custom(String, Comparison, Vec<String>),
+    /// Lowercased search text
+    Text(String),
+}
+
+/// Term of a query, optionally negated
+#[derive(Clone, Debug, PartialEq)]
+pub struct Term {
+    pub negated: bool,
+    pub predicate: Predicate,
+}
+
+/// Parsed query; a capture matches when every term matches
+#[derive(Clone, Debug, PartialEq)]
+pub struct Query {
+    pub terms: Vec<Term>,
+}
+
+/// Parse a query string
+pub fn parse_query(input: &str) -> Result<Query, QueryError> {
+    if input.len() > MAX_QUERY_LEN {
+        return Err(error(MAX_QUERY_LEN, format!("Query too long (max {} bytes)", MAX_QUERY_LEN)));
+    }
+
+    let mut parser = Parser { input, pos: 0 };
+    let mut terms = Vec::new();
+    while parser.skip_whitespace() {
+        if terms.len() == MAX_QUERY_TERMS {
+            return Err(error(parser.pos, format!("Too many terms (max {})", MAX_QUERY_TERMS)));
+        }
+        terms.push(parser.term()?);
+    }
+
+    Ok(Query { terms })
+}
+
+/// Half-open range of timestamps matching a date comparison at `now`
+pub fn date_range(comparison: &Comparison, value: &DateValue, now: Timestamp) -> (Timestamp, Timestamp) {
+    let (start, end) = value.span(now);
+    match comparison {
+        Comparison::Eq => (start, end),
+        Comparison::Lt => (0, start),
+        Comparison::Le => (0, end),
+        Comparison::Gt => (end, u64::MAX),
+        Comparison::Ge => (start, u64::MAX),
+    }
+}
+
+/// Compare a capture's estimate with a term's value
+pub fn compare_estimate(comparison: &Comparison, estimate: u32, value: u32) -> bool {
+    match comparison {
+        Comparison::Eq => estimate == value,
+        Comparison::Lt => estimate < value,
+        Comparison::Le => estimate <= value,
+        Comparison::Gt => estimate > value,
+        Comparison::Ge => estimate >= value,
+    }
+}
+
+fn day_span(day: i64) -> (Timestamp, Timestamp) {
+    let start = day.max(0) as u64 * NANOS_PER_DAY;
+    (start, start + NANOS_PER_DAY)
+}
+
+fn error(position: usize, message: String) -> QueryError {
+    QueryError {
+        position: position as u32,
+        message,
+    }
+}
+
+/// Value of a term with its byte offset
+type Value<'a> = (&'a str, usize);
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.pos).copied()
+    }
+
+    fn at_boundary(&self) -> bool {
+        self.peek().is_none_or(|b| b.is_ascii_whitespace())
+    }
+
+    /// Skip whitespace; false once the input is exhausted
+    fn skip_whitespace(&mut self) -> bool {
+        while self.peek().is_some_and(|b| b.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+        self.peek().is_some()
+    }
+
+    /// Advance while `accept` holds and return the consumed text
+    fn take_while(&mut self, accept: impl Fn(u8) -> bool) -> &'a str {
+        let start = self.pos;
+        while self.peek().is_some_and(&accept) {
+            self.pos += 1;
+        }
+        &self.input[start..self.pos]
+    }
+
+    fn term(&mut self) -> Result<Term, QueryError> {
+        let negated = self.peek() == Some(b'-')
+            && self.input.as_bytes().get(self.pos + 1).is_some_and(|b| !b.is_ascii_whitespace());
+        if negated {
+            self.pos += 1;
+        }
+
+        if self.peek() == Some(b'"') {
+            let predicate = Predicate::Text(self.quoted()?);
+            return Ok(Term { negated, predicate });
+        }
+
+        let key_pos = self.pos;
+        let key = self.take_while(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-' || b == b'.');
+        let Some(comparison) = self.operator() else {
+            // Not a field term: the whole word is search text
+            self.pos = key_pos;
+            let word = self.take_while(|b| !b.is_ascii_whitespace());
+            return Ok(Term {
+                negated,
+                predicate: Predicate::Text(word.to_lowercase()),
+            });
+        };
+        if key.is_empty() {
+            return Err(error(key_pos, "Expected a field name".to_string()));
+        }
+
+        let values = self.values(&comparison)?;
+        let predicate = field_predicate(key, key_pos, comparison, values)?;
+        Ok(Term { negated, predicate })
+    }
+
+    fn quoted(&mut self) -> Result<String, QueryError> {
+        let open = self.pos;
+        self.pos += 1;
+        let text = self.take_while(|b| b != b'"');
+        if self.peek() != Some(b'"') {
+            return Err(error(open, "Unterminated quote".to_string()));
+        }
+        self.pos += 1;
+        if text.trim().is_empty() {
+            return Err(error(open, "Empty search text".to_string()));
+        }
+        Ok(text.to_lowercase())
+    }
+
+    fn operator(&mut self) -> Option<Comparison> {
+        let (comparison, len) = match (self.peek()?, self.input.as_bytes().get(self.pos + 1)) {
+            (b':', _) => (Comparison::Eq, 1),
+            (b'<', Some(b'=')) => (Comparison::Le, 2),
+            (b'<', _) => (Comparison::Lt, 1),
+            (b'>', Some(b'=')) => (Comparison::Ge, 2),
+            (b'>', _) => (Comparison::Gt, 1),
+            _ => return None,
+        };
+        self.pos += len;
+        Some(comparison)
+    }
+
+    /// A single value, or `(a|b|...)` after `:`
+    fn values(&mut self, comparison: &Comparison) -> Result<Vec<Value<'a>>, QueryError> {
+        let is_value_byte = |b: u8| !b.is_ascii_whitespace() && b != b'(' && b != b')' && b != b'|';
+
+        if self.peek() != Some(b'(') || *comparison != Comparison::Eq {
+            let pos = self.pos;
+            let value = self.take_while(is_value_byte);
+            if value.is_empty() {
+                return Err(error(self.pos, "Expected a value".to_string()));
+            }
+            if !self.at_boundary() {
+                return Err(error(self.pos, format!("Unexpected '{}'", self.input[self.pos..].chars().next().unwrap_or(' '))));
+            }
+            return Ok(vec![(value, pos)]);
+        }
+
+        let open = self.pos;
+        self.pos += 1;
+        let mut values = Vec::new();
+        loop {
+            let pos = self.pos;
+            let value = self.take_while(is_value_byte);
+            if value.is_empty() {
+                return Err(error(pos, "Expected a value".to_string()));
+            }
+            values.push((value, pos));
+            match self.peek() {
+                Some(b'|') => self.pos += 1,
+                Some(b')') => {
+                    self.pos += 1;
+                    break;
+                }
+                _ => return Err(error(open, "Unclosed '('".to_string())),
+            }
+        }
+        if !self.at_boundary() {
+            return Err(error(self.pos, "Expected whitespace after ')'".to_string()));
+        }
+        Ok(values)
+    }
+}
+
+fn field_predicate(key: &str, key_pos: usize, comparison: Comparison, values: Vec<Value>) -> Result<Predicate, QueryError> {
+    let field = key.to_ascii_lowercase();
+    if let Some(custom_key) = field.strip_prefix("cf.") {
+        if custom_key.is_empty() {
+            return Err(error(key_pos, "Expected a custom field key after 'cf.'".to_string()));
+        }
+        if comparison != Comparison::Eq && values.len() > 1 {
+            return Err(error(key_pos, format!("Field '{}' takes a single value", key)));
+        }
+        let operands = values.iter().map(|(v, _)| v.to_string()).collect();
+        return Ok(Predicate::Custom(custom_key.to_string(), comparison, operands));
+    }
+    let date_field = match field.as_str() {
+        "due" => Some(DateField::Due),
+        "start" => Some(DateField::Start),
+        "created" => Some(DateField::Created),
+        "updated" => Some(DateField::Updated),
+        _ => None,
+    };
+
+    if field == "estimate" || date_field.is_some() {
+        let [(value, pos)] = values[..] else {
+            return Err(error(key_pos, format!("Field '{}' takes a single value", key)));
+        };
+        return match date_field {
+            Some(date_field) => Ok(Predicate::Date(date_field, comparison, parse_date(value, pos)?)),
+            None => value
+                .parse::<u32>()
+                .map(|estimate| Predicate::Estimate(comparison, estimate))
+                .map_err(|_| error(pos, format!("Invalid estimate '{}'", value))),
+        };
+    }
+
+    let known = matches!(
+        field.as_str(),
+        "type" | "status" | "priority" | "label" | "sprint" | "workspace" | "parent" | "assignee"
+    );
+    if !known {
+        return Err(error(key_pos, format!("Unknown field '{}'", key)));
+    }
+    if comparison != Comparison::Eq {
+        return Err(error(key_pos, format!("Field '{}' only supports ':'", key)));
+    }
+
+    Ok(match field.as_str() {
+        "type" => Predicate::Type(parse_each(&values, "type", parse_type)?),
+        "status" => Predicate::Status(parse_each(&values, "status", parse_status)?),
+        "priority" => Predicate::Priority(parse_each(&values, "priority", parse_priority)?),
+        "sprint" => Predicate::Sprint(parse_each(&values, "ID", |v| v.parse().ok())?),
+        "workspace" => Predicate::Workspace(parse_each(&values, "ID", |v| v.parse().ok())?),
+        "parent" => Predicate::Parent(parse_each(&values, "ID", |v| v.parse().ok())?),
+        "label" => Predicate::Label(values.iter().map(|(v, _)| v.to_string()).collect()),
+        _ => Predicate::Assignee(values.iter().map(|(v, _)| v.to_string()).collect()),
+    })
+}
+
+fn parse_each<T>(values: &[Value], what: &str, parse: impl Fn(&str) -> Option<T>) -> Result<Vec<T>, QueryError> {
+    values
+        .iter()
+        .map(|(value, pos)| parse(value).ok_or_else(|| error(*pos, format!("Unknown {} '{}'", what, value))))
+        .collect()
+}
+
+fn parse_type(value: &str) -> Option<CaptureType> {
+    match value.to_ascii_lowercase().as_str() {
+        "idea" => Some(CaptureType::Idea),
+        "task" => Some(CaptureType::Task),
+        "project" => Some(CaptureType::Project),
+        "reflection" => Some(CaptureType::Reflection),
+        "outline" => Some(CaptureType::Outline),
+        "calendar" => Some(CaptureType::Calendar),
+        _ => None,
+    }
+}
+
+fn parse_status(value: &str) -> Option<CaptureStatus> {
+    match value.to_ascii_lowercase().as_str() {
+        "draft" => Some(CaptureStatus::Draft),
+        "active" => Some(CaptureStatus::Active),
+        "inprogress" | "in_progress" | "in-progress" => Some(CaptureStatus::InProgress),
+        "blocked" => Some(CaptureStatus::Blocked),
+        "completed" | "done" => Some(CaptureStatus::Completed),
+        "archived" => Some(CaptureStatus::Archived),
+        "cancelled" | "canceled" => Some(CaptureStatus::Cancelled),
+        _ => None,
+    }
+}
+
+fn parse_priority(value: &str) -> Option<Priority> {
+    match value.to_ascii_lowercase().as_str() {
+        "low" => Some(Priority::Low),
+        "medium" => Some(Priority::Medium),
+        "high" => Some(Priority::High),
+        "critical" => Some(Priority::Critical),
+        _ => None,
+    }
+}
+
+/// `YYYY-MM-DD`, `today` or a signed offset with an `h`, `d` or `w` unit
+fn parse_date(value: &str, pos: usize) -> Result<DateValue, QueryError> {
+    let invalid = || error(pos, format!("Invalid date '{}' (expected YYYY-MM-DD, today or an offset like +7d)", value));
+
+    if value.eq_ignore_ascii_case("today") {
+        return Ok(DateValue::Today);
+    }
+
+    if let Some(body) = value.strip_prefix(['+', '-']) {
+        let unit = match body.chars().last() {
+            Some('h') => NANOS_PER_HOUR,
+            Some('d') => 24 * NANOS_PER_HOUR,
+            Some('w') => 7 * 24 * NANOS_PER_HOUR,
+            _ => return Err(invalid()),
+        };
+        let amount: u32 = body[..body.len() - 1].parse().map_err(|_| invalid())?;
+        let offset = (amount as i64).checked_mul(unit).ok_or_else(invalid)?;
+        return Ok(DateValue::Offset(if value.starts_with('-') { -offset } else { offset }));
+    }
+
+    parse_ymd(value).map(DateValue::Day).ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datetime::days_from_civil;
+
+    fn predicates(input: &str) -> Vec<Predicate> {
+        parse_query(input).unwrap().terms.into_iter().map(|t| t.predicate).collect()
+    }
+
+    fn error_at(input: &str) -> (u32, String) {
+        let err = parse_query(input).unwrap_err();
+        (err.position, err.message)
+    }
+
+    #[test]
+    fn test_parse_full_query() {
+        let query = parse_query(
+            "type:task status:(active|inprogress) label:backend due<2026-11-01 estimate>=3 \"Search Text\"",
+        )
+        .unwrap();
+        let day = days_from_civil(2026, 11, 1);
+        assert_eq!(
+            query.terms.into_iter().map(|t| t.predicate).collect::<Vec<_>>(),
+            vec![
+                Predicate::Type(vec![CaptureType::Task]),
+                Predicate::Status(vec![CaptureStatus::Active, CaptureStatus::InProgress]),
+                Predicate::Label(vec!["backend".to_string()]),
+                Predicate::Date(DateField::Due, Comparison::Lt, DateValue::Day(day)),
+                Predicate::Estimate(Comparison::Ge, 3),
+                Predicate::Text("search text".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_negation_offsets_and_bare_words() {
+        let query = parse_query("-label:wip updated>-24h Launch - plan").unwrap();
+        assert!(query.terms[0].negated);
+        assert_eq!(
+            query.terms[1].predicate,
+            Predicate::Date(DateField::Updated, Comparison::Gt, DateValue::Offset(-24 * NANOS_PER_HOUR))
+        );
+        assert_eq!(
+            predicates("Launch - plan")[..],
+            [
+                Predicate::Text("launch".to_string()),
+                Predicate::Text("-".to_string()),
+                Predicate::Text("plan".to_string()),
+            ]
+        );
+        assert!(parse_query("   ").unwrap().terms.is_empty());
+    }
+
+    #[test]
+    fn test_custom_field_terms() {
+        assert_eq!(
+            predicates("cf.story-points>=3 cf.team:(core|infra)")[..],
+            [
+                Predicate::Custom("story-points".to_string(), Comparison::Ge, vec!["3".to_string()]),
+                Predicate::Custom("team".to_string(), Comparison::Eq, vec!["core".to_string(), "infra".to_string()]),
+            ]
+        );
+        assert_eq!(error_at("cf.:x"), (0, "Expected a custom field key after 'cf.'".to_string()));
+    }
+
+    #[test]
+    fn test_errors_report_positions() {
+        assert_eq!(error_at("type:task colour:red"), (10, "Unknown field 'colour'".to_string()));
+        assert_eq!(error_at("status:(active|nope)"), (15, "Unknown status 'nope'".to_string()));
+        assert_eq!(error_at("label:a \"open"), (8, "Unterminated quote".to_string()));
+        assert_eq!(error_at("status:(active"), (7, "Unclosed '('".to_string()));
+        assert_eq!(error_at("type<task"), (0, "Field 'type' only supports ':'".to_string()));
+        assert_eq!(error_at("due:"), (4, "Expected a value".to_string()));
+        assert_eq!(error_at("estimate:(1|2)").1, "Field 'estimate' takes a single value");
+        assert_eq!(error_at("due<2026-02-30").0, 4);
+        assert_eq!(error_at("due>+7x").0, 4);
+    }
+
+    #[test]
+    fn test_date_ranges() {
+        let day = days_from_civil(2026, 11, 1);
+        let start = day as u64 * NANOS_PER_DAY;
+        let end = start + NANOS_PER_DAY;
+        let value = DateValue::Day(day);
+
+        assert_eq!(date_range(&Comparison::Eq, &value, 0), (start, end));
+        assert_eq!(date_range(&Comparison::Lt, &value, 0), (0, start));
+        assert_eq!(date_range(&Comparison::Le, &value, 0), (0, end));
+        assert_eq!(date_range(&Comparison::Gt, &value, 0), (end, u64::MAX));
+        assert_eq!(date_range(&Comparison::Ge, &value, 0), (start, u64::MAX));
+
+        // Offsets compare against an instant rather than a whole day
+        let now = start + 5 * NANOS_PER_HOUR as u64;
+        let hour_ago = DateValue::Offset(-NANOS_PER_HOUR);
+        assert_eq!(date_range(&Comparison::Gt, &hour_ago, now).0, now - NANOS_PER_HOUR as u64 + 1);
+        assert_eq!(DateValue::Today.span(now), (start, end));
+    }
+}
@@ -0,0 +1,159 @@
+//! Fractional rank keys for manual ordering
+//!
+//! Rank keys are base-62 strings that sort lexicographically. A new key can
+//! always be generated between any two existing keys, so moving an item only
+//! rewrites that item's key instead of renumbering the whole list.
+//!
+//! Keys never end in the zero digit, which guarantees there is room for
+//! another key below any existing one.
+
+/// Digit alphabet, in ascending ASCII order so byte comparison matches rank order
+const DIGITS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+const BASE: usize = 62;
+
+/// Generate a rank key that sorts strictly between `lower` and `upper`.
+///
+/// `None` for `lower` means "before everything", `None` for `upper` means
+/// "after everything".
+///
+/// # Returns
+/// * `Ok(String)` with the new key
+/// * `Err(String)` if a key is malformed or `lower` does not sort before `upper`
+pub fn rank_between(lower: Option<&str>, upper: Option<&str>) -> Result<String, String> {
+    let a = match lower {
+        Some(key) => decode(key)?,
+        None => Vec::new(),
+    };
+    let b = match upper {
+        Some(key) => Some(decode(key)?),
+        None => None,
+    };
+
+    if let (Some(lower), Some(upper)) = (lower, upper) {
+        if lower >= upper {
+            return Err(format!("Rank {} must sort before {}", lower, upper));
+        }
+    }
+
+    Ok(encode(&midpoint(&a, b.as_deref())))
+}
+
+/// Generate a rank key that sorts after `last` (or the first key if `last` is None)
+pub fn rank_after(last: Option<&str>) -> String {
+    rank_between(last, None).unwrap_or_else(|_| rank_between(None, None).unwrap_or_default())
+}
+
+/// Compute the digits of a key between `a` and `b` (`b` = None means infinity).
+/// `a` may be empty, which represents the lowest possible key.
+fn midpoint(a: &[u8], b: Option<&[u8]>) -> Vec<u8> {
+    if let Some(b) = b {
+        // Skip the common prefix (treating missing digits of `a` as zero)
+        let mut n = 0;
+        while n < b.len() && a.get(n).copied().unwrap_or(0) == b[n] {
+            n += 1;
+        }
+        if n > 0 {
+            let rest_a = if n < a.len() { &a[n..] } else { &[] };
+            let mut out = b[..n].to_vec();
+            out.extend(midpoint(rest_a, Some(&b[n..])));
+            return out;
+        }
+    }
+
+    let digit_a = a.first().copied().unwrap_or(0) as usize;
+    let digit_b = b.map(|b| b[0] as usize).unwrap_or(BASE);
+
+    if digit_b - digit_a > 1 {
+        // There is a free digit between the two leading digits
+        vec![((digit_a + digit_b) / 2) as u8]
+    } else if let Some(b) = b.filter(|b| b.len() > 1) {
+        // Leading digits are consecutive, but `b` continues: its first digit alone sorts between
+        vec![b[0]]
+    } else {
+        // Keep the leading digit of `a` and find room in the next position
+        let rest_a = if a.len() > 1 { &a[1..] } else { &[] };
+        let mut out = vec![digit_a as u8];
+        out.extend(midpoint(rest_a, None));
+        out
+    }
+}
+
+fn decode(key: &str) -> Result<Vec<u8>, String> {
+    if key.is_empty() {
+        return Err("Rank key cannot be empty".to_string());
+    }
+    if key.ends_with('0') {
+        return Err(format!("Invalid rank key: {}", key));
+    }
+
+    key.bytes()
+        .map(|c| {
+            DIGITS
+                .iter()
+                .position(|&d| d == c)
+                .map(|i| i as u8)
+                .ok_or_else(|| format!("Invalid rank key: {}", key))
+        })
+        .collect()
+}
+
+fn encode(digits: &[u8]) -> String {
+    digits.iter().map(|&d| DIGITS[d as usize] as char).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_key_is_middle_digit() {
+        assert_eq!(rank_between(None, None).unwrap(), "V");
+    }
+
+    #[test]
+    fn test_key_sorts_between_bounds() {
+        let key = rank_between(Some("V"), Some("W")).unwrap();
+        assert!(key.as_str() > "V" && key.as_str() < "W", "got {}", key);
+    }
+
+    #[test]
+    fn test_repeated_prepend_and_append_stay_ordered() {
+        let mut keys = vec![rank_between(None, None).unwrap()];
+        for _ in 0..100 {
+            let first = keys.first().cloned().unwrap();
+            keys.insert(0, rank_between(None, Some(&first)).unwrap());
+            let last = keys.last().cloned().unwrap();
+            keys.push(rank_after(Some(&last)));
+        }
+
+        let mut sorted = keys.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(keys, sorted);
+    }
+
+    #[test]
+    fn test_repeated_insert_between_same_neighbours() {
+        let low = rank_between(None, None).unwrap();
+        let mut high = rank_after(Some(&low));
+        for _ in 0..200 {
+            let mid = rank_between(Some(&low), Some(&high)).unwrap();
+            assert!(mid > low && mid < high, "{} not between {} and {}", mid, low, high);
+            assert!(!mid.ends_with('0'));
+            high = mid;
+        }
+    }
+
+    #[test]
+    fn test_rejects_unordered_bounds() {
+        assert!(rank_between(Some("W"), Some("V")).is_err());
+        assert!(rank_between(Some("V"), Some("V")).is_err());
+    }
+
+    #[test]
+    fn test_rejects_malformed_keys() {
+        assert!(rank_between(Some("V0"), None).is_err());
+        assert!(rank_between(Some("a-b"), None).is_err());
+        assert!(rank_between(Some(""), None).is_err());
+    }
+}
@@ -0,0 +1,216 @@
+//! Recurrence rules for recurring captures
+//!
+//! Supports the daily/weekly/monthly subset of iCalendar RRULE with INTERVAL,
+//! BYDAY (weekly only), COUNT and UNTIL. Occurrences keep the time of day of
+//! the series start. Monthly rules on days missing from a month (e.g. the 31st)
+//! fall on the last day of that month.
+
+use crate::datetime::{
+    civil_from_days, day_of, days_from_civil, days_in_month, weekday_of_day, NANOS_PER_DAY,
+};
+use crate::types::{DynamicFields, RecurrenceFrequency, RecurrenceRule, Timestamp, Weekday};
+
+/// Largest accepted interval
+pub const MAX_RECURRENCE_INTERVAL: u32 = 1000;
+
+/// Timestamp a recurring capture is scheduled on: its start_date, else its due_date
+pub fn recurrence_anchor(fields: &DynamicFields) -> Option<Timestamp> {
+    fields.start_date.or(fields.due_date)
+}
+
+/// Validate a rule for a series starting at `dtstart`
+pub fn validate_rule(rule: &RecurrenceRule, dtstart: Timestamp) -> Result<(), String> {
+    if rule.interval == 0 || rule.interval > MAX_RECURRENCE_INTERVAL {
+        return Err(format!(
+            "Recurrence interval must be between 1 and {}",
+            MAX_RECURRENCE_INTERVAL
+        ));
+    }
+    if !rule.by_day.is_empty() && rule.frequency != RecurrenceFrequency::Weekly {
+        return Err("by_day is only supported for weekly recurrence".to_string());
+    }
+    if rule.count == Some(0) {
+        return Err("Recurrence count must be at least 1".to_string());
+    }
+    if let Some(until) = rule.until {
+        if until < dtstart {
+            return Err("Recurrence until cannot be before the first occurrence".to_string());
+        }
+    }
+    Ok(())
+}
+
+/// First occurrence of a series strictly after `after`.
+///
+/// Returns None once the series has passed its `until` date. `count` is not
+/// checked here; the caller tracks how many occurrences exist.
+pub fn next_occurrence(rule: &RecurrenceRule, dtstart: Timestamp, after: Timestamp) -> Option<Timestamp> {
+    let next = if after < dtstart {
+        Some(dtstart)
+    } else {
+        let interval = rule.interval.max(1) as u64;
+        match rule.frequency {
+            RecurrenceFrequency::Daily => next_fixed_step(dtstart, after, interval * NANOS_PER_DAY),
+            RecurrenceFrequency::Weekly if rule.by_day.is_empty() => {
+                next_fixed_step(dtstart, after, interval * 7 * NANOS_PER_DAY)
+            }
+            RecurrenceFrequency::Weekly => Some(next_weekly_by_day(rule, dtstart, after, interval as i64)),
+            RecurrenceFrequency::Monthly => Some(next_monthly(dtstart, after, interval as i64)),
+        }
+    };
+
+    next.filter(|t| rule.until.map(|until| *t <= until).unwrap_or(true))
+}
+
+/// Next occurrence of a series that repeats every `step` nanoseconds
+fn next_fixed_step(dtstart: Timestamp, after: Timestamp, step: u64) -> Option<Timestamp> {
+    let steps = (after - dtstart) / step + 1;
+    steps.checked_mul(step).and_then(|offset| dtstart.checked_add(offset))
+}
+
+/// Next occurrence of a weekly series restricted to certain weekdays
+fn next_weekly_by_day(rule: &RecurrenceRule, dtstart: Timestamp, after: Timestamp, interval: i64) -> Timestamp {
+    let time_of_day = dtstart % NANOS_PER_DAY;
+    let start_day = day_of(dtstart);
+    // Weeks run Monday to Sunday; week 0 is the week containing the series start
+    let week0 = start_day - weekday_of_day(start_day) as i64;
+
+    let mut day = day_of(after);
+    if day as u64 * NANOS_PER_DAY + time_of_day <= after {
+        day += 1;
+    }
+
+    loop {
+        let week_index = (day - week0) / 7;
+        let offset = week_index % interval;
+        if offset != 0 {
+            // Skip to the Monday of the next active week
+            day = week0 + (week_index + interval - offset) * 7;
+            continue;
+        }
+        if rule.by_day.contains(&weekday(day)) {
+            return day as u64 * NANOS_PER_DAY + time_of_day;
+        }
+        day += 1;
+    }
+}
+
+/// Next occurrence of a monthly series
+fn next_monthly(dtstart: Timestamp, after: Timestamp, interval: i64) -> Timestamp {
+    let time_of_day = dtstart % NANOS_PER_DAY;
+    let (start_year, start_month, start_day) = civil_from_days(day_of(dtstart));
+    let (after_year, after_month, _) = civil_from_days(day_of(after));
+
+    let months_since = (after_year - start_year) * 12 + after_month as i64 - start_month as i64;
+    let mut k = months_since / interval;
+
+    loop {
+        let month_index = start_month as i64 - 1 + k * interval;
+        let year = start_year + month_index / 12;
+        let month = (month_index % 12 + 1) as u32;
+        let day = start_day.min(days_in_month(year, month));
+
+        let candidate = days_from_civil(year, month, day) as u64 * NANOS_PER_DAY + time_of_day;
+        if candidate > after {
+            return candidate;
+        }
+        k += 1;
+    }
+}
+
+fn weekday(day: i64) -> Weekday {
+    match weekday_of_day(day) {
+        0 => Weekday::Monday,
+        1 => Weekday::Tuesday,
+        2 => Weekday::Wednesday,
+        3 => Weekday::Thursday,
+        4 => Weekday::Friday,
+        5 => Weekday::Saturday,
+        _ => Weekday::Sunday,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: u64 = 3_600 * 1_000_000_000;
+
+    fn at(year: i64, month: u32, day: u32, hour: u64) -> Timestamp {
+        days_from_civil(year, month, day) as u64 * NANOS_PER_DAY + hour * HOUR
+    }
+
+    fn rule(frequency: RecurrenceFrequency, interval: u32, by_day: Vec<Weekday>) -> RecurrenceRule {
+        RecurrenceRule {
+            frequency,
+            interval,
+            by_day,
+            count: None,
+            until: None,
+        }
+    }
+
+    #[test]
+    fn test_daily_with_interval() {
+        let start = at(2024, 1, 1, 9);
+        let r = rule(RecurrenceFrequency::Daily, 2, vec![]);
+        assert_eq!(next_occurrence(&r, start, start), Some(at(2024, 1, 3, 9)));
+        // Searching from mid-cycle lands on the next scheduled day
+        assert_eq!(next_occurrence(&r, start, at(2024, 1, 4, 0)), Some(at(2024, 1, 5, 9)));
+    }
+
+    #[test]
+    fn test_weekly_by_day() {
+        // 2024-01-01 is a Monday
+        let start = at(2024, 1, 1, 9);
+        let r = rule(RecurrenceFrequency::Weekly, 1, vec![Weekday::Monday, Weekday::Thursday]);
+        assert_eq!(next_occurrence(&r, start, start), Some(at(2024, 1, 4, 9)));
+        assert_eq!(next_occurrence(&r, start, at(2024, 1, 4, 9)), Some(at(2024, 1, 8, 9)));
+    }
+
+    #[test]
+    fn test_biweekly_by_day_skips_inactive_weeks() {
+        let start = at(2024, 1, 1, 9);
+        let r = rule(RecurrenceFrequency::Weekly, 2, vec![Weekday::Friday]);
+        assert_eq!(next_occurrence(&r, start, start), Some(at(2024, 1, 5, 9)));
+        assert_eq!(next_occurrence(&r, start, at(2024, 1, 5, 9)), Some(at(2024, 1, 19, 9)));
+    }
+
+    #[test]
+    fn test_monthly_clamps_to_month_end() {
+        let start = at(2024, 1, 31, 12);
+        let r = rule(RecurrenceFrequency::Monthly, 1, vec![]);
+        let feb = next_occurrence(&r, start, start).unwrap();
+        assert_eq!(feb, at(2024, 2, 29, 12));
+        // Clamping does not drift: March is back on the 31st
+        assert_eq!(next_occurrence(&r, start, feb), Some(at(2024, 3, 31, 12)));
+    }
+
+    #[test]
+    fn test_monthly_crosses_year() {
+        let start = at(2024, 11, 15, 0);
+        let r = rule(RecurrenceFrequency::Monthly, 3, vec![]);
+        assert_eq!(next_occurrence(&r, start, start), Some(at(2025, 2, 15, 0)));
+    }
+
+    #[test]
+    fn test_until_ends_series() {
+        let start = at(2024, 1, 1, 9);
+        let mut r = rule(RecurrenceFrequency::Daily, 1, vec![]);
+        r.until = Some(at(2024, 1, 2, 9));
+        assert_eq!(next_occurrence(&r, start, start), Some(at(2024, 1, 2, 9)));
+        assert_eq!(next_occurrence(&r, start, at(2024, 1, 2, 9)), None);
+    }
+
+    #[test]
+    fn test_validate_rule() {
+        let start = at(2024, 1, 1, 9);
+        assert!(validate_rule(&rule(RecurrenceFrequency::Daily, 0, vec![]), start).is_err());
+        assert!(validate_rule(&rule(RecurrenceFrequency::Monthly, 1, vec![Weekday::Monday]), start).is_err());
+
+        let mut r = rule(RecurrenceFrequency::Weekly, 1, vec![Weekday::Monday]);
+        assert!(validate_rule(&r, start).is_ok());
+        r.until = Some(start - 1);
+        assert!(validate_rule(&r, start).is_err());
+    }
+}
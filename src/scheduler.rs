@@ -0,0 +1,32 @@
+//! Global timer scheduling for time-based jobs (reminders, recurring captures)
+//!
+//! A canister has a single global timer. State keeps its pending jobs in
+//! time-ordered queues; whenever a queue changes the timer is re-armed for the
+//! earliest job, and when it fires every job that is due is run.
+
+use crate::state::STATE;
+use crate::types::Timestamp;
+
+/// Maximum number of jobs run per timer firing; the rest run on the next firing
+pub const MAX_JOBS_PER_TICK: usize = 100;
+
+/// Arm the global timer for `next` (or disarm it when there is nothing pending)
+pub fn arm(next: Option<Timestamp>) {
+    ic_cdk::api::set_global_timer(next.unwrap_or(0));
+}
+
+/// Global timer entry point: run due jobs and re-arm for the next one
+#[export_name = "canister_global_timer"]
+extern "C" fn canister_global_timer() {
+    ic_cdk::setup();
+    let now = ic_cdk::api::time();
+
+    STATE.with(|state| {
+        let mut s = state.borrow_mut();
+        let ran = s.run_scheduled_jobs(now, MAX_JOBS_PER_TICK);
+        if ran > 0 {
+            ic_cdk::println!("Ran {} scheduled jobs", ran);
+        }
+        arm(s.next_scheduled_job());
+    });
+}
@@ -1,8 +1,20 @@
+use crate::diff::{apply_delta, diff_lines, encode_reverse_delta, LineChange};
+use crate::datetime::{day_of, format_utc, parse_ymd, weekday_of_day, NANOS_PER_DAY};
 use crate::discussion::state::{StableDiscussionState, DISCUSSION_STATE};
+use crate::discussion::types::MAX_COMMENT_LEN;
+use crate::ics::export_calendar;
+use crate::ot::TextOperation;
+use crate::query::{compare_estimate, date_range, parse_query, Predicate, Query, Term};
+use crate::rank::{rank_after, rank_between};
+use crate::recurrence::{next_occurrence, recurrence_anchor, validate_rule};
+use crate::scheduler;
+use crate::template;
 use crate::types::*;
 use candid::Principal;
+use sha2::{Digest, Sha256};
 use std::cell::RefCell;
-use std::collections::BTreeMap;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
 
 /// State structure for the FounderyOS Core canister
 #[derive(Default)]
@@ -16,6 +28,73 @@ pub struct State {
     pub user_captures: BTreeMap<Principal, Vec<CaptureId>>,
     pub user_id_captures: BTreeMap<String, Vec<CaptureId>>,  // For session-based auth
     pub next_capture_id: CaptureId,
+    /// Recurring captures awaiting their next occurrence, keyed by schedule time (derived)
+    pub recurrence_queue: BTreeSet<(Timestamp, CaptureId)>,
+    /// Each owner's captures by start_date and by due_date (derived)
+    pub start_date_index: BTreeMap<UserRef, BTreeSet<(Timestamp, CaptureId)>>,
+    pub due_date_index: BTreeMap<UserRef, BTreeSet<(Timestamp, CaptureId)>>,
+    /// Longest start_date..due_date span of each owner's indexed captures (derived)
+    pub max_date_span: BTreeMap<UserRef, u64>,
+    /// Children of each capture via fields.parent_id, and roll-ups over them (derived)
+    pub capture_children: BTreeMap<CaptureId, Vec<CaptureId>>,
+    pub rollups: BTreeMap<CaptureId, Rollup>,
+
+    // Calendar feed tokens for ICS subscriptions (token -> owner)
+    pub calendar_feeds: BTreeMap<String, UserRef>,
+
+    // Reminders storage (capture index and fire queue are derived)
+    pub reminders: BTreeMap<ReminderId, Reminder>,
+    pub capture_reminders: BTreeMap<CaptureId, Vec<ReminderId>>,
+    pub reminder_queue: BTreeSet<(Timestamp, ReminderId)>,
+    pub next_reminder_id: ReminderId,
+
+    // Notification inboxes (keyed by principal or user_id)
+    pub notifications: BTreeMap<NotificationId, Notification>,
+    pub user_notifications: BTreeMap<UserRef, Vec<NotificationId>>,
+    pub next_notification_id: NotificationId,
+
+    // Handles for @mentions and assignment; resolved capture assignees
+    // (the reverse handle index and per-user assignment index are derived)
+    pub handles: BTreeMap<String, UserRef>,
+    pub user_handles: BTreeMap<UserRef, String>,
+    pub capture_assignees: BTreeMap<CaptureId, Vec<UserRef>>,
+    pub assigned_captures: BTreeMap<UserRef, BTreeSet<CaptureId>>,
+
+    // Time entries (capture and user indexes and running timers are derived)
+    pub time_entries: BTreeMap<TimeEntryId, TimeEntry>,
+    pub capture_time_entries: BTreeMap<CaptureId, Vec<TimeEntryId>>,
+    pub user_time_entries: BTreeMap<UserRef, Vec<TimeEntryId>>,
+    pub running_timers: BTreeMap<UserRef, TimeEntryId>,
+    pub next_time_entry_id: TimeEntryId,
+
+    // Comment threads and activity on captures and documents (target indexes are derived)
+    pub thread_comments: BTreeMap<ThreadCommentId, ThreadComment>,
+    pub target_comments: BTreeMap<CommentTarget, Vec<ThreadCommentId>>,
+    pub next_thread_comment_id: ThreadCommentId,
+    pub activity: BTreeMap<ActivityId, ActivityRecord>,
+    pub target_activity: BTreeMap<CommentTarget, Vec<ActivityId>>,
+    pub next_activity_id: ActivityId,
+
+    // Saved views (the per-owner index is derived)
+    pub saved_views: BTreeMap<SavedViewId, SavedView>,
+    pub user_saved_views: BTreeMap<UserRef, Vec<SavedViewId>>,
+    pub next_saved_view_id: SavedViewId,
+
+    // Custom field definitions
+    pub custom_field_definitions: BTreeMap<CustomFieldId, CustomFieldDefinition>,
+    pub next_custom_field_id: CustomFieldId,
+
+    // Document version history, oldest first
+    pub document_versions: BTreeMap<DocumentId, Vec<StoredDocumentVersion>>,
+
+    // Wiki-links: links by source, and sources by target / unresolved title
+    pub entity_links: BTreeMap<CommentTarget, Vec<EntityLink>>,
+    pub backlinks: BTreeMap<CommentTarget, BTreeSet<CommentTarget>>,
+    pub unresolved_links: BTreeMap<String, BTreeSet<CommentTarget>>,
+
+    // In-progress chunked uploads (not persisted)
+    pub document_uploads: BTreeMap<UploadSessionId, DocumentUpload>,
+    pub next_upload_session_id: UploadSessionId,
 
     // Sprints storage (dual indexing)
     pub sprints: BTreeMap<SprintId, Sprint>,
@@ -32,6 +111,8 @@ pub struct State {
     // Documents storage
     pub documents: BTreeMap<DocumentId, Document>,
     pub workspace_documents: BTreeMap<WorkspaceId, Vec<DocumentId>>,
+    /// Each owner's documents by lowercased title, for wiki-link lookups (derived)
+    pub document_titles: BTreeMap<(Principal, String), BTreeSet<DocumentId>>,
     pub next_document_id: DocumentId,
 
     // Templates storage (dual indexing)
@@ -40,6 +121,20 @@ pub struct State {
     pub user_id_templates: BTreeMap<String, Vec<TemplateId>>,  // For session-based auth
     pub public_templates: Vec<TemplateId>,
     pub next_template_id: TemplateId,
+    /// Published versions of each template, oldest first
+    pub template_versions: BTreeMap<TemplateId, Vec<TemplateVersion>>,
+    /// Instantiation counts for the gallery
+    pub template_usage: BTreeMap<TemplateId, u64>,
+    /// Gallery moderation by controllers
+    pub featured_templates: BTreeSet<TemplateId>,
+    pub hidden_templates: BTreeSet<TemplateId>,
+
+    // Boards storage
+    pub boards: BTreeMap<BoardId, Board>,
+    pub user_boards: BTreeMap<Principal, Vec<BoardId>>,
+    /// Card order within each board, a rank space separate from backlog and sprint ranks
+    pub board_ranks: BTreeMap<BoardId, BTreeMap<CaptureId, String>>,
+    pub next_board_id: BoardId,
 }
 
 impl State {
@@ -51,6 +146,46 @@ impl State {
             user_captures: BTreeMap::new(),
             user_id_captures: BTreeMap::new(),
             next_capture_id: 1,
+            recurrence_queue: BTreeSet::new(),
+            start_date_index: BTreeMap::new(),
+            due_date_index: BTreeMap::new(),
+            max_date_span: BTreeMap::new(),
+            capture_children: BTreeMap::new(),
+            rollups: BTreeMap::new(),
+            calendar_feeds: BTreeMap::new(),
+            reminders: BTreeMap::new(),
+            capture_reminders: BTreeMap::new(),
+            reminder_queue: BTreeSet::new(),
+            next_reminder_id: 1,
+            notifications: BTreeMap::new(),
+            user_notifications: BTreeMap::new(),
+            next_notification_id: 1,
+            handles: BTreeMap::new(),
+            user_handles: BTreeMap::new(),
+            capture_assignees: BTreeMap::new(),
+            assigned_captures: BTreeMap::new(),
+            time_entries: BTreeMap::new(),
+            capture_time_entries: BTreeMap::new(),
+            user_time_entries: BTreeMap::new(),
+            running_timers: BTreeMap::new(),
+            next_time_entry_id: 1,
+            thread_comments: BTreeMap::new(),
+            target_comments: BTreeMap::new(),
+            next_thread_comment_id: 1,
+            activity: BTreeMap::new(),
+            target_activity: BTreeMap::new(),
+            next_activity_id: 1,
+            saved_views: BTreeMap::new(),
+            user_saved_views: BTreeMap::new(),
+            next_saved_view_id: 1,
+            custom_field_definitions: BTreeMap::new(),
+            next_custom_field_id: 1,
+            document_versions: BTreeMap::new(),
+            entity_links: BTreeMap::new(),
+            backlinks: BTreeMap::new(),
+            unresolved_links: BTreeMap::new(),
+            document_uploads: BTreeMap::new(),
+            next_upload_session_id: 1,
             sprints: BTreeMap::new(),
             user_sprints: BTreeMap::new(),
             user_id_sprints: BTreeMap::new(),
@@ -61,12 +196,21 @@ impl State {
             next_workspace_id: 1,
             documents: BTreeMap::new(),
             workspace_documents: BTreeMap::new(),
+            document_titles: BTreeMap::new(),
             next_document_id: 1,
             templates: BTreeMap::new(),
             user_templates: BTreeMap::new(),
             user_id_templates: BTreeMap::new(),
             public_templates: Vec::new(),
+            template_versions: BTreeMap::new(),
+            template_usage: BTreeMap::new(),
+            featured_templates: BTreeSet::new(),
+            hidden_templates: BTreeSet::new(),
             next_template_id: 1,
+            boards: BTreeMap::new(),
+            user_boards: BTreeMap::new(),
+            board_ranks: BTreeMap::new(),
+            next_board_id: 1,
         }
     }
 
@@ -92,21 +236,41 @@ impl State {
     // =========================================================================
 
     /// Create a new capture
-    pub fn create_capture(&mut self, owner: Principal, request: CreateCaptureRequest) -> Capture {
+    pub fn create_capture(&mut self, owner: Principal, request: CreateCaptureRequest) -> Result<Capture, String> {
         let now = ic_cdk::api::time();
         let id = self.next_capture_id;
+
+        validate_subtype(&request.capture_type, request.subtype.as_ref())?;
+        let fields = request.fields.unwrap_or_default();
+        self.validate_assignees(&fields.assignees)?;
+        self.validate_custom_fields(&UserRef::Principal(owner), &fields)?;
+        if let Some(parent_id) = fields.parent_id {
+            self.validate_parent(None, Some(&UserRef::Principal(owner)), parent_id)?;
+        }
+        let recurrence = request.recurrence
+            .map(|rule| start_recurrence(id, rule, &fields))
+            .transpose()?;
         self.next_capture_id += 1;
 
+        let owner_ids = self.user_captures.get(&owner).cloned().unwrap_or_default();
+        let rank = self.next_rank_in_list(&owner_ids, fields.sprint_id);
+
         let capture = Capture {
             id,
             owner,
             capture_type: request.capture_type,
+            subtype: request.subtype,
             title: request.title,
             description: request.description,
             content: request.content,
             priority: request.priority.unwrap_or_default(),
             status: CaptureStatus::Draft,
-            fields: request.fields.unwrap_or_default(),
+            fields,
+            rank: Some(rank),
+            recurrence,
+            checklist: None,
+            conversions: None,
+            template: None,
             created_at: now,
             updated_at: now,
         };
@@ -116,8 +280,9 @@ impl State {
             .entry(owner)
             .or_default()
             .push(id);
+        self.on_capture_changed(None, id);
 
-        capture
+        Ok(capture)
     }
 
     /// Get a capture by ID
@@ -126,8 +291,29 @@ impl State {
     }
 
     /// Update a capture
-    pub fn update_capture(&mut self, request: UpdateCaptureRequest) -> Option<Capture> {
-        let capture = self.captures.get_mut(&request.id)?;
+    pub fn update_capture(&mut self, request: UpdateCaptureRequest) -> Result<Capture, String> {
+        let capture = self.captures.get(&request.id)
+            .ok_or_else(|| "Capture not found".to_string())?;
+        if let Some(parent_id) = request.fields.as_ref().and_then(|f| f.parent_id) {
+            if capture.fields.parent_id != Some(parent_id) {
+                let owner = self.capture_owner_ref(capture);
+                self.validate_parent(Some(request.id), owner.as_ref(), parent_id)?;
+            }
+        }
+        if let Some(fields) = request.fields.as_ref() {
+            if fields.assignees != capture.fields.assignees {
+                self.validate_assignees(&fields.assignees)?;
+            }
+            let custom_changed = fields.custom_fields != capture.fields.custom_fields
+                || fields.workspace_id != capture.fields.workspace_id;
+            if let (true, Some(owner)) = (custom_changed, self.capture_owner_ref(capture)) {
+                self.validate_custom_fields(&owner, fields)?;
+            }
+        }
+
+        let capture = self.captures.get_mut(&request.id)
+            .ok_or_else(|| "Capture not found".to_string())?;
+        let previous = capture.clone();
         let now = ic_cdk::api::time();
 
         if let Some(title) = request.title {
@@ -151,7 +337,10 @@ impl State {
 
         capture.updated_at = now;
 
-        Some(capture.clone())
+        self.on_capture_changed(Some(&previous), request.id);
+        self.captures.get(&request.id)
+            .cloned()
+            .ok_or_else(|| "Capture not found".to_string())
     }
 
     /// Delete a capture
@@ -161,6 +350,7 @@ impl State {
         if let Some(user_captures) = self.user_captures.get_mut(&capture.owner) {
             user_captures.retain(|&cid| cid != id);
         }
+        self.on_capture_removed(&capture);
 
         Some(capture)
     }
@@ -182,21 +372,8 @@ impl State {
 
         // Apply filters
         if let Some(ref f) = filter {
-            if let Some(ref ct) = f.capture_type {
-                captures.retain(|c| &c.capture_type == ct);
-            }
-            if let Some(ref s) = f.status {
-                captures.retain(|c| &c.status == s);
-            }
-            if let Some(ref p) = f.priority {
-                captures.retain(|c| &c.priority == p);
-            }
-            if let Some(sid) = f.sprint_id {
-                captures.retain(|c| c.fields.sprint_id == Some(sid));
-            }
-            if let Some(wid) = f.workspace_id {
-                captures.retain(|c| c.fields.workspace_id == Some(wid));
-            }
+            let now = ic_cdk::api::time();
+            captures.retain(|c| self.capture_matches(f, c, now));
         }
 
         let total = captures.len() as u64;
@@ -218,253 +395,4270 @@ impl State {
     }
 
     // =========================================================================
-    // User ID-based Operations (for session-based authentication)
+    // Capture Change Hooks
     // =========================================================================
 
-    /// Create a capture owned by a user_id (session-based auth)
-    pub fn create_capture_for_user_id(&mut self, user_id: &str, request: CreateCaptureRequest) -> Capture {
-        let now = ic_cdk::api::time();
-        let id = self.next_capture_id;
-        self.next_capture_id += 1;
-
-        // Use anonymous principal as placeholder for user_id-owned captures
-        let capture = Capture {
-            id,
-            owner: Principal::anonymous(),  // Placeholder - actual owner is user_id
-            capture_type: request.capture_type,
-            title: request.title,
-            description: request.description,
-            content: request.content,
-            priority: request.priority.unwrap_or_default(),
-            status: CaptureStatus::Draft,
-            fields: request.fields.unwrap_or_default(),
-            created_at: now,
-            updated_at: now,
+    /// Keep derived capture indexes in sync and run side effects after a capture
+    /// is created (`previous` = None) or changed
+    fn on_capture_changed(&mut self, previous: Option<&Capture>, id: CaptureId) {
+        let events = match (previous, self.captures.get(&id)) {
+            (Some(previous), Some(current)) => capture_changes(previous, current),
+            (None, Some(_)) => vec![ActivityEvent::Created],
+            _ => vec![],
         };
+        for event in events {
+            self.record_activity(CommentTarget::Capture(id), event);
+        }
 
-        self.captures.insert(id, capture.clone());
-        self.user_id_captures
-            .entry(user_id.to_string())
-            .or_default()
-            .push(id);
-
-        capture
-    }
-
-    /// Get captures by user_id (for session-based auth)
-    pub fn get_user_id_captures(
-        &self,
-        user_id: &str,
-        filter: Option<CaptureFilter>,
-        pagination: PaginationParams,
-    ) -> PaginatedResponse<Capture> {
-        let capture_ids = self.user_id_captures.get(user_id).cloned().unwrap_or_default();
-
-        let mut captures: Vec<Capture> = capture_ids
-            .iter()
-            .filter_map(|id| self.captures.get(id))
-            .cloned()
-            .collect();
-
-        // Apply filters (same as get_user_captures)
-        if let Some(ref f) = filter {
-            if let Some(ref ct) = f.capture_type {
-                captures.retain(|c| &c.capture_type == ct);
+        if let Some(current) = self.captures.get(&id) {
+            let assignees_changed = previous
+                .map(|p| p.fields.assignees != current.fields.assignees)
+                .unwrap_or(true);
+            let text = mention_text(current);
+            let previous_text = previous.map(mention_text).unwrap_or_default();
+            let owner = self.capture_owner_ref(current);
+            let title = format!("Mentioned in {}", current.title);
+
+            if assignees_changed {
+                self.sync_capture_assignees(id);
+            }
+            self.notify_mentions(&text, &previous_text, owner.as_ref(), Some(id), None, title);
+            if text != previous_text {
+                self.index_links(CommentTarget::Capture(id));
             }
-            if let Some(ref s) = f.status {
-                captures.retain(|c| &c.status == s);
+        }
+
+        if let Some(previous) = previous {
+            if let Some(key) = recurrence_queue_key(previous) {
+                self.recurrence_queue.remove(&key);
             }
-            if let Some(ref p) = f.priority {
-                captures.retain(|c| &c.priority == p);
+            self.unindex_capture_dates(previous);
+        }
+        let Some(current) = self.captures.get(&id) else {
+            return;
+        };
+        let current_fields = current.fields.clone();
+        let queue_key = recurrence_queue_key(current);
+        let due_changed = previous
+            .map(|p| p.fields.due_date != current.fields.due_date)
+            .unwrap_or(false);
+        let completed = current.status == CaptureStatus::Completed
+            && previous.map(|p| p.status != CaptureStatus::Completed).unwrap_or(true);
+        let old_parent = previous.and_then(|p| p.fields.parent_id);
+        let new_parent = current.fields.parent_id;
+        let rollup_changed = previous.map(|p| rollup_inputs_changed(p, current)).unwrap_or(true);
+
+        if let Some(key) = queue_key {
+            self.recurrence_queue.insert(key);
+        }
+        self.index_capture_dates(id, &current_fields);
+
+        // Keep the hierarchy index and the parents' roll-ups current
+        if old_parent != new_parent {
+            if let Some(old_parent) = old_parent {
+                self.unlink_child(old_parent, id);
             }
-            if let Some(sid) = f.sprint_id {
-                captures.retain(|c| c.fields.sprint_id == Some(sid));
+            if let Some(new_parent) = new_parent {
+                self.capture_children.entry(new_parent).or_default().push(id);
             }
-            if let Some(wid) = f.workspace_id {
-                captures.retain(|c| c.fields.workspace_id == Some(wid));
+        }
+        if rollup_changed {
+            if old_parent != new_parent {
+                self.refresh_rollups_from(old_parent);
             }
+            self.refresh_rollups_from(new_parent);
         }
 
-        let total = captures.len() as u64;
-        let offset = pagination.offset.unwrap_or(0);
-        let limit = pagination.limit.unwrap_or(50);
-
-        let items: Vec<Capture> = captures
-            .into_iter()
-            .skip(offset as usize)
-            .take(limit as usize)
-            .collect();
+        // BeforeDue reminders follow the due date
+        if due_changed {
+            self.reschedule_capture_reminders(id);
+        }
 
-        PaginatedResponse {
-            items,
-            total,
-            offset,
-            limit,
+        // Completing an occurrence of a recurring capture spawns the next one
+        if completed {
+            self.spawn_next_occurrence(id, ic_cdk::api::time());
         }
-    }
 
-    /// Check if a capture is owned by user_id
-    pub fn is_capture_owned_by_user_id(&self, capture_id: CaptureId, user_id: &str) -> bool {
-        self.user_id_captures
-            .get(user_id)
-            .map(|ids| ids.contains(&capture_id))
-            .unwrap_or(false)
+        scheduler::arm(self.next_scheduled_job());
     }
 
-    /// Delete a capture owned by user_id
-    pub fn delete_capture_by_user_id(&mut self, id: CaptureId, user_id: &str) -> Option<Capture> {
-        // Verify ownership
-        if !self.is_capture_owned_by_user_id(id, user_id) {
-            return None;
+    /// Clean up derived capture indexes after a capture is deleted
+    fn on_capture_removed(&mut self, capture: &Capture) {
+        if let Some(key) = recurrence_queue_key(capture) {
+            self.recurrence_queue.remove(&key);
         }
-
-        let capture = self.captures.remove(&id)?;
-
-        // Remove from user_id index
-        if let Some(user_captures) = self.user_id_captures.get_mut(user_id) {
-            user_captures.retain(|&cid| cid != id);
+        self.unindex_capture_dates(capture);
+        // Children of a deleted capture keep their parent_id and become roots
+        self.capture_children.remove(&capture.id);
+        self.rollups.remove(&capture.id);
+        if let Some(parent_id) = capture.fields.parent_id {
+            self.unlink_child(parent_id, capture.id);
+            self.refresh_rollups_from(Some(parent_id));
+        }
+        self.remove_thread(&CommentTarget::Capture(capture.id));
+        self.remove_linked_entity(CommentTarget::Capture(capture.id));
+        for ranks in self.board_ranks.values_mut() {
+            ranks.remove(&capture.id);
+        }
+        for user in self.capture_assignees.remove(&capture.id).unwrap_or_default() {
+            self.unassign(&user, capture.id);
+        }
+        for entry_id in self.capture_time_entries.remove(&capture.id).unwrap_or_default() {
+            if let Some(entry) = self.time_entries.remove(&entry_id) {
+                self.unindex_time_entry(&entry);
+            }
+        }
+        for reminder_id in self.capture_reminders.remove(&capture.id).unwrap_or_default() {
+            if let Some(reminder) = self.reminders.remove(&reminder_id) {
+                if let Some(at) = reminder.fire_at {
+                    self.reminder_queue.remove(&(at, reminder_id));
+                }
+            }
         }
 
-        Some(capture)
+        scheduler::arm(self.next_scheduled_job());
     }
 
-    /// Create a sprint owned by user_id
-    pub fn create_sprint_for_user_id(&mut self, user_id: &str, request: CreateSprintRequest) -> Sprint {
-        let now = ic_cdk::api::time();
-        let id = self.next_sprint_id;
-        self.next_sprint_id += 1;
+    /// Add a capture's dates to its owner's date indexes
+    fn index_capture_dates(&mut self, id: CaptureId, fields: &DynamicFields) {
+        let Some(owner) = self.captures.get(&id).and_then(|c| self.capture_owner_ref(c)) else {
+            return;
+        };
+        if let (Some(start), Some(due)) = (fields.start_date, fields.due_date) {
+            let span = self.max_date_span.entry(owner.clone()).or_default();
+            *span = (*span).max(due.saturating_sub(start));
+        }
+        if let Some(start) = fields.start_date {
+            self.start_date_index.entry(owner.clone()).or_default().insert((start, id));
+        }
+        if let Some(due) = fields.due_date {
+            self.due_date_index.entry(owner).or_default().insert((due, id));
+        }
+    }
 
-        let sprint = Sprint {
-            id,
-            owner: Principal::anonymous(),  // Placeholder - actual owner is user_id
-            name: request.name,
-            goal: request.goal,
-            status: SprintStatus::Planning,
-            start_date: request.start_date,
-            end_date: request.end_date,
-            capacity: request.capacity,
-            capture_ids: Vec::new(),
-            created_at: now,
-            updated_at: now,
+    /// Remove a capture's dates from its owner's date indexes.
+    /// The capture must still be registered with its owner.
+    fn unindex_capture_dates(&mut self, capture: &Capture) {
+        let Some(owner) = self.capture_owner_ref(capture) else {
+            return;
         };
+        if let (Some(start), Some(index)) = (capture.fields.start_date, self.start_date_index.get_mut(&owner)) {
+            index.remove(&(start, capture.id));
+        }
+        if let (Some(due), Some(index)) = (capture.fields.due_date, self.due_date_index.get_mut(&owner)) {
+            index.remove(&(due, capture.id));
+        }
+    }
 
-        self.sprints.insert(id, sprint.clone());
-        self.user_id_sprints
-            .entry(user_id.to_string())
-            .or_default()
-            .push(id);
+    // =========================================================================
+    // Conversion Operations
+    // =========================================================================
 
-        sprint
-    }
+    /// Change a capture's type and subtype in place, remapping fields the new
+    /// type does not use. The ID, links and history are kept.
+    pub fn convert_capture(&mut self, request: ConvertCaptureRequest) -> Result<Capture, String> {
+        validate_subtype(&request.capture_type, request.subtype.as_ref())?;
+        let previous = self.captures.get(&request.id)
+            .cloned()
+            .ok_or_else(|| "Capture not found".to_string())?;
+        if previous.capture_type == request.capture_type && previous.subtype == request.subtype {
+            return Ok(previous);
+        }
 
-    /// Get sprints by user_id
-    pub fn get_user_id_sprints(&self, user_id: &str) -> Vec<Sprint> {
-        self.user_id_sprints
-            .get(user_id)
-            .map(|ids| {
-                ids.iter()
-                    .filter_map(|id| self.sprints.get(id))
-                    .cloned()
-                    .collect()
-            })
-            .unwrap_or_default()
-    }
+        let mut fields = previous.fields.clone();
+        let remapped_fields = remap_fields_for_type(&mut fields, &request.capture_type);
 
-    /// Check if a sprint is owned by user_id
-    pub fn is_sprint_owned_by_user_id(&self, sprint_id: SprintId, user_id: &str) -> bool {
-        self.user_id_sprints
-            .get(user_id)
-            .map(|ids| ids.contains(&sprint_id))
-            .unwrap_or(false)
-    }
+        // Captures leaving a sprint return to the bottom of the backlog
+        let mut rank = previous.rank.clone();
+        if let (Some(sprint_id), None) = (previous.fields.sprint_id, fields.sprint_id) {
+            if let Some(sprint) = self.sprints.get_mut(&sprint_id) {
+                sprint.capture_ids.retain(|&cid| cid != request.id);
+            }
+            let owner_ids = self.capture_owner_ids(&previous);
+            rank = Some(self.next_rank_in_list(&owner_ids, None));
+        }
 
-    /// Create a workspace owned by user_id
-    pub fn create_workspace_for_user_id(&mut self, user_id: &str, request: CreateWorkspaceRequest) -> Workspace {
         let now = ic_cdk::api::time();
-        let id = self.next_workspace_id;
-        self.next_workspace_id += 1;
+        let capture = self.captures.get_mut(&request.id)
+            .ok_or_else(|| "Capture not found".to_string())?;
+        capture.conversions.get_or_insert_with(Vec::new).push(CaptureConversion {
+            from_type: previous.capture_type.clone(),
+            from_subtype: previous.subtype.clone(),
+            to_type: request.capture_type.clone(),
+            to_subtype: request.subtype.clone(),
+            remapped_fields,
+            converted_at: now,
+        });
+        capture.capture_type = request.capture_type;
+        capture.subtype = request.subtype;
+        capture.fields = fields;
+        capture.rank = rank;
+        capture.updated_at = now;
 
-        let workspace = Workspace {
-            id,
-            owner: Principal::anonymous(),  // Placeholder - actual owner is user_id
-            name: request.name,
-            description: request.description,
-            icon: request.icon,
-            parent_id: request.parent_id,
-            is_archived: false,
-            created_at: now,
-            updated_at: now,
-        };
+        self.on_capture_changed(Some(&previous), request.id);
+        self.captures.get(&request.id)
+            .cloned()
+            .ok_or_else(|| "Capture not found".to_string())
+    }
 
-        self.workspaces.insert(id, workspace.clone());
-        self.user_id_workspaces
-            .entry(user_id.to_string())
-            .or_default()
-            .push(id);
+    /// Check whether a template is visible to an owner
+    fn template_accessible(&self, template: &Template, owner: &UserRef) -> bool {
+        self.template_listed(template) || self.template_owned_by(template, owner)
+    }
 
-        workspace
+    /// Check whether a template is public and not hidden by moderation
+    fn template_listed(&self, template: &Template) -> bool {
+        template.is_public && !self.hidden_templates.contains(&template.id)
     }
 
-    /// Get workspaces by user_id
-    pub fn get_user_id_workspaces(&self, user_id: &str) -> Vec<Workspace> {
-        self.user_id_workspaces
-            .get(user_id)
-            .map(|ids| {
-                ids.iter()
-                    .filter_map(|id| self.workspaces.get(id))
-                    .cloned()
-                    .collect()
-            })
-            .unwrap_or_default()
+    /// Check whether a user owns a template
+    fn template_owned_by(&self, template: &Template, owner: &UserRef) -> bool {
+        match owner {
+            UserRef::Principal(principal) => template.owner == *principal,
+            UserRef::UserId(user_id) => self.user_id_templates
+                .get(user_id)
+                .map(|ids| ids.contains(&template.id))
+                .unwrap_or(false),
+        }
     }
 
-    /// Check if a workspace is owned by user_id
-    pub fn is_workspace_owned_by_user_id(&self, workspace_id: WorkspaceId, user_id: &str) -> bool {
-        self.user_id_workspaces
-            .get(user_id)
-            .map(|ids| ids.contains(&workspace_id))
-            .unwrap_or(false)
+    /// Template contents a user instantiates: the requested published
+    /// version, or else the draft for the owner and the latest published
+    /// version for everyone else. Returns the version used.
+    fn resolve_template(
+        &self,
+        user: &UserRef,
+        id: TemplateId,
+        version: Option<TemplateVersionNumber>,
+    ) -> Result<(Template, Option<TemplateVersionNumber>), String> {
+        let template = self.templates.get(&id)
+            .ok_or_else(|| "Template not found".to_string())?;
+        if !self.template_accessible(template, user) {
+            return Err("Not authorized to use this template".to_string());
+        }
+        let version = match version {
+            Some(version) => version,
+            None if self.template_owned_by(template, user) => return Ok((template.clone(), None)),
+            None => template.latest_version
+                .ok_or_else(|| "Template has no published version".to_string())?,
+        };
+        let snapshot = self.template_version(id, version)
+            .ok_or_else(|| "Template version not found".to_string())?;
+        Ok((apply_template_version(template, snapshot), Some(version)))
     }
 
-    /// Create a template owned by user_id
-    pub fn create_template_for_user_id(&mut self, user_id: &str, request: CreateTemplateRequest) -> Template {
-        let now = ic_cdk::api::time();
-        let id = self.next_template_id;
-        self.next_template_id += 1;
+    /// Create a capture for either kind of owner
+    fn create_capture_for(&mut self, owner: &UserRef, request: CreateCaptureRequest) -> Result<Capture, String> {
+        match owner {
+            UserRef::Principal(principal) => self.create_capture(*principal, request),
+            UserRef::UserId(user_id) => self.create_capture_for_user_id(user_id, request),
+        }
+    }
 
-        let is_public = request.is_public.unwrap_or(false);
+    /// Promote an idea to a project, applying an optional capture template and
+    /// creating child tasks from the request or the template's list items
+    pub fn promote_idea(&mut self, owner: &UserRef, request: PromoteIdeaRequest) -> Result<PromoteIdeaResult, String> {
+        let idea = self.captures.get(&request.idea_id)
+            .ok_or_else(|| "Capture not found".to_string())?;
+        if idea.capture_type != CaptureType::Idea {
+            return Err("Only ideas can be promoted".to_string());
+        }
 
-        let template = Template {
-            id,
-            owner: Principal::anonymous(),  // Placeholder - actual owner is user_id
-            template_type: request.template_type,
-            name: request.name,
-            description: request.description,
-            content: request.content,
-            capture_type: request.capture_type,
-            default_fields: request.default_fields,
-            is_public,
-            created_at: now,
-            updated_at: now,
+        // Render the template up front so missing variables fail before anything changes
+        let template = match request.template_id {
+            Some(template_id) => {
+                let (template, version) = self.resolve_template(owner, template_id, None)?;
+                if template.template_type != TemplateType::Capture
+                    || template.capture_type.as_ref().map(|t| *t != CaptureType::Project).unwrap_or(false)
+                {
+                    return Err("Template is not a project capture template".to_string());
+                }
+                let variables = template.variables.as_deref().unwrap_or_default();
+                let values = template::resolve_values(variables, request.values.as_deref().unwrap_or_default(), ic_cdk::api::time())?;
+                let content = template::render(&template.content, &values);
+                let default_fields = template.default_fields.as_ref().map(|f| template::render_fields(f, &values));
+                let source = TemplateSource { template_id: template.id, version };
+                Some((source, content, default_fields))
+            }
+            None => None,
         };
 
-        self.templates.insert(id, template.clone());
-        self.user_id_templates
-            .entry(user_id.to_string())
-            .or_default()
-            .push(id);
+        let task_titles: Vec<String> = request.task_titles
+            .unwrap_or_else(|| template.as_ref().map(|(_, content, _)| scaffold_task_titles(content)).unwrap_or_default())
+            .into_iter()
+            .map(|title| title.trim().to_string())
+            .collect();
+        if task_titles.iter().any(|title| title.is_empty()) {
+            return Err("Task titles cannot be empty".to_string());
+        }
+        if task_titles.len() > MAX_SCAFFOLD_TASKS {
+            return Err(format!("Cannot create more than {} tasks", MAX_SCAFFOLD_TASKS));
+        }
 
-        if is_public {
-            self.public_templates.push(id);
+        // Check custom fields of the project (with template defaults merged) and of
+        // its tasks before anything changes
+        let mut project_fields = idea.fields.clone();
+        if let Some(defaults) = template.as_ref().and_then(|(_, _, default_fields)| default_fields.as_ref()) {
+            apply_default_fields(&mut project_fields, defaults);
+            self.validate_custom_fields(owner, &project_fields)?;
+        }
+        let task_fields = DynamicFields {
+            parent_id: Some(request.idea_id),
+            workspace_id: project_fields.workspace_id,
+            ..Default::default()
+        };
+        if !task_titles.is_empty() {
+            self.validate_custom_fields(owner, &task_fields)?;
         }
 
-        template
-    }
+        self.convert_capture(ConvertCaptureRequest {
+            id: request.idea_id,
+            capture_type: CaptureType::Project,
+            subtype: request.subtype.map(CaptureSubtype::Project),
+        })?;
+
+        // Template defaults fill in whatever the idea left empty
+        let template_id = template.as_ref().map(|(source, _, _)| source.template_id);
+        if let Some((source, content, default_fields)) = template {
+            let previous = self.captures.get(&request.idea_id).cloned();
+            if let Some(project) = self.captures.get_mut(&request.idea_id) {
+                if project.content.is_none() && !content.is_empty() {
+                    project.content = Some(content);
+                }
+                if let Some(defaults) = &default_fields {
+                    apply_default_fields(&mut project.fields, defaults);
+                }
+            }
+            self.set_capture_template(request.idea_id, &source);
+            self.on_capture_changed(previous.as_ref(), request.idea_id);
+        }
 
-    /// Get templates by user_id
-    pub fn get_user_id_templates(&self, user_id: &str) -> Vec<Template> {
+        let mut tasks = Vec::with_capacity(task_titles.len());
+        for title in task_titles {
+            tasks.push(self.create_capture_for(owner, CreateCaptureRequest {
+                capture_type: CaptureType::Task,
+                subtype: None,
+                title,
+                description: None,
+                content: None,
+                priority: None,
+                fields: Some(task_fields.clone()),
+                recurrence: None,
+            })?);
+        }
+
+        let project = self.captures.get(&request.idea_id)
+            .cloned()
+            .ok_or_else(|| "Capture not found".to_string())?;
+        if let Some(template_id) = template_id {
+            self.record_template_use(template_id);
+        }
+        Ok(PromoteIdeaResult { project, tasks })
+    }
+
+    // =========================================================================
+    // Hierarchy & Roll-up Operations
+    // =========================================================================
+
+    /// Validate a parent for a capture (`id` = None for a new capture): the parent
+    /// must exist, belong to the same owner and not be the capture or its descendant
+    fn validate_parent(&self, id: Option<CaptureId>, owner: Option<&UserRef>, parent_id: CaptureId) -> Result<(), String> {
+        if id == Some(parent_id) {
+            return Err("A capture cannot be its own parent".to_string());
+        }
+        let parent = self.captures.get(&parent_id)
+            .ok_or_else(|| "Parent capture not found".to_string())?;
+        if self.capture_owner_ref(parent).as_ref() != owner {
+            return Err("Not authorized to use this parent capture".to_string());
+        }
+
+        if let Some(id) = id {
+            let mut visited = BTreeSet::new();
+            let mut ancestor = Some(parent_id);
+            while let Some(ancestor_id) = ancestor {
+                if ancestor_id == id {
+                    return Err("Parent would create a cycle in the capture hierarchy".to_string());
+                }
+                if !visited.insert(ancestor_id) {
+                    break;
+                }
+                ancestor = self.captures.get(&ancestor_id).and_then(|c| c.fields.parent_id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove a capture from its parent's children index
+    fn unlink_child(&mut self, parent_id: CaptureId, id: CaptureId) {
+        if let Some(children) = self.capture_children.get_mut(&parent_id) {
+            children.retain(|&cid| cid != id);
+            if children.is_empty() {
+                self.capture_children.remove(&parent_id);
+            }
+        }
+    }
+
+    /// Recompute the roll-up of a capture from its direct children.
+    /// Returns whether the roll-up changed.
+    fn recompute_rollup(&mut self, id: CaptureId) -> bool {
+        let mut rollup = Rollup::default();
+
+        for child_id in self.capture_children.get(&id).into_iter().flatten() {
+            let Some(child) = self.captures.get(child_id) else {
+                continue;
+            };
+            if child.status == CaptureStatus::Cancelled {
+                continue;
+            }
+            let completed = child.status == CaptureStatus::Completed;
+
+            rollup.descendant_count += 1;
+            rollup.completed_count += completed as u64;
+            rollup.earliest_start = min_option(rollup.earliest_start, child.fields.start_date);
+            rollup.latest_due = rollup.latest_due.max(child.fields.due_date);
+
+            match self.rollups.get(child_id) {
+                // Points come from the leaves below a child with children
+                Some(child_rollup) => {
+                    rollup.descendant_count += child_rollup.descendant_count;
+                    rollup.completed_count += child_rollup.completed_count;
+                    rollup.total_estimate += child_rollup.total_estimate;
+                    rollup.remaining_estimate += child_rollup.remaining_estimate;
+                    rollup.earliest_start = min_option(rollup.earliest_start, child_rollup.earliest_start);
+                    rollup.latest_due = rollup.latest_due.max(child_rollup.latest_due);
+                }
+                None => {
+                    let estimate = child.fields.estimate.unwrap_or(0) as u64;
+                    rollup.total_estimate += estimate;
+                    if !completed {
+                        rollup.remaining_estimate += estimate;
+                    }
+                }
+            }
+        }
+
+        if rollup.descendant_count == 0 {
+            return self.rollups.remove(&id).is_some();
+        }
+
+        rollup.percent_complete_by_count = percent(rollup.completed_count, rollup.descendant_count);
+        rollup.percent_complete_by_points = percent(
+            rollup.total_estimate - rollup.remaining_estimate,
+            rollup.total_estimate,
+        );
+
+        if self.rollups.get(&id) == Some(&rollup) {
+            return false;
+        }
+        self.rollups.insert(id, rollup);
+        true
+    }
+
+    /// Recompute roll-ups from a capture up through its ancestors, stopping
+    /// once a roll-up is unchanged
+    fn refresh_rollups_from(&mut self, start: Option<CaptureId>) {
+        let mut visited = BTreeSet::new();
+        let mut next = start;
+        while let Some(id) = next {
+            if !visited.insert(id) || !self.captures.contains_key(&id) {
+                break;
+            }
+            if !self.recompute_rollup(id) {
+                break;
+            }
+            next = self.captures.get(&id).and_then(|c| c.fields.parent_id);
+        }
+    }
+
+    /// Rebuild the children index and every roll-up (after an upgrade)
+    fn rebuild_hierarchy(&mut self) {
+        self.capture_children.clear();
+        self.rollups.clear();
+        for capture in self.captures.values() {
+            if let Some(parent_id) = capture.fields.parent_id {
+                if parent_id != capture.id && self.captures.contains_key(&parent_id) {
+                    self.capture_children.entry(parent_id).or_default().push(capture.id);
+                }
+            }
+        }
+
+        let parents: Vec<CaptureId> = self.capture_children.keys().copied().collect();
+        let mut done = BTreeSet::new();
+        for id in parents {
+            self.rollup_subtree(id, &mut done);
+        }
+    }
+
+    /// Compute roll-ups for a subtree, children before parents
+    fn rollup_subtree(&mut self, id: CaptureId, done: &mut BTreeSet<CaptureId>) {
+        if !done.insert(id) {
+            return;
+        }
+        for child_id in self.capture_children.get(&id).cloned().unwrap_or_default() {
+            self.rollup_subtree(child_id, done);
+        }
+        self.recompute_rollup(id);
+    }
+
+    /// Get a capture and its descendants in depth-first order, children by rank
+    pub fn get_capture_tree(&self, owner: &UserRef, root_id: CaptureId) -> Result<Vec<CaptureTreeNode>, String> {
+        let root = self.captures.get(&root_id)
+            .ok_or_else(|| "Capture not found".to_string())?;
+        if self.capture_owner_ref(root).as_ref() != Some(owner) {
+            return Err("Not authorized to view this capture".to_string());
+        }
+
+        let mut nodes = Vec::new();
+        let mut visited = BTreeSet::new();
+        let mut stack = vec![(root_id, 0u32)];
+
+        while let Some((id, depth)) = stack.pop() {
+            if nodes.len() >= MAX_TREE_NODES {
+                break;
+            }
+            if !visited.insert(id) {
+                continue;
+            }
+            let Some(capture) = self.captures.get(&id) else {
+                continue;
+            };
+            nodes.push(CaptureTreeNode {
+                capture: capture.clone(),
+                depth,
+                rollup: self.rollups.get(&id).cloned(),
+            });
+
+            let mut children: Vec<&Capture> = self.capture_children.get(&id)
+                .into_iter()
+                .flatten()
+                .filter_map(|cid| self.captures.get(cid))
+                .collect();
+            // Unranked captures sort last, by ID
+            children.sort_by(|a, b| {
+                (a.rank.is_none(), &a.rank, a.id).cmp(&(b.rank.is_none(), &b.rank, b.id))
+            });
+            for child in children.into_iter().rev() {
+                stack.push((child.id, depth + 1));
+            }
+        }
+
+        Ok(nodes)
+    }
+
+    // =========================================================================
+    // Checklist Operations
+    // =========================================================================
+
+    /// Apply an edit to a capture's checklist, refreshing its counts
+    fn edit_checklist<F>(&mut self, capture_id: CaptureId, edit: F) -> Result<Capture, String>
+    where
+        F: FnOnce(&mut Checklist, Timestamp) -> Result<(), String>,
+    {
+        let capture = self.captures.get_mut(&capture_id)
+            .ok_or_else(|| "Capture not found".to_string())?;
+        let previous = capture.clone();
+        let now = ic_cdk::api::time();
+
+        // Edit a copy so a failed edit leaves the capture untouched
+        let mut checklist = capture.checklist.clone().unwrap_or_default();
+        edit(&mut checklist, now)?;
+        checklist.total_count = checklist.items.len() as u32;
+        checklist.completed_count = checklist.items.iter().filter(|item| item.checked).count() as u32;
+        capture.checklist = Some(checklist);
+        capture.updated_at = now;
+
+        self.on_capture_changed(Some(&previous), capture_id);
+        self.captures.get(&capture_id)
+            .cloned()
+            .ok_or_else(|| "Capture not found".to_string())
+    }
+
+    /// Add an item to a capture's checklist, at `position` or at the end
+    pub fn add_checklist_item(&mut self, capture_id: CaptureId, text: String, position: Option<u32>) -> Result<Capture, String> {
+        let text = text.trim().to_string();
+        if text.is_empty() {
+            return Err("Checklist item text cannot be empty".to_string());
+        }
+
+        self.edit_checklist(capture_id, |checklist, now| {
+            if checklist.items.len() >= MAX_CHECKLIST_ITEMS {
+                return Err(format!("Checklist cannot have more than {} items", MAX_CHECKLIST_ITEMS));
+            }
+            checklist.next_item_id += 1;
+            let item = ChecklistItem {
+                id: checklist.next_item_id,
+                text,
+                checked: false,
+                checked_at: None,
+                created_at: now,
+            };
+            let index = position
+                .map(|p| (p as usize).min(checklist.items.len()))
+                .unwrap_or(checklist.items.len());
+            checklist.items.insert(index, item);
+            Ok(())
+        })
+    }
+
+    /// Check or uncheck a checklist item
+    pub fn check_checklist_item(&mut self, capture_id: CaptureId, item_id: ChecklistItemId, checked: bool) -> Result<Capture, String> {
+        self.edit_checklist(capture_id, |checklist, now| {
+            let item = checklist.items.iter_mut()
+                .find(|item| item.id == item_id)
+                .ok_or_else(|| "Checklist item not found".to_string())?;
+            if item.checked != checked {
+                item.checked = checked;
+                item.checked_at = checked.then_some(now);
+            }
+            Ok(())
+        })
+    }
+
+    /// Move a checklist item to a new position (clamped to the end of the list)
+    pub fn move_checklist_item(&mut self, capture_id: CaptureId, item_id: ChecklistItemId, position: u32) -> Result<Capture, String> {
+        self.edit_checklist(capture_id, |checklist, _| {
+            let index = checklist.items.iter()
+                .position(|item| item.id == item_id)
+                .ok_or_else(|| "Checklist item not found".to_string())?;
+            let item = checklist.items.remove(index);
+            let position = (position as usize).min(checklist.items.len());
+            checklist.items.insert(position, item);
+            Ok(())
+        })
+    }
+
+    /// Remove an item from a capture's checklist
+    pub fn remove_checklist_item(&mut self, capture_id: CaptureId, item_id: ChecklistItemId) -> Result<Capture, String> {
+        self.edit_checklist(capture_id, |checklist, _| {
+            let index = checklist.items.iter()
+                .position(|item| item.id == item_id)
+                .ok_or_else(|| "Checklist item not found".to_string())?;
+            checklist.items.remove(index);
+            Ok(())
+        })
+    }
+
+    /// Turn a checklist item into a child task of the capture, removing it from
+    /// the checklist. Checked items become completed tasks.
+    pub fn promote_checklist_item(&mut self, capture_id: CaptureId, item_id: ChecklistItemId) -> Result<Capture, String> {
+        let capture = self.captures.get(&capture_id)
+            .ok_or_else(|| "Capture not found".to_string())?;
+        let item = capture.checklist.as_ref()
+            .and_then(|checklist| checklist.items.iter().find(|item| item.id == item_id))
+            .cloned()
+            .ok_or_else(|| "Checklist item not found".to_string())?;
+        let owner = self.capture_owner_ref(capture)
+            .ok_or_else(|| "Capture owner not found".to_string())?;
+        let workspace_id = capture.fields.workspace_id;
+
+        let child = self.create_capture_for(&owner, CreateCaptureRequest {
+            capture_type: CaptureType::Task,
+            subtype: None,
+            title: item.text,
+            description: None,
+            content: None,
+            priority: None,
+            fields: Some(DynamicFields {
+                parent_id: Some(capture_id),
+                workspace_id,
+                ..Default::default()
+            }),
+            recurrence: None,
+        })?;
+        self.remove_checklist_item(capture_id, item_id)?;
+
+        if item.checked {
+            let previous = child.clone();
+            if let Some(task) = self.captures.get_mut(&child.id) {
+                task.status = CaptureStatus::Completed;
+            }
+            self.on_capture_changed(Some(&previous), child.id);
+        }
+
+        self.captures.get(&child.id)
+            .cloned()
+            .ok_or_else(|| "Capture not found".to_string())
+    }
+
+    // =========================================================================
+    // Recurrence Operations
+    // =========================================================================
+
+    /// Set or clear a capture's recurrence rule.
+    /// A new rule starts from the capture's current date; clearing ends the series at this capture.
+    pub fn set_capture_recurrence(&mut self, id: CaptureId, rule: Option<RecurrenceRule>) -> Result<Capture, String> {
+        let capture = self.captures.get(&id)
+            .ok_or_else(|| "Capture not found".to_string())?;
+        let previous = capture.clone();
+
+        let recurrence = match rule {
+            Some(rule) => {
+                let mut recurrence = start_recurrence(id, rule, &capture.fields)?;
+                if let Some(existing) = &capture.recurrence {
+                    recurrence.series_id = existing.series_id;
+                    recurrence.next_occurrence_id = existing.next_occurrence_id;
+                }
+                Some(recurrence)
+            }
+            None => None,
+        };
+
+        let capture = self.captures.get_mut(&id)
+            .ok_or_else(|| "Capture not found".to_string())?;
+        capture.recurrence = recurrence;
+        capture.updated_at = ic_cdk::api::time();
+
+        self.on_capture_changed(Some(&previous), id);
+        self.captures.get(&id).cloned().ok_or_else(|| "Capture not found".to_string())
+    }
+
+    /// Create the next occurrence of a recurring capture, copying its content and
+    /// fields with the dates advanced. Occurrences are never scheduled before `not_before`.
+    /// Returns the new capture's ID, or None if the series has ended or already continued.
+    fn spawn_next_occurrence(&mut self, id: CaptureId, not_before: Timestamp) -> Option<CaptureId> {
+        let capture = self.captures.get(&id)?.clone();
+        let recurrence = capture.recurrence.clone()?;
+        if recurrence.next_occurrence_id.is_some() {
+            return None;
+        }
+        if recurrence.rule.count.map(|count| recurrence.occurrence >= count).unwrap_or(false) {
+            return None;
+        }
+
+        let anchor = recurrence_anchor(&capture.fields)?;
+        let next = next_occurrence(&recurrence.rule, recurrence.dtstart, anchor.max(not_before))?;
+        let shift = next.saturating_sub(anchor);
+
+        let mut fields = capture.fields.clone();
+        fields.start_date = fields.start_date.map(|t| t + shift);
+        fields.due_date = fields.due_date.map(|t| t + shift);
+        // New occurrences start in the backlog
+        fields.sprint_id = None;
+        // and work through the checklist afresh
+        let checklist = capture.checklist.clone().map(|mut checklist| {
+            for item in &mut checklist.items {
+                item.checked = false;
+                item.checked_at = None;
+            }
+            checklist.completed_count = 0;
+            checklist
+        });
+
+        let owner_ids = self.capture_owner_ids(&capture);
+        let rank = self.next_rank_in_list(&owner_ids, None);
+        let owner_ref = self.capture_owner_ref(&capture)?;
+
+        let now = ic_cdk::api::time();
+        let new_id = self.next_capture_id;
+        self.next_capture_id += 1;
+
+        let occurrence = Capture {
+            id: new_id,
+            owner: capture.owner,
+            capture_type: capture.capture_type.clone(),
+            subtype: capture.subtype.clone(),
+            title: capture.title.clone(),
+            description: capture.description.clone(),
+            content: capture.content.clone(),
+            priority: capture.priority.clone(),
+            status: CaptureStatus::Draft,
+            fields,
+            rank: Some(rank),
+            recurrence: Some(Recurrence {
+                occurrence: recurrence.occurrence + 1,
+                next_occurrence_id: None,
+                ..recurrence
+            }),
+            checklist,
+            conversions: None,
+            template: None,
+            created_at: now,
+            updated_at: now,
+        };
+
+        self.captures.insert(new_id, occurrence);
+        match owner_ref {
+            UserRef::UserId(user_id) => self.user_id_captures.entry(user_id).or_default().push(new_id),
+            UserRef::Principal(owner) => self.user_captures.entry(owner).or_default().push(new_id),
+        }
+
+        // Link the previous occurrence to the new one, which takes over the series
+        if let Some(key) = recurrence_queue_key(&capture) {
+            self.recurrence_queue.remove(&key);
+        }
+        if let Some(previous) = self.captures.get_mut(&id) {
+            if let Some(r) = previous.recurrence.as_mut() {
+                r.next_occurrence_id = Some(new_id);
+            }
+        }
+        self.on_capture_changed(None, new_id);
+
+        // Relative reminders carry over to the next occurrence
+        let relative: Vec<ReminderTrigger> = self.get_capture_reminders(id)
+            .into_iter()
+            .map(|r| r.trigger)
+            .filter(|t| matches!(t, ReminderTrigger::BeforeDue(_)))
+            .collect();
+        for trigger in relative {
+            let _ = self.create_reminder(CreateReminderRequest { capture_id: new_id, trigger });
+        }
+
+        Some(new_id)
+    }
+
+    // =========================================================================
+    // Calendar Operations
+    // =========================================================================
+
+    /// IDs of all captures owned by a user (principal or user_id)
+    pub fn owner_capture_ids(&self, owner: &UserRef) -> Vec<CaptureId> {
+        match owner {
+            UserRef::Principal(principal) => self.user_captures.get(principal),
+            UserRef::UserId(user_id) => self.user_id_captures.get(user_id),
+        }
+        .cloned()
+        .unwrap_or_default()
+    }
+
+    /// Get a user's calendar: dated captures overlapping the range, plus projected
+    /// occurrences of recurring series that have not been created yet
+    pub fn get_calendar(&self, owner: &UserRef, range: CalendarRange) -> Result<Vec<CalendarEntry>, String> {
+        if range.end <= range.start {
+            return Err("Calendar range end must be after its start".to_string());
+        }
+        if range.end - range.start > MAX_CALENDAR_RANGE {
+            return Err("Calendar range cannot exceed 366 days".to_string());
+        }
+
+        // Anything overlapping the range starts no earlier than the owner's longest span before it
+        let mut candidates: BTreeSet<CaptureId> = BTreeSet::new();
+        let max_span = self.max_date_span.get(owner).copied().unwrap_or(0);
+        let earliest_start = range.start.saturating_sub(max_span);
+        if let Some(index) = self.start_date_index.get(owner) {
+            candidates.extend(index.range((earliest_start, 0)..(range.end, 0)).map(|(_, id)| *id));
+        }
+        if let Some(index) = self.due_date_index.get(owner) {
+            candidates.extend(index.range((range.start, 0)..(range.end, 0)).map(|(_, id)| *id));
+        }
+
+        let mut entries: Vec<CalendarEntry> = candidates
+            .into_iter()
+            .filter_map(|id| self.captures.get(&id))
+            .filter(|c| c.status != CaptureStatus::Archived)
+            .filter_map(|c| {
+                let (start, end) = capture_span(c)?;
+                (start < range.end && end >= range.start).then(|| CalendarEntry {
+                    capture: c.clone(),
+                    start,
+                    end,
+                    projected: false,
+                })
+            })
+            .collect();
+
+        // Expand the owner's recurring series forward from their latest occurrence
+        for id in self.owner_capture_ids(owner) {
+            let Some(capture) = self.captures.get(&id) else {
+                continue;
+            };
+            if recurrence_queue_key(capture).is_some_and(|key| self.recurrence_queue.contains(&key)) {
+                entries.extend(project_occurrences(capture, &range));
+            }
+        }
+
+        entries.sort_by_key(|e| (e.start, e.capture.id));
+        Ok(entries)
+    }
+
+    /// Issue a calendar feed token for a user, replacing any previous token
+    pub fn set_calendar_feed(&mut self, owner: UserRef, token: String) {
+        self.calendar_feeds.retain(|_, o| *o != owner);
+        self.calendar_feeds.insert(token, owner);
+    }
+
+    /// Revoke a user's calendar feed. Returns whether a feed existed.
+    pub fn revoke_calendar_feed(&mut self, owner: &UserRef) -> bool {
+        let before = self.calendar_feeds.len();
+        self.calendar_feeds.retain(|_, o| o != owner);
+        self.calendar_feeds.len() != before
+    }
+
+    /// Render the iCalendar document for a feed token
+    pub fn export_calendar_feed(&self, token: &str) -> Option<String> {
+        let owner = self.calendar_feeds.get(token)?;
+
+        let captures: Vec<&Capture> = self.owner_capture_ids(owner)
+            .iter()
+            .filter_map(|id| self.captures.get(id))
+            .filter(|c| c.status != CaptureStatus::Archived)
+            .filter(|c| c.fields.start_date.is_some() || c.fields.due_date.is_some())
+            .collect();
+
+        Some(export_calendar("FounderyOS", &captures))
+    }
+
+    // =========================================================================
+    // Dashboard Operations
+    // =========================================================================
+
+    /// IDs of all sprints owned by a user (principal or user_id)
+    pub fn owner_sprint_ids(&self, owner: &UserRef) -> Vec<SprintId> {
+        match owner {
+            UserRef::Principal(principal) => self.user_sprints.get(principal),
+            UserRef::UserId(user_id) => self.user_id_sprints.get(user_id),
+        }
+        .cloned()
+        .unwrap_or_default()
+    }
+
+    /// Build a user's dashboard at reference time `now`
+    pub fn get_dashboard(&self, owner: &UserRef, now: Timestamp) -> Dashboard {
+        let owned: BTreeSet<CaptureId> = self.owner_capture_ids(owner).into_iter().collect();
+
+        let today = day_of(now);
+        let end_of_today = (today + 1) as u64 * NANOS_PER_DAY;
+        let end_of_week = (today + 7 - weekday_of_day(today) as i64) as u64 * NANOS_PER_DAY;
+
+        // Open captures by due date, from the owner's due-date index
+        let due_index = self.due_date_index.get(owner);
+        let due_between = |from: Timestamp, to: Timestamp| -> Vec<&Capture> {
+            due_index
+                .into_iter()
+                .flat_map(|index| index.range((from, 0)..(to, 0)))
+                .filter_map(|(_, id)| self.captures.get(id))
+                .filter(|c| is_open(&c.status))
+                .collect()
+        };
+        let overdue = due_between(0, now);
+        let due_today = due_between(now, end_of_today);
+        let due_this_week = due_between(end_of_today, end_of_week);
+
+        let with_status = |status: CaptureStatus| -> Vec<&Capture> {
+            let mut captures: Vec<&Capture> = owned
+                .iter()
+                .filter_map(|id| self.captures.get(id))
+                .filter(|c| c.status == status)
+                .collect();
+            captures.sort_by_key(|c| (priority_order(&c.priority), c.fields.due_date.is_none(), c.fields.due_date, c.id));
+            captures
+        };
+
+        let active_sprints = self.owner_sprint_ids(owner)
+            .iter()
+            .filter_map(|id| self.sprints.get(id))
+            .filter(|sprint| sprint.status == SprintStatus::Active)
+            .map(|sprint| self.sprint_summary(sprint, now))
+            .collect();
+
+        Dashboard {
+            now,
+            overdue: dashboard_section(overdue),
+            due_today: dashboard_section(due_today),
+            due_this_week: dashboard_section(due_this_week),
+            in_progress: dashboard_section(with_status(CaptureStatus::InProgress)),
+            blocked: dashboard_section(with_status(CaptureStatus::Blocked)),
+            active_sprints,
+        }
+    }
+
+    /// Progress summary of a sprint
+    fn sprint_summary(&self, sprint: &Sprint, now: Timestamp) -> SprintSummary {
+        let captures: Vec<&Capture> = sprint.capture_ids
+            .iter()
+            .filter_map(|id| self.captures.get(id))
+            .collect();
+        let completed: Vec<&&Capture> = captures
+            .iter()
+            .filter(|c| c.status == CaptureStatus::Completed)
+            .collect();
+
+        SprintSummary {
+            sprint_id: sprint.id,
+            name: sprint.name.clone(),
+            stream: sprint.stream.clone(),
+            start_date: sprint.start_date,
+            end_date: sprint.end_date,
+            total_captures: captures.len() as u64,
+            completed_captures: completed.len() as u64,
+            total_estimate: captures.iter().map(|c| c.fields.estimate.unwrap_or(0) as u64).sum(),
+            completed_estimate: completed.iter().map(|c| c.fields.estimate.unwrap_or(0) as u64).sum(),
+            days_remaining: sprint.end_date.saturating_sub(now).div_ceil(NANOS_PER_DAY),
+        }
+    }
+
+    // =========================================================================
+    // Reminder & Notification Operations
+    // =========================================================================
+
+    /// Create a reminder on a capture
+    pub fn create_reminder(&mut self, request: CreateReminderRequest) -> Result<Reminder, String> {
+        let capture = self.captures.get(&request.capture_id)
+            .ok_or_else(|| "Capture not found".to_string())?;
+        let now = ic_cdk::api::time();
+
+        let fire_at = match request.trigger {
+            ReminderTrigger::At(at) => {
+                if at <= now {
+                    return Err("Reminder time must be in the future".to_string());
+                }
+                Some(at)
+            }
+            ReminderTrigger::BeforeDue(_) => {
+                if capture.fields.due_date.is_none() {
+                    return Err("Capture has no due_date to remind before".to_string());
+                }
+                reminder_fire_at(&request.trigger, capture)
+            }
+        };
+
+        let id = self.next_reminder_id;
+        self.next_reminder_id += 1;
+
+        let reminder = Reminder {
+            id,
+            capture_id: request.capture_id,
+            trigger: request.trigger,
+            fire_at,
+            // A relative reminder whose time already passed has nothing left to do
+            fired: fire_at.map(|at| at <= now).unwrap_or(false),
+            created_at: now,
+        };
+
+        self.reminders.insert(id, reminder.clone());
+        self.capture_reminders
+            .entry(reminder.capture_id)
+            .or_default()
+            .push(id);
+        if let Some(key) = reminder_queue_key(&reminder) {
+            self.reminder_queue.insert(key);
+        }
+        scheduler::arm(self.next_scheduled_job());
+
+        Ok(reminder)
+    }
+
+    /// Get a reminder by ID
+    pub fn get_reminder(&self, id: ReminderId) -> Option<&Reminder> {
+        self.reminders.get(&id)
+    }
+
+    /// Get a capture's reminders
+    pub fn get_capture_reminders(&self, capture_id: CaptureId) -> Vec<Reminder> {
+        self.capture_reminders
+            .get(&capture_id)
+            .map(|ids| {
+                ids.iter()
+                    .filter_map(|id| self.reminders.get(id))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Delete a reminder
+    pub fn delete_reminder(&mut self, id: ReminderId) -> Option<Reminder> {
+        let reminder = self.reminders.remove(&id)?;
+
+        if let Some(ids) = self.capture_reminders.get_mut(&reminder.capture_id) {
+            ids.retain(|&rid| rid != id);
+        }
+        if let Some(key) = reminder_queue_key(&reminder) {
+            self.reminder_queue.remove(&key);
+            scheduler::arm(self.next_scheduled_job());
+        }
+
+        Some(reminder)
+    }
+
+    /// Recompute fire times of a capture's BeforeDue reminders after its due date changed.
+    /// Reminders moved into the future fire again.
+    fn reschedule_capture_reminders(&mut self, capture_id: CaptureId) {
+        let Some(capture) = self.captures.get(&capture_id) else {
+            return;
+        };
+        let now = ic_cdk::api::time();
+        let ids = self.capture_reminders.get(&capture_id).cloned().unwrap_or_default();
+
+        for id in ids {
+            let Some(reminder) = self.reminders.get_mut(&id) else {
+                continue;
+            };
+            if !matches!(reminder.trigger, ReminderTrigger::BeforeDue(_)) {
+                continue;
+            }
+            if let Some(key) = reminder_queue_key(reminder) {
+                self.reminder_queue.remove(&key);
+            }
+
+            reminder.fire_at = reminder_fire_at(&reminder.trigger, capture);
+            reminder.fired = reminder.fire_at.map(|at| at <= now).unwrap_or(false);
+
+            if let Some(key) = reminder_queue_key(reminder) {
+                self.reminder_queue.insert(key);
+            }
+        }
+    }
+
+    /// Fire a due reminder, notifying the capture's owner unless the capture is closed
+    fn fire_reminder(&mut self, id: ReminderId) -> bool {
+        let Some(reminder) = self.reminders.get_mut(&id) else {
+            return false;
+        };
+        if reminder.fired {
+            return false;
+        }
+        reminder.fired = true;
+        let capture_id = reminder.capture_id;
+
+        let Some(capture) = self.captures.get(&capture_id) else {
+            return false;
+        };
+        if !is_open(&capture.status) {
+            return false;
+        }
+
+        let title = capture.title.clone();
+        let body = capture.fields.due_date.map(|due| format!("Due {}", format_utc(due)));
+        match self.capture_owner_ref(capture) {
+            Some(recipient) => {
+                self.notify(recipient, NotificationKind::Reminder, Some(capture_id), None, title, body);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Add a notification to a user's inbox, dropping the oldest beyond the inbox limit
+    fn notify(
+        &mut self,
+        recipient: UserRef,
+        kind: NotificationKind,
+        capture_id: Option<CaptureId>,
+        document_id: Option<DocumentId>,
+        title: String,
+        body: Option<String>,
+    ) -> NotificationId {
+        let id = self.next_notification_id;
+        self.next_notification_id += 1;
+
+        self.notifications.insert(id, Notification {
+            id,
+            recipient: recipient.clone(),
+            kind,
+            capture_id,
+            document_id,
+            title,
+            body,
+            read: false,
+            created_at: ic_cdk::api::time(),
+        });
+
+        let inbox = self.user_notifications.entry(recipient).or_default();
+        inbox.push(id);
+        if inbox.len() > MAX_NOTIFICATIONS_PER_USER {
+            let overflow = inbox.len() - MAX_NOTIFICATIONS_PER_USER;
+            for old in inbox.drain(..overflow) {
+                self.notifications.remove(&old);
+            }
+        }
+
+        id
+    }
+
+    /// Get a user's notifications, newest first
+    pub fn get_notifications(
+        &self,
+        recipient: &UserRef,
+        unread_only: bool,
+        pagination: PaginationParams,
+    ) -> PaginatedResponse<Notification> {
+        let notifications: Vec<Notification> = self.user_notifications
+            .get(recipient)
+            .map(|ids| {
+                ids.iter()
+                    .rev()
+                    .filter_map(|id| self.notifications.get(id))
+                    .filter(|n| !unread_only || !n.read)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let total = notifications.len() as u64;
+        let offset = pagination.offset.unwrap_or(0);
+        let limit = pagination.limit.unwrap_or(50);
+
+        let items: Vec<Notification> = notifications
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .collect();
+
+        PaginatedResponse {
+            items,
+            total,
+            offset,
+            limit,
+        }
+    }
+
+    /// Mark a user's notifications as read (all of them when `ids` is empty).
+    /// Returns the number of notifications that changed.
+    pub fn mark_notifications_read(&mut self, recipient: &UserRef, ids: Vec<NotificationId>) -> u64 {
+        let inbox = self.user_notifications.get(recipient).cloned().unwrap_or_default();
+        let targets: Vec<NotificationId> = if ids.is_empty() {
+            inbox
+        } else {
+            ids.into_iter().filter(|id| inbox.contains(id)).collect()
+        };
+
+        let mut changed = 0;
+        for id in targets {
+            if let Some(notification) = self.notifications.get_mut(&id) {
+                if !notification.read {
+                    notification.read = true;
+                    changed += 1;
+                }
+            }
+        }
+        changed
+    }
+
+    // =========================================================================
+    // Time Tracking Operations
+    // =========================================================================
+
+    /// Store a time entry and add it to the indexes
+    fn insert_time_entry(&mut self, entry: TimeEntry) -> TimeEntry {
+        self.capture_time_entries.entry(entry.capture_id).or_default().push(entry.id);
+        self.user_time_entries.entry(entry.user.clone()).or_default().push(entry.id);
+        if entry.ended_at.is_none() {
+            self.running_timers.insert(entry.user.clone(), entry.id);
+        }
+        self.time_entries.insert(entry.id, entry.clone());
+        entry
+    }
+
+    /// Remove a time entry from the indexes
+    fn unindex_time_entry(&mut self, entry: &TimeEntry) {
+        if let Some(ids) = self.capture_time_entries.get_mut(&entry.capture_id) {
+            ids.retain(|&id| id != entry.id);
+            if ids.is_empty() {
+                self.capture_time_entries.remove(&entry.capture_id);
+            }
+        }
+        if let Some(ids) = self.user_time_entries.get_mut(&entry.user) {
+            ids.retain(|&id| id != entry.id);
+        }
+        if self.running_timers.get(&entry.user) == Some(&entry.id) {
+            self.running_timers.remove(&entry.user);
+        }
+    }
+
+    /// Start a timer on a capture. A user can only run one timer at a time.
+    pub fn start_timer(&mut self, user: UserRef, capture_id: CaptureId, note: Option<String>) -> Result<TimeEntry, String> {
+        if !self.captures.contains_key(&capture_id) {
+            return Err("Capture not found".to_string());
+        }
+        if let Some(running) = self.running_timers.get(&user).and_then(|id| self.time_entries.get(id)) {
+            return Err(format!("A timer is already running on capture {}", running.capture_id));
+        }
+
+        let now = ic_cdk::api::time();
+        let id = self.next_time_entry_id;
+        self.next_time_entry_id += 1;
+
+        Ok(self.insert_time_entry(TimeEntry {
+            id,
+            capture_id,
+            user,
+            started_at: now,
+            ended_at: None,
+            note,
+            manual: false,
+            created_at: now,
+        }))
+    }
+
+    /// Stop a user's running timer
+    pub fn stop_timer(&mut self, user: &UserRef) -> Result<TimeEntry, String> {
+        let id = self.running_timers.remove(user)
+            .ok_or_else(|| "No timer is running".to_string())?;
+        let entry = self.time_entries.get_mut(&id)
+            .ok_or_else(|| "Time entry not found".to_string())?;
+        entry.ended_at = Some(ic_cdk::api::time().max(entry.started_at));
+        Ok(entry.clone())
+    }
+
+    /// Get a user's running timer
+    pub fn get_running_timer(&self, user: &UserRef) -> Option<TimeEntry> {
+        self.running_timers.get(user)
+            .and_then(|id| self.time_entries.get(id))
+            .cloned()
+    }
+
+    /// Log time manually
+    pub fn add_time_entry(&mut self, user: UserRef, request: CreateTimeEntryRequest) -> Result<TimeEntry, String> {
+        if !self.captures.contains_key(&request.capture_id) {
+            return Err("Capture not found".to_string());
+        }
+        if request.ended_at <= request.started_at {
+            return Err("Time entry must end after it starts".to_string());
+        }
+        if request.ended_at - request.started_at > MAX_TIME_ENTRY_DURATION {
+            return Err("Time entry cannot exceed 24 hours".to_string());
+        }
+        let now = ic_cdk::api::time();
+        if request.ended_at > now {
+            return Err("Time entry cannot end in the future".to_string());
+        }
+
+        let id = self.next_time_entry_id;
+        self.next_time_entry_id += 1;
+
+        Ok(self.insert_time_entry(TimeEntry {
+            id,
+            capture_id: request.capture_id,
+            user,
+            started_at: request.started_at,
+            ended_at: Some(request.ended_at),
+            note: request.note,
+            manual: true,
+            created_at: now,
+        }))
+    }
+
+    /// Get a time entry by ID
+    pub fn get_time_entry(&self, id: TimeEntryId) -> Option<&TimeEntry> {
+        self.time_entries.get(&id)
+    }
+
+    /// Delete a time entry (stopping it if it is running)
+    pub fn delete_time_entry(&mut self, id: TimeEntryId) -> Option<TimeEntry> {
+        let entry = self.time_entries.remove(&id)?;
+        self.unindex_time_entry(&entry);
+        Some(entry)
+    }
+
+    /// Time logged against a capture, by all users
+    pub fn get_capture_time(&self, capture_id: CaptureId) -> TimeReport {
+        let entries: Vec<&TimeEntry> = self.capture_time_entries
+            .get(&capture_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|id| self.time_entries.get(id))
+            .collect();
+        time_report(entries, ic_cdk::api::time(), None)
+    }
+
+    /// Time logged against the captures in a sprint
+    pub fn get_sprint_time(&self, sprint_id: SprintId) -> TimeReport {
+        let entries: Vec<&TimeEntry> = self.sprints
+            .get(&sprint_id)
+            .map(|sprint| sprint.capture_ids.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|capture_id| self.capture_time_entries.get(capture_id))
+            .flatten()
+            .filter_map(|id| self.time_entries.get(id))
+            .collect();
+        time_report(entries, ic_cdk::api::time(), None)
+    }
+
+    /// Time a user logged within a range, clipped to the range
+    pub fn get_time_report(&self, user: &UserRef, range: CalendarRange) -> Result<TimeReport, String> {
+        if range.end <= range.start {
+            return Err("Time range end must be after its start".to_string());
+        }
+        if range.end - range.start > MAX_CALENDAR_RANGE {
+            return Err("Time range cannot exceed 366 days".to_string());
+        }
+
+        let entries: Vec<&TimeEntry> = self.user_time_entries
+            .get(user)
+            .into_iter()
+            .flatten()
+            .filter_map(|id| self.time_entries.get(id))
+            .collect();
+        Ok(time_report(entries, ic_cdk::api::time(), Some(&range)))
+    }
+
+    /// Compare completed points with logged time over an owner's completed sprints
+    pub fn get_estimate_accuracy(&self, owner: &UserRef) -> EstimateAccuracyReport {
+        let now = ic_cdk::api::time();
+        let mut sprints: Vec<&Sprint> = self.owner_sprint_ids(owner)
+            .iter()
+            .filter_map(|id| self.sprints.get(id))
+            .filter(|sprint| sprint.status == SprintStatus::Completed)
+            .collect();
+        sprints.sort_by_key(|sprint| (sprint.end_date, sprint.id));
+
+        let mut rows: Vec<SprintAccuracy> = sprints
+            .into_iter()
+            .map(|sprint| {
+                let completed: Vec<&Capture> = sprint.capture_ids
+                    .iter()
+                    .filter_map(|id| self.captures.get(id))
+                    .filter(|c| c.status == CaptureStatus::Completed)
+                    .collect();
+                let completed_points: u64 = completed.iter()
+                    .map(|c| c.fields.estimate.unwrap_or(0) as u64)
+                    .sum();
+                let logged_duration: u64 = completed.iter()
+                    .filter_map(|c| self.capture_time_entries.get(&c.id))
+                    .flatten()
+                    .filter_map(|id| self.time_entries.get(id))
+                    .map(|entry| entry_duration(entry, now))
+                    .sum();
+
+                SprintAccuracy {
+                    sprint_id: sprint.id,
+                    name: sprint.name.clone(),
+                    end_date: sprint.end_date,
+                    completed_points,
+                    logged_duration,
+                    duration_per_point: logged_duration.checked_div(completed_points),
+                    deviation_percent: None,
+                }
+            })
+            .collect();
+
+        let completed_points: u64 = rows.iter().map(|row| row.completed_points).sum();
+        let logged_duration: u64 = rows.iter().map(|row| row.logged_duration).sum();
+        let duration_per_point = logged_duration.checked_div(completed_points);
+
+        if let Some(average) = duration_per_point.filter(|average| *average > 0) {
+            for row in &mut rows {
+                row.deviation_percent = row.duration_per_point.map(|per_point| {
+                    ((per_point as i128 - average as i128) * 100 / average as i128) as i64
+                });
+            }
+        }
+
+        EstimateAccuracyReport {
+            sprints: rows,
+            completed_points,
+            logged_duration,
+            duration_per_point,
+        }
+    }
+
+    // =========================================================================
+    // Comment & Activity Operations
+    // =========================================================================
+
+    /// Check that a user can read and comment on a capture or document (owners only)
+    fn require_target_access(&self, user: &UserRef, target: &CommentTarget) -> Result<(), String> {
+        let owner = match target {
+            CommentTarget::Capture(id) => {
+                let capture = self.captures.get(id)
+                    .ok_or_else(|| "Capture not found".to_string())?;
+                self.capture_owner_ref(capture)
+            }
+            CommentTarget::Document(id) => {
+                let document = self.documents.get(id)
+                    .ok_or_else(|| "Document not found".to_string())?;
+                Some(UserRef::Principal(document.owner))
+            }
+        };
+
+        if owner.as_ref() != Some(user) {
+            return Err("Not authorized to access this thread".to_string());
+        }
+        Ok(())
+    }
+
+    /// Comment on a capture or document
+    pub fn add_thread_comment(&mut self, author: UserRef, request: AddThreadCommentRequest) -> Result<ThreadComment, String> {
+        if request.content.trim().is_empty() {
+            return Err("Comment content cannot be empty".to_string());
+        }
+        if request.content.len() > MAX_COMMENT_LEN {
+            return Err(format!("Comment too long (max {} bytes)", MAX_COMMENT_LEN));
+        }
+        self.require_target_access(&author, &request.target)?;
+
+        let id = self.next_thread_comment_id;
+        self.next_thread_comment_id += 1;
+
+        let comment = ThreadComment {
+            id,
+            target: request.target,
+            author,
+            author_type: request.author_type,
+            content: request.content,
+            created_at: ic_cdk::api::time(),
+            is_retracted: false,
+            retracted_at: None,
+        };
+
+        self.thread_comments.insert(id, comment.clone());
+        self.target_comments
+            .entry(comment.target.clone())
+            .or_default()
+            .push(id);
+
+        let (capture_id, document_id, title) = match comment.target {
+            CommentTarget::Capture(id) => (Some(id), None, self.captures.get(&id).map(|c| c.title.clone())),
+            CommentTarget::Document(id) => (None, Some(id), self.documents.get(&id).map(|d| d.title.clone())),
+        };
+        let title = format!("Mentioned in a comment on {}", title.unwrap_or_default());
+        self.notify_mentions(&comment.content, "", Some(&comment.author), capture_id, document_id, title);
+
+        Ok(comment)
+    }
+
+    /// Retract a comment (marked as retracted, never deleted)
+    pub fn retract_thread_comment(&mut self, author: &UserRef, id: ThreadCommentId) -> Result<ThreadComment, String> {
+        let comment = self.thread_comments.get_mut(&id)
+            .ok_or_else(|| "Comment not found".to_string())?;
+
+        if comment.author != *author {
+            return Err("Only the comment author can retract this comment".to_string());
+        }
+        if comment.is_retracted {
+            return Err("Comment is already retracted".to_string());
+        }
+
+        comment.is_retracted = true;
+        comment.retracted_at = Some(ic_cdk::api::time());
+        Ok(comment.clone())
+    }
+
+    /// Get the comments on a capture or document, oldest first
+    pub fn get_thread_comments(
+        &self,
+        user: &UserRef,
+        target: &CommentTarget,
+        pagination: PaginationParams,
+    ) -> Result<PaginatedResponse<ThreadComment>, String> {
+        self.require_target_access(user, target)?;
+
+        let comments: Vec<ThreadComment> = self.target_comments
+            .get(target)
+            .into_iter()
+            .flatten()
+            .filter_map(|id| self.thread_comments.get(id))
+            .cloned()
+            .collect();
+
+        let total = comments.len() as u64;
+        let offset = pagination.offset.unwrap_or(0);
+        let limit = pagination.limit.unwrap_or(50);
+
+        let items: Vec<ThreadComment> = comments
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .collect();
+
+        Ok(PaginatedResponse {
+            items,
+            total,
+            offset,
+            limit,
+        })
+    }
+
+    /// Get the merged stream of comments and recorded activity on a capture or
+    /// document, newest first
+    pub fn get_activity_stream(
+        &self,
+        user: &UserRef,
+        target: &CommentTarget,
+        pagination: PaginationParams,
+    ) -> Result<PaginatedResponse<ActivityItem>, String> {
+        self.require_target_access(user, target)?;
+
+        let comments = self.target_comments
+            .get(target)
+            .into_iter()
+            .flatten()
+            .filter_map(|id| self.thread_comments.get(id))
+            .map(|comment| ((comment.created_at, 1, comment.id), ActivityItem::Comment(comment.clone())));
+        let records = self.target_activity
+            .get(target)
+            .into_iter()
+            .flatten()
+            .filter_map(|id| self.activity.get(id))
+            .map(|record| ((record.created_at, 0, record.id), ActivityItem::Activity(record.clone())));
+
+        // Changes sort before comments made at the same instant
+        let mut stream: Vec<((Timestamp, u8, u64), ActivityItem)> = comments.chain(records).collect();
+        stream.sort_by_key(|(key, _)| std::cmp::Reverse(*key));
+
+        let total = stream.len() as u64;
+        let offset = pagination.offset.unwrap_or(0);
+        let limit = pagination.limit.unwrap_or(50);
+
+        let items: Vec<ActivityItem> = stream
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .map(|(_, item)| item)
+            .collect();
+
+        Ok(PaginatedResponse {
+            items,
+            total,
+            offset,
+            limit,
+        })
+    }
+
+    /// Record activity on a capture or document, dropping the oldest records
+    /// beyond MAX_ACTIVITY_PER_TARGET
+    fn record_activity(&mut self, target: CommentTarget, event: ActivityEvent) {
+        let id = self.next_activity_id;
+        self.next_activity_id += 1;
+
+        let ids = self.target_activity.entry(target.clone()).or_default();
+        ids.push(id);
+        let overflow = ids.len().saturating_sub(MAX_ACTIVITY_PER_TARGET);
+        for old_id in ids.drain(..overflow) {
+            self.activity.remove(&old_id);
+        }
+
+        self.activity.insert(id, ActivityRecord {
+            id,
+            target,
+            event,
+            created_at: ic_cdk::api::time(),
+        });
+    }
+
+    /// Remove the comments and activity of a deleted capture or document
+    fn remove_thread(&mut self, target: &CommentTarget) {
+        for id in self.target_comments.remove(target).unwrap_or_default() {
+            self.thread_comments.remove(&id);
+        }
+        for id in self.target_activity.remove(target).unwrap_or_default() {
+            self.activity.remove(&id);
+        }
+    }
+
+    // =========================================================================
+    // Mention & Assignment Operations
+    // =========================================================================
+
+    /// Claim a handle for @mentions and assignment, replacing the user's previous handle
+    pub fn set_handle(&mut self, user: UserRef, handle: String) -> Result<String, String> {
+        let handle = normalize_handle(&handle)?;
+
+        if let Some(holder) = self.handles.get(&handle) {
+            if *holder != user {
+                return Err("Handle is already taken".to_string());
+            }
+            return Ok(handle);
+        }
+
+        if let Some(previous) = self.user_handles.remove(&user) {
+            self.handles.remove(&previous);
+        }
+        self.handles.insert(handle.clone(), user.clone());
+        self.user_handles.insert(user, handle.clone());
+
+        Ok(handle)
+    }
+
+    /// Get a user's handle
+    pub fn get_handle(&self, user: &UserRef) -> Option<String> {
+        self.user_handles.get(user).cloned()
+    }
+
+    /// Resolve an assignee entry: `@handle`, `handle`, a principal in text form,
+    /// or the user_id of a known session user (bare or as `user:<id>`)
+    fn resolve_assignee(&self, assignee: &str) -> Option<UserRef> {
+        let assignee = assignee.trim();
+        if let Some(user_id) = assignee.strip_prefix("user:") {
+            let user_id = user_id.trim();
+            return self.is_known_user_id(user_id).then(|| UserRef::UserId(user_id.to_string()));
+        }
+        let handle = assignee.strip_prefix('@').unwrap_or(assignee).to_lowercase();
+
+        self.handles.get(&handle).cloned()
+            .or_else(|| Principal::from_text(assignee).ok().map(UserRef::Principal))
+            .or_else(|| self.is_known_user_id(assignee).then(|| UserRef::UserId(assignee.to_string())))
+    }
+
+    /// Check whether a session user_id owns anything in the canister
+    fn is_known_user_id(&self, user_id: &str) -> bool {
+        self.user_id_captures.contains_key(user_id)
+            || self.user_id_sprints.contains_key(user_id)
+            || self.user_id_workspaces.contains_key(user_id)
+            || self.user_id_templates.contains_key(user_id)
+    }
+
+    /// Check that every assignee resolves to a user
+    fn validate_assignees(&self, assignees: &[String]) -> Result<(), String> {
+        match assignees.iter().find(|a| self.resolve_assignee(a).is_none()) {
+            Some(unknown) => Err(format!("Unknown assignee: {}", unknown)),
+            None => Ok(()),
+        }
+    }
+
+    /// Re-resolve a capture's assignees after its assignee list changed and
+    /// notify the newly assigned users (owners are not notified about their own captures)
+    fn sync_capture_assignees(&mut self, id: CaptureId) {
+        let Some(capture) = self.captures.get(&id) else {
+            return;
+        };
+        let owner = self.capture_owner_ref(capture);
+        let title = format!("Assigned to you: {}", capture.title);
+
+        let mut resolved: Vec<UserRef> = Vec::new();
+        for assignee in &capture.fields.assignees {
+            if let Some(user) = self.resolve_assignee(assignee) {
+                if !resolved.contains(&user) {
+                    resolved.push(user);
+                }
+            }
+        }
+
+        let previous = self.capture_assignees.remove(&id).unwrap_or_default();
+        for user in &previous {
+            self.unassign(user, id);
+        }
+        for user in &resolved {
+            self.assigned_captures.entry(user.clone()).or_default().insert(id);
+            if !previous.contains(user) && owner.as_ref() != Some(user) {
+                self.notify(user.clone(), NotificationKind::Assigned, Some(id), None, title.clone(), None);
+            }
+        }
+        if !resolved.is_empty() {
+            self.capture_assignees.insert(id, resolved);
+        }
+    }
+
+    /// Drop a capture from a user's assignment index
+    fn unassign(&mut self, user: &UserRef, capture_id: CaptureId) {
+        if let Some(ids) = self.assigned_captures.get_mut(user) {
+            ids.remove(&capture_id);
+            if ids.is_empty() {
+                self.assigned_captures.remove(user);
+            }
+        }
+    }
+
+    /// Notify the users @mentioned in `text` that were not already mentioned in
+    /// `previous_text`. Unknown handles are ignored.
+    fn notify_mentions(
+        &mut self,
+        text: &str,
+        previous_text: &str,
+        skip: Option<&UserRef>,
+        capture_id: Option<CaptureId>,
+        document_id: Option<DocumentId>,
+        title: String,
+    ) {
+        let already_mentioned = parse_mentions(previous_text);
+        let mut recipients: Vec<UserRef> = Vec::new();
+        for handle in parse_mentions(text) {
+            if already_mentioned.contains(&handle) {
+                continue;
+            }
+            if let Some(user) = self.handles.get(&handle) {
+                if skip != Some(user) && !recipients.contains(user) {
+                    recipients.push(user.clone());
+                }
+            }
+        }
+
+        for user in recipients {
+            self.notify(user, NotificationKind::Mention, capture_id, document_id, title.clone(), None);
+        }
+    }
+
+    /// Get the captures assigned to a user across all owners, soonest due first
+    pub fn get_assigned_captures(
+        &self,
+        user: &UserRef,
+        include_closed: bool,
+        pagination: PaginationParams,
+    ) -> PaginatedResponse<Capture> {
+        let mut captures: Vec<Capture> = self.assigned_captures
+            .get(user)
+            .into_iter()
+            .flatten()
+            .filter_map(|id| self.captures.get(id))
+            .filter(|c| include_closed || is_open(&c.status))
+            .cloned()
+            .collect();
+        captures.sort_by_key(|c| (c.fields.due_date.is_none(), c.fields.due_date, c.id));
+
+        let total = captures.len() as u64;
+        let offset = pagination.offset.unwrap_or(0);
+        let limit = pagination.limit.unwrap_or(50);
+
+        let items: Vec<Capture> = captures
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .collect();
+
+        PaginatedResponse {
+            items,
+            total,
+            offset,
+            limit,
+        }
+    }
+
+    // =========================================================================
+    // Saved View Operations
+    // =========================================================================
+
+    /// Whether a workspace belongs to a user (principal or user_id)
+    fn is_workspace_owned_by(&self, owner: &UserRef, workspace_id: WorkspaceId) -> bool {
+        match owner {
+            UserRef::Principal(principal) => self.workspaces
+                .get(&workspace_id)
+                .map(|w| w.owner == *principal)
+                .unwrap_or(false),
+            UserRef::UserId(user_id) => self.user_id_workspaces
+                .get(user_id)
+                .map(|ids| ids.contains(&workspace_id))
+                .unwrap_or(false),
+        }
+    }
+
+    /// Resolve the users a view is shared with (handles or principals); the owner is dropped
+    fn resolve_view_members(&self, owner: &UserRef, entries: &[String]) -> Result<Vec<UserRef>, String> {
+        let mut members: Vec<UserRef> = Vec::new();
+        for entry in entries {
+            let user = self.resolve_assignee(entry)
+                .ok_or_else(|| format!("Unknown user: {}", entry))?;
+            if user != *owner && !members.contains(&user) {
+                members.push(user);
+            }
+        }
+        Ok(members)
+    }
+
+    /// Validate the pinned workspace and column list of a view
+    fn validate_view(&self, owner: &UserRef, workspace_id: Option<WorkspaceId>, columns: &[String]) -> Result<(), String> {
+        if columns.len() > MAX_VIEW_COLUMNS {
+            return Err(format!("Too many columns (max {})", MAX_VIEW_COLUMNS));
+        }
+        if let Some(workspace_id) = workspace_id {
+            if !self.workspaces.contains_key(&workspace_id) {
+                return Err("Workspace not found".to_string());
+            }
+            if !self.is_workspace_owned_by(owner, workspace_id) {
+                return Err("Not authorized to pin views to this workspace".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Save a named view
+    pub fn create_saved_view(&mut self, owner: UserRef, request: CreateSavedViewRequest) -> Result<SavedView, String> {
+        let name = request.name.trim().to_string();
+        if name.is_empty() {
+            return Err("View name cannot be empty".to_string());
+        }
+        let owned = self.user_saved_views.get(&owner).map(|ids| ids.len()).unwrap_or(0);
+        if owned >= MAX_SAVED_VIEWS_PER_USER {
+            return Err(format!("Saved view limit reached (max {})", MAX_SAVED_VIEWS_PER_USER));
+        }
+        let columns = request.columns.unwrap_or_default();
+        self.validate_view(&owner, request.workspace_id, &columns)?;
+        let shared_with = self.resolve_view_members(&owner, &request.shared_with.unwrap_or_default())?;
+
+        let now = ic_cdk::api::time();
+        let id = self.next_saved_view_id;
+        self.next_saved_view_id += 1;
+
+        let view = SavedView {
+            id,
+            owner: owner.clone(),
+            name,
+            filter: request.filter.unwrap_or_default(),
+            sort: request.sort,
+            group_by: request.group_by,
+            columns,
+            workspace_id: request.workspace_id,
+            shared_with,
+            created_at: now,
+            updated_at: now,
+        };
+
+        self.saved_views.insert(id, view.clone());
+        self.user_saved_views.entry(owner).or_default().push(id);
+
+        Ok(view)
+    }
+
+    /// Update a saved view (owner only)
+    pub fn update_saved_view(&mut self, user: &UserRef, request: UpdateSavedViewRequest) -> Result<SavedView, String> {
+        let view = self.saved_views.get(&request.id)
+            .ok_or_else(|| "Saved view not found".to_string())?;
+        if view.owner != *user {
+            return Err("Not authorized to update this view".to_string());
+        }
+
+        let name = match request.name {
+            Some(name) if name.trim().is_empty() => return Err("View name cannot be empty".to_string()),
+            Some(name) => name.trim().to_string(),
+            None => view.name.clone(),
+        };
+        let columns = request.columns.unwrap_or_else(|| view.columns.clone());
+        let workspace_id = request.workspace_id.or(view.workspace_id);
+        self.validate_view(user, workspace_id, &columns)?;
+        let shared_with = match request.shared_with {
+            Some(entries) => self.resolve_view_members(user, &entries)?,
+            None => view.shared_with.clone(),
+        };
+
+        let view = self.saved_views.get_mut(&request.id)
+            .ok_or_else(|| "Saved view not found".to_string())?;
+        view.name = name;
+        view.columns = columns;
+        view.workspace_id = workspace_id;
+        view.shared_with = shared_with;
+        if let Some(filter) = request.filter {
+            view.filter = filter;
+        }
+        if let Some(sort) = request.sort {
+            view.sort = Some(sort);
+        }
+        if let Some(group_by) = request.group_by {
+            view.group_by = Some(group_by);
+        }
+        view.updated_at = ic_cdk::api::time();
+
+        Ok(view.clone())
+    }
+
+    /// Delete a saved view (owner only)
+    pub fn delete_saved_view(&mut self, user: &UserRef, id: SavedViewId) -> Result<SavedView, String> {
+        let view = self.saved_views.get(&id)
+            .ok_or_else(|| "Saved view not found".to_string())?;
+        if view.owner != *user {
+            return Err("Not authorized to delete this view".to_string());
+        }
+
+        let view = self.saved_views.remove(&id)
+            .ok_or_else(|| "Saved view not found".to_string())?;
+        if let Some(ids) = self.user_saved_views.get_mut(user) {
+            ids.retain(|&vid| vid != id);
+        }
+        Ok(view)
+    }
+
+    /// Get the views a user owns or that are shared with them, optionally only
+    /// those pinned to a workspace
+    pub fn get_saved_views(&self, user: &UserRef, workspace_id: Option<WorkspaceId>) -> Vec<SavedView> {
+        self.saved_views
+            .values()
+            .filter(|v| v.owner == *user || v.shared_with.contains(user))
+            .filter(|v| workspace_id.is_none() || v.workspace_id == workspace_id)
+            .cloned()
+            .collect()
+    }
+
+    /// Run a saved view over its owner's captures. Date-relative conditions are
+    /// evaluated against `now`; pagination applies before grouping.
+    pub fn run_saved_view(
+        &self,
+        user: &UserRef,
+        id: SavedViewId,
+        pagination: PaginationParams,
+        now: Timestamp,
+    ) -> Result<SavedViewResult, String> {
+        let view = self.saved_views.get(&id)
+            .ok_or_else(|| "Saved view not found".to_string())?;
+        if view.owner != *user && !view.shared_with.contains(user) {
+            return Err("Not authorized to view this saved view".to_string());
+        }
+
+        let mut captures: Vec<Capture> = self.owner_capture_ids(&view.owner)
+            .iter()
+            .filter_map(|id| self.captures.get(id))
+            .filter(|c| view.workspace_id.is_none() || c.fields.workspace_id == view.workspace_id)
+            .filter(|c| self.capture_matches(&view.filter, c, now))
+            .cloned()
+            .collect();
+        let sort = view.sort.clone().unwrap_or(ViewSort {
+            field: ViewSortField::Rank,
+            descending: false,
+        });
+        let custom_kind = match &sort.field {
+            ViewSortField::Custom(key) => self
+                .applicable_custom_fields(&view.owner, view.workspace_id)
+                .get(key.as_str())
+                .map(|definition| definition.kind.clone()),
+            _ => None,
+        };
+        sort_captures(&mut captures, &sort, custom_kind.as_ref());
+
+        let total = captures.len() as u64;
+        let offset = pagination.offset.unwrap_or(0);
+        let limit = pagination.limit.unwrap_or(50);
+
+        let page: Vec<Capture> = captures
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .collect();
+
+        Ok(SavedViewResult {
+            view: view.clone(),
+            groups: group_captures(page, view.group_by.as_ref()),
+            total,
+            offset,
+            limit,
+        })
+    }
+
+    // =========================================================================
+    // Custom Field Operations
+    // =========================================================================
+
+    /// Define a custom field for the owner, or for one of the owner's workspaces
+    pub fn create_custom_field(&mut self, owner: UserRef, request: CreateCustomFieldRequest) -> Result<CustomFieldDefinition, String> {
+        let key = normalize_field_key(&request.key)?;
+        let name = request.name.trim().to_string();
+        if name.is_empty() {
+            return Err("Custom field name cannot be empty".to_string());
+        }
+        validate_field_kind(&request.kind)?;
+        if let Some(workspace_id) = request.workspace_id {
+            if !self.workspaces.contains_key(&workspace_id) {
+                return Err("Workspace not found".to_string());
+            }
+            if !self.is_workspace_owned_by(&owner, workspace_id) {
+                return Err("Not authorized to define fields in this workspace".to_string());
+            }
+        }
+
+        let scope: Vec<&CustomFieldDefinition> = self.custom_field_definitions
+            .values()
+            .filter(|d| d.owner == owner && d.workspace_id == request.workspace_id)
+            .collect();
+        if scope.len() >= MAX_CUSTOM_FIELDS_PER_SCOPE {
+            return Err(format!("Custom field limit reached (max {})", MAX_CUSTOM_FIELDS_PER_SCOPE));
+        }
+        if scope.iter().any(|d| d.key == key) {
+            return Err(format!("A custom field with key '{}' already exists", key));
+        }
+
+        let now = ic_cdk::api::time();
+        let id = self.next_custom_field_id;
+        self.next_custom_field_id += 1;
+
+        let definition = CustomFieldDefinition {
+            id,
+            owner,
+            workspace_id: request.workspace_id,
+            key,
+            name,
+            kind: request.kind,
+            required: request.required.unwrap_or(false),
+            created_at: now,
+            updated_at: now,
+        };
+        self.custom_field_definitions.insert(id, definition.clone());
+
+        Ok(definition)
+    }
+
+    /// Update a custom field definition (owner only)
+    pub fn update_custom_field(&mut self, user: &UserRef, request: UpdateCustomFieldRequest) -> Result<CustomFieldDefinition, String> {
+        if let Some(kind) = request.kind.as_ref() {
+            validate_field_kind(kind)?;
+        }
+        let definition = self.custom_field_definitions.get_mut(&request.id)
+            .ok_or_else(|| "Custom field not found".to_string())?;
+        if definition.owner != *user {
+            return Err("Not authorized to update this custom field".to_string());
+        }
+
+        if let Some(name) = request.name {
+            if name.trim().is_empty() {
+                return Err("Custom field name cannot be empty".to_string());
+            }
+            definition.name = name.trim().to_string();
+        }
+        if let Some(kind) = request.kind {
+            definition.kind = kind;
+        }
+        if let Some(required) = request.required {
+            definition.required = required;
+        }
+        definition.updated_at = ic_cdk::api::time();
+
+        Ok(definition.clone())
+    }
+
+    /// Delete a custom field definition (owner only). Stored values are kept as untyped fields.
+    pub fn delete_custom_field(&mut self, user: &UserRef, id: CustomFieldId) -> Result<CustomFieldDefinition, String> {
+        let definition = self.custom_field_definitions.get(&id)
+            .ok_or_else(|| "Custom field not found".to_string())?;
+        if definition.owner != *user {
+            return Err("Not authorized to delete this custom field".to_string());
+        }
+
+        self.custom_field_definitions.remove(&id)
+            .ok_or_else(|| "Custom field not found".to_string())
+    }
+
+    /// Get the custom fields that apply to a user's captures, optionally within one
+    /// of their workspaces
+    pub fn get_custom_fields(&self, user: &UserRef, workspace_id: Option<WorkspaceId>) -> Result<Vec<CustomFieldDefinition>, String> {
+        if let Some(workspace_id) = workspace_id {
+            if !self.is_workspace_owned_by(user, workspace_id) {
+                return Err("Not authorized to access this workspace".to_string());
+            }
+        }
+
+        Ok(self.applicable_custom_fields(user, workspace_id).into_values().cloned().collect())
+    }
+
+    /// Definitions that apply to an owner's captures in a workspace, by key;
+    /// workspace definitions override the owner's own
+    fn applicable_custom_fields(&self, owner: &UserRef, workspace_id: Option<WorkspaceId>) -> BTreeMap<&str, &CustomFieldDefinition> {
+        let mut definitions: BTreeMap<&str, &CustomFieldDefinition> = BTreeMap::new();
+        for definition in self.custom_field_definitions.values() {
+            if definition.owner == *owner && definition.workspace_id.is_none() {
+                definitions.insert(definition.key.as_str(), definition);
+            }
+        }
+        if workspace_id.is_some() {
+            for definition in self.custom_field_definitions.values() {
+                if definition.workspace_id == workspace_id {
+                    definitions.insert(definition.key.as_str(), definition);
+                }
+            }
+        }
+        definitions
+    }
+
+    /// Kind of a custom field on a capture, if it is defined
+    fn custom_field_kind(&self, capture: &Capture, key: &str) -> Option<CustomFieldKind> {
+        let owner = self.capture_owner_ref(capture)?;
+        self.applicable_custom_fields(&owner, capture.fields.workspace_id)
+            .get(key)
+            .map(|definition| definition.kind.clone())
+    }
+
+    /// Check a capture's custom field values against the definitions that apply to
+    /// it. Keys without a definition are accepted as untyped values.
+    fn validate_custom_fields(&self, owner: &UserRef, fields: &DynamicFields) -> Result<(), String> {
+        let definitions = self.applicable_custom_fields(owner, fields.workspace_id);
+
+        for (key, value) in &fields.custom_fields {
+            if value.len() > MAX_CUSTOM_FIELD_VALUE_LEN {
+                return Err(format!("Custom field '{}' is too long (max {} bytes)", key, MAX_CUSTOM_FIELD_VALUE_LEN));
+            }
+            if let Some(definition) = definitions.get(key.as_str()) {
+                validate_custom_value(definition, value)?;
+            }
+        }
+        for definition in definitions.values().filter(|d| d.required) {
+            let present = fields.custom_fields
+                .iter()
+                .any(|(key, value)| *key == definition.key && !value.trim().is_empty());
+            if !present {
+                return Err(format!("Custom field '{}' is required", definition.name));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a capture passes a filter; date-relative conditions are evaluated against `now`
+    fn capture_matches(&self, filter: &CaptureFilter, capture: &Capture, now: Timestamp) -> bool {
+        let fields = &capture.fields;
+
+        filter.capture_type.as_ref().is_none_or(|t| capture.capture_type == *t)
+            && filter.status.as_ref().is_none_or(|s| capture.status == *s)
+            && filter.priority.as_ref().is_none_or(|p| capture.priority == *p)
+            && filter.sprint_id.is_none_or(|sid| fields.sprint_id == Some(sid))
+            && filter.workspace_id.is_none_or(|wid| fields.workspace_id == Some(wid))
+            && filter.labels.as_ref().is_none_or(|labels| labels.iter().all(|l| fields.labels.contains(l)))
+            && filter.dates.iter().flatten().all(|condition| date_matches(condition, capture, now))
+            && filter.custom_fields.iter().flatten().all(|condition| {
+                let kind = self.custom_field_kind(capture, &condition.key);
+                custom_value_matches(capture, &condition.key, kind.as_ref(), &condition.comparison, &condition.value)
+            })
+    }
+
+    // =========================================================================
+    // Query Operations
+    // =========================================================================
+
+    /// Run a capture query (see `query.rs`) over a user's captures, in creation
+    /// order. Date-relative terms are evaluated against `now`.
+    pub fn query_captures(
+        &self,
+        owner: &UserRef,
+        query: &str,
+        pagination: PaginationParams,
+        now: Timestamp,
+    ) -> Result<PaginatedResponse<Capture>, String> {
+        let query = parse_query(query)
+            .map_err(|e| format!("{} (at position {})", e.message, e.position))?;
+
+        let candidates: Vec<CaptureId> = match self.indexed_candidates(owner, &query, now) {
+            Some(ids) => ids
+                .into_iter()
+                .filter(|id| {
+                    self.captures
+                        .get(id)
+                        .is_some_and(|c| self.capture_owner_ref(c).as_ref() == Some(owner))
+                })
+                .collect(),
+            None => self.owner_capture_ids(owner),
+        };
+        let captures: Vec<Capture> = candidates
+            .iter()
+            .filter_map(|id| self.captures.get(id))
+            .filter(|c| query.terms.iter().all(|term| self.term_matches(term, c, now)))
+            .cloned()
+            .collect();
+
+        let total = captures.len() as u64;
+        let offset = pagination.offset.unwrap_or(0);
+        let limit = pagination.limit.unwrap_or(50);
+
+        let items: Vec<Capture> = captures
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .collect();
+
+        Ok(PaginatedResponse {
+            items,
+            total,
+            offset,
+            limit,
+        })
+    }
+
+    /// Candidate captures from the indexes behind the query's non-negated parent,
+    /// assignee and due/start date terms; None when no term can use an index
+    fn indexed_candidates(&self, owner: &UserRef, query: &Query, now: Timestamp) -> Option<BTreeSet<CaptureId>> {
+        let mut candidates: Option<BTreeSet<CaptureId>> = None;
+
+        for term in query.terms.iter().filter(|t| !t.negated) {
+            let ids: BTreeSet<CaptureId> = match &term.predicate {
+                Predicate::Parent(parents) => parents
+                    .iter()
+                    .filter_map(|id| self.capture_children.get(id))
+                    .flatten()
+                    .copied()
+                    .collect(),
+                Predicate::Assignee(entries) => entries
+                    .iter()
+                    .filter_map(|entry| self.resolve_assignee(entry))
+                    .filter_map(|user| self.assigned_captures.get(&user))
+                    .flatten()
+                    .copied()
+                    .collect(),
+                Predicate::Date(field @ (DateField::Due | DateField::Start), comparison, value) => {
+                    let index = if *field == DateField::Due {
+                        &self.due_date_index
+                    } else {
+                        &self.start_date_index
+                    };
+                    let (from, to) = date_range(comparison, value, now);
+                    index
+                        .get(owner)
+                        .into_iter()
+                        .flat_map(|index| index.range((from, 0)..(to, 0)))
+                        .map(|(_, id)| *id)
+                        .collect()
+                }
+                _ => continue,
+            };
+
+            candidates = Some(match candidates {
+                Some(current) => current.intersection(&ids).copied().collect(),
+                None => ids,
+            });
+        }
+
+        candidates
+    }
+
+    /// Whether a capture satisfies a query term
+    fn term_matches(&self, term: &Term, capture: &Capture, now: Timestamp) -> bool {
+        let fields = &capture.fields;
+        let matched = match &term.predicate {
+            Predicate::Type(types) => types.contains(&capture.capture_type),
+            Predicate::Status(statuses) => statuses.contains(&capture.status),
+            Predicate::Priority(priorities) => priorities.contains(&capture.priority),
+            Predicate::Label(labels) => labels
+                .iter()
+                .any(|label| fields.labels.iter().any(|l| l.eq_ignore_ascii_case(label))),
+            Predicate::Sprint(ids) => fields.sprint_id.is_some_and(|id| ids.contains(&id)),
+            Predicate::Workspace(ids) => fields.workspace_id.is_some_and(|id| ids.contains(&id)),
+            Predicate::Parent(ids) => fields.parent_id.is_some_and(|id| ids.contains(&id)),
+            Predicate::Assignee(entries) => {
+                let assigned = self.capture_assignees.get(&capture.id);
+                entries
+                    .iter()
+                    .filter_map(|entry| self.resolve_assignee(entry))
+                    .any(|user| assigned.is_some_and(|users| users.contains(&user)))
+            }
+            Predicate::Estimate(comparison, value) => fields
+                .estimate
+                .is_some_and(|estimate| compare_estimate(comparison, estimate, *value)),
+            Predicate::Date(field, comparison, value) => {
+                let (from, to) = date_range(comparison, value, now);
+                capture_date(capture, field).is_some_and(|date| from <= date && date < to)
+            }
+            Predicate::Custom(key, comparison, operands) => {
+                let kind = self.custom_field_kind(capture, key);
+                operands
+                    .iter()
+                    .any(|operand| custom_value_matches(capture, key, kind.as_ref(), comparison, operand))
+            }
+            Predicate::Text(text) => [Some(&capture.title), capture.description.as_ref(), capture.content.as_ref()]
+                .into_iter()
+                .flatten()
+                .any(|value| value.to_lowercase().contains(text.as_str())),
+        };
+
+        matched != term.negated
+    }
+
+    // =========================================================================
+    // Scheduled Jobs
+    // =========================================================================
+
+    /// Earliest pending scheduled job, if any
+    pub fn next_scheduled_job(&self) -> Option<Timestamp> {
+        let recurrence = self.recurrence_queue.first().map(|(at, _)| *at);
+        let reminder = self.reminder_queue.first().map(|(at, _)| *at);
+        match (recurrence, reminder) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Run up to `limit` scheduled jobs that are due at `now`: reminders fire, and
+    /// recurring captures whose date has passed get their next occurrence.
+    /// Returns the number of jobs run.
+    pub fn run_scheduled_jobs(&mut self, now: Timestamp, limit: usize) -> usize {
+        let reminders: Vec<(Timestamp, ReminderId)> = self.reminder_queue
+            .range(..=(now, ReminderId::MAX))
+            .take(limit)
+            .cloned()
+            .collect();
+        for key in &reminders {
+            self.reminder_queue.remove(key);
+            self.fire_reminder(key.1);
+        }
+
+        let recurring: Vec<(Timestamp, CaptureId)> = self.recurrence_queue
+            .range(..=(now, CaptureId::MAX))
+            .take(limit - reminders.len())
+            .cloned()
+            .collect();
+        for key in &recurring {
+            self.recurrence_queue.remove(key);
+            self.spawn_next_occurrence(key.1, now);
+        }
+
+        reminders.len() + recurring.len()
+    }
+
+    // =========================================================================
+    // Backlog & Ranking Operations
+    // =========================================================================
+
+    /// Get the IDs of all captures belonging to the same owner as the given capture
+    /// (resolves user_id ownership for session-based captures)
+    fn capture_owner_ids(&self, capture: &Capture) -> Vec<CaptureId> {
+        if capture.owner != Principal::anonymous() {
+            return self.user_captures.get(&capture.owner).cloned().unwrap_or_default();
+        }
+
+        self.user_id_captures
+            .values()
+            .find(|ids| ids.contains(&capture.id))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Identify the owner of a capture (principal, or user_id for session-based captures)
+    fn capture_owner_ref(&self, capture: &Capture) -> Option<UserRef> {
+        if capture.owner != Principal::anonymous() {
+            return Some(UserRef::Principal(capture.owner));
+        }
+
+        self.user_id_captures
+            .iter()
+            .find(|(_, ids)| ids.contains(&capture.id))
+            .map(|(user_id, _)| UserRef::UserId(user_id.clone()))
+    }
+
+    /// Get the ordered (id, rank) list of an owner's captures in a sprint,
+    /// or in the backlog when `sprint_id` is None.
+    /// Unranked captures (created before ranking existed) sort last, by ID.
+    fn ranked_list(&self, owner_ids: &[CaptureId], sprint_id: Option<SprintId>) -> Vec<(CaptureId, Option<String>)> {
+        let mut list: Vec<(CaptureId, Option<String>)> = owner_ids
+            .iter()
+            .filter_map(|id| self.captures.get(id))
+            .filter(|c| c.fields.sprint_id == sprint_id)
+            .map(|c| (c.id, c.rank.clone()))
+            .collect();
+
+        list.sort_by(|(a_id, a_rank), (b_id, b_rank)| {
+            (a_rank.is_none(), a_rank, a_id).cmp(&(b_rank.is_none(), b_rank, b_id))
+        });
+        list
+    }
+
+    /// Rank key for appending a capture to the bottom of a backlog or sprint
+    fn next_rank_in_list(&self, owner_ids: &[CaptureId], sprint_id: Option<SprintId>) -> String {
+        let last = self.ranked_list(owner_ids, sprint_id)
+            .into_iter()
+            .filter_map(|(_, rank)| rank)
+            .next_back();
+        rank_after(last.as_deref())
+    }
+
+    /// Assign ranks to any unranked captures in a list, keeping their current order
+    fn fill_missing_ranks(&mut self, owner_ids: &[CaptureId], sprint_id: Option<SprintId>) {
+        let list = self.ranked_list(owner_ids, sprint_id);
+        let mut last = list.iter().filter_map(|(_, rank)| rank.clone()).next_back();
+
+        for (id, rank) in list {
+            if rank.is_some() {
+                continue;
+            }
+            let next = rank_after(last.as_deref());
+            if let Some(capture) = self.captures.get_mut(&id) {
+                capture.rank = Some(next.clone());
+            }
+            last = Some(next);
+        }
+    }
+
+    /// Keep a sprint's capture_ids in rank order
+    fn sort_sprint_capture_ids(&mut self, sprint_id: SprintId) {
+        let Some(sprint) = self.sprints.get(&sprint_id) else {
+            return;
+        };
+
+        let mut ordered: Vec<(Option<String>, CaptureId)> = sprint.capture_ids
+            .iter()
+            .map(|id| (self.captures.get(id).and_then(|c| c.rank.clone()), *id))
+            .collect();
+        ordered.sort_by(|(a_rank, a_id), (b_rank, b_id)| {
+            (a_rank.is_none(), a_rank, a_id).cmp(&(b_rank.is_none(), b_rank, b_id))
+        });
+
+        if let Some(sprint) = self.sprints.get_mut(&sprint_id) {
+            sprint.capture_ids = ordered.into_iter().map(|(_, id)| id).collect();
+        }
+    }
+
+    /// Move a capture within its backlog or sprint.
+    /// `before` is the capture that should end up directly above it, `after` the one directly below.
+    /// Either may be omitted to place the capture next to the single given neighbour.
+    pub fn reorder_capture(
+        &mut self,
+        id: CaptureId,
+        before: Option<CaptureId>,
+        after: Option<CaptureId>,
+    ) -> Result<Capture, String> {
+        if before.is_none() && after.is_none() {
+            return Err("Provide a before or after capture to reorder against".to_string());
+        }
+        if before == Some(id) || after == Some(id) {
+            return Err("A capture cannot be ordered relative to itself".to_string());
+        }
+
+        let capture = self.captures.get(&id)
+            .ok_or_else(|| "Capture not found".to_string())?;
+        let sprint_id = capture.fields.sprint_id;
+        let owner_ids = self.capture_owner_ids(capture);
+
+        self.fill_missing_ranks(&owner_ids, sprint_id);
+
+        let list: Vec<(CaptureId, String)> = self.ranked_list(&owner_ids, sprint_id)
+            .into_iter()
+            .filter(|(cid, _)| *cid != id)
+            .filter_map(|(cid, rank)| rank.map(|r| (cid, r)))
+            .collect();
+
+        let rank = rank_between_neighbours(&list, before, after, "the same backlog or sprint")?;
+        let now = ic_cdk::api::time();
+
+        let capture = self.captures.get_mut(&id)
+            .ok_or_else(|| "Capture not found".to_string())?;
+        capture.rank = Some(rank);
+        capture.updated_at = now;
+        let updated = capture.clone();
+
+        if let Some(sid) = sprint_id {
+            self.sort_sprint_capture_ids(sid);
+        }
+
+        Ok(updated)
+    }
+
+    /// Get an owner's backlog: open captures without a sprint, in rank order
+    pub fn get_backlog(&self, owner: Principal, pagination: PaginationParams) -> PaginatedResponse<Capture> {
+        let owner_ids = self.user_captures.get(&owner).cloned().unwrap_or_default();
+
+        let captures: Vec<Capture> = self.ranked_list(&owner_ids, None)
+            .into_iter()
+            .filter_map(|(id, _)| self.captures.get(&id))
+            .filter(|c| is_open(&c.status))
+            .cloned()
+            .collect();
+
+        let total = captures.len() as u64;
+        let offset = pagination.offset.unwrap_or(0);
+        let limit = pagination.limit.unwrap_or(50);
+
+        let items: Vec<Capture> = captures
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .collect();
+
+        PaginatedResponse {
+            items,
+            total,
+            offset,
+            limit,
+        }
+    }
+
+    /// Get a sprint's captures in rank order
+    pub fn get_sprint_captures(&self, sprint_id: SprintId) -> Vec<Capture> {
+        let Some(sprint) = self.sprints.get(&sprint_id) else {
+            return vec![];
+        };
+
+        let mut captures: Vec<Capture> = sprint.capture_ids
+            .iter()
+            .filter_map(|id| self.captures.get(id))
+            .cloned()
+            .collect();
+        captures.sort_by(|a, b| {
+            (a.rank.is_none(), &a.rank, a.id).cmp(&(b.rank.is_none(), &b.rank, b.id))
+        });
+        captures
+    }
+
+    // =========================================================================
+    // User ID-based Operations (for session-based authentication)
+    // =========================================================================
+
+    /// Create a capture owned by a user_id (session-based auth)
+    pub fn create_capture_for_user_id(&mut self, user_id: &str, request: CreateCaptureRequest) -> Result<Capture, String> {
+        let now = ic_cdk::api::time();
+        let id = self.next_capture_id;
+
+        validate_subtype(&request.capture_type, request.subtype.as_ref())?;
+        let fields = request.fields.unwrap_or_default();
+        self.validate_assignees(&fields.assignees)?;
+        self.validate_custom_fields(&UserRef::UserId(user_id.to_string()), &fields)?;
+        if let Some(parent_id) = fields.parent_id {
+            self.validate_parent(None, Some(&UserRef::UserId(user_id.to_string())), parent_id)?;
+        }
+        let recurrence = request.recurrence
+            .map(|rule| start_recurrence(id, rule, &fields))
+            .transpose()?;
+        self.next_capture_id += 1;
+
+        let owner_ids = self.user_id_captures.get(user_id).cloned().unwrap_or_default();
+        let rank = self.next_rank_in_list(&owner_ids, fields.sprint_id);
+
+        // Use anonymous principal as placeholder for user_id-owned captures
+        let capture = Capture {
+            id,
+            owner: Principal::anonymous(),  // Placeholder - actual owner is user_id
+            capture_type: request.capture_type,
+            subtype: request.subtype,
+            title: request.title,
+            description: request.description,
+            content: request.content,
+            priority: request.priority.unwrap_or_default(),
+            status: CaptureStatus::Draft,
+            fields,
+            rank: Some(rank),
+            recurrence,
+            checklist: None,
+            conversions: None,
+            template: None,
+            created_at: now,
+            updated_at: now,
+        };
+
+        self.captures.insert(id, capture.clone());
+        self.user_id_captures
+            .entry(user_id.to_string())
+            .or_default()
+            .push(id);
+        self.on_capture_changed(None, id);
+
+        Ok(capture)
+    }
+
+    /// Get captures by user_id (for session-based auth)
+    pub fn get_user_id_captures(
+        &self,
+        user_id: &str,
+        filter: Option<CaptureFilter>,
+        pagination: PaginationParams,
+    ) -> PaginatedResponse<Capture> {
+        let capture_ids = self.user_id_captures.get(user_id).cloned().unwrap_or_default();
+
+        let mut captures: Vec<Capture> = capture_ids
+            .iter()
+            .filter_map(|id| self.captures.get(id))
+            .cloned()
+            .collect();
+
+        // Apply filters (same as get_user_captures)
+        if let Some(ref f) = filter {
+            let now = ic_cdk::api::time();
+            captures.retain(|c| self.capture_matches(f, c, now));
+        }
+
+        let total = captures.len() as u64;
+        let offset = pagination.offset.unwrap_or(0);
+        let limit = pagination.limit.unwrap_or(50);
+
+        let items: Vec<Capture> = captures
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .collect();
+
+        PaginatedResponse {
+            items,
+            total,
+            offset,
+            limit,
+        }
+    }
+
+    /// Check if a capture is owned by user_id
+    pub fn is_capture_owned_by_user_id(&self, capture_id: CaptureId, user_id: &str) -> bool {
+        self.user_id_captures
+            .get(user_id)
+            .map(|ids| ids.contains(&capture_id))
+            .unwrap_or(false)
+    }
+
+    /// Delete a capture owned by user_id
+    pub fn delete_capture_by_user_id(&mut self, id: CaptureId, user_id: &str) -> Option<Capture> {
+        // Verify ownership
+        if !self.is_capture_owned_by_user_id(id, user_id) {
+            return None;
+        }
+
+        let capture = self.captures.remove(&id)?;
+
+        // Clean up while the capture is still in the user_id index, which
+        // identifies its owner
+        self.on_capture_removed(&capture);
+        if let Some(user_captures) = self.user_id_captures.get_mut(user_id) {
+            user_captures.retain(|&cid| cid != id);
+        }
+
+        Some(capture)
+    }
+
+    /// Create a sprint owned by user_id
+    pub fn create_sprint_for_user_id(&mut self, user_id: &str, request: CreateSprintRequest) -> Result<Sprint, String> {
+        let stream = normalize_stream(request.stream);
+        let sibling_ids = self.user_id_sprints.get(user_id).cloned().unwrap_or_default();
+        self.validate_sprint_schedule(
+            &sibling_ids,
+            None,
+            request.start_date,
+            request.end_date,
+            &SprintStatus::Planning,
+            &stream,
+        )?;
+
+        let now = ic_cdk::api::time();
+        let id = self.next_sprint_id;
+        self.next_sprint_id += 1;
+
+        let sprint = Sprint {
+            id,
+            owner: Principal::anonymous(),  // Placeholder - actual owner is user_id
+            name: request.name,
+            goal: request.goal,
+            status: SprintStatus::Planning,
+            start_date: request.start_date,
+            end_date: request.end_date,
+            capacity: request.capacity,
+            capture_ids: Vec::new(),
+            stream,
+            created_at: now,
+            updated_at: now,
+        };
+
+        self.sprints.insert(id, sprint.clone());
+        self.user_id_sprints
+            .entry(user_id.to_string())
+            .or_default()
+            .push(id);
+
+        Ok(sprint)
+    }
+
+    /// Get sprints by user_id
+    pub fn get_user_id_sprints(&self, user_id: &str) -> Vec<Sprint> {
+        self.user_id_sprints
+            .get(user_id)
+            .map(|ids| {
+                ids.iter()
+                    .filter_map(|id| self.sprints.get(id))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Check if a sprint is owned by user_id
+    pub fn is_sprint_owned_by_user_id(&self, sprint_id: SprintId, user_id: &str) -> bool {
+        self.user_id_sprints
+            .get(user_id)
+            .map(|ids| ids.contains(&sprint_id))
+            .unwrap_or(false)
+    }
+
+    /// Create a workspace owned by user_id
+    pub fn create_workspace_for_user_id(&mut self, user_id: &str, request: CreateWorkspaceRequest) -> Workspace {
+        let now = ic_cdk::api::time();
+        let id = self.next_workspace_id;
+        self.next_workspace_id += 1;
+
+        let workspace = Workspace {
+            id,
+            owner: Principal::anonymous(),  // Placeholder - actual owner is user_id
+            name: request.name,
+            description: request.description,
+            icon: request.icon,
+            parent_id: request.parent_id,
+            is_archived: false,
+            created_at: now,
+            updated_at: now,
+        };
+
+        self.workspaces.insert(id, workspace.clone());
+        self.user_id_workspaces
+            .entry(user_id.to_string())
+            .or_default()
+            .push(id);
+
+        workspace
+    }
+
+    /// Get workspaces by user_id
+    pub fn get_user_id_workspaces(&self, user_id: &str) -> Vec<Workspace> {
+        self.user_id_workspaces
+            .get(user_id)
+            .map(|ids| {
+                ids.iter()
+                    .filter_map(|id| self.workspaces.get(id))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Check if a workspace is owned by user_id
+    pub fn is_workspace_owned_by_user_id(&self, workspace_id: WorkspaceId, user_id: &str) -> bool {
+        self.user_id_workspaces
+            .get(user_id)
+            .map(|ids| ids.contains(&workspace_id))
+            .unwrap_or(false)
+    }
+
+    /// Create a template owned by user_id
+    pub fn create_template_for_user_id(&mut self, user_id: &str, request: CreateTemplateRequest) -> Result<Template, String> {
+        validate_new_template(&request)?;
+        let tags = request.tags.map(normalize_template_tags).transpose()?;
+        let now = ic_cdk::api::time();
+        let id = self.next_template_id;
+        self.next_template_id += 1;
+
+        let is_public = request.is_public.unwrap_or(false);
+
+        let template = Template {
+            id,
+            owner: Principal::anonymous(),  // Placeholder - actual owner is user_id
+            template_type: request.template_type,
+            name: request.name,
+            description: request.description,
+            content: request.content,
+            capture_type: request.capture_type,
+            default_fields: request.default_fields,
+            is_public,
+            variables: request.variables,
+            scaffold: request.scaffold,
+            latest_version: None,
+            forked_from: None,
+            category: request.category,
+            tags,
+            created_at: now,
+            updated_at: now,
+        };
+
+        self.templates.insert(id, template.clone());
+        self.user_id_templates
+            .entry(user_id.to_string())
+            .or_default()
+            .push(id);
+
+        if is_public {
+            self.public_templates.push(id);
+            self.push_template_version(&template, None, now);
+        }
+
+        self.templates.get(&id)
+            .cloned()
+            .ok_or_else(|| "Template not found".to_string())
+    }
+
+    /// Get templates by user_id
+    pub fn get_user_id_templates(&self, user_id: &str) -> Vec<Template> {
+        self.user_id_templates
+            .get(user_id)
+            .map(|ids| {
+                ids.iter()
+                    .filter_map(|id| self.templates.get(id))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Check if a template is owned by user_id
+    pub fn is_template_owned_by_user_id(&self, template_id: TemplateId, user_id: &str) -> bool {
         self.user_id_templates
             .get(user_id)
+            .map(|ids| ids.contains(&template_id))
+            .unwrap_or(false)
+    }
+
+    // =========================================================================
+    // Sprint Operations
+    // =========================================================================
+
+    /// Create a new sprint
+    /// Rejects empty or inverted date ranges and overlaps with the owner's active sprints
+    pub fn create_sprint(&mut self, owner: Principal, request: CreateSprintRequest) -> Result<Sprint, String> {
+        let stream = normalize_stream(request.stream);
+        let sibling_ids = self.user_sprints.get(&owner).cloned().unwrap_or_default();
+        self.validate_sprint_schedule(
+            &sibling_ids,
+            None,
+            request.start_date,
+            request.end_date,
+            &SprintStatus::Planning,
+            &stream,
+        )?;
+
+        let now = ic_cdk::api::time();
+        let id = self.next_sprint_id;
+        self.next_sprint_id += 1;
+
+        let sprint = Sprint {
+            id,
+            owner,
+            name: request.name,
+            goal: request.goal,
+            status: SprintStatus::Planning,
+            start_date: request.start_date,
+            end_date: request.end_date,
+            capacity: request.capacity,
+            capture_ids: Vec::new(),
+            stream,
+            created_at: now,
+            updated_at: now,
+        };
+
+        self.sprints.insert(id, sprint.clone());
+        self.user_sprints
+            .entry(owner)
+            .or_default()
+            .push(id);
+
+        Ok(sprint)
+    }
+
+    /// Get a sprint by ID
+    pub fn get_sprint(&self, id: SprintId) -> Option<&Sprint> {
+        self.sprints.get(&id)
+    }
+
+    /// Add capture to sprint
+    pub fn add_capture_to_sprint(&mut self, sprint_id: SprintId, capture_id: CaptureId) -> Result<(), String> {
+        let sprint = self.sprints.get(&sprint_id)
+            .ok_or_else(|| "Sprint not found".to_string())?;
+
+        let capture = self.captures.get(&capture_id)
+            .ok_or_else(|| "Capture not found".to_string())?;
+
+        if sprint.capture_ids.contains(&capture_id) {
+            return Ok(());
+        }
+
+        // New sprint members go to the bottom of the sprint
+        let owner_ids = self.capture_owner_ids(capture);
+        let rank = self.next_rank_in_list(&owner_ids, Some(sprint_id));
+
+        if let Some(sprint) = self.sprints.get_mut(&sprint_id) {
+            sprint.capture_ids.push(capture_id);
+        }
+        if let Some(capture) = self.captures.get_mut(&capture_id) {
+            capture.fields.sprint_id = Some(sprint_id);
+            capture.rank = Some(rank);
+        }
+
+        Ok(())
+    }
+
+    /// Remove capture from sprint
+    pub fn remove_capture_from_sprint(&mut self, sprint_id: SprintId, capture_id: CaptureId) -> Result<(), String> {
+        let sprint = self.sprints.get_mut(&sprint_id)
+            .ok_or_else(|| "Sprint not found".to_string())?;
+
+        sprint.capture_ids.retain(|&id| id != capture_id);
+
+        let in_sprint = self.captures.get(&capture_id)
+            .map(|c| c.fields.sprint_id == Some(sprint_id))
+            .unwrap_or(false);
+
+        if in_sprint {
+            // Captures leaving a sprint return to the bottom of the backlog
+            let owner_ids = self.captures.get(&capture_id)
+                .map(|c| self.capture_owner_ids(c))
+                .unwrap_or_default();
+            let rank = self.next_rank_in_list(&owner_ids, None);
+
+            if let Some(capture) = self.captures.get_mut(&capture_id) {
+                capture.fields.sprint_id = None;
+                capture.rank = Some(rank);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get user's sprints
+    pub fn get_user_sprints(&self, owner: Principal) -> Vec<Sprint> {
+        self.user_sprints
+            .get(&owner)
+            .map(|ids| {
+                ids.iter()
+                    .filter_map(|id| self.sprints.get(id))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Get the IDs of all sprints belonging to the same owner as the given sprint
+    /// (resolves user_id ownership for session-based sprints)
+    fn sprint_sibling_ids(&self, sprint: &Sprint) -> Vec<SprintId> {
+        if sprint.owner != Principal::anonymous() {
+            return self.user_sprints.get(&sprint.owner).cloned().unwrap_or_default();
+        }
+
+        self.user_id_sprints
+            .values()
+            .find(|ids| ids.contains(&sprint.id))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Validate a sprint's date range against the owner's other sprints.
+    /// Two sprints in the same stream may not overlap while either of them is Active.
+    fn validate_sprint_schedule(
+        &self,
+        sibling_ids: &[SprintId],
+        sprint_id: Option<SprintId>,
+        start_date: Timestamp,
+        end_date: Timestamp,
+        status: &SprintStatus,
+        stream: &Option<String>,
+    ) -> Result<(), String> {
+        if end_date < start_date {
+            return Err("Sprint end_date cannot be before start_date".to_string());
+        }
+        if end_date == start_date {
+            return Err("Sprint must have a non-zero duration".to_string());
+        }
+
+        for other in sibling_ids.iter().filter_map(|id| self.sprints.get(id)) {
+            if Some(other.id) == sprint_id || &other.stream != stream {
+                continue;
+            }
+            if *status != SprintStatus::Active && other.status != SprintStatus::Active {
+                continue;
+            }
+            if start_date < other.end_date && other.start_date < end_date {
+                return Err(format!(
+                    "Sprint overlaps active sprint {} ({}) in the same stream; use a separate stream to run sprints in parallel",
+                    other.id, other.name
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Update a sprint
+    pub fn update_sprint(&mut self, id: SprintId, request: UpdateSprintRequest) -> Result<Sprint, String> {
+        let current = self.sprints.get(&id)
+            .ok_or_else(|| "Sprint not found".to_string())?;
+
+        let start_date = request.start_date.unwrap_or(current.start_date);
+        let end_date = request.end_date.unwrap_or(current.end_date);
+        let status = request.status.clone().unwrap_or_else(|| current.status.clone());
+        let stream = match request.stream {
+            Some(stream) => normalize_stream(Some(stream)),
+            None => current.stream.clone(),
+        };
+        let sibling_ids = self.sprint_sibling_ids(current);
+        self.validate_sprint_schedule(&sibling_ids, Some(id), start_date, end_date, &status, &stream)?;
+
+        let sprint = self.sprints.get_mut(&id)
+            .ok_or_else(|| "Sprint not found".to_string())?;
+        let now = ic_cdk::api::time();
+
+        if let Some(name) = request.name {
+            sprint.name = name;
+        }
+        if let Some(goal) = request.goal {
+            sprint.goal = Some(goal);
+        }
+        if let Some(status) = request.status {
+            sprint.status = status;
+        }
+        if let Some(start_date) = request.start_date {
+            sprint.start_date = start_date;
+        }
+        if let Some(end_date) = request.end_date {
+            sprint.end_date = end_date;
+        }
+        if let Some(capacity) = request.capacity {
+            sprint.capacity = Some(capacity);
+        }
+        sprint.stream = stream;
+
+        sprint.updated_at = now;
+
+        Ok(sprint.clone())
+    }
+
+    /// Delete a sprint
+    pub fn delete_sprint(&mut self, id: SprintId) -> Option<Sprint> {
+        let sprint = self.sprints.remove(&id)?;
+
+        // Remove from user_sprints index
+        if let Some(user_sprints) = self.user_sprints.get_mut(&sprint.owner) {
+            user_sprints.retain(|&sid| sid != id);
+        }
+        self.delete_scoped_boards(&BoardScope::Sprint(id));
+
+        Some(sprint)
+    }
+
+    // =========================================================================
+    // Workspace Operations
+    // =========================================================================
+
+    /// Create a new workspace
+    pub fn create_workspace(&mut self, owner: Principal, request: CreateWorkspaceRequest) -> Workspace {
+        let now = ic_cdk::api::time();
+        let id = self.next_workspace_id;
+        self.next_workspace_id += 1;
+
+        let workspace = Workspace {
+            id,
+            owner,
+            name: request.name,
+            description: request.description,
+            icon: request.icon,
+            parent_id: request.parent_id,
+            is_archived: false,
+            created_at: now,
+            updated_at: now,
+        };
+
+        self.workspaces.insert(id, workspace.clone());
+        self.user_workspaces
+            .entry(owner)
+            .or_default()
+            .push(id);
+
+        workspace
+    }
+
+    /// Get a workspace by ID
+    pub fn get_workspace(&self, id: WorkspaceId) -> Option<&Workspace> {
+        self.workspaces.get(&id)
+    }
+
+    /// Get user's workspaces
+    pub fn get_user_workspaces(&self, owner: Principal) -> Vec<Workspace> {
+        self.user_workspaces
+            .get(&owner)
+            .map(|ids| {
+                ids.iter()
+                    .filter_map(|id| self.workspaces.get(id))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Update a workspace
+    pub fn update_workspace(&mut self, id: WorkspaceId, request: UpdateWorkspaceRequest) -> Option<Workspace> {
+        let workspace = self.workspaces.get_mut(&id)?;
+        let now = ic_cdk::api::time();
+
+        if let Some(name) = request.name {
+            workspace.name = name;
+        }
+        if let Some(description) = request.description {
+            workspace.description = Some(description);
+        }
+        if let Some(icon) = request.icon {
+            workspace.icon = Some(icon);
+        }
+        if let Some(parent_id) = request.parent_id {
+            workspace.parent_id = Some(parent_id);
+        }
+        if let Some(is_archived) = request.is_archived {
+            workspace.is_archived = is_archived;
+        }
+
+        workspace.updated_at = now;
+
+        Some(workspace.clone())
+    }
+
+    /// Delete a workspace
+    pub fn delete_workspace(&mut self, id: WorkspaceId) -> Option<Workspace> {
+        let workspace = self.workspaces.remove(&id)?;
+
+        // Remove from user_workspaces index
+        if let Some(user_workspaces) = self.user_workspaces.get_mut(&workspace.owner) {
+            user_workspaces.retain(|&wid| wid != id);
+        }
+
+        // Also remove workspace_documents index
+        self.workspace_documents.remove(&id);
+        self.delete_scoped_boards(&BoardScope::Workspace(id));
+
+        Some(workspace)
+    }
+
+    // =========================================================================
+    // Document Operations
+    // =========================================================================
+
+    /// Create a new document
+    pub fn create_document(&mut self, owner: Principal, request: CreateDocumentRequest) -> Result<Document, String> {
+        self.create_document_from(owner, request, "")
+    }
+
+    /// Create a document; only @mentions not already in `previous_content` notify
+    fn create_document_from(
+        &mut self,
+        owner: Principal,
+        request: CreateDocumentRequest,
+        previous_content: &str,
+    ) -> Result<Document, String> {
+        // Verify workspace exists
+        if !self.workspaces.contains_key(&request.workspace_id) {
+            return Err("Workspace not found".to_string());
+        }
+        if let Some(parent_id) = request.parent_id {
+            self.validate_document_parent(None, owner, request.workspace_id, parent_id)?;
+        }
+
+        let now = ic_cdk::api::time();
+        let id = self.next_document_id;
+        self.next_document_id += 1;
+
+        let mut template_version = None;
+        let content = if let Some(template_id) = request.template_id {
+            // Render the template with its variable defaults
+            let (template, version) = self.resolve_template(&UserRef::Principal(owner), template_id, None)?;
+            if template.template_type != TemplateType::Document {
+                return Err("Template is not a document template".to_string());
+            }
+            template_version = version;
+            let variables = template.variables.as_deref().unwrap_or_default();
+            let values = template::resolve_values(variables, &[], ic_cdk::api::time())?;
+            template::render(&template.content, &values)
+        } else {
+            request.content.unwrap_or_default()
+        };
+        check_document_size(&content)?;
+        let rank = self.document_rank_at(None, request.workspace_id, request.parent_id, None)?;
+
+        let document = Document {
+            id,
+            workspace_id: request.workspace_id,
+            owner,
+            title: request.title,
+            content,
+            is_template: false,
+            template_id: request.template_id,
+            parent_id: request.parent_id,
+            rank: Some(rank),
+            template_version,
+            created_at: now,
+            updated_at: now,
+        };
+
+        self.documents.insert(id, document.clone());
+        self.index_document_title(&document);
+        self.workspace_documents
+            .entry(request.workspace_id)
+            .or_default()
+            .push(id);
+        self.record_activity(CommentTarget::Document(id), ActivityEvent::Created);
+        self.record_document_version(&document, None, &[], UserRef::Principal(owner), None);
+        self.index_links(CommentTarget::Document(id));
+        self.relink_title(&document.title);
+        let title = format!("Mentioned in {}", document.title);
+        self.notify_mentions(&document.content, previous_content, Some(&UserRef::Principal(owner)), None, Some(id), title);
+        if let Some(template_id) = document.template_id {
+            self.record_template_use(template_id);
+        }
+
+        Ok(document)
+    }
+
+    /// Get a document by ID
+    pub fn get_document(&self, id: DocumentId) -> Option<&Document> {
+        self.documents.get(&id)
+    }
+
+    /// Update document content; saves that change the title or content create a version
+    pub fn update_document(
+        &mut self,
+        id: DocumentId,
+        author: UserRef,
+        title: Option<String>,
+        content: Option<String>,
+    ) -> Result<Document, String> {
+        self.save_document(id, author, title, content, None)
+    }
+
+    /// Save a document. `patch` is the edit to the content when the caller
+    /// already has it; otherwise it is derived from a line diff.
+    fn save_document(
+        &mut self,
+        id: DocumentId,
+        author: UserRef,
+        title: Option<String>,
+        content: Option<String>,
+        patch: Option<Vec<PatchOp>>,
+    ) -> Result<Document, String> {
+        if let Some(content) = content.as_ref() {
+            check_document_size(content)?;
+        }
+        let doc = self.documents.get_mut(&id)
+            .ok_or_else(|| "Document not found".to_string())?;
+        let previous = doc.clone();
+        let now = ic_cdk::api::time();
+        let mut events = Vec::new();
+
+        if let Some(t) = title {
+            if t != doc.title {
+                events.push(field_changed("title", Some(doc.title.clone()), Some(t.clone())));
+            }
+            doc.title = t;
+        }
+        let previous_content = doc.content.clone();
+        if let Some(c) = content {
+            if c != doc.content {
+                events.push(field_changed("content", None, None));
+            }
+            doc.content = c;
+        }
+        doc.updated_at = now;
+        let document = doc.clone();
+        if document.title != previous.title {
+            self.unindex_document_title(&previous);
+            self.index_document_title(&document);
+        }
+
+        if !events.is_empty() {
+            // One line diff serves both the stored delta and the patch
+            let changes = diff_lines(&previous.content, &document.content);
+            let patch = patch.unwrap_or_else(|| TextOperation::from_changes(&changes).to_patch());
+            self.record_document_version(&document, Some(&previous), &changes, author, Some(patch));
+        }
+        if document.content != previous.content {
+            self.index_links(CommentTarget::Document(id));
+        }
+        if document.title != previous.title {
+            self.relink_title(&document.title);
+        }
+        for event in events {
+            self.record_activity(CommentTarget::Document(id), event);
+        }
+        let title = format!("Mentioned in {}", document.title);
+        let owner = UserRef::Principal(document.owner);
+        self.notify_mentions(&document.content, &previous_content, Some(&owner), None, Some(id), title);
+        Ok(document)
+    }
+
+    /// Get documents in a workspace
+    pub fn get_workspace_documents(&self, workspace_id: WorkspaceId) -> Vec<Document> {
+        self.workspace_documents
+            .get(&workspace_id)
+            .map(|ids| {
+                ids.iter()
+                    .filter_map(|id| self.documents.get(id))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Delete a document
+    pub fn delete_document(&mut self, id: DocumentId) -> Option<Document> {
+        let document = self.documents.remove(&id)?;
+        self.unindex_document_title(&document);
+
+        // Remove from workspace_documents index
+        if let Some(workspace_docs) = self.workspace_documents.get_mut(&document.workspace_id) {
+            workspace_docs.retain(|&did| did != id);
+        }
+        self.remove_thread(&CommentTarget::Document(id));
+        self.remove_linked_entity(CommentTarget::Document(id));
+        self.document_versions.remove(&id);
+
+        Some(document)
+    }
+
+    // =========================================================================
+    // Document Tree Operations
+    // =========================================================================
+
+    /// Check that a document can be placed under a parent: same owner and
+    /// workspace, and not itself or one of its descendants
+    fn validate_document_parent(
+        &self,
+        id: Option<DocumentId>,
+        owner: Principal,
+        workspace_id: WorkspaceId,
+        parent_id: DocumentId,
+    ) -> Result<(), String> {
+        if id == Some(parent_id) {
+            return Err("A document cannot be its own parent".to_string());
+        }
+        let parent = self.documents.get(&parent_id)
+            .ok_or_else(|| "Parent document not found".to_string())?;
+        if parent.owner != owner {
+            return Err("Not authorized to use this parent document".to_string());
+        }
+        if parent.workspace_id != workspace_id {
+            return Err("Parent document must be in the same workspace".to_string());
+        }
+
+        if let Some(id) = id {
+            let mut visited = BTreeSet::new();
+            let mut ancestor = Some(parent_id);
+            while let Some(ancestor_id) = ancestor {
+                if ancestor_id == id {
+                    return Err("Parent would create a cycle in the document tree".to_string());
+                }
+                if !visited.insert(ancestor_id) {
+                    break;
+                }
+                ancestor = self.documents.get(&ancestor_id).and_then(|d| d.parent_id);
+            }
+        }
+        Ok(())
+    }
+
+    /// A document's parent in the tree; documents whose parent is missing or in
+    /// another workspace are top-level
+    fn document_parent(&self, document: &Document) -> Option<DocumentId> {
+        document.parent_id.filter(|pid| {
+            self.documents.get(pid).is_some_and(|parent| parent.workspace_id == document.workspace_id)
+        })
+    }
+
+    /// Children of each document in a workspace (None for top level), in rank order
+    fn document_children(&self, workspace_id: WorkspaceId) -> BTreeMap<Option<DocumentId>, Vec<DocumentId>> {
+        let mut documents: Vec<&Document> = self.workspace_documents
+            .get(&workspace_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|id| self.documents.get(id))
+            .collect();
+        documents.sort_by(|a, b| (a.rank.is_none(), &a.rank, a.id).cmp(&(b.rank.is_none(), &b.rank, b.id)));
+
+        let mut children: BTreeMap<Option<DocumentId>, Vec<DocumentId>> = BTreeMap::new();
+        for document in documents {
+            children.entry(self.document_parent(document)).or_default().push(document.id);
+        }
+        children
+    }
+
+    /// A document and its descendants, depth-first in rank order
+    fn document_subtree(&self, id: DocumentId) -> Vec<DocumentId> {
+        let Some(document) = self.documents.get(&id) else {
+            return Vec::new();
+        };
+        let children = self.document_children(document.workspace_id);
+        let mut subtree = Vec::new();
+        let mut visited = BTreeSet::new();
+        let mut stack = vec![id];
+        while let Some(next) = stack.pop() {
+            if !visited.insert(next) {
+                continue;
+            }
+            subtree.push(next);
+            stack.extend(children.get(&Some(next)).into_iter().flatten().rev());
+        }
+        subtree
+    }
+
+    /// Rank key placing a document at `index` among its future siblings (the
+    /// end when None). Unranked siblings are ranked first, keeping their order.
+    fn document_rank_at(
+        &mut self,
+        id: Option<DocumentId>,
+        workspace_id: WorkspaceId,
+        parent_id: Option<DocumentId>,
+        index: Option<usize>,
+    ) -> Result<String, String> {
+        let siblings: Vec<DocumentId> = self.document_children(workspace_id)
+            .remove(&parent_id)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|sid| Some(*sid) != id)
+            .collect();
+
+        let mut ranks: Vec<String> = Vec::with_capacity(siblings.len());
+        for sibling in siblings {
+            let Some(document) = self.documents.get_mut(&sibling) else {
+                continue;
+            };
+            let rank = document.rank.clone().unwrap_or_else(|| rank_after(ranks.last().map(String::as_str)));
+            document.rank = Some(rank.clone());
+            ranks.push(rank);
+        }
+
+        let index = index.unwrap_or(ranks.len()).min(ranks.len());
+        rank_between(
+            index.checked_sub(1).map(|i| ranks[i].as_str()),
+            ranks.get(index).map(String::as_str),
+        )
+    }
+
+    /// Move a document (with its descendants) to a workspace, parent and
+    /// position among its new siblings. A None workspace keeps the current one;
+    /// a None parent makes it top-level; a None position appends it.
+    pub fn move_document(
+        &mut self,
+        owner: Principal,
+        id: DocumentId,
+        workspace_id: Option<WorkspaceId>,
+        parent_id: Option<DocumentId>,
+        position: Option<u32>,
+    ) -> Result<Document, String> {
+        let document = self.require_document_owner(id, owner)?.clone();
+        let workspace_id = workspace_id.unwrap_or(document.workspace_id);
+        if !self.workspaces.contains_key(&workspace_id) {
+            return Err("Workspace not found".to_string());
+        }
+        if !self.is_workspace_owned_by(&UserRef::Principal(owner), workspace_id) {
+            return Err("Not authorized to move documents into this workspace".to_string());
+        }
+        if let Some(parent_id) = parent_id {
+            self.validate_document_parent(Some(id), owner, workspace_id, parent_id)?;
+        }
+
+        if workspace_id != document.workspace_id {
+            for moved in self.document_subtree(id) {
+                if let Some(ids) = self.workspace_documents.get_mut(&document.workspace_id) {
+                    ids.retain(|&did| did != moved);
+                }
+                self.workspace_documents.entry(workspace_id).or_default().push(moved);
+                if let Some(doc) = self.documents.get_mut(&moved) {
+                    doc.workspace_id = workspace_id;
+                }
+            }
+        }
+
+        let rank = self.document_rank_at(Some(id), workspace_id, parent_id, position.map(|p| p as usize))?;
+        let doc = self.documents.get_mut(&id)
+            .ok_or_else(|| "Document not found".to_string())?;
+        doc.parent_id = parent_id;
+        doc.rank = Some(rank);
+        doc.updated_at = ic_cdk::api::time();
+        let moved = doc.clone();
+
+        let format_id = |id: Option<u64>| id.map(|id| id.to_string());
+        if document.workspace_id != workspace_id {
+            let event = field_changed("workspace_id", format_id(Some(document.workspace_id)), format_id(Some(workspace_id)));
+            self.record_activity(CommentTarget::Document(id), event);
+        }
+        if document.parent_id != parent_id {
+            let event = field_changed("parent_id", format_id(document.parent_id), format_id(parent_id));
+            self.record_activity(CommentTarget::Document(id), event);
+        }
+
+        Ok(moved)
+    }
+
+    /// A workspace's documents as a depth-first list in rank order
+    pub fn get_document_tree(&self, user: Principal, workspace_id: WorkspaceId) -> Result<Vec<DocumentTreeEntry>, String> {
+        if !self.workspaces.contains_key(&workspace_id) {
+            return Err("Workspace not found".to_string());
+        }
+        if !self.is_workspace_owned_by(&UserRef::Principal(user), workspace_id) {
+            return Err("Not authorized to access this workspace".to_string());
+        }
+
+        let children = self.document_children(workspace_id);
+        // Documents caught in a parent cycle have no path from the top level
+        // and are listed as top-level after the others
+        let roots: Vec<DocumentId> = children.get(&None).cloned().unwrap_or_default();
+        let mut entries = Vec::new();
+        let mut visited = BTreeSet::new();
+        let all = children.values().flatten().copied().collect::<Vec<_>>();
+
+        for root in roots.into_iter().chain(all) {
+            let mut stack = vec![(root, 0u32)];
+            while let Some((id, depth)) = stack.pop() {
+                if !visited.insert(id) {
+                    continue;
+                }
+                let Some(document) = self.documents.get(&id) else {
+                    continue;
+                };
+                let kids = children.get(&Some(id)).map(Vec::as_slice).unwrap_or_default();
+                entries.push(DocumentTreeEntry {
+                    id,
+                    parent_id: if depth == 0 { None } else { self.document_parent(document) },
+                    title: document.title.clone(),
+                    depth,
+                    child_count: kids.len() as u32,
+                    updated_at: document.updated_at,
+                });
+                stack.extend(kids.iter().rev().map(|kid| (*kid, depth + 1)));
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Copy a document, optionally with its descendants. The copy is placed
+    /// right after the original and its title gets a " (copy)" suffix.
+    pub fn duplicate_document(&mut self, owner: Principal, id: DocumentId, include_children: bool) -> Result<Document, String> {
+        let original = self.require_document_owner(id, owner)?.clone();
+        let subtree = if include_children { self.document_subtree(id) } else { vec![id] };
+        if subtree.len() > MAX_DUPLICATE_DOCUMENTS {
+            return Err(format!("Too many documents to duplicate (max {})", MAX_DUPLICATE_DOCUMENTS));
+        }
+
+        let parent_id = self.document_parent(&original);
+        let mut copies: BTreeMap<DocumentId, DocumentId> = BTreeMap::new();
+        for source_id in subtree {
+            let Some(source) = self.documents.get(&source_id).cloned() else {
+                continue;
+            };
+            let (title, parent) = if source_id == id {
+                (format!("{} (copy)", source.title), parent_id)
+            } else {
+                (source.title, source.parent_id.and_then(|pid| copies.get(&pid).copied()))
+            };
+            // Mentions were already notified for the source
+            let copy = self.create_document_from(owner, CreateDocumentRequest {
+                workspace_id: source.workspace_id,
+                title,
+                content: Some(source.content.clone()),
+                template_id: None,
+                parent_id: parent,
+            }, &source.content)?;
+            copies.insert(source_id, copy.id);
+        }
+
+        let copy_id = copies.get(&id).copied().ok_or_else(|| "Document not found".to_string())?;
+        let siblings = self.document_children(original.workspace_id).remove(&parent_id).unwrap_or_default();
+        let index = siblings.iter().filter(|sid| **sid != copy_id).position(|sid| *sid == id).map(|i| i + 1);
+        let rank = self.document_rank_at(Some(copy_id), original.workspace_id, parent_id, index)?;
+        let copy = self.documents.get_mut(&copy_id)
+            .ok_or_else(|| "Document not found".to_string())?;
+        copy.rank = Some(rank);
+
+        Ok(copy.clone())
+    }
+
+    // =========================================================================
+    // Link Operations
+    // =========================================================================
+
+    /// Owner, workspace and linkable text of a document or capture
+    fn link_source(&self, source: &CommentTarget) -> Option<(Option<UserRef>, Option<WorkspaceId>, String)> {
+        match source {
+            CommentTarget::Document(id) => self.documents
+                .get(id)
+                .map(|d| (Some(UserRef::Principal(d.owner)), Some(d.workspace_id), d.content.clone())),
+            CommentTarget::Capture(id) => self.captures
+                .get(id)
+                .map(|c| (self.capture_owner_ref(c), c.fields.workspace_id, mention_text(c))),
+        }
+    }
+
+    fn link_target_exists(&self, target: &CommentTarget) -> bool {
+        match target {
+            CommentTarget::Document(id) => self.documents.contains_key(id),
+            CommentTarget::Capture(id) => self.captures.contains_key(id),
+        }
+    }
+
+    /// The owner's document with a title, preferring the given workspace and
+    /// then the oldest document
+    fn resolve_document_title(&self, owner: Option<&UserRef>, workspace_id: Option<WorkspaceId>, title: &str) -> Option<DocumentId> {
+        let Some(UserRef::Principal(owner)) = owner else {
+            return None;
+        };
+        self.document_titles
+            .get(&(*owner, title.to_lowercase()))?
+            .iter()
+            .filter_map(|id| self.documents.get(id))
+            .min_by_key(|d| (Some(d.workspace_id) != workspace_id, d.id))
+            .map(|d| d.id)
+    }
+
+    /// Add a document to its owner's title index
+    fn index_document_title(&mut self, document: &Document) {
+        self.document_titles
+            .entry((document.owner, document.title.to_lowercase()))
+            .or_default()
+            .insert(document.id);
+    }
+
+    /// Remove a document from its owner's title index
+    fn unindex_document_title(&mut self, document: &Document) {
+        let key = (document.owner, document.title.to_lowercase());
+        if let Some(ids) = self.document_titles.get_mut(&key) {
+            ids.remove(&document.id);
+            if ids.is_empty() {
+                self.document_titles.remove(&key);
+            }
+        }
+    }
+
+    /// Re-parse a document or capture's links. Links that already resolved keep
+    /// their target while it exists, so renamed documents stay linked.
+    fn index_links(&mut self, source: CommentTarget) {
+        let previous = self.remove_links(&source);
+        let Some((owner, workspace_id, text)) = self.link_source(&source) else {
+            return;
+        };
+
+        let mut links: Vec<EntityLink> = Vec::new();
+        for reference in parse_links(&text).into_iter().take(MAX_LINKS_PER_ENTITY) {
+            let (text, target) = match reference {
+                LinkReference::Capture(id) => {
+                    let target = CommentTarget::Capture(id);
+                    let exists = self.link_target_exists(&target);
+                    (format!("#capture-{}", id), exists.then_some(target))
+                }
+                LinkReference::Title(title) => {
+                    let kept = previous.iter()
+                        .find(|l| l.text.to_lowercase() == title.to_lowercase())
+                        .and_then(|l| l.target.clone())
+                        .filter(|target| self.link_target_exists(target));
+                    let target = kept.or_else(|| {
+                        self.resolve_document_title(owner.as_ref(), workspace_id, &title)
+                            .map(CommentTarget::Document)
+                    });
+                    (title, target)
+                }
+            };
+            if target.as_ref() != Some(&source) {
+                links.push(EntityLink { text, target });
+            }
+        }
+        self.insert_links(source, links);
+    }
+
+    /// Store a source's links and index them by target, or by title while unresolved
+    fn insert_links(&mut self, source: CommentTarget, links: Vec<EntityLink>) {
+        if links.is_empty() {
+            return;
+        }
+        for link in &links {
+            match &link.target {
+                Some(target) => {
+                    self.backlinks.entry(target.clone()).or_default().insert(source.clone());
+                }
+                None if !link.text.starts_with("#capture-") => {
+                    self.unresolved_links.entry(link.text.to_lowercase()).or_default().insert(source.clone());
+                }
+                None => {}
+            }
+        }
+        self.entity_links.insert(source, links);
+    }
+
+    /// Drop a source's links from the index, returning them
+    fn remove_links(&mut self, source: &CommentTarget) -> Vec<EntityLink> {
+        let links = self.entity_links.remove(source).unwrap_or_default();
+        for link in &links {
+            let (index, key) = match &link.target {
+                Some(target) => (&mut self.backlinks, target.clone()),
+                None => {
+                    if let Some(sources) = self.unresolved_links.get_mut(&link.text.to_lowercase()) {
+                        sources.remove(source);
+                        if sources.is_empty() {
+                            self.unresolved_links.remove(&link.text.to_lowercase());
+                        }
+                    }
+                    continue;
+                }
+            };
+            if let Some(sources) = index.get_mut(&key) {
+                sources.remove(source);
+                if sources.is_empty() {
+                    index.remove(&key);
+                }
+            }
+        }
+        links
+    }
+
+    /// Resolve links waiting for a document with this title
+    fn relink_title(&mut self, title: &str) {
+        let sources = self.unresolved_links.get(&title.to_lowercase()).cloned().unwrap_or_default();
+        for source in sources {
+            self.index_links(source);
+        }
+    }
+
+    /// Unlink a deleted document or capture: drop its own links and re-resolve
+    /// the links pointing at it
+    fn remove_linked_entity(&mut self, entity: CommentTarget) {
+        self.remove_links(&entity);
+        for source in self.backlinks.remove(&entity).unwrap_or_default() {
+            self.index_links(source);
+        }
+    }
+
+    /// Index the links of every document and capture (upgrade from before links were stored)
+    fn reindex_all_links(&mut self) {
+        let documents = self.documents.keys().map(|id| CommentTarget::Document(*id));
+        let captures = self.captures.keys().map(|id| CommentTarget::Capture(*id));
+        for source in documents.chain(captures).collect::<Vec<_>>() {
+            self.index_links(source);
+        }
+    }
+
+    /// Documents and captures owned by the user that link to an entity
+    pub fn get_backlinks(&self, user: &UserRef, entity: CommentTarget) -> Result<Vec<Backlink>, String> {
+        if !self.link_target_exists(&entity) {
+            return Err(match entity {
+                CommentTarget::Document(_) => "Document not found".to_string(),
+                CommentTarget::Capture(_) => "Capture not found".to_string(),
+            });
+        }
+        if self.link_source(&entity).and_then(|(owner, _, _)| owner).as_ref() != Some(user) {
+            return Err("Not authorized to access this item".to_string());
+        }
+
+        let sources = self.backlinks.get(&entity).into_iter().flatten();
+        Ok(sources
+            .filter(|source| self.link_source(source).and_then(|(owner, _, _)| owner).as_ref() == Some(user))
+            .filter_map(|source| {
+                let title = match source {
+                    CommentTarget::Document(id) => self.documents.get(id)?.title.clone(),
+                    CommentTarget::Capture(id) => self.captures.get(id)?.title.clone(),
+                };
+                let link = self.entity_links.get(source)?.iter().find(|l| l.target.as_ref() == Some(&entity))?;
+                Some(Backlink { source: source.clone(), title, text: link.text.clone() })
+            })
+            .collect())
+    }
+
+    // =========================================================================
+    // Document Version Operations
+    // =========================================================================
+
+    /// Get a document the user owns
+    fn require_document_owner(&self, id: DocumentId, user: Principal) -> Result<&Document, String> {
+        let document = self.documents.get(&id)
+            .ok_or_else(|| "Document not found".to_string())?;
+        if document.owner != user {
+            return Err("Not authorized to access this document".to_string());
+        }
+        Ok(document)
+    }
+
+    /// Save a new head version. The previous head is re-encoded as a delta from
+    /// the new content (using `changes`, the line diff from the previous content),
+    /// or kept whole on snapshot versions. Documents created before versioning
+    /// get their pre-update state as version 1.
+    fn record_document_version(
+        &mut self,
+        document: &Document,
+        previous: Option<&Document>,
+        changes: &[LineChange],
+        author: UserRef,
+        patch: Option<Vec<PatchOp>>,
+    ) {
+        let history = self.document_versions.entry(document.id).or_default();
+
+        if let (true, Some(previous)) = (history.is_empty(), previous) {
+            history.push(StoredDocumentVersion {
+                info: DocumentVersion {
+                    document_id: document.id,
+                    version: 1,
+                    author: UserRef::Principal(previous.owner),
+                    title: previous.title.clone(),
+                    size: previous.content.len() as u64,
+                    created_at: previous.updated_at,
+                },
+                content: VersionContent::Current,
+                patch: None,
+            });
+        }
+        if let (Some(head), Some(previous)) = (history.last_mut(), previous) {
+            head.content = if head.info.version % VERSION_SNAPSHOT_INTERVAL == 0 {
+                VersionContent::Full(previous.content.clone())
+            } else {
+                VersionContent::Delta(encode_reverse_delta(changes))
+            };
+        }
+
+        let version = history.last().map_or(1, |head| head.info.version + 1);
+        history.push(StoredDocumentVersion {
+            info: DocumentVersion {
+                document_id: document.id,
+                version,
+                author,
+                title: document.title.clone(),
+                size: document.content.len() as u64,
+                created_at: document.updated_at,
+            },
+            content: VersionContent::Current,
+            patch,
+        });
+        if history.len() > MAX_DOCUMENT_VERSIONS {
+            history.drain(..history.len() - MAX_DOCUMENT_VERSIONS);
+        }
+    }
+
+    /// List a document's versions, newest first
+    pub fn get_document_versions(&self, user: Principal, id: DocumentId) -> Result<Vec<DocumentVersion>, String> {
+        self.require_document_owner(id, user)?;
+
+        Ok(self.document_versions
+            .get(&id)
+            .map(|history| history.iter().rev().map(|v| v.info.clone()).collect())
+            .unwrap_or_default())
+    }
+
+    /// Rebuild a version's title and content by replaying deltas back from the
+    /// nearest newer full copy
+    fn document_version_content(&self, document: &Document, version: DocumentVersionNumber) -> Result<(String, String), String> {
+        let history = self.document_versions.get(&document.id).map(Vec::as_slice).unwrap_or_default();
+        let index = history.iter()
+            .position(|v| v.info.version == version)
+            .ok_or_else(|| format!("Version {} not found", version))?;
+
+        let base = history[index..].iter()
+            .position(|v| !matches!(v.content, VersionContent::Delta(_)))
+            .map(|offset| index + offset)
+            .ok_or_else(|| "Version history is corrupted".to_string())?;
+        let mut content = match &history[base].content {
+            VersionContent::Full(text) => text.clone(),
+            _ => document.content.clone(),
+        };
+        for stored in history[index..base].iter().rev() {
+            if let VersionContent::Delta(delta) = &stored.content {
+                content = apply_delta(&content, delta)?;
+            }
+        }
+
+        Ok((history[index].info.title.clone(), content))
+    }
+
+    /// Line-based diff between two versions of a document. With `context`, only
+    /// unchanged lines within that many lines of a change are returned.
+    pub fn diff_document_versions(
+        &self,
+        user: Principal,
+        id: DocumentId,
+        from_version: DocumentVersionNumber,
+        to_version: DocumentVersionNumber,
+        context: Option<u32>,
+    ) -> Result<DocumentDiff, String> {
+        let document = self.require_document_owner(id, user)?;
+        let (_, old) = self.document_version_content(document, from_version)?;
+        let (_, new) = self.document_version_content(document, to_version)?;
+
+        let changes = diff_lines(&old, &new);
+        let mut lines = Vec::with_capacity(changes.len());
+        let (mut old_line, mut new_line) = (0u32, 0u32);
+        for change in &changes {
+            let (kind, text) = match change {
+                LineChange::Unchanged(text) => (DiffLineKind::Unchanged, text),
+                LineChange::Removed(text) => (DiffLineKind::Removed, text),
+                LineChange::Added(text) => (DiffLineKind::Added, text),
+            };
+            if kind != DiffLineKind::Added {
+                old_line += 1;
+            }
+            if kind != DiffLineKind::Removed {
+                new_line += 1;
+            }
+            lines.push(DiffLine {
+                old_line: (kind != DiffLineKind::Added).then_some(old_line),
+                new_line: (kind != DiffLineKind::Removed).then_some(new_line),
+                kind,
+                text: text.strip_suffix('\n').unwrap_or(text).to_string(),
+            });
+        }
+        let additions = lines.iter().filter(|l| l.kind == DiffLineKind::Added).count() as u32;
+        let deletions = lines.iter().filter(|l| l.kind == DiffLineKind::Removed).count() as u32;
+
+        if let Some(context) = context.map(|c| c as usize) {
+            let changed: Vec<usize> = (0..lines.len()).filter(|&i| lines[i].kind != DiffLineKind::Unchanged).collect();
+            let near_change = |i: usize| {
+                let next = changed.partition_point(|&c| c < i);
+                changed.get(next).is_some_and(|&c| c - i <= context)
+                    || next.checked_sub(1).is_some_and(|prev| i - changed[prev] <= context)
+            };
+            lines = lines.into_iter()
+                .enumerate()
+                .filter(|(i, line)| line.kind != DiffLineKind::Unchanged || near_change(*i))
+                .map(|(_, line)| line)
+                .collect();
+        }
+
+        Ok(DocumentDiff {
+            document_id: id,
+            from_version,
+            to_version,
+            lines,
+            additions,
+            deletions,
+        })
+    }
+
+    /// Restore a document's title and content from a version. The restore is
+    /// saved as a new version, so it can itself be undone.
+    pub fn restore_document_version(
+        &mut self,
+        user: Principal,
+        id: DocumentId,
+        version: DocumentVersionNumber,
+    ) -> Result<Document, String> {
+        let document = self.require_document_owner(id, user)?;
+        let (title, content) = self.document_version_content(document, version)?;
+
+        self.update_document(id, UserRef::Principal(user), Some(title), Some(content))
+    }
+
+    // =========================================================================
+    // Document Patch Operations
+    // =========================================================================
+
+    /// Apply a patch made against `base_version`, rebasing it over the versions
+    /// saved since. Returns the patch as applied to the previous head version.
+    pub fn apply_document_patch(&mut self, author: Principal, request: ApplyDocumentPatchRequest) -> Result<DocumentPatch, String> {
+        if request.ops.is_empty() {
+            return Err("Patch has no ops".to_string());
+        }
+        if request.ops.len() > MAX_PATCH_OPS {
+            return Err(format!("Too many ops in patch (max {})", MAX_PATCH_OPS));
+        }
+        let document = self.require_document_owner(request.document_id, author)?;
+        let (_, base_content) = self.document_version_content(document, request.base_version)?;
+
+        let mut operation = TextOperation::from_patch(&request.ops, base_content.chars().count())?;
+        for concurrent in self.patches_since(document.id, request.base_version)? {
+            let concurrent = TextOperation::from_patch(&concurrent.ops, operation.base_len())?;
+            operation = TextOperation::transform(&operation, &concurrent)?.0;
+        }
+        let content = operation.apply(&document.content)?;
+
+        let id = document.id;
+        let head = self.document_versions.get(&id).and_then(|history| history.last());
+        if operation.is_noop() {
+            return Ok(DocumentPatch {
+                document_id: id,
+                version: head.map_or(0, |v| v.info.version),
+                author: UserRef::Principal(author),
+                ops: Vec::new(),
+                created_at: ic_cdk::api::time(),
+            });
+        }
+
+        self.save_document(id, UserRef::Principal(author), None, Some(content), Some(operation.to_patch()))?;
+        self.document_versions
+            .get(&id)
+            .and_then(|history| history.last())
+            .and_then(document_patch)
+            .ok_or_else(|| "Failed to record patch".to_string())
+    }
+
+    // =========================================================================
+    // Document Transfer Operations
+    // =========================================================================
+
+    /// Start a chunked upload that will replace a document's content
+    pub fn begin_document_upload(
+        &mut self,
+        owner: Principal,
+        document_id: DocumentId,
+        total_size: u64,
+        now: Timestamp,
+    ) -> Result<UploadSession, String> {
+        self.require_document_owner(document_id, owner)?;
+        if total_size > MAX_DOCUMENT_SIZE as u64 {
+            return Err(document_too_large(total_size));
+        }
+
+        self.document_uploads.retain(|_, upload| upload.session.expires_at > now);
+        let open = self.document_uploads.values().filter(|upload| upload.owner == owner).count();
+        if open >= MAX_UPLOAD_SESSIONS_PER_USER {
+            return Err(format!("Too many open uploads (max {})", MAX_UPLOAD_SESSIONS_PER_USER));
+        }
+
+        let id = self.next_upload_session_id;
+        self.next_upload_session_id += 1;
+
+        let session = UploadSession {
+            id,
+            document_id,
+            total_size,
+            received: 0,
+            expires_at: now + UPLOAD_SESSION_TTL,
+        };
+        self.document_uploads.insert(id, DocumentUpload {
+            session: session.clone(),
+            owner,
+            buffer: Vec::new(),
+        });
+
+        Ok(session)
+    }
+
+    /// Get one of the caller's live upload sessions
+    fn upload_mut(&mut self, owner: Principal, id: UploadSessionId, now: Timestamp) -> Result<&mut DocumentUpload, String> {
+        let upload = self.document_uploads.get(&id)
+            .ok_or_else(|| "Upload session not found".to_string())?;
+        if upload.owner != owner {
+            return Err("Not authorized to access this upload".to_string());
+        }
+        if upload.session.expires_at <= now {
+            self.document_uploads.remove(&id);
+            return Err("Upload session expired".to_string());
+        }
+        self.document_uploads.get_mut(&id)
+            .ok_or_else(|| "Upload session not found".to_string())
+    }
+
+    /// Append a chunk at `offset`, which must equal the bytes received so far
+    pub fn append_document_chunk(
+        &mut self,
+        owner: Principal,
+        id: UploadSessionId,
+        offset: u64,
+        data: Vec<u8>,
+        now: Timestamp,
+    ) -> Result<UploadSession, String> {
+        if data.is_empty() {
+            return Err("Chunk is empty".to_string());
+        }
+        if data.len() > MAX_DOCUMENT_CHUNK_SIZE {
+            return Err(format!("Chunk too large (max {} bytes)", MAX_DOCUMENT_CHUNK_SIZE));
+        }
+        let upload = self.upload_mut(owner, id, now)?;
+        if offset != upload.session.received {
+            return Err(format!("Expected chunk at offset {}", upload.session.received));
+        }
+        let received = upload.session.received + data.len() as u64;
+        if received > upload.session.total_size {
+            return Err(format!("Upload exceeds its declared size of {} bytes", upload.session.total_size));
+        }
+
+        upload.buffer.extend_from_slice(&data);
+        upload.session.received = received;
+        Ok(upload.session.clone())
+    }
+
+    /// Finish an upload: check the SHA-256 (hex) of the received bytes and save
+    /// them as the document's content. The session is closed either way.
+    pub fn commit_document_upload(
+        &mut self,
+        owner: Principal,
+        id: UploadSessionId,
+        sha256: &str,
+        now: Timestamp,
+    ) -> Result<Document, String> {
+        self.upload_mut(owner, id, now)?;
+        let upload = self.document_uploads.remove(&id)
+            .ok_or_else(|| "Upload session not found".to_string())?;
+        if upload.session.received != upload.session.total_size {
+            return Err(format!(
+                "Upload incomplete ({} of {} bytes received)",
+                upload.session.received, upload.session.total_size
+            ));
+        }
+        let digest = hex::encode(Sha256::digest(&upload.buffer));
+        if !digest.eq_ignore_ascii_case(sha256.trim()) {
+            return Err("SHA-256 checksum does not match the uploaded content".to_string());
+        }
+        let content = String::from_utf8(upload.buffer)
+            .map_err(|_| "Uploaded content is not valid UTF-8".to_string())?;
+
+        self.require_document_owner(upload.session.document_id, owner)?;
+        self.update_document(upload.session.document_id, UserRef::Principal(owner), None, Some(content))
+    }
+
+    /// Discard one of the caller's uploads
+    pub fn abort_document_upload(&mut self, owner: Principal, id: UploadSessionId) -> Result<(), String> {
+        let upload = self.document_uploads.get(&id)
+            .ok_or_else(|| "Upload session not found".to_string())?;
+        if upload.owner != owner {
+            return Err("Not authorized to access this upload".to_string());
+        }
+        self.document_uploads.remove(&id);
+        Ok(())
+    }
+
+    /// Read up to `length` bytes of a document's content from `offset`
+    pub fn read_document_content(
+        &self,
+        user: Principal,
+        id: DocumentId,
+        offset: u64,
+        length: u64,
+    ) -> Result<DocumentContentChunk, String> {
+        let document = self.require_document_owner(id, user)?;
+        let bytes = document.content.as_bytes();
+        if offset > bytes.len() as u64 {
+            return Err(format!("Offset {} is past the end of the content ({} bytes)", offset, bytes.len()));
+        }
+        let start = offset as usize;
+        let end = start + (length as usize).min(MAX_DOCUMENT_CHUNK_SIZE).min(bytes.len() - start);
+
+        Ok(DocumentContentChunk {
+            document_id: id,
+            version: self.document_versions
+                .get(&id)
+                .and_then(|history| history.last())
+                .map_or(0, |v| v.info.version),
+            offset,
+            total_size: bytes.len() as u64,
+            data: bytes[start..end].to_vec(),
+        })
+    }
+
+    /// Patches saved after a version, oldest first
+    pub fn get_document_patches(
+        &self,
+        user: Principal,
+        id: DocumentId,
+        since_version: DocumentVersionNumber,
+    ) -> Result<Vec<DocumentPatch>, String> {
+        self.require_document_owner(id, user)?;
+        self.patches_since(id, since_version)
+    }
+
+    fn patches_since(&self, id: DocumentId, since_version: DocumentVersionNumber) -> Result<Vec<DocumentPatch>, String> {
+        let history = self.document_versions.get(&id).map(Vec::as_slice).unwrap_or_default();
+        let (Some(first), Some(head)) = (history.first(), history.last()) else {
+            return Err(format!("Version {} not found", since_version));
+        };
+        if since_version > head.info.version {
+            return Err(format!("Version {} not found", since_version));
+        }
+        if since_version < first.info.version {
+            return Err(format!("Version {} is no longer available; reload the document", since_version));
+        }
+
+        history.iter()
+            .filter(|v| v.info.version > since_version)
+            .map(|v| {
+                document_patch(v)
+                    .ok_or_else(|| format!("Version {} was saved without a patch; reload the document", v.info.version))
+            })
+            .collect()
+    }
+
+    // =========================================================================
+    // Template Operations
+    // =========================================================================
+
+    /// Create a new template
+    pub fn create_template(&mut self, owner: Principal, request: CreateTemplateRequest) -> Result<Template, String> {
+        validate_new_template(&request)?;
+        let tags = request.tags.map(normalize_template_tags).transpose()?;
+        let now = ic_cdk::api::time();
+        let id = self.next_template_id;
+        self.next_template_id += 1;
+
+        let is_public = request.is_public.unwrap_or(false);
+
+        let template = Template {
+            id,
+            owner,
+            template_type: request.template_type,
+            name: request.name,
+            description: request.description,
+            content: request.content,
+            capture_type: request.capture_type,
+            default_fields: request.default_fields,
+            is_public,
+            variables: request.variables,
+            scaffold: request.scaffold,
+            latest_version: None,
+            forked_from: None,
+            category: request.category,
+            tags,
+            created_at: now,
+            updated_at: now,
+        };
+
+        self.templates.insert(id, template.clone());
+        self.user_templates
+            .entry(owner)
+            .or_default()
+            .push(id);
+
+        if is_public {
+            self.public_templates.push(id);
+            self.push_template_version(&template, None, now);
+        }
+
+        self.templates.get(&id)
+            .cloned()
+            .ok_or_else(|| "Template not found".to_string())
+    }
+
+    /// Get a template by ID
+    pub fn get_template(&self, id: TemplateId) -> Option<&Template> {
+        self.templates.get(&id)
+    }
+
+    /// Get user's templates
+    pub fn get_user_templates(&self, owner: Principal) -> Vec<Template> {
+        self.user_templates
+            .get(&owner)
             .map(|ids| {
                 ids.iter()
                     .filter_map(|id| self.templates.get(id))
@@ -474,444 +4668,1738 @@ impl State {
             .unwrap_or_default()
     }
 
-    /// Check if a template is owned by user_id
-    pub fn is_template_owned_by_user_id(&self, template_id: TemplateId, user_id: &str) -> bool {
-        self.user_id_templates
-            .get(user_id)
-            .map(|ids| ids.contains(&template_id))
-            .unwrap_or(false)
+    /// Get public templates, as their latest published versions
+    pub fn get_public_templates(&self) -> Vec<Template> {
+        self.public_templates
+            .iter()
+            .filter_map(|id| self.templates.get(id))
+            .filter(|template| self.template_listed(template))
+            .filter_map(|template| self.published_template(template))
+            .collect()
+    }
+
+    /// Search the public template gallery
+    pub fn search_public_templates(
+        &self,
+        query: TemplateGalleryQuery,
+        pagination: PaginationParams,
+    ) -> PaginatedResponse<TemplateGalleryEntry> {
+        let words: Vec<String> = query.text
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(str::to_lowercase)
+            .collect();
+        let tags: Vec<String> = query.tags
+            .unwrap_or_default()
+            .iter()
+            .map(|tag| tag.trim().to_lowercase())
+            .collect();
+        let featured_only = query.featured_only.unwrap_or(false);
+
+        let mut entries: Vec<TemplateGalleryEntry> = self.public_templates
+            .iter()
+            .filter_map(|id| self.templates.get(id))
+            .filter(|template| self.template_listed(template))
+            .filter_map(|template| self.published_template(template))
+            .filter(|template| query.category.as_ref().is_none_or(|c| template.category.as_ref() == Some(c)))
+            .filter(|template| query.template_type.as_ref().is_none_or(|t| template.template_type == *t))
+            .filter(|template| {
+                let template_tags = template.tags.as_deref().unwrap_or_default();
+                tags.iter().all(|tag| template_tags.contains(tag))
+            })
+            .filter(|template| {
+                let text = format!("{}\n{}", template.name, template.description.as_deref().unwrap_or_default())
+                    .to_lowercase();
+                words.iter().all(|word| text.contains(word.as_str()))
+            })
+            .filter(|template| !featured_only || self.featured_templates.contains(&template.id))
+            .map(|template| TemplateGalleryEntry {
+                usage_count: self.template_usage.get(&template.id).copied().unwrap_or(0),
+                featured: self.featured_templates.contains(&template.id),
+                published_at: template.latest_version
+                    .and_then(|version| self.template_version(template.id, version))
+                    .map(|version| version.published_at)
+                    .unwrap_or(template.updated_at),
+                template,
+            })
+            .collect();
+
+        entries.sort_by(|a, b| {
+            let order = match query.sort.as_ref().unwrap_or(&TemplateSort::Usage) {
+                TemplateSort::Usage => b.usage_count.cmp(&a.usage_count).then(b.published_at.cmp(&a.published_at)),
+                TemplateSort::Recent => b.published_at.cmp(&a.published_at),
+            };
+            order.then(a.template.id.cmp(&b.template.id))
+        });
+
+        let total = entries.len() as u64;
+        let offset = pagination.offset.unwrap_or(0);
+        let limit = pagination.limit.unwrap_or(50);
+
+        let items: Vec<TemplateGalleryEntry> = entries
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .collect();
+
+        PaginatedResponse {
+            items,
+            total,
+            offset,
+            limit,
+        }
+    }
+
+    /// Withdraw a template from the gallery. Published versions are kept so
+    /// forks and instantiated entities keep their provenance.
+    pub fn unpublish_template(&mut self, owner: &UserRef, id: TemplateId) -> Result<Template, String> {
+        let template = self.templates.get(&id)
+            .ok_or_else(|| "Template not found".to_string())?;
+        if !self.template_owned_by(template, owner) {
+            return Err("Not authorized to unpublish this template".to_string());
+        }
+        if !template.is_public {
+            return Err("Template is not public".to_string());
+        }
+
+        self.public_templates.retain(|&tid| tid != id);
+        let template = self.templates.get_mut(&id)
+            .ok_or_else(|| "Template not found".to_string())?;
+        template.is_public = false;
+        template.updated_at = ic_cdk::api::time();
+        Ok(template.clone())
+    }
+
+    /// Feature or hide a template in the gallery (controllers only)
+    pub fn moderate_template(&mut self, id: TemplateId, request: ModerateTemplateRequest) -> Result<(), String> {
+        if !self.templates.contains_key(&id) {
+            return Err("Template not found".to_string());
+        }
+        match request.featured {
+            Some(true) => self.featured_templates.insert(id),
+            Some(false) => self.featured_templates.remove(&id),
+            None => false,
+        };
+        match request.hidden {
+            Some(true) => self.hidden_templates.insert(id),
+            Some(false) => self.hidden_templates.remove(&id),
+            None => false,
+        };
+        Ok(())
+    }
+
+    /// Count an instantiation of a template
+    fn record_template_use(&mut self, id: TemplateId) {
+        *self.template_usage.entry(id).or_default() += 1;
+    }
+
+    /// Template as a viewer sees it: the draft for its owner and the latest
+    /// published version for everyone else
+    pub fn view_template(&self, id: TemplateId, viewer: Option<&UserRef>) -> Option<Template> {
+        let template = self.templates.get(&id)?;
+        if viewer.is_some_and(|viewer| self.template_owned_by(template, viewer)) {
+            return Some(template.clone());
+        }
+        if self.template_listed(template) {
+            return self.published_template(template);
+        }
+        None
+    }
+
+    /// Update a template
+    pub fn update_template(&mut self, id: TemplateId, request: UpdateTemplateRequest) -> Result<Template, String> {
+        validate_template_variables(request.variables.as_deref())?;
+        let tags = request.tags.map(normalize_template_tags).transpose()?;
+        let template = self.templates.get(&id)
+            .ok_or_else(|| "Template not found".to_string())?;
+        if request.scaffold.is_some() {
+            validate_template_scaffold(&template.template_type, request.scaffold.as_ref())?;
+        }
+
+        let template = self.templates.get_mut(&id)
+            .ok_or_else(|| "Template not found".to_string())?;
+        let now = ic_cdk::api::time();
+        let was_public = template.is_public;
+
+        if let Some(name) = request.name {
+            template.name = name;
+        }
+        if let Some(description) = request.description {
+            template.description = Some(description);
+        }
+        if let Some(content) = request.content {
+            template.content = content;
+        }
+        if let Some(capture_type) = request.capture_type {
+            template.capture_type = Some(capture_type);
+        }
+        if let Some(default_fields) = request.default_fields {
+            template.default_fields = Some(default_fields);
+        }
+        if let Some(variables) = request.variables {
+            template.variables = Some(variables);
+        }
+        if let Some(scaffold) = request.scaffold {
+            template.scaffold = Some(scaffold);
+        }
+        if let Some(category) = request.category {
+            template.category = Some(category);
+        }
+        if let Some(tags) = tags {
+            template.tags = Some(tags);
+        }
+        if let Some(is_public) = request.is_public {
+            template.is_public = is_public;
+
+            // Update public_templates index
+            if is_public && !was_public {
+                // Add to public templates
+                if !self.public_templates.contains(&id) {
+                    self.public_templates.push(id);
+                }
+            } else if !is_public && was_public {
+                // Remove from public templates
+                self.public_templates.retain(|&tid| tid != id);
+            }
+        }
+
+        template.updated_at = now;
+
+        // Sharing a template for the first time publishes its draft as version 1
+        if template.is_public && template.latest_version.is_none() {
+            let draft = template.clone();
+            self.push_template_version(&draft, None, now);
+        }
+
+        self.templates.get(&id)
+            .cloned()
+            .ok_or_else(|| "Template not found".to_string())
+    }
+
+    /// Delete a template
+    pub fn delete_template(&mut self, id: TemplateId) -> Option<Template> {
+        let template = self.templates.remove(&id)?;
+        self.template_versions.remove(&id);
+        self.template_usage.remove(&id);
+        self.featured_templates.remove(&id);
+        self.hidden_templates.remove(&id);
+
+        // Remove from user_templates index
+        if let Some(user_templates) = self.user_templates.get_mut(&template.owner) {
+            user_templates.retain(|&tid| tid != id);
+        }
+
+        // Remove from public_templates if applicable
+        if template.is_public {
+            self.public_templates.retain(|&tid| tid != id);
+        }
+
+        Some(template)
+    }
+
+    /// Publish a template's draft as its next immutable version
+    pub fn publish_template(
+        &mut self,
+        owner: &UserRef,
+        id: TemplateId,
+        changelog: Option<String>,
+    ) -> Result<TemplateVersion, String> {
+        let template = self.templates.get(&id)
+            .ok_or_else(|| "Template not found".to_string())?;
+        if !self.template_owned_by(template, owner) {
+            return Err("Not authorized to publish this template".to_string());
+        }
+        let history = self.template_versions.get(&id).map(Vec::as_slice).unwrap_or_default();
+        if let Some(latest) = history.last() {
+            if template_matches_version(template, latest) {
+                return Err(format!("Template has no changes since version {}", latest.version));
+            }
+        }
+        if history.len() >= MAX_TEMPLATE_VERSIONS {
+            return Err(format!("Templates cannot have more than {} versions", MAX_TEMPLATE_VERSIONS));
+        }
+
+        let template = template.clone();
+        let changelog = changelog
+            .map(|text| text.trim().to_string())
+            .filter(|text| !text.is_empty());
+        Ok(self.push_template_version(&template, changelog, ic_cdk::api::time()))
+    }
+
+    /// Published versions of a template, oldest first. Anyone who can use the
+    /// template can see them.
+    pub fn get_template_versions(&self, user: &UserRef, id: TemplateId) -> Result<Vec<TemplateVersion>, String> {
+        let template = self.templates.get(&id)
+            .ok_or_else(|| "Template not found".to_string())?;
+        if !self.template_accessible(template, user) {
+            return Err("Not authorized to access this template".to_string());
+        }
+        Ok(self.template_versions.get(&id).cloned().unwrap_or_default())
+    }
+
+    /// Copy a template into a new private template owned by the user,
+    /// recording where it came from. Other users' templates are copied from
+    /// their latest published version unless a version is given.
+    pub fn fork_template(
+        &mut self,
+        user: &UserRef,
+        id: TemplateId,
+        version: Option<TemplateVersionNumber>,
+    ) -> Result<Template, String> {
+        let (source, version) = self.resolve_template(user, id, version)?;
+        let request = CreateTemplateRequest {
+            template_type: source.template_type,
+            name: source.name,
+            description: source.description,
+            content: source.content,
+            capture_type: source.capture_type,
+            default_fields: source.default_fields,
+            is_public: Some(false),
+            variables: source.variables,
+            scaffold: source.scaffold,
+            category: source.category,
+            tags: source.tags,
+        };
+        let mut fork = match user {
+            UserRef::Principal(owner) => self.create_template(*owner, request)?,
+            UserRef::UserId(user_id) => self.create_template_for_user_id(user_id, request)?,
+        };
+
+        let forked_from = TemplateSource { template_id: id, version };
+        if let Some(stored) = self.templates.get_mut(&fork.id) {
+            stored.forked_from = Some(forked_from.clone());
+        }
+        fork.forked_from = Some(forked_from);
+        Ok(fork)
+    }
+
+    /// A published template version
+    fn template_version(&self, id: TemplateId, version: TemplateVersionNumber) -> Option<&TemplateVersion> {
+        let index = version.checked_sub(1)? as usize;
+        self.template_versions.get(&id)?.get(index)
+    }
+
+    /// A template as other users see it: its latest published version
+    fn published_template(&self, template: &Template) -> Option<Template> {
+        let version = self.template_version(template.id, template.latest_version?)?;
+        Some(apply_template_version(template, version))
+    }
+
+    /// Snapshot a template's current draft as its next version
+    fn push_template_version(
+        &mut self,
+        template: &Template,
+        changelog: Option<String>,
+        published_at: Timestamp,
+    ) -> TemplateVersion {
+        let history = self.template_versions.entry(template.id).or_default();
+        let version = TemplateVersion {
+            template_id: template.id,
+            version: history.len() as TemplateVersionNumber + 1,
+            template_type: template.template_type.clone(),
+            name: template.name.clone(),
+            description: template.description.clone(),
+            content: template.content.clone(),
+            capture_type: template.capture_type.clone(),
+            default_fields: template.default_fields.clone(),
+            variables: template.variables.clone(),
+            scaffold: template.scaffold.clone(),
+            changelog,
+            published_at,
+        };
+        history.push(version.clone());
+        if let Some(stored) = self.templates.get_mut(&template.id) {
+            stored.latest_version = Some(version.version);
+        }
+        version
+    }
+
+    /// Record the template version a document was created from
+    fn set_document_template(&mut self, id: DocumentId, source: &TemplateSource) {
+        if let Some(document) = self.documents.get_mut(&id) {
+            document.template_id = Some(source.template_id);
+            document.template_version = source.version;
+        }
+    }
+
+    /// Record the template version a capture was instantiated from
+    fn set_capture_template(&mut self, id: CaptureId, source: &TemplateSource) {
+        if let Some(capture) = self.captures.get_mut(&id) {
+            capture.template = Some(source.clone());
+        }
+    }
+
+    /// Render a template with variable values and create the document,
+    /// capture or scaffold it describes
+    pub fn instantiate_template(&mut self, user: &UserRef, request: InstantiateTemplateRequest) -> Result<TemplateInstance, String> {
+        let (template, version) = self.resolve_template(user, request.template_id, request.version)?;
+        let source = TemplateSource { template_id: template.id, version };
+
+        let variables = template.variables.as_deref().unwrap_or_default();
+        let values = template::resolve_values(variables, &request.values, ic_cdk::api::time())?;
+        let title = template::render(request.title.as_deref().unwrap_or(&template.name), &values);
+        if title.trim().is_empty() {
+            return Err("Title cannot be empty".to_string());
+        }
+        let content = template::render(&template.content, &values);
+
+        let instance = match template.template_type {
+            TemplateType::Document => {
+                let UserRef::Principal(owner) = user else {
+                    return Err("Document templates require principal authentication".to_string());
+                };
+                let workspace_id = request.workspace_id
+                    .ok_or_else(|| "A workspace is required for document templates".to_string())?;
+                if !self.workspaces.contains_key(&workspace_id) {
+                    return Err("Workspace not found".to_string());
+                }
+                if !self.is_workspace_owned_by(user, workspace_id) {
+                    return Err("Not authorized to create documents in this workspace".to_string());
+                }
+
+                let mut document = self.create_document(*owner, CreateDocumentRequest {
+                    workspace_id,
+                    title,
+                    content: Some(content),
+                    template_id: None,
+                    parent_id: request.parent_id,
+                })?;
+                self.set_document_template(document.id, &source);
+                document.template_id = Some(source.template_id);
+                document.template_version = source.version;
+                TemplateInstance::Document(document)
+            }
+            TemplateType::Capture => {
+                let capture_type = template.capture_type
+                    .ok_or_else(|| "Capture template has no capture type".to_string())?;
+                let mut fields = request.fields.unwrap_or_default();
+                if let Some(defaults) = &template.default_fields {
+                    apply_default_fields(&mut fields, &template::render_fields(defaults, &values));
+                }
+
+                let mut capture = self.create_capture_for(user, CreateCaptureRequest {
+                    capture_type,
+                    subtype: None,
+                    title,
+                    description: None,
+                    content: (!content.is_empty()).then_some(content),
+                    priority: None,
+                    fields: Some(fields),
+                    recurrence: None,
+                })?;
+                self.set_capture_template(capture.id, &source);
+                capture.template = Some(source.clone());
+                TemplateInstance::Capture(Box::new(capture))
+            }
+            TemplateType::Scaffold => {
+                let spec = template.scaffold
+                    .ok_or_else(|| "Scaffold template has no scaffold".to_string())?;
+                let workspace_name = request.title.is_some().then_some(title);
+                let instance = self.instantiate_scaffold(user, &source, &spec, &values, workspace_name, &request)?;
+                TemplateInstance::Scaffold(instance)
+            }
+        };
+        self.record_template_use(source.template_id);
+        Ok(instance)
+    }
+
+    /// Create everything a scaffold describes. The whole scaffold is checked
+    /// before anything is created, so instantiation either creates the full
+    /// bundle or nothing.
+    fn instantiate_scaffold(
+        &mut self,
+        user: &UserRef,
+        source: &TemplateSource,
+        spec: &ScaffoldSpec,
+        values: &BTreeMap<String, String>,
+        workspace_name: Option<String>,
+        request: &InstantiateTemplateRequest,
+    ) -> Result<ScaffoldInstance, String> {
+        template::validate_scaffold(spec)?;
+        let render = |text: &str| template::render(text, values);
+        let anchor = request.anchor_date.unwrap_or_else(ic_cdk::api::time);
+
+        let workspace_name = match &spec.workspace {
+            Some(workspace) => {
+                if request.workspace_id.is_some() {
+                    return Err("This scaffold creates its own workspace".to_string());
+                }
+                let name = workspace_name.unwrap_or_else(|| render(&workspace.name));
+                if name.trim().is_empty() {
+                    return Err("Workspace name cannot be empty".to_string());
+                }
+                Some(name)
+            }
+            None => None,
+        };
+        if let Some(workspace_id) = request.workspace_id {
+            if !self.workspaces.contains_key(&workspace_id) {
+                return Err("Workspace not found".to_string());
+            }
+            if !self.is_workspace_owned_by(user, workspace_id) {
+                return Err("Not authorized to use this workspace".to_string());
+            }
+        }
+
+        if !spec.documents.is_empty() {
+            if !matches!(user, UserRef::Principal(_)) {
+                return Err("Scaffolds with documents require principal authentication".to_string());
+            }
+            if spec.workspace.is_none() && request.workspace_id.is_none() {
+                return Err("A workspace is required for this scaffold's documents".to_string());
+            }
+        }
+        for document in &spec.documents {
+            if render(&document.title).trim().is_empty() {
+                return Err(format!("Title of document '{}' cannot be empty", document.key));
+            }
+            check_document_size(&render(&document.content))?;
+        }
+
+        let sibling_ids = self.owner_sprint_ids(user);
+        for sprint in &spec.sprints {
+            if render(&sprint.name).trim().is_empty() {
+                return Err(format!("Name of sprint '{}' cannot be empty", sprint.key));
+            }
+            let (start_date, end_date) = scaffold_sprint_dates(sprint, anchor);
+            self.validate_sprint_schedule(
+                &sibling_ids,
+                None,
+                start_date,
+                end_date,
+                &SprintStatus::Planning,
+                &normalize_stream(sprint.stream.clone()),
+            )?;
+        }
+
+        for capture in &spec.captures {
+            if render(&capture.title).trim().is_empty() {
+                return Err(format!("Title of capture '{}' cannot be empty", capture.key));
+            }
+            let fields = scaffold_capture_fields(capture, values, anchor, request.workspace_id, None);
+            self.validate_custom_fields(user, &fields)?;
+        }
+
+        let mut instance = ScaffoldInstance {
+            workspace_id: request.workspace_id,
+            documents: Vec::new(),
+            sprints: Vec::new(),
+            captures: Vec::new(),
+        };
+        if let (Some(workspace), Some(name)) = (&spec.workspace, workspace_name) {
+            let request = CreateWorkspaceRequest {
+                name,
+                description: workspace.description.as_deref().map(render),
+                icon: workspace.icon.clone(),
+                parent_id: None,
+            };
+            let workspace = match user {
+                UserRef::Principal(owner) => self.create_workspace(*owner, request),
+                UserRef::UserId(user_id) => self.create_workspace_for_user_id(user_id, request),
+            };
+            instance.workspace_id = Some(workspace.id);
+        }
+
+        // The checks above cover every error the create calls below can
+        // report. Should one still fail, trapping discards the partly created
+        // scaffold along with the rest of the call.
+        self.create_scaffold(user, source, spec, values, instance, anchor)
+            .map_err(|error| ic_cdk::trap(&format!("Scaffold instantiation failed: {}", error)))
+    }
+
+    /// Create a checked scaffold's documents, sprints and captures in order,
+    /// parents first
+    fn create_scaffold(
+        &mut self,
+        user: &UserRef,
+        source: &TemplateSource,
+        spec: &ScaffoldSpec,
+        values: &BTreeMap<String, String>,
+        mut instance: ScaffoldInstance,
+        anchor: Timestamp,
+    ) -> Result<ScaffoldInstance, String> {
+        let render = |text: &str| template::render(text, values);
+
+        for document in &spec.documents {
+            let (UserRef::Principal(owner), Some(workspace_id)) = (user, instance.workspace_id) else {
+                return Err("Documents need a principal owner and a workspace".to_string());
+            };
+            let created = self.create_document(*owner, CreateDocumentRequest {
+                workspace_id,
+                title: render(&document.title),
+                content: Some(render(&document.content)),
+                template_id: None,
+                parent_id: document.parent.as_deref().and_then(|key| scaffold_id(&instance.documents, key)),
+            })?;
+            self.set_document_template(created.id, source);
+            instance.documents.push((document.key.clone(), created.id));
+        }
+
+        for sprint in &spec.sprints {
+            let (start_date, end_date) = scaffold_sprint_dates(sprint, anchor);
+            let request = CreateSprintRequest {
+                name: render(&sprint.name),
+                goal: sprint.goal.as_deref().map(render),
+                start_date,
+                end_date,
+                capacity: sprint.capacity,
+                stream: sprint.stream.clone(),
+            };
+            let created = match user {
+                UserRef::Principal(owner) => self.create_sprint(*owner, request)?,
+                UserRef::UserId(user_id) => self.create_sprint_for_user_id(user_id, request)?,
+            };
+            instance.sprints.push((sprint.key.clone(), created.id));
+        }
+
+        for capture in &spec.captures {
+            let parent_id = capture.parent.as_deref().and_then(|key| scaffold_id(&instance.captures, key));
+            let fields = scaffold_capture_fields(capture, values, anchor, instance.workspace_id, parent_id);
+            let created = self.create_capture_for(user, CreateCaptureRequest {
+                capture_type: capture.capture_type.clone(),
+                subtype: capture.subtype.clone(),
+                title: render(&capture.title),
+                description: None,
+                content: capture.content.as_deref().map(render),
+                priority: capture.priority.clone(),
+                fields: Some(fields),
+                recurrence: None,
+            })?;
+            self.set_capture_template(created.id, source);
+            if let Some(sprint_id) = capture.sprint.as_deref().and_then(|key| scaffold_id(&instance.sprints, key)) {
+                self.add_capture_to_sprint(sprint_id, created.id)?;
+            }
+            instance.captures.push((capture.key.clone(), created.id));
+        }
+
+        Ok(instance)
     }
 
     // =========================================================================
-    // Sprint Operations
+    // Board Operations
     // =========================================================================
 
-    /// Create a new sprint
-    pub fn create_sprint(&mut self, owner: Principal, request: CreateSprintRequest) -> Sprint {
+    /// Create a new board
+    pub fn create_board(&mut self, owner: Principal, request: CreateBoardRequest) -> Result<Board, String> {
+        validate_board(&request.name, &request.columns)?;
+
         let now = ic_cdk::api::time();
-        let id = self.next_sprint_id;
-        self.next_sprint_id += 1;
+        let id = self.next_board_id;
+        self.next_board_id += 1;
 
-        let sprint = Sprint {
+        let board = Board {
             id,
             owner,
             name: request.name,
-            goal: request.goal,
-            status: SprintStatus::Planning,
-            start_date: request.start_date,
-            end_date: request.end_date,
-            capacity: request.capacity,
-            capture_ids: Vec::new(),
+            scope: request.scope,
+            columns: request.columns,
             created_at: now,
             updated_at: now,
         };
 
-        self.sprints.insert(id, sprint.clone());
-        self.user_sprints
+        self.boards.insert(id, board.clone());
+        self.user_boards
             .entry(owner)
             .or_default()
             .push(id);
 
-        sprint
+        Ok(board)
     }
 
-    /// Get a sprint by ID
-    pub fn get_sprint(&self, id: SprintId) -> Option<&Sprint> {
-        self.sprints.get(&id)
+    /// Get a board by ID
+    pub fn get_board(&self, id: BoardId) -> Option<&Board> {
+        self.boards.get(&id)
     }
 
-    /// Add capture to sprint
-    pub fn add_capture_to_sprint(&mut self, sprint_id: SprintId, capture_id: CaptureId) -> Result<(), String> {
-        let sprint = self.sprints.get_mut(&sprint_id)
-            .ok_or_else(|| "Sprint not found".to_string())?;
+    /// Get user's boards
+    pub fn get_user_boards(&self, owner: Principal) -> Vec<Board> {
+        self.user_boards
+            .get(&owner)
+            .map(|ids| {
+                ids.iter()
+                    .filter_map(|id| self.boards.get(id))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
 
-        let capture = self.captures.get_mut(&capture_id)
-            .ok_or_else(|| "Capture not found".to_string())?;
+    /// Update a board
+    pub fn update_board(&mut self, id: BoardId, request: UpdateBoardRequest) -> Result<Board, String> {
+        let board = self.boards.get_mut(&id)
+            .ok_or_else(|| "Board not found".to_string())?;
 
-        if !sprint.capture_ids.contains(&capture_id) {
-            sprint.capture_ids.push(capture_id);
-            capture.fields.sprint_id = Some(sprint_id);
-        }
+        let name = request.name.unwrap_or_else(|| board.name.clone());
+        let columns = request.columns.unwrap_or_else(|| board.columns.clone());
+        validate_board(&name, &columns)?;
 
-        Ok(())
-    }
+        board.name = name;
+        board.columns = columns;
+        board.updated_at = ic_cdk::api::time();
 
-    /// Remove capture from sprint
-    pub fn remove_capture_from_sprint(&mut self, sprint_id: SprintId, capture_id: CaptureId) -> Result<(), String> {
-        let sprint = self.sprints.get_mut(&sprint_id)
-            .ok_or_else(|| "Sprint not found".to_string())?;
+        Ok(board.clone())
+    }
 
-        sprint.capture_ids.retain(|&id| id != capture_id);
+    /// Delete a board
+    pub fn delete_board(&mut self, id: BoardId) -> Option<Board> {
+        let board = self.boards.remove(&id)?;
+        self.board_ranks.remove(&id);
 
-        if let Some(capture) = self.captures.get_mut(&capture_id) {
-            if capture.fields.sprint_id == Some(sprint_id) {
-                capture.fields.sprint_id = None;
-            }
+        if let Some(user_boards) = self.user_boards.get_mut(&board.owner) {
+            user_boards.retain(|&bid| bid != id);
         }
 
-        Ok(())
+        Some(board)
     }
 
-    /// Get user's sprints
-    pub fn get_user_sprints(&self, owner: Principal) -> Vec<Sprint> {
-        self.user_sprints
-            .get(&owner)
+    /// Delete the boards over a sprint or workspace that is being deleted
+    fn delete_scoped_boards(&mut self, scope: &BoardScope) {
+        let ids: Vec<BoardId> = self.boards
+            .values()
+            .filter(|board| board.scope == *scope)
+            .map(|board| board.id)
+            .collect();
+        for id in ids {
+            self.delete_board(id);
+        }
+    }
+
+    /// Captures a board draws its cards from (the board owner's captures within its scope)
+    fn board_cards(&self, board: &Board) -> Vec<&Capture> {
+        self.user_captures
+            .get(&board.owner)
             .map(|ids| {
                 ids.iter()
-                    .filter_map(|id| self.sprints.get(id))
-                    .cloned()
+                    .filter_map(|id| self.captures.get(id))
+                    .filter(|c| board_scope_contains(&board.scope, c))
                     .collect()
             })
             .unwrap_or_default()
     }
 
-    /// Update a sprint
-    pub fn update_sprint(&mut self, id: SprintId, request: UpdateSprintRequest) -> Option<Sprint> {
-        let sprint = self.sprints.get_mut(&id)?;
-        let now = ic_cdk::api::time();
+    /// Cards in one board column, in board rank order. Cards never moved on the
+    /// board follow, grouped by backlog/sprint list and ordered by their list rank.
+    fn board_column_cards(&self, board: &Board, column: usize) -> Vec<&Capture> {
+        let ranks = self.board_ranks.get(&board.id);
+        let mut cards: Vec<(Option<&String>, &Capture)> = self.board_cards(board)
+            .into_iter()
+            .filter(|c| board_column_index(board, c) == Some(column))
+            .map(|c| (ranks.and_then(|r| r.get(&c.id)), c))
+            .collect();
+        cards.sort_by(|(a_board, a), (b_board, b)| {
+            (a_board.is_none(), a_board, a.fields.sprint_id, a.rank.is_none(), &a.rank, a.id)
+                .cmp(&(b_board.is_none(), b_board, b.fields.sprint_id, b.rank.is_none(), &b.rank, b.id))
+        });
+        cards.into_iter().map(|(_, c)| c).collect()
+    }
 
-        if let Some(name) = request.name {
-            sprint.name = name;
+    /// Give every card in a board column a board rank, keeping the current order
+    fn fill_board_ranks(&mut self, board: &Board, column: usize) {
+        let ids: Vec<CaptureId> = self.board_column_cards(board, column).iter().map(|c| c.id).collect();
+        let ranks = self.board_ranks.entry(board.id).or_default();
+        let mut last: Option<String> = None;
+
+        for id in ids {
+            match ranks.get(&id) {
+                Some(rank) => last = Some(rank.clone()),
+                None => {
+                    let next = rank_after(last.as_deref());
+                    ranks.insert(id, next.clone());
+                    last = Some(next);
+                }
+            }
         }
-        if let Some(goal) = request.goal {
-            sprint.goal = Some(goal);
+    }
+
+    /// Render a board: each column with its cards in rank order
+    pub fn get_board_view(&self, id: BoardId) -> Option<BoardView> {
+        let board = self.boards.get(&id)?;
+
+        let columns = board.columns
+            .iter()
+            .enumerate()
+            .map(|(index, column)| {
+                let cards = self.board_column_cards(board, index);
+                let card_count = cards.len() as u64;
+                BoardColumnView {
+                    index: index as u32,
+                    name: column.name.clone(),
+                    wip_limit: column.wip_limit,
+                    card_count,
+                    over_limit: column.wip_limit.map(|l| card_count > l as u64).unwrap_or(false),
+                    cards: cards.into_iter().cloned().collect(),
+                }
+            })
+            .collect();
+
+        Some(BoardView {
+            board: board.clone(),
+            columns,
+        })
+    }
+
+    /// Move a card to a column and position, changing its status/labels and rank together.
+    /// Moving a card into a column that is at its WIP limit is rejected.
+    pub fn move_card(&mut self, request: MoveCardRequest) -> Result<Capture, String> {
+        let board = self.boards.get(&request.board_id)
+            .ok_or_else(|| "Board not found".to_string())?
+            .clone();
+        let target = request.column as usize;
+        let column = board.columns.get(target)
+            .ok_or_else(|| "Column not found".to_string())?;
+
+        let capture = self.captures.get(&request.capture_id)
+            .ok_or_else(|| "Capture not found".to_string())?;
+        if capture.owner != board.owner || !board_scope_contains(&board.scope, capture) {
+            return Err("Capture is not on this board".to_string());
         }
-        if let Some(status) = request.status {
-            sprint.status = status;
+        let current_column = board_column_index(&board, capture);
+
+        // Work out the status and labels that place the card in the target column
+        let mut moved = capture.clone();
+        if !column.statuses.is_empty() && !column.statuses.contains(&moved.status) {
+            moved.status = column.statuses[0].clone();
         }
-        if let Some(start_date) = request.start_date {
-            sprint.start_date = start_date;
+        if !column.labels.is_empty() && !moved.fields.labels.iter().any(|l| column.labels.contains(l)) {
+            // Drop labels that would match the card to another column
+            let other_labels: Vec<&String> = board.columns
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != target)
+                .flat_map(|(_, c)| c.labels.iter())
+                .collect();
+            moved.fields.labels.retain(|l| !other_labels.contains(&l));
+            moved.fields.labels.push(column.labels[0].clone());
         }
-        if let Some(end_date) = request.end_date {
-            sprint.end_date = end_date;
+        if board_column_index(&board, &moved) != Some(target) {
+            return Err(format!(
+                "Card cannot be placed in column {}: an earlier column also matches it",
+                column.name
+            ));
         }
-        if let Some(capacity) = request.capacity {
-            sprint.capacity = Some(capacity);
+
+        // Enforce the WIP limit when the card enters a new column
+        if current_column != Some(target) {
+            if let Some(limit) = column.wip_limit {
+                let in_column = self.board_cards(&board)
+                    .into_iter()
+                    .filter(|c| c.id != request.capture_id && board_column_index(&board, c) == Some(target))
+                    .count();
+                if in_column >= limit as usize {
+                    return Err(format!("Column {} is at its WIP limit of {}", column.name, limit));
+                }
+            }
         }
 
-        sprint.updated_at = now;
+        // Rank against the other cards in the target column, in the board's own rank space
+        self.fill_board_ranks(&board, target);
+        let ranks = self.board_ranks.get(&board.id).cloned().unwrap_or_default();
+        let column_cards: Vec<(CaptureId, String)> = self.board_column_cards(&board, target)
+            .into_iter()
+            .filter(|c| c.id != request.capture_id)
+            .filter_map(|c| ranks.get(&c.id).map(|r| (c.id, r.clone())))
+            .collect();
+
+        let keep_rank = request.before.is_none() && request.after.is_none() && current_column == Some(target);
+        let rank = match ranks.get(&request.capture_id) {
+            Some(rank) if keep_rank => rank.clone(),
+            _ => {
+                let list_name = format!("column {}", column.name);
+                rank_between_neighbours(&column_cards, request.before, request.after, &list_name)?
+            }
+        };
+        self.board_ranks.entry(board.id).or_default().insert(request.capture_id, rank);
 
-        Some(sprint.clone())
+        let capture = self.captures.get_mut(&request.capture_id)
+            .ok_or_else(|| "Capture not found".to_string())?;
+        let previous = capture.clone();
+        capture.status = moved.status;
+        capture.fields.labels = moved.fields.labels;
+        capture.updated_at = ic_cdk::api::time();
+        let updated = capture.clone();
+        self.on_capture_changed(Some(&previous), request.capture_id);
+
+        Ok(updated)
     }
+}
 
-    /// Delete a sprint
-    pub fn delete_sprint(&mut self, id: SprintId) -> Option<Sprint> {
-        let sprint = self.sprints.remove(&id)?;
+/// Start a recurrence series at a new capture, validating the rule against its dates
+fn start_recurrence(id: CaptureId, rule: RecurrenceRule, fields: &DynamicFields) -> Result<Recurrence, String> {
+    let dtstart = recurrence_anchor(fields)
+        .ok_or_else(|| "Recurring captures need a start_date or due_date".to_string())?;
+    validate_rule(&rule, dtstart)?;
+
+    Ok(Recurrence {
+        rule,
+        series_id: id,
+        dtstart,
+        occurrence: 1,
+        next_occurrence_id: None,
+    })
+}
 
-        // Remove from user_sprints index
-        if let Some(user_sprints) = self.user_sprints.get_mut(&sprint.owner) {
-            user_sprints.retain(|&sid| sid != id);
-        }
+/// Recurrence queue entry for a capture whose series still needs a next occurrence
+fn recurrence_queue_key(capture: &Capture) -> Option<(Timestamp, CaptureId)> {
+    let recurrence = capture.recurrence.as_ref()?;
+    if recurrence.next_occurrence_id.is_some() || capture.status == CaptureStatus::Archived {
+        return None;
+    }
+    if recurrence.rule.count.map(|count| recurrence.occurrence >= count).unwrap_or(false) {
+        return None;
+    }
+    recurrence_anchor(&capture.fields).map(|anchor| (anchor, capture.id))
+}
 
-        Some(sprint)
+/// Validate and normalize a handle (leading `@` removed, lowercased)
+fn normalize_handle(handle: &str) -> Result<String, String> {
+    let handle = handle.trim();
+    let handle = handle.strip_prefix('@').unwrap_or(handle).to_lowercase();
+
+    if handle.len() < MIN_HANDLE_LEN || handle.len() > MAX_HANDLE_LEN {
+        return Err(format!("Handle must be {}-{} characters", MIN_HANDLE_LEN, MAX_HANDLE_LEN));
+    }
+    if !handle.chars().all(is_handle_char) {
+        return Err("Handle may only contain letters, digits, '_' and '-'".to_string());
     }
+    Ok(handle)
+}
 
-    // =========================================================================
-    // Workspace Operations
-    // =========================================================================
+fn is_handle_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-'
+}
 
-    /// Create a new workspace
-    pub fn create_workspace(&mut self, owner: Principal, request: CreateWorkspaceRequest) -> Workspace {
-        let now = ic_cdk::api::time();
-        let id = self.next_workspace_id;
-        self.next_workspace_id += 1;
+/// Lowercased handles @mentioned in a text. A mention starts at an `@` that is
+/// not preceded by a handle character, so email addresses are not mentions.
+fn parse_mentions(text: &str) -> Vec<String> {
+    let mut mentions: Vec<String> = Vec::new();
+    let mut previous: Option<char> = None;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        if c == '@' && !previous.map(is_handle_char).unwrap_or(false) {
+            let mut end = start + 1;
+            while let Some(&(i, next)) = chars.peek() {
+                if !is_handle_char(next) {
+                    break;
+                }
+                end = i + next.len_utf8();
+                chars.next();
+            }
+            let handle = text[start + 1..end].to_lowercase();
+            if handle.len() >= MIN_HANDLE_LEN && handle.len() <= MAX_HANDLE_LEN && !mentions.contains(&handle) {
+                mentions.push(handle);
+            }
+            previous = text[..end].chars().next_back();
+            continue;
+        }
+        previous = Some(c);
+    }
 
-        let workspace = Workspace {
-            id,
-            owner,
-            name: request.name,
-            description: request.description,
-            icon: request.icon,
-            parent_id: request.parent_id,
-            is_archived: false,
-            created_at: now,
-            updated_at: now,
+    mentions
+}
+
+/// Reference found in text by `parse_links`
+enum LinkReference {
+    Title(String),
+    Capture(CaptureId),
+}
+
+/// `[[Title]]` (or `[[Title|label]]`) and `#capture-123` references in a text,
+/// without duplicates
+fn parse_links(text: &str) -> Vec<LinkReference> {
+    let mut titles: Vec<String> = Vec::new();
+    let mut captures: Vec<CaptureId> = Vec::new();
+    let mut references = Vec::new();
+
+    let mut rest = text;
+    while let Some(start) = rest.find("[[") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("]]") else {
+            break;
         };
+        let inner = &after[..end];
+        let title = inner.split('|').next().unwrap_or_default().trim();
+        let valid = !title.is_empty() && title.len() <= MAX_LINK_TITLE_LEN && !inner.contains(['\n', '[']);
+        if valid && !titles.iter().any(|t| t.to_lowercase() == title.to_lowercase()) {
+            titles.push(title.to_string());
+            references.push(LinkReference::Title(title.to_string()));
+        }
+        rest = if valid { &after[end + 2..] } else { after };
+    }
 
-        self.workspaces.insert(id, workspace.clone());
-        self.user_workspaces
-            .entry(owner)
-            .or_default()
-            .push(id);
+    const CAPTURE_PREFIX: &str = "#capture-";
+    for (start, _) in text.match_indices(CAPTURE_PREFIX) {
+        if text[..start].chars().next_back().is_some_and(char::is_alphanumeric) {
+            continue;
+        }
+        let digits: &str = &text[start + CAPTURE_PREFIX.len()..];
+        let len = digits.find(|c: char| !c.is_ascii_digit()).unwrap_or(digits.len());
+        let followed_by_word = digits[len..].chars().next().is_some_and(|c| c.is_alphanumeric() || c == '_');
+        if let (false, Ok(id)) = (followed_by_word, digits[..len].parse::<CaptureId>()) {
+            if !captures.contains(&id) {
+                captures.push(id);
+                references.push(LinkReference::Capture(id));
+            }
+        }
+    }
 
-        workspace
+    references
+}
+
+/// Capture text that is scanned for @mentions
+fn mention_text(capture: &Capture) -> String {
+    [capture.description.as_deref(), capture.content.as_deref()]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+/// Field change event
+fn field_changed(field: &str, old_value: Option<String>, new_value: Option<String>) -> ActivityEvent {
+    ActivityEvent::FieldChanged {
+        field: field.to_string(),
+        old_value,
+        new_value,
     }
+}
 
-    /// Get a workspace by ID
-    pub fn get_workspace(&self, id: WorkspaceId) -> Option<&Workspace> {
-        self.workspaces.get(&id)
+/// Activity events for the differences between two versions of a capture.
+/// Ranks and timestamps are not recorded.
+fn capture_changes(previous: &Capture, current: &Capture) -> Vec<ActivityEvent> {
+    fn debug<T: std::fmt::Debug>(value: &T) -> Option<String> {
+        Some(format!("{:?}", value))
+    }
+    fn opt<T: ToString>(value: &Option<T>) -> Option<String> {
+        value.as_ref().map(|v| v.to_string())
+    }
+    fn date(value: &Option<Timestamp>) -> Option<String> {
+        value.map(format_utc)
+    }
+    fn list(values: &[String]) -> Option<String> {
+        Some(values.join(", "))
+    }
+    fn checklist(value: &Option<Checklist>) -> Option<String> {
+        value.as_ref().map(|c| format!("{}/{}", c.completed_count, c.total_count))
     }
 
-    /// Get user's workspaces
-    pub fn get_user_workspaces(&self, owner: Principal) -> Vec<Workspace> {
-        self.user_workspaces
-            .get(&owner)
-            .map(|ids| {
-                ids.iter()
-                    .filter_map(|id| self.workspaces.get(id))
-                    .cloned()
-                    .collect()
-            })
-            .unwrap_or_default()
+    let (p, c) = (previous, current);
+    let (pf, cf) = (&p.fields, &c.fields);
+    let candidates = [
+        ("title", p.title != c.title, Some(p.title.clone()), Some(c.title.clone())),
+        ("description", p.description != c.description, None, None),
+        ("content", p.content != c.content, None, None),
+        ("capture_type", p.capture_type != c.capture_type, debug(&p.capture_type), debug(&c.capture_type)),
+        ("subtype", p.subtype != c.subtype, p.subtype.as_ref().and_then(debug), c.subtype.as_ref().and_then(debug)),
+        ("priority", p.priority != c.priority, debug(&p.priority), debug(&c.priority)),
+        ("status", p.status != c.status, debug(&p.status), debug(&c.status)),
+        ("estimate", pf.estimate != cf.estimate, opt(&pf.estimate), opt(&cf.estimate)),
+        ("start_date", pf.start_date != cf.start_date, date(&pf.start_date), date(&cf.start_date)),
+        ("due_date", pf.due_date != cf.due_date, date(&pf.due_date), date(&cf.due_date)),
+        ("assignees", pf.assignees != cf.assignees, list(&pf.assignees), list(&cf.assignees)),
+        ("labels", pf.labels != cf.labels, list(&pf.labels), list(&cf.labels)),
+        ("parent_id", pf.parent_id != cf.parent_id, opt(&pf.parent_id), opt(&cf.parent_id)),
+        ("sprint_id", pf.sprint_id != cf.sprint_id, opt(&pf.sprint_id), opt(&cf.sprint_id)),
+        ("workspace_id", pf.workspace_id != cf.workspace_id, opt(&pf.workspace_id), opt(&cf.workspace_id)),
+        ("related_captures", pf.related_captures != cf.related_captures, None, None),
+        ("custom_fields", pf.custom_fields != cf.custom_fields, None, None),
+        ("checklist", checklist(&p.checklist) != checklist(&c.checklist), checklist(&p.checklist), checklist(&c.checklist)),
+        ("recurrence", p.recurrence.is_some() != c.recurrence.is_some(), None, None),
+    ];
+
+    candidates
+        .into_iter()
+        .filter(|(_, changed, _, _)| *changed)
+        .map(|(field, _, old_value, new_value)| field_changed(field, old_value, new_value))
+        .collect()
+}
+
+/// Length of a time entry; running timers count up to `now`
+fn entry_duration(entry: &TimeEntry, now: Timestamp) -> u64 {
+    entry.ended_at.unwrap_or(now).saturating_sub(entry.started_at)
+}
+
+/// Aggregate time entries, optionally clipped to a range (entries outside it are dropped)
+fn time_report(entries: Vec<&TimeEntry>, now: Timestamp, range: Option<&CalendarRange>) -> TimeReport {
+    let mut by_capture: BTreeMap<CaptureId, u64> = BTreeMap::new();
+    let mut by_user: BTreeMap<UserRef, u64> = BTreeMap::new();
+    let mut included: Vec<TimeEntry> = Vec::new();
+    let mut total_duration = 0;
+
+    for entry in entries {
+        let mut start = entry.started_at;
+        let mut end = entry.ended_at.unwrap_or(now).max(start);
+        if let Some(range) = range {
+            start = start.max(range.start);
+            end = end.min(range.end);
+            if end <= start {
+                continue;
+            }
+        }
+
+        let duration = end - start;
+        total_duration += duration;
+        *by_capture.entry(entry.capture_id).or_default() += duration;
+        *by_user.entry(entry.user.clone()).or_default() += duration;
+        included.push(entry.clone());
     }
+    included.sort_by_key(|entry| std::cmp::Reverse((entry.started_at, entry.id)));
 
-    /// Update a workspace
-    pub fn update_workspace(&mut self, id: WorkspaceId, request: UpdateWorkspaceRequest) -> Option<Workspace> {
-        let workspace = self.workspaces.get_mut(&id)?;
-        let now = ic_cdk::api::time();
+    TimeReport {
+        total_duration,
+        by_capture: by_capture
+            .into_iter()
+            .map(|(capture_id, duration)| CaptureTime { capture_id, duration })
+            .collect(),
+        by_user: by_user
+            .into_iter()
+            .map(|(user, duration)| UserTime { user, duration })
+            .collect(),
+        entries: included,
+    }
+}
 
-        if let Some(name) = request.name {
-            workspace.name = name;
+/// Check that a subtype belongs to the capture type
+fn validate_subtype(capture_type: &CaptureType, subtype: Option<&CaptureSubtype>) -> Result<(), String> {
+    match subtype {
+        Some(subtype) if subtype.capture_type() != *capture_type => {
+            Err(format!("Subtype {:?} does not apply to {:?} captures", subtype, capture_type))
         }
-        if let Some(description) = request.description {
-            workspace.description = Some(description);
+        _ => Ok(()),
+    }
+}
+
+/// Adjust fields for a capture's new type. Calendar captures are placed by
+/// their start date; only tasks, projects and events keep estimates and sprints.
+/// Returns the fields that were moved or cleared, with their previous values.
+fn remap_fields_for_type(fields: &mut DynamicFields, capture_type: &CaptureType) -> Vec<(String, String)> {
+    let mut remapped = Vec::new();
+    match capture_type {
+        CaptureType::Calendar => {
+            if fields.start_date.is_none() {
+                if let Some(due) = fields.due_date.take() {
+                    fields.start_date = Some(due);
+                    remapped.push(("due_date".to_string(), due.to_string()));
+                }
+            }
         }
-        if let Some(icon) = request.icon {
-            workspace.icon = Some(icon);
+        CaptureType::Idea | CaptureType::Reflection | CaptureType::Outline => {
+            if let Some(estimate) = fields.estimate.take() {
+                remapped.push(("estimate".to_string(), estimate.to_string()));
+            }
+            if let Some(sprint_id) = fields.sprint_id.take() {
+                remapped.push(("sprint_id".to_string(), sprint_id.to_string()));
+            }
         }
-        if let Some(parent_id) = request.parent_id {
-            workspace.parent_id = Some(parent_id);
+        CaptureType::Task | CaptureType::Project => {}
+    }
+    remapped
+}
+
+/// Fill empty fields from a template's defaults (labels and custom fields are merged)
+fn apply_default_fields(fields: &mut DynamicFields, defaults: &DynamicFields) {
+    fields.estimate = fields.estimate.or(defaults.estimate);
+    fields.workspace_id = fields.workspace_id.or(defaults.workspace_id);
+    for label in &defaults.labels {
+        if !fields.labels.contains(label) {
+            fields.labels.push(label.clone());
         }
-        if let Some(is_archived) = request.is_archived {
-            workspace.is_archived = is_archived;
+    }
+    for (key, value) in &defaults.custom_fields {
+        if !fields.custom_fields.iter().any(|(k, _)| k == key) {
+            fields.custom_fields.push((key.clone(), value.clone()));
         }
+    }
+}
 
-        workspace.updated_at = now;
+/// Check template variable declarations when a request sets them
+fn validate_template_variables(variables: Option<&[TemplateVariable]>) -> Result<(), String> {
+    variables.map(template::validate_variables).unwrap_or(Ok(()))
+}
 
-        Some(workspace.clone())
+/// A template with its contents replaced by a published version
+fn apply_template_version(template: &Template, version: &TemplateVersion) -> Template {
+    Template {
+        template_type: version.template_type.clone(),
+        name: version.name.clone(),
+        description: version.description.clone(),
+        content: version.content.clone(),
+        capture_type: version.capture_type.clone(),
+        default_fields: version.default_fields.clone(),
+        variables: version.variables.clone(),
+        scaffold: version.scaffold.clone(),
+        ..template.clone()
     }
+}
 
-    /// Delete a workspace
-    pub fn delete_workspace(&mut self, id: WorkspaceId) -> Option<Workspace> {
-        let workspace = self.workspaces.remove(&id)?;
+/// Check whether a template's draft is unchanged from a published version
+fn template_matches_version(template: &Template, version: &TemplateVersion) -> bool {
+    template.template_type == version.template_type
+        && template.name == version.name
+        && template.description == version.description
+        && template.content == version.content
+        && template.capture_type == version.capture_type
+        && template.default_fields == version.default_fields
+        && template.variables == version.variables
+        && template.scaffold == version.scaffold
+}
 
-        // Remove from user_workspaces index
-        if let Some(user_workspaces) = self.user_workspaces.get_mut(&workspace.owner) {
-            user_workspaces.retain(|&wid| wid != id);
+/// Lowercase, trimmed and de-duplicated template tags
+fn normalize_template_tags(tags: Vec<String>) -> Result<Vec<String>, String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim().to_lowercase();
+        if tag.is_empty() || tag.chars().count() > MAX_TEMPLATE_TAG_LEN {
+            return Err(format!("Tags must be 1-{} characters", MAX_TEMPLATE_TAG_LEN));
         }
-
-        // Also remove workspace_documents index
-        self.workspace_documents.remove(&id);
-
-        Some(workspace)
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    if normalized.len() > MAX_TEMPLATE_TAGS {
+        return Err(format!("Templates cannot have more than {} tags", MAX_TEMPLATE_TAGS));
     }
+    Ok(normalized)
+}
 
-    // =========================================================================
-    // Document Operations
-    // =========================================================================
+/// Check the variables and scaffold of a new template
+fn validate_new_template(request: &CreateTemplateRequest) -> Result<(), String> {
+    validate_template_variables(request.variables.as_deref())?;
+    validate_template_scaffold(&request.template_type, request.scaffold.as_ref())
+}
 
-    /// Create a new document
-    pub fn create_document(&mut self, owner: Principal, request: CreateDocumentRequest) -> Result<Document, String> {
-        // Verify workspace exists
-        if !self.workspaces.contains_key(&request.workspace_id) {
-            return Err("Workspace not found".to_string());
-        }
+/// Scaffold templates need a valid scaffold, and only they can have one
+fn validate_template_scaffold(template_type: &TemplateType, scaffold: Option<&ScaffoldSpec>) -> Result<(), String> {
+    match (template_type, scaffold) {
+        (TemplateType::Scaffold, Some(spec)) => template::validate_scaffold(spec),
+        (TemplateType::Scaffold, None) => Err("Scaffold templates need a scaffold".to_string()),
+        (_, Some(_)) => Err("Only scaffold templates can have a scaffold".to_string()),
+        (_, None) => Ok(()),
+    }
+}
 
-        let now = ic_cdk::api::time();
-        let id = self.next_document_id;
-        self.next_document_id += 1;
+/// ID created for a scaffold key
+fn scaffold_id(ids: &[(String, u64)], key: &str) -> Option<u64> {
+    ids.iter().find(|(k, _)| k == key).map(|(_, id)| *id)
+}
 
-        let content = if let Some(template_id) = request.template_id {
-            // Load content from template
-            self.templates
-                .get(&template_id)
-                .map(|t| t.content.clone())
-                .unwrap_or_default()
-        } else {
-            request.content.unwrap_or_default()
-        };
+/// Start and end of a scaffold sprint
+fn scaffold_sprint_dates(sprint: &ScaffoldSprint, anchor: Timestamp) -> (Timestamp, Timestamp) {
+    let start_date = anchor.saturating_add(sprint.start_offset_days as u64 * NANOS_PER_DAY);
+    (start_date, start_date.saturating_add(sprint.duration_days as u64 * NANOS_PER_DAY))
+}
 
-        let document = Document {
-            id,
-            workspace_id: request.workspace_id,
-            owner,
-            title: request.title,
-            content,
-            is_template: false,
-            template_id: request.template_id,
-            parent_id: request.parent_id,
-            created_at: now,
-            updated_at: now,
-        };
+/// Fields of a scaffold capture, with day offsets resolved against the anchor
+fn scaffold_capture_fields(
+    capture: &ScaffoldCapture,
+    values: &BTreeMap<String, String>,
+    anchor: Timestamp,
+    workspace_id: Option<WorkspaceId>,
+    parent_id: Option<CaptureId>,
+) -> DynamicFields {
+    let offset = |days: u32| anchor.saturating_add(days as u64 * NANOS_PER_DAY);
+    let mut labels: Vec<String> = Vec::new();
+    for label in &capture.labels {
+        let label = template::render(label, values);
+        if !label.trim().is_empty() && !labels.contains(&label) {
+            labels.push(label);
+        }
+    }
+    DynamicFields {
+        estimate: capture.estimate,
+        start_date: capture.start_offset_days.map(offset),
+        due_date: capture.due_offset_days.map(offset),
+        labels,
+        parent_id,
+        workspace_id,
+        ..Default::default()
+    }
+}
 
-        self.documents.insert(id, document.clone());
-        self.workspace_documents
-            .entry(request.workspace_id)
-            .or_default()
-            .push(id);
+/// Task titles from the list items ("- ", "* " or "- [ ] ") of template content
+fn scaffold_task_titles(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let item = line.strip_prefix("- ").or_else(|| line.strip_prefix("* "))?;
+            let item = item.strip_prefix("[ ] ").unwrap_or(item).trim();
+            (!item.is_empty()).then(|| item.to_string())
+        })
+        .collect()
+}
 
-        Ok(document)
+/// Check whether a change to a capture affects its ancestors' roll-ups
+fn rollup_inputs_changed(previous: &Capture, current: &Capture) -> bool {
+    previous.fields.parent_id != current.fields.parent_id
+        || previous.fields.estimate != current.fields.estimate
+        || previous.fields.start_date != current.fields.start_date
+        || previous.fields.due_date != current.fields.due_date
+        || previous.status != current.status
+}
+
+/// Smaller of two optional timestamps, ignoring None
+fn min_option(a: Option<Timestamp>, b: Option<Timestamp>) -> Option<Timestamp> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        _ => a.or(b),
     }
+}
 
-    /// Get a document by ID
-    pub fn get_document(&self, id: DocumentId) -> Option<&Document> {
-        self.documents.get(&id)
+/// Whole-number percentage, 0 when the total is 0
+fn percent(part: u64, total: u64) -> u32 {
+    if total == 0 {
+        return 0;
     }
+    (part * 100 / total) as u32
+}
 
-    /// Update document content
-    pub fn update_document(&mut self, id: DocumentId, title: Option<String>, content: Option<String>) -> Option<Document> {
-        let doc = self.documents.get_mut(&id)?;
-        let now = ic_cdk::api::time();
+/// Whether a capture still needs work
+fn is_open(status: &CaptureStatus) -> bool {
+    !matches!(
+        status,
+        CaptureStatus::Completed | CaptureStatus::Archived | CaptureStatus::Cancelled
+    )
+}
 
-        if let Some(t) = title {
-            doc.title = t;
-        }
-        if let Some(c) = content {
-            doc.content = c;
-        }
-        doc.updated_at = now;
+/// Whether a capture's date falls in a date-relative window
+fn date_matches(condition: &DateCondition, capture: &Capture, now: Timestamp) -> bool {
+    let Some(date) = capture_date(capture, &condition.field) else {
+        return false;
+    };
+
+    match condition.window {
+        RelativeWindow::Last(span) => date <= now && date >= now.saturating_sub(span),
+        RelativeWindow::Next(span) => date >= now && date <= now.saturating_add(span),
+        RelativeWindow::Past => date < now,
+        RelativeWindow::Today => day_of(date) == day_of(now),
+    }
+}
 
-        Some(doc.clone())
+/// Enforce the per-document size limit
+fn check_document_size(content: &str) -> Result<(), String> {
+    if content.len() > MAX_DOCUMENT_SIZE {
+        return Err(document_too_large(content.len() as u64));
     }
+    Ok(())
+}
 
-    /// Get documents in a workspace
-    pub fn get_workspace_documents(&self, workspace_id: WorkspaceId) -> Vec<Document> {
-        self.workspace_documents
-            .get(&workspace_id)
-            .map(|ids| {
-                ids.iter()
-                    .filter_map(|id| self.documents.get(id))
-                    .cloned()
-                    .collect()
-            })
-            .unwrap_or_default()
+fn document_too_large(size: u64) -> String {
+    format!("Document is too large ({} bytes, max {} bytes)", size, MAX_DOCUMENT_SIZE)
+}
+
+/// Patch that produced a stored version, if one was recorded
+fn document_patch(stored: &StoredDocumentVersion) -> Option<DocumentPatch> {
+    let ops = stored.patch.as_ref()?;
+    Some(DocumentPatch {
+        document_id: stored.info.document_id,
+        version: stored.info.version,
+        author: stored.info.author.clone(),
+        ops: ops.clone(),
+        created_at: stored.info.created_at,
+    })
+}
+
+/// Validate and normalize a custom field key (lowercased)
+fn normalize_field_key(key: &str) -> Result<String, String> {
+    let key = key.trim().to_lowercase();
+    if key.is_empty() || key.len() > 40 {
+        return Err("Custom field key must be 1-40 characters".to_string());
+    }
+    if !key.chars().all(is_handle_char) {
+        return Err("Custom field key may only contain letters, digits, '_' and '-'".to_string());
     }
+    Ok(key)
+}
 
-    /// Delete a document
-    pub fn delete_document(&mut self, id: DocumentId) -> Option<Document> {
-        let document = self.documents.remove(&id)?;
+/// Select fields need at least one option and options cannot repeat or contain commas
+fn validate_field_kind(kind: &CustomFieldKind) -> Result<(), String> {
+    let options = match kind {
+        CustomFieldKind::SingleSelect(options) | CustomFieldKind::MultiSelect(options) => options,
+        _ => return Ok(()),
+    };
+    if options.is_empty() {
+        return Err("Select fields need at least one option".to_string());
+    }
+    for (i, option) in options.iter().enumerate() {
+        if option.trim().is_empty() || option.contains(',') {
+            return Err(format!("Invalid option '{}'", option));
+        }
+        if options[..i].contains(option) {
+            return Err(format!("Duplicate option '{}'", option));
+        }
+    }
+    Ok(())
+}
 
-        // Remove from workspace_documents index
-        if let Some(workspace_docs) = self.workspace_documents.get_mut(&document.workspace_id) {
-            workspace_docs.retain(|&did| did != id);
+/// Check a value against its custom field definition
+fn validate_custom_value(definition: &CustomFieldDefinition, value: &str) -> Result<(), String> {
+    let name = &definition.name;
+    let valid = match &definition.kind {
+        CustomFieldKind::Text => true,
+        CustomFieldKind::Number => value.trim().parse::<f64>().is_ok_and(f64::is_finite),
+        CustomFieldKind::Date => parse_ymd(value.trim()).is_some(),
+        CustomFieldKind::SingleSelect(options) => options.iter().any(|o| o == value),
+        CustomFieldKind::MultiSelect(options) => {
+            let selected: Vec<&str> = value.split(',').map(str::trim).collect();
+            selected.iter().enumerate().all(|(i, s)| options.iter().any(|o| o == s) && !selected[..i].contains(s))
         }
+        CustomFieldKind::Principal => Principal::from_text(value.trim()).is_ok(),
+        CustomFieldKind::Url => ["https://", "http://"].iter().any(|scheme| {
+            value.strip_prefix(scheme).is_some_and(|rest| !rest.is_empty() && !rest.contains(char::is_whitespace))
+        }),
+    };
+    if valid {
+        return Ok(());
+    }
 
-        Some(document)
+    Err(match &definition.kind {
+        CustomFieldKind::Text => unreachable!(),
+        CustomFieldKind::Number => format!("Custom field '{}' must be a number", name),
+        CustomFieldKind::Date => format!("Custom field '{}' must be a date (YYYY-MM-DD)", name),
+        CustomFieldKind::SingleSelect(options) => format!("Custom field '{}' must be one of: {}", name, options.join(", ")),
+        CustomFieldKind::MultiSelect(options) => {
+            format!("Custom field '{}' must be a comma-separated selection of: {}", name, options.join(", "))
+        }
+        CustomFieldKind::Principal => format!("Custom field '{}' must be a principal", name),
+        CustomFieldKind::Url => format!("Custom field '{}' must be an http(s) URL", name),
+    })
+}
+
+/// A capture's value for a custom field key
+fn custom_value<'a>(capture: &'a Capture, key: &str) -> Option<&'a str> {
+    capture.fields.custom_fields
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, value)| value.as_str())
+}
+
+/// Order two custom field values by the field's kind: numbers and dates by
+/// value, everything else as case-insensitive text. None when the values
+/// cannot be compared as the kind (e.g. a non-number in a number field).
+fn custom_ordering(kind: Option<&CustomFieldKind>, a: &str, b: &str) -> Option<Ordering> {
+    match kind {
+        Some(CustomFieldKind::Number) => {
+            let a: f64 = a.trim().parse().ok()?;
+            let b: f64 = b.trim().parse().ok()?;
+            a.partial_cmp(&b)
+        }
+        Some(CustomFieldKind::Date) => Some(parse_ymd(a.trim())?.cmp(&parse_ymd(b.trim())?)),
+        _ => Some(a.to_lowercase().cmp(&b.to_lowercase())),
     }
+}
 
-    // =========================================================================
-    // Template Operations
-    // =========================================================================
+/// Whether a capture's custom field value satisfies a comparison. Multi-select
+/// values match `Eq` when the operand is one of the selected options.
+fn custom_value_matches(
+    capture: &Capture,
+    key: &str,
+    kind: Option<&CustomFieldKind>,
+    comparison: &FieldComparison,
+    operand: &str,
+) -> bool {
+    let Some(value) = custom_value(capture, key) else {
+        return false;
+    };
+    if let Some(CustomFieldKind::MultiSelect(_)) = kind {
+        return *comparison == FieldComparison::Eq
+            && value.split(',').any(|option| option.trim().eq_ignore_ascii_case(operand));
+    }
 
-    /// Create a new template
-    pub fn create_template(&mut self, owner: Principal, request: CreateTemplateRequest) -> Template {
-        let now = ic_cdk::api::time();
-        let id = self.next_template_id;
-        self.next_template_id += 1;
+    custom_ordering(kind, value, operand).is_some_and(|ordering| match comparison {
+        FieldComparison::Eq => ordering.is_eq(),
+        FieldComparison::Lt => ordering.is_lt(),
+        FieldComparison::Le => ordering.is_le(),
+        FieldComparison::Gt => ordering.is_gt(),
+        FieldComparison::Ge => ordering.is_ge(),
+    })
+}
 
-        let is_public = request.is_public.unwrap_or(false);
+/// A capture's date for a date filter or query term
+fn capture_date(capture: &Capture, field: &DateField) -> Option<Timestamp> {
+    match field {
+        DateField::Due => capture.fields.due_date,
+        DateField::Start => capture.fields.start_date,
+        DateField::Created => Some(capture.created_at),
+        DateField::Updated => Some(capture.updated_at),
+    }
+}
 
-        let template = Template {
-            id,
-            owner,
-            template_type: request.template_type,
-            name: request.name,
-            description: request.description,
-            content: request.content,
-            capture_type: request.capture_type,
-            default_fields: request.default_fields,
-            is_public,
-            created_at: now,
-            updated_at: now,
+/// Sort captures for a view; captures without the sorted date or custom field
+/// value come last in either direction
+fn sort_captures(captures: &mut [Capture], sort: &ViewSort, custom_kind: Option<&CustomFieldKind>) {
+    fn missing_last<T>(a: Option<T>, b: Option<T>, descending: bool, cmp: impl Fn(T, T) -> Ordering) -> Ordering {
+        match (a, b) {
+            (Some(a), Some(b)) if descending => cmp(b, a),
+            (Some(a), Some(b)) => cmp(a, b),
+            (a, b) => b.is_some().cmp(&a.is_some()),
+        }
+    }
+    let by_date = |a: Option<Timestamp>, b: Option<Timestamp>| missing_last(a, b, sort.descending, |a, b| a.cmp(&b));
+    let by_custom = |key: &str, a: &Capture, b: &Capture| {
+        missing_last(custom_value(a, key), custom_value(b, key), sort.descending, |a, b| {
+            custom_ordering(custom_kind, a, b).unwrap_or_else(|| a.cmp(b))
+        })
+    };
+
+    captures.sort_by(|a, b| {
+        let ordering = match sort.field {
+            ViewSortField::Rank => a.rank.cmp(&b.rank),
+            ViewSortField::Priority => priority_order(&a.priority).cmp(&priority_order(&b.priority)),
+            ViewSortField::Status => status_order(&a.status).cmp(&status_order(&b.status)),
+            ViewSortField::Title => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+            ViewSortField::DueDate => return by_date(a.fields.due_date, b.fields.due_date),
+            ViewSortField::StartDate => return by_date(a.fields.start_date, b.fields.start_date),
+            ViewSortField::CreatedAt => a.created_at.cmp(&b.created_at),
+            ViewSortField::UpdatedAt => a.updated_at.cmp(&b.updated_at),
+            ViewSortField::Custom(ref key) => return by_custom(key, a, b),
         };
+        if sort.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+}
 
-        self.templates.insert(id, template.clone());
-        self.user_templates
-            .entry(owner)
-            .or_default()
-            .push(id);
-
-        if is_public {
-            self.public_templates.push(id);
+/// Group sorted captures; groups appear in the order of their first capture
+fn group_captures(captures: Vec<Capture>, group_by: Option<&ViewGrouping>) -> Vec<ViewGroup> {
+    let Some(group_by) = group_by else {
+        return vec![ViewGroup {
+            key: String::new(),
+            captures,
+        }];
+    };
+
+    let mut groups: Vec<ViewGroup> = Vec::new();
+    for capture in captures {
+        let keys = match group_by {
+            ViewGrouping::Status => vec![format!("{:?}", capture.status)],
+            ViewGrouping::Priority => vec![format!("{:?}", capture.priority)],
+            ViewGrouping::CaptureType => vec![format!("{:?}", capture.capture_type)],
+            ViewGrouping::Label if capture.fields.labels.is_empty() => vec![String::new()],
+            ViewGrouping::Label => capture.fields.labels.clone(),
+            ViewGrouping::Sprint => vec![capture.fields.sprint_id.map(|id| id.to_string()).unwrap_or_default()],
+        };
+        for key in keys {
+            match groups.iter_mut().find(|g| g.key == key) {
+                Some(group) => group.captures.push(capture.clone()),
+                None => groups.push(ViewGroup {
+                    key,
+                    captures: vec![capture.clone()],
+                }),
+            }
         }
+    }
+    groups
+}
 
-        template
+/// Sort key following the capture lifecycle
+fn status_order(status: &CaptureStatus) -> u8 {
+    match status {
+        CaptureStatus::Draft => 0,
+        CaptureStatus::Active => 1,
+        CaptureStatus::InProgress => 2,
+        CaptureStatus::Blocked => 3,
+        CaptureStatus::Completed => 4,
+        CaptureStatus::Archived => 5,
+        CaptureStatus::Cancelled => 6,
     }
+}
 
-    /// Get a template by ID
-    pub fn get_template(&self, id: TemplateId) -> Option<&Template> {
-        self.templates.get(&id)
+/// Sort key putting the most urgent priority first
+fn priority_order(priority: &Priority) -> u8 {
+    match priority {
+        Priority::Critical => 0,
+        Priority::High => 1,
+        Priority::Medium => 2,
+        Priority::Low => 3,
     }
+}
 
-    /// Get user's templates
-    pub fn get_user_templates(&self, owner: Principal) -> Vec<Template> {
-        self.user_templates
-            .get(&owner)
-            .map(|ids| {
-                ids.iter()
-                    .filter_map(|id| self.templates.get(id))
-                    .cloned()
-                    .collect()
-            })
-            .unwrap_or_default()
+/// Summarize captures (already in display order) as a dashboard section
+fn dashboard_section(captures: Vec<&Capture>) -> DashboardSection {
+    let mut by_priority = PriorityBreakdown::default();
+    for capture in &captures {
+        match capture.priority {
+            Priority::Critical => by_priority.critical += 1,
+            Priority::High => by_priority.high += 1,
+            Priority::Medium => by_priority.medium += 1,
+            Priority::Low => by_priority.low += 1,
+        }
     }
 
-    /// Get public templates
-    pub fn get_public_templates(&self) -> Vec<Template> {
-        self.public_templates
-            .iter()
-            .filter_map(|id| self.templates.get(id))
-            .cloned()
-            .collect()
+    DashboardSection {
+        count: captures.len() as u64,
+        by_priority,
+        items: captures.into_iter().take(DASHBOARD_LIST_LIMIT).cloned().collect(),
     }
+}
 
-    /// Update a template
-    pub fn update_template(&mut self, id: TemplateId, request: UpdateTemplateRequest) -> Option<Template> {
-        let template = self.templates.get_mut(&id)?;
-        let now = ic_cdk::api::time();
-        let was_public = template.is_public;
+/// Maximum projected occurrences returned per recurring series
+const MAX_PROJECTED_OCCURRENCES: usize = 500;
+/// Maximum occurrences stepped through per series when projecting (bounds queries far in the future)
+const MAX_PROJECTION_STEPS: usize = 10_000;
 
-        if let Some(name) = request.name {
-            template.name = name;
-        }
-        if let Some(description) = request.description {
-            template.description = Some(description);
+/// Start and end of a dated capture (start_date, else due_date; due_date, else start)
+fn capture_span(capture: &Capture) -> Option<(Timestamp, Timestamp)> {
+    let start = capture.fields.start_date.or(capture.fields.due_date)?;
+    let end = capture.fields.due_date.unwrap_or(start).max(start);
+    Some((start, end))
+}
+
+/// Future occurrences of a series head that overlap a range, with their dates shifted
+fn project_occurrences(head: &Capture, range: &CalendarRange) -> Vec<CalendarEntry> {
+    let (Some(recurrence), Some(anchor), Some((start, end))) =
+        (head.recurrence.as_ref(), recurrence_anchor(&head.fields), capture_span(head))
+    else {
+        return vec![];
+    };
+
+    let mut entries = Vec::new();
+    let mut at = anchor;
+    let mut occurrence = recurrence.occurrence;
+
+    for _ in 0..MAX_PROJECTION_STEPS {
+        if entries.len() >= MAX_PROJECTED_OCCURRENCES {
+            break;
         }
-        if let Some(content) = request.content {
-            template.content = content;
+        if recurrence.rule.count.map(|count| occurrence >= count).unwrap_or(false) {
+            break;
         }
-        if let Some(capture_type) = request.capture_type {
-            template.capture_type = Some(capture_type);
+        let Some(next) = next_occurrence(&recurrence.rule, recurrence.dtstart, at) else {
+            break;
+        };
+        let shift = next - anchor;
+        if start + shift >= range.end {
+            break;
         }
-        if let Some(default_fields) = request.default_fields {
-            template.default_fields = Some(default_fields);
+        at = next;
+        occurrence += 1;
+
+        if end + shift < range.start {
+            continue;
         }
-        if let Some(is_public) = request.is_public {
-            template.is_public = is_public;
 
-            // Update public_templates index
-            if is_public && !was_public {
-                // Add to public templates
-                if !self.public_templates.contains(&id) {
-                    self.public_templates.push(id);
-                }
-            } else if !is_public && was_public {
-                // Remove from public templates
-                self.public_templates.retain(|&tid| tid != id);
-            }
+        let mut capture = head.clone();
+        capture.fields.start_date = capture.fields.start_date.map(|t| t + shift);
+        capture.fields.due_date = capture.fields.due_date.map(|t| t + shift);
+        if let Some(r) = capture.recurrence.as_mut() {
+            r.occurrence = occurrence;
         }
 
-        template.updated_at = now;
+        entries.push(CalendarEntry {
+            capture,
+            start: start + shift,
+            end: end + shift,
+            projected: true,
+        });
+    }
+    entries
+}
 
-        Some(template.clone())
+/// Resolve when a reminder fires for a capture
+fn reminder_fire_at(trigger: &ReminderTrigger, capture: &Capture) -> Option<Timestamp> {
+    match trigger {
+        ReminderTrigger::At(at) => Some(*at),
+        ReminderTrigger::BeforeDue(offset) => capture.fields.due_date.map(|due| due.saturating_sub(*offset)),
     }
+}
 
-    /// Delete a template
-    pub fn delete_template(&mut self, id: TemplateId) -> Option<Template> {
-        let template = self.templates.remove(&id)?;
+/// Reminder queue entry for a reminder that has yet to fire
+fn reminder_queue_key(reminder: &Reminder) -> Option<(Timestamp, ReminderId)> {
+    if reminder.fired {
+        return None;
+    }
+    reminder.fire_at.map(|at| (at, reminder.id))
+}
 
-        // Remove from user_templates index
-        if let Some(user_templates) = self.user_templates.get_mut(&template.owner) {
-            user_templates.retain(|&tid| tid != id);
+/// Check whether a capture falls within a board's scope
+fn board_scope_contains(scope: &BoardScope, capture: &Capture) -> bool {
+    match scope {
+        BoardScope::Workspace(workspace_id) => capture.fields.workspace_id == Some(*workspace_id),
+        BoardScope::Sprint(sprint_id) => capture.fields.sprint_id == Some(*sprint_id),
+    }
+}
+
+/// Index of the first board column a capture matches
+fn board_column_index(board: &Board, capture: &Capture) -> Option<usize> {
+    board.columns.iter().position(|column| {
+        let status_matches = column.statuses.is_empty() || column.statuses.contains(&capture.status);
+        let labels_match = column.labels.is_empty()
+            || capture.fields.labels.iter().any(|l| column.labels.contains(l));
+        status_matches && labels_match
+    })
+}
+
+/// Validate a board's name and column definitions
+fn validate_board(name: &str, columns: &[BoardColumn]) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("Board name cannot be empty".to_string());
+    }
+    if columns.is_empty() {
+        return Err("Board must have at least one column".to_string());
+    }
+    if columns.len() > MAX_BOARD_COLUMNS {
+        return Err(format!("Board cannot have more than {} columns", MAX_BOARD_COLUMNS));
+    }
+    for column in columns {
+        if column.name.trim().is_empty() {
+            return Err("Column name cannot be empty".to_string());
+        }
+        if column.statuses.is_empty() && column.labels.is_empty() {
+            return Err(format!("Column {} must map to at least one status or label", column.name));
         }
+        if column.wip_limit == Some(0) {
+            return Err(format!("Column {} has a WIP limit of zero", column.name));
+        }
+    }
+    Ok(())
+}
 
-        // Remove from public_templates if applicable
-        if template.is_public {
-            self.public_templates.retain(|&tid| tid != id);
+/// Compute a rank key placing an item between `before` and `after` in a ranked list.
+/// When only one neighbour is given, the other side is that neighbour's current neighbour.
+/// `list_name` describes the list in error messages.
+fn rank_between_neighbours(
+    list: &[(CaptureId, String)],
+    before: Option<CaptureId>,
+    after: Option<CaptureId>,
+    list_name: &str,
+) -> Result<String, String> {
+    let position = |neighbour: CaptureId| {
+        list.iter()
+            .position(|(cid, _)| *cid == neighbour)
+            .ok_or_else(|| format!("Capture {} is not in {}", neighbour, list_name))
+    };
+
+    let (lower, upper) = match (before, after) {
+        (Some(b), Some(a)) => {
+            let (bi, ai) = (position(b)?, position(a)?);
+            if bi >= ai {
+                return Err("The before capture must be ranked above the after capture".to_string());
+            }
+            (Some(list[bi].1.as_str()), Some(list[ai].1.as_str()))
+        }
+        (Some(b), None) => {
+            let bi = position(b)?;
+            (Some(list[bi].1.as_str()), list.get(bi + 1).map(|(_, r)| r.as_str()))
         }
+        (None, Some(a)) => {
+            let ai = position(a)?;
+            (ai.checked_sub(1).map(|i| list[i].1.as_str()), Some(list[ai].1.as_str()))
+        }
+        // No neighbours: place after the last item
+        (None, None) => (list.last().map(|(_, r)| r.as_str()), None),
+    };
 
-        Some(template)
-    }
+    rank_between(lower, upper)
+}
+
+/// Normalize a sprint stream name: trimmed, with blank names mapping to the default stream
+fn normalize_stream(stream: Option<String>) -> Option<String> {
+    stream
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
 }
 
 thread_local! {
@@ -946,6 +6434,60 @@ pub struct StableState {
     /// Discussion state (Story FOS-4.1.2)
     #[serde(default)]
     pub discussion_state: Option<StableDiscussionState>,
+    #[serde(default)]
+    pub boards: Option<Vec<(BoardId, Board)>>,
+    #[serde(default)]
+    pub user_boards: Option<Vec<(Principal, Vec<BoardId>)>>,
+    #[serde(default)]
+    pub board_ranks: Option<Vec<(BoardId, CaptureId, String)>>,
+    #[serde(default)]
+    pub next_board_id: Option<BoardId>,
+    #[serde(default)]
+    pub reminders: Option<Vec<(ReminderId, Reminder)>>,
+    #[serde(default)]
+    pub next_reminder_id: Option<ReminderId>,
+    #[serde(default)]
+    pub notifications: Option<Vec<(NotificationId, Notification)>>,
+    #[serde(default)]
+    pub next_notification_id: Option<NotificationId>,
+    #[serde(default)]
+    pub handles: Option<Vec<(String, UserRef)>>,
+    #[serde(default)]
+    pub capture_assignees: Option<Vec<(CaptureId, Vec<UserRef>)>>,
+    #[serde(default)]
+    pub calendar_feeds: Option<Vec<(String, UserRef)>>,
+    #[serde(default)]
+    pub time_entries: Option<Vec<(TimeEntryId, TimeEntry)>>,
+    #[serde(default)]
+    pub next_time_entry_id: Option<TimeEntryId>,
+    #[serde(default)]
+    pub thread_comments: Option<Vec<(ThreadCommentId, ThreadComment)>>,
+    #[serde(default)]
+    pub next_thread_comment_id: Option<ThreadCommentId>,
+    #[serde(default)]
+    pub activity: Option<Vec<(ActivityId, ActivityRecord)>>,
+    #[serde(default)]
+    pub next_activity_id: Option<ActivityId>,
+    #[serde(default)]
+    pub saved_views: Option<Vec<(SavedViewId, SavedView)>>,
+    #[serde(default)]
+    pub next_saved_view_id: Option<SavedViewId>,
+    #[serde(default)]
+    pub custom_field_definitions: Option<Vec<(CustomFieldId, CustomFieldDefinition)>>,
+    #[serde(default)]
+    pub next_custom_field_id: Option<CustomFieldId>,
+    #[serde(default)]
+    pub document_versions: Option<Vec<(DocumentId, Vec<StoredDocumentVersion>)>>,
+    #[serde(default)]
+    pub entity_links: Option<Vec<(CommentTarget, Vec<EntityLink>)>>,
+    #[serde(default)]
+    pub template_versions: Option<Vec<(TemplateId, Vec<TemplateVersion>)>>,
+    #[serde(default)]
+    pub template_usage: Option<Vec<(TemplateId, u64)>>,
+    #[serde(default)]
+    pub featured_templates: Option<Vec<TemplateId>>,
+    #[serde(default)]
+    pub hidden_templates: Option<Vec<TemplateId>>,
 }
 
 impl From<&State> for StableState {
@@ -979,6 +6521,36 @@ impl From<&State> for StableState {
             public_templates: state.public_templates.clone(),
             next_template_id: state.next_template_id,
             discussion_state,
+            boards: Some(state.boards.iter().map(|(k, v)| (*k, v.clone())).collect()),
+            user_boards: Some(state.user_boards.iter().map(|(k, v)| (*k, v.clone())).collect()),
+            board_ranks: Some(state.board_ranks
+                .iter()
+                .flat_map(|(board_id, ranks)| ranks.iter().map(|(id, rank)| (*board_id, *id, rank.clone())))
+                .collect()),
+            next_board_id: Some(state.next_board_id),
+            reminders: Some(state.reminders.iter().map(|(k, v)| (*k, v.clone())).collect()),
+            next_reminder_id: Some(state.next_reminder_id),
+            notifications: Some(state.notifications.iter().map(|(k, v)| (*k, v.clone())).collect()),
+            next_notification_id: Some(state.next_notification_id),
+            handles: Some(state.handles.iter().map(|(k, v)| (k.clone(), v.clone())).collect()),
+            capture_assignees: Some(state.capture_assignees.iter().map(|(k, v)| (*k, v.clone())).collect()),
+            calendar_feeds: Some(state.calendar_feeds.iter().map(|(k, v)| (k.clone(), v.clone())).collect()),
+            time_entries: Some(state.time_entries.iter().map(|(k, v)| (*k, v.clone())).collect()),
+            next_time_entry_id: Some(state.next_time_entry_id),
+            thread_comments: Some(state.thread_comments.iter().map(|(k, v)| (*k, v.clone())).collect()),
+            next_thread_comment_id: Some(state.next_thread_comment_id),
+            activity: Some(state.activity.iter().map(|(k, v)| (*k, v.clone())).collect()),
+            next_activity_id: Some(state.next_activity_id),
+            saved_views: Some(state.saved_views.iter().map(|(k, v)| (*k, v.clone())).collect()),
+            next_saved_view_id: Some(state.next_saved_view_id),
+            custom_field_definitions: Some(state.custom_field_definitions.iter().map(|(k, v)| (*k, v.clone())).collect()),
+            next_custom_field_id: Some(state.next_custom_field_id),
+            document_versions: Some(state.document_versions.iter().map(|(k, v)| (*k, v.clone())).collect()),
+            entity_links: Some(state.entity_links.iter().map(|(k, v)| (k.clone(), v.clone())).collect()),
+            template_versions: Some(state.template_versions.iter().map(|(k, v)| (*k, v.clone())).collect()),
+            template_usage: Some(state.template_usage.iter().map(|(k, v)| (*k, *v)).collect()),
+            featured_templates: Some(state.featured_templates.iter().copied().collect()),
+            hidden_templates: Some(state.hidden_templates.iter().copied().collect()),
         }
     }
 }
@@ -992,13 +6564,154 @@ impl From<StableState> for State {
             });
         }
 
-        State {
+        let captures: BTreeMap<CaptureId, Capture> = stable.captures.into_iter().collect();
+        let recurrence_queue = captures.values().filter_map(recurrence_queue_key).collect();
+        let mut capture_owners: BTreeMap<CaptureId, UserRef> = captures.values()
+            .filter(|c| c.owner != Principal::anonymous())
+            .map(|c| (c.id, UserRef::Principal(c.owner)))
+            .collect();
+        for (user_id, ids) in &stable.user_id_captures {
+            for id in ids {
+                capture_owners.entry(*id).or_insert_with(|| UserRef::UserId(user_id.clone()));
+            }
+        }
+        let mut start_date_index: BTreeMap<UserRef, BTreeSet<(Timestamp, CaptureId)>> = BTreeMap::new();
+        let mut due_date_index: BTreeMap<UserRef, BTreeSet<(Timestamp, CaptureId)>> = BTreeMap::new();
+        let mut max_date_span: BTreeMap<UserRef, u64> = BTreeMap::new();
+        for capture in captures.values() {
+            let Some(owner) = capture_owners.get(&capture.id) else {
+                continue;
+            };
+            if let Some(start) = capture.fields.start_date {
+                start_date_index.entry(owner.clone()).or_default().insert((start, capture.id));
+            }
+            if let Some(due) = capture.fields.due_date {
+                due_date_index.entry(owner.clone()).or_default().insert((due, capture.id));
+            }
+            if let (Some(start), Some(due)) = (capture.fields.start_date, capture.fields.due_date) {
+                let span = max_date_span.entry(owner.clone()).or_default();
+                *span = (*span).max(due.saturating_sub(start));
+            }
+        }
+
+        let reminders: BTreeMap<ReminderId, Reminder> = stable.reminders.unwrap_or_default().into_iter().collect();
+        let mut capture_reminders: BTreeMap<CaptureId, Vec<ReminderId>> = BTreeMap::new();
+        for reminder in reminders.values() {
+            capture_reminders.entry(reminder.capture_id).or_default().push(reminder.id);
+        }
+        let reminder_queue = reminders.values().filter_map(reminder_queue_key).collect();
+
+        let notifications: BTreeMap<NotificationId, Notification> =
+            stable.notifications.unwrap_or_default().into_iter().collect();
+        let mut user_notifications: BTreeMap<UserRef, Vec<NotificationId>> = BTreeMap::new();
+        for notification in notifications.values() {
+            user_notifications.entry(notification.recipient.clone()).or_default().push(notification.id);
+        }
+
+        let time_entries: BTreeMap<TimeEntryId, TimeEntry> =
+            stable.time_entries.unwrap_or_default().into_iter().collect();
+        let mut capture_time_entries: BTreeMap<CaptureId, Vec<TimeEntryId>> = BTreeMap::new();
+        let mut user_time_entries: BTreeMap<UserRef, Vec<TimeEntryId>> = BTreeMap::new();
+        let mut running_timers: BTreeMap<UserRef, TimeEntryId> = BTreeMap::new();
+        for entry in time_entries.values() {
+            capture_time_entries.entry(entry.capture_id).or_default().push(entry.id);
+            user_time_entries.entry(entry.user.clone()).or_default().push(entry.id);
+            if entry.ended_at.is_none() {
+                running_timers.insert(entry.user.clone(), entry.id);
+            }
+        }
+
+        let handles: BTreeMap<String, UserRef> = stable.handles.unwrap_or_default().into_iter().collect();
+        let user_handles: BTreeMap<UserRef, String> = handles
+            .iter()
+            .map(|(handle, user)| (user.clone(), handle.clone()))
+            .collect();
+        let capture_assignees: BTreeMap<CaptureId, Vec<UserRef>> =
+            stable.capture_assignees.unwrap_or_default().into_iter().collect();
+        let mut assigned_captures: BTreeMap<UserRef, BTreeSet<CaptureId>> = BTreeMap::new();
+        for (capture_id, users) in &capture_assignees {
+            for user in users {
+                assigned_captures.entry(user.clone()).or_default().insert(*capture_id);
+            }
+        }
+
+        let thread_comments: BTreeMap<ThreadCommentId, ThreadComment> =
+            stable.thread_comments.unwrap_or_default().into_iter().collect();
+        let mut target_comments: BTreeMap<CommentTarget, Vec<ThreadCommentId>> = BTreeMap::new();
+        for comment in thread_comments.values() {
+            target_comments.entry(comment.target.clone()).or_default().push(comment.id);
+        }
+        let activity: BTreeMap<ActivityId, ActivityRecord> =
+            stable.activity.unwrap_or_default().into_iter().collect();
+        let mut target_activity: BTreeMap<CommentTarget, Vec<ActivityId>> = BTreeMap::new();
+        for record in activity.values() {
+            target_activity.entry(record.target.clone()).or_default().push(record.id);
+        }
+        let saved_views: BTreeMap<SavedViewId, SavedView> =
+            stable.saved_views.unwrap_or_default().into_iter().collect();
+        let mut user_saved_views: BTreeMap<UserRef, Vec<SavedViewId>> = BTreeMap::new();
+        for view in saved_views.values() {
+            user_saved_views.entry(view.owner.clone()).or_default().push(view.id);
+        }
+        let mut board_ranks: BTreeMap<BoardId, BTreeMap<CaptureId, String>> = BTreeMap::new();
+        for (board_id, id, rank) in stable.board_ranks.unwrap_or_default() {
+            board_ranks.entry(board_id).or_default().insert(id, rank);
+        }
+
+        let mut document_titles: BTreeMap<(Principal, String), BTreeSet<DocumentId>> = BTreeMap::new();
+        for (id, document) in &stable.documents {
+            document_titles.entry((document.owner, document.title.to_lowercase())).or_default().insert(*id);
+        }
+        let entity_links = stable.entity_links;
+        let template_versions = stable.template_versions;
+
+        let mut state = State {
             controllers: stable.controllers,
             auth_service: stable.auth_service,
-            captures: stable.captures.into_iter().collect(),
+            captures,
             user_captures: stable.user_captures.into_iter().collect(),
             user_id_captures: stable.user_id_captures.into_iter().collect(),
             next_capture_id: stable.next_capture_id,
+            recurrence_queue,
+            start_date_index,
+            due_date_index,
+            max_date_span,
+            capture_children: BTreeMap::new(),
+            rollups: BTreeMap::new(),
+            calendar_feeds: stable.calendar_feeds.unwrap_or_default().into_iter().collect(),
+            reminders,
+            capture_reminders,
+            reminder_queue,
+            next_reminder_id: stable.next_reminder_id.unwrap_or(1),
+            notifications,
+            user_notifications,
+            next_notification_id: stable.next_notification_id.unwrap_or(1),
+            handles,
+            user_handles,
+            capture_assignees,
+            assigned_captures,
+            time_entries,
+            capture_time_entries,
+            user_time_entries,
+            running_timers,
+            next_time_entry_id: stable.next_time_entry_id.unwrap_or(1),
+            thread_comments,
+            target_comments,
+            next_thread_comment_id: stable.next_thread_comment_id.unwrap_or(1),
+            activity,
+            target_activity,
+            next_activity_id: stable.next_activity_id.unwrap_or(1),
+            saved_views,
+            user_saved_views,
+            next_saved_view_id: stable.next_saved_view_id.unwrap_or(1),
+            custom_field_definitions: stable.custom_field_definitions.unwrap_or_default().into_iter().collect(),
+            next_custom_field_id: stable.next_custom_field_id.unwrap_or(1),
+            document_versions: stable.document_versions.unwrap_or_default().into_iter().collect(),
+            entity_links: BTreeMap::new(),
+            backlinks: BTreeMap::new(),
+            unresolved_links: BTreeMap::new(),
+            document_uploads: BTreeMap::new(),
+            next_upload_session_id: 1,
             sprints: stable.sprints.into_iter().collect(),
             user_sprints: stable.user_sprints.into_iter().collect(),
             user_id_sprints: stable.user_id_sprints.into_iter().collect(),
@@ -1009,12 +6722,45 @@ impl From<StableState> for State {
             next_workspace_id: stable.next_workspace_id,
             documents: stable.documents.into_iter().collect(),
             workspace_documents: stable.workspace_documents.into_iter().collect(),
+            document_titles,
             next_document_id: stable.next_document_id,
             templates: stable.templates.into_iter().collect(),
             user_templates: stable.user_templates.into_iter().collect(),
             user_id_templates: stable.user_id_templates.into_iter().collect(),
             public_templates: stable.public_templates,
             next_template_id: stable.next_template_id,
+            template_versions: BTreeMap::new(),
+            template_usage: stable.template_usage.unwrap_or_default().into_iter().collect(),
+            featured_templates: stable.featured_templates.unwrap_or_default().into_iter().collect(),
+            hidden_templates: stable.hidden_templates.unwrap_or_default().into_iter().collect(),
+            boards: stable.boards.unwrap_or_default().into_iter().collect(),
+            user_boards: stable.user_boards.unwrap_or_default().into_iter().collect(),
+            board_ranks,
+            next_board_id: stable.next_board_id.unwrap_or(1),
+        };
+        state.rebuild_hierarchy();
+        match entity_links {
+            Some(links) => {
+                for (source, links) in links {
+                    state.insert_links(source, links);
+                }
+            }
+            None => state.reindex_all_links(),
+        }
+        match template_versions {
+            Some(versions) => state.template_versions = versions.into_iter().collect(),
+            // Templates that were public before versioning start at version 1
+            None => {
+                let public: Vec<Template> = state.public_templates
+                    .iter()
+                    .filter_map(|id| state.templates.get(id))
+                    .cloned()
+                    .collect();
+                for template in public {
+                    state.push_template_version(&template, None, template.updated_at);
+                }
+            }
         }
+        state
     }
 }
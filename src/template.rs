@@ -0,0 +1,347 @@
+//! Template variables and rendering
+//!
+//! Template content refers to variables as `{{name}}`; whitespace inside the
+//! braces is ignored. Besides the variables a template declares, the built-ins
+//! `today` (`YYYY-MM-DD`) and `now` (`YYYY-MM-DD HH:MM UTC`) are always
+//! available. Placeholders naming neither are left as written, so content
+//! that happens to contain braces renders unchanged.
+//!
+//! Scaffold entries refer to each other by key. Parents must come before
+//! their children, which keeps the hierarchies acyclic and gives the order
+//! entities are created in.
+
+use std::collections::BTreeMap;
+
+use crate::datetime::{civil_from_days, day_of, format_utc, parse_ymd};
+use crate::types::{
+    DynamicFields, ScaffoldSpec, TemplateVariable, TemplateVariableKind, Timestamp,
+    MAX_SCAFFOLD_ENTITIES, MAX_TEMPLATE_VARIABLES,
+};
+
+/// Variables every template can use without declaring them
+pub const BUILTIN_VARIABLES: [&str; 2] = ["today", "now"];
+
+const MAX_VARIABLE_NAME_LEN: usize = 64;
+const MAX_VARIABLE_VALUE_LEN: usize = 10_000;
+
+/// Check a template's variable declarations, including their defaults
+pub fn validate_variables(variables: &[TemplateVariable]) -> Result<(), String> {
+    if variables.len() > MAX_TEMPLATE_VARIABLES {
+        return Err(format!("Templates cannot declare more than {} variables", MAX_TEMPLATE_VARIABLES));
+    }
+    for (index, variable) in variables.iter().enumerate() {
+        let name = &variable.name;
+        if name.is_empty()
+            || name.len() > MAX_VARIABLE_NAME_LEN
+            || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return Err(format!(
+                "Invalid variable name '{}': use 1-{} letters, digits or underscores",
+                name, MAX_VARIABLE_NAME_LEN
+            ));
+        }
+        if BUILTIN_VARIABLES.contains(&name.as_str()) {
+            return Err(format!("'{}' is a built-in variable", name));
+        }
+        if variables[..index].iter().any(|other| other.name == *name) {
+            return Err(format!("Variable '{}' is declared more than once", name));
+        }
+        if let TemplateVariableKind::Select(options) = &variable.kind {
+            if options.is_empty() {
+                return Err(format!("Select variable '{}' needs at least one option", name));
+            }
+            if options.iter().enumerate().any(|(i, option)| options[..i].contains(option)) {
+                return Err(format!("Select variable '{}' has duplicate options", name));
+            }
+        }
+        if let Some(default) = &variable.default {
+            validate_value(variable, default)?;
+        }
+    }
+    Ok(())
+}
+
+/// Check a value against its variable's kind
+pub fn validate_value(variable: &TemplateVariable, value: &str) -> Result<(), String> {
+    if value.len() > MAX_VARIABLE_VALUE_LEN {
+        return Err(format!("Value for '{}' is too long", variable.name));
+    }
+    let valid = match &variable.kind {
+        TemplateVariableKind::Text => true,
+        TemplateVariableKind::Number => value.trim().parse::<f64>().map(f64::is_finite).unwrap_or(false),
+        TemplateVariableKind::Date => parse_ymd(value.trim()).is_some(),
+        TemplateVariableKind::Boolean => value == "true" || value == "false",
+        TemplateVariableKind::Select(options) => options.iter().any(|option| option == value),
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid value '{}' for {:?} variable '{}'", value, variable.kind, variable.name))
+    }
+}
+
+/// Values for every declared and built-in variable. Provided values override
+/// defaults; optional variables without either render as empty text. All
+/// missing required variables are reported together.
+pub fn resolve_values(
+    variables: &[TemplateVariable],
+    provided: &[(String, String)],
+    now: Timestamp,
+) -> Result<BTreeMap<String, String>, String> {
+    for (index, (name, _)) in provided.iter().enumerate() {
+        if !variables.iter().any(|variable| variable.name == *name) {
+            return Err(format!("Unknown template variable '{}'", name));
+        }
+        if provided[..index].iter().any(|(other, _)| other == name) {
+            return Err(format!("Template variable '{}' is given more than once", name));
+        }
+    }
+
+    let mut values = BTreeMap::new();
+    let mut missing = Vec::new();
+    for variable in variables {
+        let value = provided
+            .iter()
+            .find(|(name, _)| *name == variable.name)
+            .map(|(_, value)| value)
+            .or(variable.default.as_ref());
+        match value {
+            Some(value) => {
+                validate_value(variable, value)?;
+                values.insert(variable.name.clone(), value.clone());
+            }
+            None if variable.required => missing.push(variable.name.as_str()),
+            None => {
+                values.insert(variable.name.clone(), String::new());
+            }
+        }
+    }
+    if !missing.is_empty() {
+        return Err(format!("Missing required template variables: {}", missing.join(", ")));
+    }
+
+    let (year, month, day) = civil_from_days(day_of(now));
+    values.insert("today".to_string(), format!("{:04}-{:02}-{:02}", year, month, day));
+    values.insert("now".to_string(), format_utc(now));
+    Ok(values)
+}
+
+/// Substitute `{{name}}` placeholders that have a value
+pub fn render(content: &str, values: &BTreeMap<String, String>) -> String {
+    let mut rendered = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let value = after
+            .find("}}")
+            .and_then(|end| values.get(after[..end].trim()).map(|value| (value, end)));
+        match value {
+            Some((value, end)) => {
+                rendered.push_str(value);
+                rest = &after[end + 2..];
+            }
+            None => {
+                rendered.push('{');
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// Check the structure of a scaffold: entity count, keys, references between
+/// entries and capture subtypes
+pub fn validate_scaffold(spec: &ScaffoldSpec) -> Result<(), String> {
+    let entities = spec.workspace.iter().count() + spec.documents.len() + spec.sprints.len() + spec.captures.len();
+    if entities == 0 {
+        return Err("Scaffold does not create anything".to_string());
+    }
+    if entities > MAX_SCAFFOLD_ENTITIES {
+        return Err(format!("Scaffolds cannot create more than {} entities", MAX_SCAFFOLD_ENTITIES));
+    }
+
+    let document_keys: Vec<&str> = spec.documents.iter().map(|d| d.key.as_str()).collect();
+    let sprint_keys: Vec<&str> = spec.sprints.iter().map(|s| s.key.as_str()).collect();
+    let capture_keys: Vec<&str> = spec.captures.iter().map(|c| c.key.as_str()).collect();
+    for (kind, keys) in [("document", &document_keys), ("sprint", &sprint_keys), ("capture", &capture_keys)] {
+        for (index, key) in keys.iter().enumerate() {
+            if key.trim().is_empty() {
+                return Err(format!("Scaffold {} keys cannot be empty", kind));
+            }
+            if keys[..index].contains(key) {
+                return Err(format!("Scaffold {} key '{}' is used more than once", kind, key));
+            }
+        }
+    }
+
+    for (index, document) in spec.documents.iter().enumerate() {
+        if let Some(parent) = &document.parent {
+            if !document_keys[..index].contains(&parent.as_str()) {
+                return Err(format!("Parent '{}' of document '{}' must be an earlier document", parent, document.key));
+            }
+        }
+    }
+    for sprint in &spec.sprints {
+        if sprint.duration_days == 0 {
+            return Err(format!("Sprint '{}' must last at least one day", sprint.key));
+        }
+    }
+    for (index, capture) in spec.captures.iter().enumerate() {
+        if let Some(subtype) = &capture.subtype {
+            if subtype.capture_type() != capture.capture_type {
+                return Err(format!(
+                    "Subtype {:?} does not apply to {:?} capture '{}'",
+                    subtype, capture.capture_type, capture.key
+                ));
+            }
+        }
+        if let Some(parent) = &capture.parent {
+            if !capture_keys[..index].contains(&parent.as_str()) {
+                return Err(format!("Parent '{}' of capture '{}' must be an earlier capture", parent, capture.key));
+            }
+        }
+        if let Some(sprint) = &capture.sprint {
+            if !sprint_keys.contains(&sprint.as_str()) {
+                return Err(format!("Capture '{}' refers to unknown sprint '{}'", capture.key, sprint));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Template default fields with variables rendered in labels and custom values
+pub fn render_fields(fields: &DynamicFields, values: &BTreeMap<String, String>) -> DynamicFields {
+    let mut fields = fields.clone();
+    for label in &mut fields.labels {
+        *label = render(label, values);
+    }
+    fields.labels.retain(|label| !label.trim().is_empty());
+    for (_, value) in &mut fields.custom_fields {
+        *value = render(value, values);
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datetime::{days_from_civil, NANOS_PER_DAY};
+    use crate::types::{CaptureSubtype, CaptureType, ProjectSubtype, ScaffoldCapture, ScaffoldDocument};
+
+    fn variable(name: &str, kind: TemplateVariableKind, default: Option<&str>, required: bool) -> TemplateVariable {
+        TemplateVariable {
+            name: name.to_string(),
+            kind,
+            description: None,
+            default: default.map(str::to_string),
+            required,
+        }
+    }
+
+    fn values(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_render_substitutes_known_placeholders() {
+        let mut map = BTreeMap::new();
+        map.insert("project_name".to_string(), "Apollo".to_string());
+        assert_eq!(render("# {{project_name}} / {{ project_name }}", &map), "# Apollo / Apollo");
+        assert_eq!(render("{{unknown}} {{project_name", &map), "{{unknown}} {{project_name");
+        assert_eq!(render("{{{project_name}}}", &map), "{Apollo}");
+    }
+
+    #[test]
+    fn test_resolve_values_applies_defaults_and_builtins() {
+        let variables = vec![
+            variable("project_name", TemplateVariableKind::Text, None, true),
+            variable("budget", TemplateVariableKind::Number, Some("100"), false),
+            variable("owner", TemplateVariableKind::Text, None, false),
+        ];
+        let now = days_from_civil(2024, 3, 5) as u64 * NANOS_PER_DAY;
+        let resolved = resolve_values(&variables, &values(&[("project_name", "Apollo")]), now).unwrap();
+        assert_eq!(resolved["project_name"], "Apollo");
+        assert_eq!(resolved["budget"], "100");
+        assert_eq!(resolved["owner"], "");
+        assert_eq!(resolved["today"], "2024-03-05");
+        assert_eq!(resolved["now"], "2024-03-05 00:00 UTC");
+    }
+
+    #[test]
+    fn test_resolve_values_reports_errors() {
+        let variables = vec![
+            variable("a", TemplateVariableKind::Text, None, true),
+            variable("b", TemplateVariableKind::Date, None, true),
+            variable("c", TemplateVariableKind::Select(vec!["x".into(), "y".into()]), None, false),
+        ];
+        assert_eq!(
+            resolve_values(&variables, &[], 0).unwrap_err(),
+            "Missing required template variables: a, b"
+        );
+        assert!(resolve_values(&variables, &values(&[("a", "1"), ("b", "2024-02-30")]), 0).is_err());
+        assert!(resolve_values(&variables, &values(&[("a", "1"), ("b", "2024-02-29"), ("c", "z")]), 0).is_err());
+        assert!(resolve_values(&variables, &values(&[("a", "1"), ("b", "2024-02-29"), ("d", "")]), 0).is_err());
+        assert!(resolve_values(&variables, &values(&[("a", "1"), ("b", "2024-02-29"), ("c", "y")]), 0).is_ok());
+    }
+
+    #[test]
+    fn test_validate_variables() {
+        assert!(validate_variables(&[variable("ok_1", TemplateVariableKind::Boolean, Some("true"), false)]).is_ok());
+        assert!(validate_variables(&[variable("bad name", TemplateVariableKind::Text, None, false)]).is_err());
+        assert!(validate_variables(&[variable("today", TemplateVariableKind::Text, None, false)]).is_err());
+        assert!(validate_variables(&[variable("n", TemplateVariableKind::Number, Some("many"), false)]).is_err());
+        assert!(validate_variables(&[variable("s", TemplateVariableKind::Select(vec![]), None, false)]).is_err());
+        let twice = variable("x", TemplateVariableKind::Text, None, false);
+        assert!(validate_variables(&[twice.clone(), twice]).is_err());
+    }
+
+    fn capture(key: &str, parent: Option<&str>) -> ScaffoldCapture {
+        ScaffoldCapture {
+            key: key.to_string(),
+            capture_type: CaptureType::Project,
+            subtype: Some(CaptureSubtype::Project(ProjectSubtype::Epic)),
+            title: key.to_string(),
+            content: None,
+            priority: None,
+            estimate: None,
+            labels: vec![],
+            parent: parent.map(str::to_string),
+            sprint: None,
+            start_offset_days: None,
+            due_offset_days: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_scaffold_references() {
+        let mut spec = ScaffoldSpec {
+            workspace: None,
+            documents: vec![ScaffoldDocument {
+                key: "readme".to_string(),
+                title: "Readme".to_string(),
+                content: String::new(),
+                parent: None,
+            }],
+            sprints: vec![],
+            captures: vec![capture("project", None), capture("epic", Some("project"))],
+        };
+        assert!(validate_scaffold(&spec).is_ok());
+
+        spec.captures.swap(0, 1);
+        assert!(validate_scaffold(&spec).unwrap_err().contains("earlier capture"));
+        spec.captures.swap(0, 1);
+
+        spec.captures[1].sprint = Some("sprint-1".to_string());
+        assert!(validate_scaffold(&spec).unwrap_err().contains("unknown sprint"));
+        spec.captures[1].sprint = None;
+
+        spec.captures[1].capture_type = CaptureType::Task;
+        assert!(validate_scaffold(&spec).is_err());
+        spec.captures[1].capture_type = CaptureType::Project;
+
+        spec.documents.push(spec.documents[0].clone());
+        assert!(validate_scaffold(&spec).unwrap_err().contains("more than once"));
+    }
+}
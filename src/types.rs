@@ -1,3 +1,4 @@
+use crate::discussion::types::AuthorType;
 use candid::{CandidType, Principal};
 use serde::{Deserialize, Serialize};
 
@@ -10,6 +11,13 @@ pub type SprintId = u64;
 pub type WorkspaceId = u64;
 pub type DocumentId = u64;
 pub type TemplateId = u64;
+pub type BoardId = u64;
+pub type ReminderId = u64;
+pub type NotificationId = u64;
+pub type ChecklistItemId = u64;
+pub type TimeEntryId = u64;
+pub type ThreadCommentId = u64;
+pub type ActivityId = u64;
 pub type Timestamp = u64;
 
 /// User account - owner principal with optional subaccount
@@ -113,8 +121,27 @@ pub enum ProjectSubtype {
     Campaign,
 }
 
+/// Subtype of a capture; the variant must match the capture type
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub enum CaptureSubtype {
+    Idea(IdeaSubtype),
+    Task(TaskSubtype),
+    Project(ProjectSubtype),
+}
+
+impl CaptureSubtype {
+    /// Capture type this subtype belongs to
+    pub fn capture_type(&self) -> CaptureType {
+        match self {
+            CaptureSubtype::Idea(_) => CaptureType::Idea,
+            CaptureSubtype::Task(_) => CaptureType::Task,
+            CaptureSubtype::Project(_) => CaptureType::Project,
+        }
+    }
+}
+
 /// Dynamic fields that vary by capture type/subtype
-#[derive(Clone, Debug, CandidType, Deserialize, Serialize, Default)]
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, Default, PartialEq)]
 pub struct DynamicFields {
     /// Estimated effort (story points)
     pub estimate: Option<u32>,
@@ -122,7 +149,7 @@ pub struct DynamicFields {
     pub due_date: Option<Timestamp>,
     /// Start date (timestamp)
     pub start_date: Option<Timestamp>,
-    /// Assigned to (user IDs)
+    /// Assigned to (handles, principals or user IDs)
     pub assignees: Vec<String>,
     /// Labels/tags
     pub labels: Vec<String>,
@@ -138,31 +165,134 @@ pub struct DynamicFields {
     pub custom_fields: Vec<(String, String)>,
 }
 
+/// How often a recurring capture repeats
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub enum RecurrenceFrequency {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+/// Day of the week
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+/// Recurrence rule (subset of iCalendar RRULE)
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct RecurrenceRule {
+    pub frequency: RecurrenceFrequency,
+    /// Repeat every `interval` days/weeks/months (minimum 1)
+    pub interval: u32,
+    /// Days of the week to repeat on (weekly rules only)
+    pub by_day: Vec<Weekday>,
+    /// Total number of occurrences in the series
+    pub count: Option<u32>,
+    /// No occurrences are generated after this timestamp
+    pub until: Option<Timestamp>,
+}
+
+/// Recurrence state of a capture that belongs to a series
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct Recurrence {
+    pub rule: RecurrenceRule,
+    /// ID of the first capture in the series
+    pub series_id: CaptureId,
+    /// Start of the series (occurrences are computed from this timestamp)
+    pub dtstart: Timestamp,
+    /// 1-based position of this capture in the series
+    pub occurrence: u32,
+    /// Capture spawned as the next occurrence, once it exists
+    pub next_occurrence_id: Option<CaptureId>,
+}
+
 /// Core capture record
 #[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
 pub struct Capture {
     pub id: CaptureId,
     pub owner: Principal,
     pub capture_type: CaptureType,
+    #[serde(default)]
+    pub subtype: Option<CaptureSubtype>,
     pub title: String,
     pub description: Option<String>,
     pub content: Option<String>,
     pub priority: Priority,
     pub status: CaptureStatus,
     pub fields: DynamicFields,
+    /// Fractional rank key ordering the capture within its backlog or sprint
+    #[serde(default)]
+    pub rank: Option<String>,
+    /// Recurrence rule and series position for recurring captures
+    #[serde(default)]
+    pub recurrence: Option<Recurrence>,
+    /// Ordered sub-steps that don't warrant their own capture
+    #[serde(default)]
+    pub checklist: Option<Checklist>,
+    /// Type changes, oldest first
+    #[serde(default)]
+    pub conversions: Option<Vec<CaptureConversion>>,
+    /// Template version the capture was instantiated from
+    #[serde(default)]
+    pub template: Option<TemplateSource>,
     pub created_at: Timestamp,
     pub updated_at: Timestamp,
 }
 
+/// Maximum items in a capture's checklist
+pub const MAX_CHECKLIST_ITEMS: usize = 100;
+
+/// Single step in a capture's checklist
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct ChecklistItem {
+    pub id: ChecklistItemId,
+    pub text: String,
+    pub checked: bool,
+    pub checked_at: Option<Timestamp>,
+    pub created_at: Timestamp,
+}
+
+/// Ordered checklist with completion counts for listings
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, Default)]
+pub struct Checklist {
+    pub items: Vec<ChecklistItem>,
+    pub completed_count: u32,
+    pub total_count: u32,
+    /// Item IDs are unique within the checklist
+    pub next_item_id: ChecklistItemId,
+}
+
+/// Record of a capture changing type
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct CaptureConversion {
+    pub from_type: CaptureType,
+    pub from_subtype: Option<CaptureSubtype>,
+    pub to_type: CaptureType,
+    pub to_subtype: Option<CaptureSubtype>,
+    /// Fields moved or cleared by the conversion, as (field, previous value)
+    pub remapped_fields: Vec<(String, String)>,
+    pub converted_at: Timestamp,
+}
+
 /// Request to create a new capture
 #[derive(Clone, Debug, CandidType, Deserialize)]
 pub struct CreateCaptureRequest {
     pub capture_type: CaptureType,
+    pub subtype: Option<CaptureSubtype>,
     pub title: String,
     pub description: Option<String>,
     pub content: Option<String>,
     pub priority: Option<Priority>,
     pub fields: Option<DynamicFields>,
+    /// Make the capture recurring (requires a start_date or due_date)
+    pub recurrence: Option<RecurrenceRule>,
 }
 
 /// Request to update a capture
@@ -177,6 +307,69 @@ pub struct UpdateCaptureRequest {
     pub fields: Option<DynamicFields>,
 }
 
+/// Request to change a capture's type (ID, links and history are kept)
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct ConvertCaptureRequest {
+    pub id: CaptureId,
+    pub capture_type: CaptureType,
+    pub subtype: Option<CaptureSubtype>,
+}
+
+/// Maximum tasks created when promoting an idea
+pub const MAX_SCAFFOLD_TASKS: usize = 50;
+
+/// Request to promote an idea to a project with child tasks
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct PromoteIdeaRequest {
+    pub idea_id: CaptureId,
+    pub subtype: Option<ProjectSubtype>,
+    /// Capture template supplying default fields, content and the task scaffold
+    pub template_id: Option<TemplateId>,
+    /// Task titles; defaults to the list items in the template content
+    pub task_titles: Option<Vec<String>>,
+    /// Values for the template's variables by name
+    pub values: Option<Vec<(String, String)>>,
+}
+
+/// Result of promoting an idea
+#[derive(Clone, Debug, CandidType, Serialize)]
+pub struct PromoteIdeaResult {
+    pub project: Capture,
+    pub tasks: Vec<Capture>,
+}
+
+// =============================================================================
+// Hierarchy Types (roll-ups through fields.parent_id)
+// =============================================================================
+
+/// Maximum captures returned by a tree query
+pub const MAX_TREE_NODES: usize = 1000;
+
+/// Derived totals over a capture's descendants (cancelled captures are excluded).
+/// Points come from leaf captures; a child with children contributes its own roll-up.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, Default, PartialEq)]
+pub struct Rollup {
+    pub descendant_count: u64,
+    pub completed_count: u64,
+    pub total_estimate: u64,
+    pub remaining_estimate: u64,
+    /// Completed descendants as a percentage of all descendants
+    pub percent_complete_by_count: u32,
+    /// Completed points as a percentage of total points
+    pub percent_complete_by_points: u32,
+    pub earliest_start: Option<Timestamp>,
+    pub latest_due: Option<Timestamp>,
+}
+
+/// Capture in a tree query, with its depth below the root and its roll-up
+#[derive(Clone, Debug, CandidType, Serialize)]
+pub struct CaptureTreeNode {
+    pub capture: Capture,
+    pub depth: u32,
+    /// None for captures without children
+    pub rollup: Option<Rollup>,
+}
+
 // =============================================================================
 // Sprint Types
 // =============================================================================
@@ -209,6 +402,9 @@ pub struct Sprint {
     pub end_date: Timestamp,
     pub capacity: Option<u32>,
     pub capture_ids: Vec<CaptureId>,
+    /// Named stream for running parallel sprint tracks (None = default stream)
+    #[serde(default)]
+    pub stream: Option<String>,
     pub created_at: Timestamp,
     pub updated_at: Timestamp,
 }
@@ -221,6 +417,7 @@ pub struct CreateSprintRequest {
     pub start_date: Timestamp,
     pub end_date: Timestamp,
     pub capacity: Option<u32>,
+    pub stream: Option<String>,
 }
 
 /// Request to update a sprint
@@ -232,6 +429,93 @@ pub struct UpdateSprintRequest {
     pub start_date: Option<Timestamp>,
     pub end_date: Option<Timestamp>,
     pub capacity: Option<u32>,
+    pub stream: Option<String>,
+}
+
+// =============================================================================
+// Board Types (Kanban views over captures)
+// =============================================================================
+
+/// Maximum number of columns on a board
+pub const MAX_BOARD_COLUMNS: usize = 20;
+
+/// Source of a board's cards
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub enum BoardScope {
+    /// Captures with `fields.workspace_id` set to this workspace
+    Workspace(WorkspaceId),
+    /// Captures assigned to this sprint
+    Sprint(SprintId),
+}
+
+/// Board column - a card belongs to the first column whose statuses and labels it matches
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct BoardColumn {
+    pub name: String,
+    /// Statuses shown in this column (the first is applied when a card is moved here)
+    pub statuses: Vec<CaptureStatus>,
+    /// Labels shown in this column (the first is applied when a card is moved here)
+    pub labels: Vec<String>,
+    /// Maximum number of cards allowed in the column
+    pub wip_limit: Option<u32>,
+}
+
+/// Board record
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct Board {
+    pub id: BoardId,
+    pub owner: Principal,
+    pub name: String,
+    pub scope: BoardScope,
+    pub columns: Vec<BoardColumn>,
+    pub created_at: Timestamp,
+    pub updated_at: Timestamp,
+}
+
+/// Request to create a board
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct CreateBoardRequest {
+    pub name: String,
+    pub scope: BoardScope,
+    pub columns: Vec<BoardColumn>,
+}
+
+/// Request to update a board
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct UpdateBoardRequest {
+    pub name: Option<String>,
+    pub columns: Option<Vec<BoardColumn>>,
+}
+
+/// Rendered board column with its ranked cards
+#[derive(Clone, Debug, CandidType, Serialize)]
+pub struct BoardColumnView {
+    pub index: u32,
+    pub name: String,
+    pub wip_limit: Option<u32>,
+    pub card_count: u64,
+    pub over_limit: bool,
+    pub cards: Vec<Capture>,
+}
+
+/// Rendered board
+#[derive(Clone, Debug, CandidType, Serialize)]
+pub struct BoardView {
+    pub board: Board,
+    pub columns: Vec<BoardColumnView>,
+}
+
+/// Request to move a card to a column and position
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct MoveCardRequest {
+    pub board_id: BoardId,
+    pub capture_id: CaptureId,
+    /// Target column index
+    pub column: u32,
+    /// Card that should end up directly above the moved card
+    pub before: Option<CaptureId>,
+    /// Card that should end up directly below the moved card
+    pub after: Option<CaptureId>,
 }
 
 // =============================================================================
@@ -263,10 +547,31 @@ pub struct Document {
     pub is_template: bool,
     pub template_id: Option<TemplateId>,
     pub parent_id: Option<DocumentId>,
+    /// Fractional rank key ordering the document among its siblings
+    #[serde(default)]
+    pub rank: Option<String>,
+    /// Published version of `template_id` the document was created from
+    #[serde(default)]
+    pub template_version: Option<TemplateVersionNumber>,
     pub created_at: Timestamp,
     pub updated_at: Timestamp,
 }
 
+/// Document in a workspace's tree, listed in depth-first order
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct DocumentTreeEntry {
+    pub id: DocumentId,
+    /// None for top-level documents, including those whose parent is missing
+    pub parent_id: Option<DocumentId>,
+    pub title: String,
+    pub depth: u32,
+    pub child_count: u32,
+    pub updated_at: Timestamp,
+}
+
+/// Maximum documents copied by one duplicate
+pub const MAX_DUPLICATE_DOCUMENTS: usize = 200;
+
 /// Request to create a workspace
 #[derive(Clone, Debug, CandidType, Deserialize)]
 pub struct CreateWorkspaceRequest {
@@ -305,6 +610,8 @@ pub struct CreateDocumentRequest {
 pub enum TemplateType {
     Capture,
     Document,
+    /// A bundle of workspace, documents, sprints and captures
+    Scaffold,
 }
 
 /// Template record
@@ -319,10 +626,226 @@ pub struct Template {
     pub capture_type: Option<CaptureType>,
     pub default_fields: Option<DynamicFields>,
     pub is_public: bool,
+    /// Variables referenced as `{{name}}` in the content
+    #[serde(default)]
+    pub variables: Option<Vec<TemplateVariable>>,
+    /// Entities created by scaffold templates
+    #[serde(default)]
+    pub scaffold: Option<ScaffoldSpec>,
+    /// Latest published version; other users see and instantiate that
+    /// version while the owner edits the draft
+    #[serde(default)]
+    pub latest_version: Option<TemplateVersionNumber>,
+    /// Template this one was forked from
+    #[serde(default)]
+    pub forked_from: Option<TemplateSource>,
+    /// Gallery category; with the tags, editable without publishing
+    #[serde(default)]
+    pub category: Option<TemplateCategory>,
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
     pub created_at: Timestamp,
     pub updated_at: Timestamp,
 }
 
+/// Gallery category of a template
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub enum TemplateCategory {
+    Engineering,
+    Product,
+    Design,
+    Marketing,
+    Operations,
+    Meetings,
+    Personal,
+    Other,
+}
+
+/// Maximum tags per template
+pub const MAX_TEMPLATE_TAGS: usize = 10;
+
+/// Maximum length of a template tag
+pub const MAX_TEMPLATE_TAG_LEN: usize = 32;
+
+/// Gallery sort order
+#[derive(Clone, Debug, CandidType, Deserialize, PartialEq)]
+pub enum TemplateSort {
+    /// Most instantiated first
+    Usage,
+    /// Most recently published first
+    Recent,
+}
+
+/// Public template gallery search. Text matches name and description
+/// case-insensitively, and every word must match.
+#[derive(Clone, Debug, CandidType, Deserialize, Default)]
+pub struct TemplateGalleryQuery {
+    pub text: Option<String>,
+    pub category: Option<TemplateCategory>,
+    pub template_type: Option<TemplateType>,
+    /// Templates must carry all of these tags
+    pub tags: Option<Vec<String>>,
+    pub featured_only: Option<bool>,
+    /// Defaults to usage
+    pub sort: Option<TemplateSort>,
+}
+
+/// Public template as listed in the gallery
+#[derive(Clone, Debug, CandidType, Serialize)]
+pub struct TemplateGalleryEntry {
+    /// Latest published version
+    pub template: Template,
+    pub usage_count: u64,
+    pub featured: bool,
+    pub published_at: Timestamp,
+}
+
+/// Controller moderation of a public template; None leaves a flag unchanged
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct ModerateTemplateRequest {
+    pub featured: Option<bool>,
+    /// Hidden templates are only visible to their owner
+    pub hidden: Option<bool>,
+}
+
+/// Published template version number, starting at 1
+pub type TemplateVersionNumber = u32;
+
+/// Maximum number of published versions per template
+pub const MAX_TEMPLATE_VERSIONS: usize = 200;
+
+/// Template version an entity or fork was made from
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub struct TemplateSource {
+    pub template_id: TemplateId,
+    /// Published version used; None for the owner's unpublished draft
+    pub version: Option<TemplateVersionNumber>,
+}
+
+/// Immutable snapshot of a template taken when it is published
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct TemplateVersion {
+    pub template_id: TemplateId,
+    pub version: TemplateVersionNumber,
+    pub template_type: TemplateType,
+    pub name: String,
+    pub description: Option<String>,
+    pub content: String,
+    pub capture_type: Option<CaptureType>,
+    pub default_fields: Option<DynamicFields>,
+    pub variables: Option<Vec<TemplateVariable>>,
+    pub scaffold: Option<ScaffoldSpec>,
+    /// What changed since the previous version
+    pub changelog: Option<String>,
+    pub published_at: Timestamp,
+}
+
+/// Maximum number of variables a template can declare
+pub const MAX_TEMPLATE_VARIABLES: usize = 50;
+
+/// Kind of value a template variable accepts
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub enum TemplateVariableKind {
+    Text,
+    /// A finite decimal number
+    Number,
+    /// A `YYYY-MM-DD` date
+    Date,
+    /// `true` or `false`
+    Boolean,
+    /// One of the listed options
+    Select(Vec<String>),
+}
+
+/// Variable declared by a template
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub struct TemplateVariable {
+    pub name: String,
+    pub kind: TemplateVariableKind,
+    pub description: Option<String>,
+    /// Value used when instantiation does not provide one
+    pub default: Option<String>,
+    /// Instantiation fails when a required variable has no value or default
+    pub required: bool,
+}
+
+/// Maximum number of entities a scaffold template can create
+pub const MAX_SCAFFOLD_ENTITIES: usize = 200;
+
+/// Workspace created by a scaffold template
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub struct ScaffoldWorkspace {
+    pub name: String,
+    pub description: Option<String>,
+    pub icon: Option<String>,
+}
+
+/// Document created by a scaffold template
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub struct ScaffoldDocument {
+    /// Key other scaffold entries use to refer to this document
+    pub key: String,
+    pub title: String,
+    pub content: String,
+    /// Key of an earlier document to nest under
+    pub parent: Option<String>,
+}
+
+/// Sprint created by a scaffold template
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub struct ScaffoldSprint {
+    pub key: String,
+    pub name: String,
+    pub goal: Option<String>,
+    /// Days after the anchor date the sprint starts
+    pub start_offset_days: u32,
+    pub duration_days: u32,
+    pub capacity: Option<u32>,
+    pub stream: Option<String>,
+}
+
+/// Capture created by a scaffold template
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub struct ScaffoldCapture {
+    pub key: String,
+    pub capture_type: CaptureType,
+    pub subtype: Option<CaptureSubtype>,
+    pub title: String,
+    pub content: Option<String>,
+    pub priority: Option<Priority>,
+    pub estimate: Option<u32>,
+    pub labels: Vec<String>,
+    /// Key of an earlier capture to nest under
+    pub parent: Option<String>,
+    /// Key of the sprint the capture is planned into
+    pub sprint: Option<String>,
+    /// Days after the anchor date the capture starts
+    pub start_offset_days: Option<u32>,
+    /// Days after the anchor date the capture is due
+    pub due_offset_days: Option<u32>,
+}
+
+/// Entities a scaffold template creates, in creation order. Titles, names
+/// and contents may use the template's variables.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub struct ScaffoldSpec {
+    /// Workspace to create; without one, instantiation targets an existing
+    /// workspace
+    pub workspace: Option<ScaffoldWorkspace>,
+    pub documents: Vec<ScaffoldDocument>,
+    pub sprints: Vec<ScaffoldSprint>,
+    pub captures: Vec<ScaffoldCapture>,
+}
+
+/// IDs of the entities created from a scaffold template, by scaffold key
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct ScaffoldInstance {
+    pub workspace_id: Option<WorkspaceId>,
+    pub documents: Vec<(String, DocumentId)>,
+    pub sprints: Vec<(String, SprintId)>,
+    pub captures: Vec<(String, CaptureId)>,
+}
+
 /// Request to create a template
 #[derive(Clone, Debug, CandidType, Deserialize)]
 pub struct CreateTemplateRequest {
@@ -333,6 +856,10 @@ pub struct CreateTemplateRequest {
     pub capture_type: Option<CaptureType>,
     pub default_fields: Option<DynamicFields>,
     pub is_public: Option<bool>,
+    pub variables: Option<Vec<TemplateVariable>>,
+    pub scaffold: Option<ScaffoldSpec>,
+    pub category: Option<TemplateCategory>,
+    pub tags: Option<Vec<String>>,
 }
 
 /// Request to update a template
@@ -344,6 +871,359 @@ pub struct UpdateTemplateRequest {
     pub capture_type: Option<CaptureType>,
     pub default_fields: Option<DynamicFields>,
     pub is_public: Option<bool>,
+    pub variables: Option<Vec<TemplateVariable>>,
+    pub scaffold: Option<ScaffoldSpec>,
+    pub category: Option<TemplateCategory>,
+    pub tags: Option<Vec<String>>,
+}
+
+/// Request to create a document or capture from a template
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct InstantiateTemplateRequest {
+    pub template_id: TemplateId,
+    /// Variable values by name
+    pub values: Vec<(String, String)>,
+    /// Title of the new entity; defaults to the template name. Variables are
+    /// rendered in the title as well. Scaffolds use it as the workspace name.
+    pub title: Option<String>,
+    /// Workspace for document templates and scaffolds without a workspace
+    pub workspace_id: Option<WorkspaceId>,
+    /// Parent document for document templates
+    pub parent_id: Option<DocumentId>,
+    /// Fields for capture templates; the template's default fields fill in
+    /// whatever these leave empty
+    pub fields: Option<DynamicFields>,
+    /// Date scaffold day offsets count from; defaults to now
+    pub anchor_date: Option<Timestamp>,
+    /// Published version to use; defaults to the draft for the owner and the
+    /// latest published version for everyone else
+    pub version: Option<TemplateVersionNumber>,
+}
+
+/// Entity created from a template
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub enum TemplateInstance {
+    Document(Document),
+    Capture(Box<Capture>),
+    Scaffold(ScaffoldInstance),
+}
+
+// =============================================================================
+// Calendar Types
+// =============================================================================
+
+/// Longest range accepted by calendar queries (366 days, in nanoseconds)
+pub const MAX_CALENDAR_RANGE: u64 = 366 * 86_400 * 1_000_000_000;
+
+/// Time range for calendar queries (start inclusive, end exclusive)
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct CalendarRange {
+    pub start: Timestamp,
+    pub end: Timestamp,
+}
+
+/// Calendar entry - a dated capture, or a projected future occurrence of a recurring one
+#[derive(Clone, Debug, CandidType, Serialize)]
+pub struct CalendarEntry {
+    pub capture: Capture,
+    /// When the entry begins (start_date, else due_date, shifted for projected occurrences)
+    pub start: Timestamp,
+    /// When the entry ends (due_date, else the same as `start`)
+    pub end: Timestamp,
+    /// True for occurrences of a recurring series that have not been created yet
+    pub projected: bool,
+}
+
+// =============================================================================
+// Dashboard Types
+// =============================================================================
+
+/// Maximum captures listed per dashboard section (counts cover all matches)
+pub const DASHBOARD_LIST_LIMIT: usize = 20;
+
+/// Capture counts per priority
+#[derive(Clone, Debug, CandidType, Serialize, Default)]
+pub struct PriorityBreakdown {
+    pub critical: u64,
+    pub high: u64,
+    pub medium: u64,
+    pub low: u64,
+}
+
+/// Dashboard section - total count, per-priority counts and the first captures
+#[derive(Clone, Debug, CandidType, Serialize, Default)]
+pub struct DashboardSection {
+    pub count: u64,
+    pub by_priority: PriorityBreakdown,
+    pub items: Vec<Capture>,
+}
+
+/// Progress summary of an active sprint
+#[derive(Clone, Debug, CandidType, Serialize)]
+pub struct SprintSummary {
+    pub sprint_id: SprintId,
+    pub name: String,
+    pub stream: Option<String>,
+    pub start_date: Timestamp,
+    pub end_date: Timestamp,
+    pub total_captures: u64,
+    pub completed_captures: u64,
+    pub total_estimate: u64,
+    pub completed_estimate: u64,
+    /// Whole days until the sprint ends (0 once it has ended)
+    pub days_remaining: u64,
+}
+
+/// Home screen dashboard. Day boundaries are UTC; "this week" runs from
+/// tomorrow to the end of Sunday.
+#[derive(Clone, Debug, CandidType, Serialize)]
+pub struct Dashboard {
+    /// Reference time the dashboard was computed for
+    pub now: Timestamp,
+    pub overdue: DashboardSection,
+    pub due_today: DashboardSection,
+    pub due_this_week: DashboardSection,
+    pub in_progress: DashboardSection,
+    pub blocked: DashboardSection,
+    pub active_sprints: Vec<SprintSummary>,
+}
+
+// =============================================================================
+// HTTP Gateway Types
+// =============================================================================
+
+/// HTTP request from the IC HTTP gateway
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct HttpRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+/// HTTP response to the IC HTTP gateway
+#[derive(Clone, Debug, CandidType, Serialize)]
+pub struct HttpResponse {
+    pub status_code: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+// =============================================================================
+// Reminder & Notification Types
+// =============================================================================
+
+/// Maximum notifications kept per inbox (oldest are dropped first)
+pub const MAX_NOTIFICATIONS_PER_USER: usize = 500;
+
+/// A user identified by principal or by auth-service user_id (session-based auth)
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum UserRef {
+    Principal(Principal),
+    UserId(String),
+}
+
+/// When a reminder fires
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub enum ReminderTrigger {
+    /// At an absolute time
+    At(Timestamp),
+    /// This many nanoseconds before the capture's due_date (follows due_date changes)
+    BeforeDue(u64),
+}
+
+/// Reminder attached to a capture
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct Reminder {
+    pub id: ReminderId,
+    pub capture_id: CaptureId,
+    pub trigger: ReminderTrigger,
+    /// Resolved fire time (None while a BeforeDue reminder's capture has no due_date)
+    pub fire_at: Option<Timestamp>,
+    pub fired: bool,
+    pub created_at: Timestamp,
+}
+
+/// Request to create a reminder
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct CreateReminderRequest {
+    pub capture_id: CaptureId,
+    pub trigger: ReminderTrigger,
+}
+
+/// Kind of notification
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub enum NotificationKind {
+    Reminder,
+    /// The recipient was added to a capture's assignees
+    Assigned,
+    /// The recipient was @mentioned in a capture, document or comment
+    Mention,
+}
+
+/// Notification in a user's inbox
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct Notification {
+    pub id: NotificationId,
+    pub recipient: UserRef,
+    pub kind: NotificationKind,
+    pub capture_id: Option<CaptureId>,
+    #[serde(default)]
+    pub document_id: Option<DocumentId>,
+    pub title: String,
+    pub body: Option<String>,
+    pub read: bool,
+    pub created_at: Timestamp,
+}
+
+// =============================================================================
+// Mention & Assignment Types
+// =============================================================================
+
+/// Handle length limits (handles are lowercase `a-z`, `0-9`, `_` and `-`)
+pub const MIN_HANDLE_LEN: usize = 3;
+pub const MAX_HANDLE_LEN: usize = 32;
+
+// =============================================================================
+// Comment & Activity Types
+// =============================================================================
+
+/// Maximum activity records kept per capture or document (oldest are dropped)
+pub const MAX_ACTIVITY_PER_TARGET: usize = 500;
+
+/// Capture or document that a comment thread and activity stream belong to
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CommentTarget {
+    Capture(CaptureId),
+    Document(DocumentId),
+}
+
+/// Comment on a capture or document. Same model as discussion comments:
+/// append-only, retracted rather than deleted, agent comments tagged.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct ThreadComment {
+    pub id: ThreadCommentId,
+    pub target: CommentTarget,
+    pub author: UserRef,
+    pub author_type: AuthorType,
+    pub content: String,
+    pub created_at: Timestamp,
+    /// Retracted comments keep their content but are marked
+    pub is_retracted: bool,
+    pub retracted_at: Option<Timestamp>,
+}
+
+/// Request to comment on a capture or document
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct AddThreadCommentRequest {
+    pub target: CommentTarget,
+    pub content: String,
+    pub author_type: AuthorType,
+}
+
+/// Something that happened to a capture or document
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub enum ActivityEvent {
+    Created,
+    /// Long text fields (description, content) are recorded without values
+    FieldChanged {
+        field: String,
+        old_value: Option<String>,
+        new_value: Option<String>,
+    },
+}
+
+/// Recorded activity on a capture or document
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct ActivityRecord {
+    pub id: ActivityId,
+    pub target: CommentTarget,
+    pub event: ActivityEvent,
+    pub created_at: Timestamp,
+}
+
+/// Entry in a merged activity stream
+#[derive(Clone, Debug, CandidType, Serialize)]
+pub enum ActivityItem {
+    Comment(ThreadComment),
+    Activity(ActivityRecord),
+}
+
+// =============================================================================
+// Time Tracking Types
+// =============================================================================
+
+/// Longest accepted manual time entry (24 hours, in nanoseconds)
+pub const MAX_TIME_ENTRY_DURATION: u64 = 86_400 * 1_000_000_000;
+
+/// Time logged by a user against a capture. Running timers have no `ended_at`.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct TimeEntry {
+    pub id: TimeEntryId,
+    pub capture_id: CaptureId,
+    pub user: UserRef,
+    pub started_at: Timestamp,
+    pub ended_at: Option<Timestamp>,
+    pub note: Option<String>,
+    /// Entered by hand rather than with a timer
+    pub manual: bool,
+    pub created_at: Timestamp,
+}
+
+/// Request to log time manually
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct CreateTimeEntryRequest {
+    pub capture_id: CaptureId,
+    pub started_at: Timestamp,
+    pub ended_at: Timestamp,
+    pub note: Option<String>,
+}
+
+/// Time logged against one capture (durations in nanoseconds)
+#[derive(Clone, Debug, CandidType, Serialize)]
+pub struct CaptureTime {
+    pub capture_id: CaptureId,
+    pub duration: u64,
+}
+
+/// Time logged by one user
+#[derive(Clone, Debug, CandidType, Serialize)]
+pub struct UserTime {
+    pub user: UserRef,
+    pub duration: u64,
+}
+
+/// Aggregated time entries. Running timers count up to now; range reports
+/// only count the part of each entry inside the range.
+#[derive(Clone, Debug, CandidType, Serialize)]
+pub struct TimeReport {
+    pub total_duration: u64,
+    pub by_capture: Vec<CaptureTime>,
+    pub by_user: Vec<UserTime>,
+    /// Entries, newest first
+    pub entries: Vec<TimeEntry>,
+}
+
+/// Points completed in a sprint against the time logged on them
+#[derive(Clone, Debug, CandidType, Serialize)]
+pub struct SprintAccuracy {
+    pub sprint_id: SprintId,
+    pub name: String,
+    pub end_date: Timestamp,
+    pub completed_points: u64,
+    pub logged_duration: u64,
+    pub duration_per_point: Option<u64>,
+    /// How far this sprint's time per point is from the overall average, in percent
+    pub deviation_percent: Option<i64>,
+}
+
+/// Estimate accuracy over an owner's completed sprints, oldest first
+#[derive(Clone, Debug, CandidType, Serialize)]
+pub struct EstimateAccuracyReport {
+    pub sprints: Vec<SprintAccuracy>,
+    pub completed_points: u64,
+    pub logged_duration: u64,
+    pub duration_per_point: Option<u64>,
 }
 
 // =============================================================================
@@ -351,14 +1231,77 @@ pub struct UpdateTemplateRequest {
 // =============================================================================
 
 /// Filter for querying captures
-#[derive(Clone, Debug, CandidType, Deserialize, Default)]
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, Default)]
 pub struct CaptureFilter {
     pub capture_type: Option<CaptureType>,
     pub status: Option<CaptureStatus>,
     pub priority: Option<Priority>,
     pub sprint_id: Option<SprintId>,
     pub workspace_id: Option<WorkspaceId>,
+    /// Captures must carry all of these labels
     pub labels: Option<Vec<String>>,
+    /// Date-relative conditions, evaluated at query time
+    #[serde(default)]
+    pub dates: Option<Vec<DateCondition>>,
+    /// Conditions on custom field values, compared according to the field's kind
+    #[serde(default)]
+    pub custom_fields: Option<Vec<CustomFieldCondition>>,
+}
+
+/// Comparison operator for filters and query terms
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub enum FieldComparison {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// Condition on a custom field value. Multi-select fields only support `Eq`,
+/// which matches when the option is selected.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub struct CustomFieldCondition {
+    pub key: String,
+    pub comparison: FieldComparison,
+    pub value: String,
+}
+
+/// Capture date that a date condition applies to
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub enum DateField {
+    Due,
+    Start,
+    Created,
+    Updated,
+}
+
+/// Time window relative to the moment a filter is evaluated
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub enum RelativeWindow {
+    /// Within the last n nanoseconds ("updated in last 24h")
+    Last(u64),
+    /// Within the next n nanoseconds ("due within 7 days"); already passed dates do not match
+    Next(u64),
+    /// Any time before now ("overdue")
+    Past,
+    /// The current UTC day
+    Today,
+}
+
+/// Date-relative filter condition; captures without the date never match
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub struct DateCondition {
+    pub field: DateField,
+    pub window: RelativeWindow,
+}
+
+/// Capture query parse error
+#[derive(Clone, Debug, CandidType, Deserialize, PartialEq)]
+pub struct QueryError {
+    /// Byte offset of the offending input
+    pub position: u32,
+    pub message: String,
 }
 
 /// Pagination params
@@ -385,3 +1328,375 @@ pub struct PaginatedResponse<T> {
     pub offset: u64,
     pub limit: u64,
 }
+
+// =============================================================================
+// Saved View Types
+// =============================================================================
+
+pub type SavedViewId = u64;
+
+/// Maximum saved views owned by one user
+pub const MAX_SAVED_VIEWS_PER_USER: usize = 100;
+
+/// Maximum columns stored on a saved view
+pub const MAX_VIEW_COLUMNS: usize = 50;
+
+/// Field a saved view sorts by
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub enum ViewSortField {
+    Rank,
+    Priority,
+    Status,
+    Title,
+    DueDate,
+    StartDate,
+    CreatedAt,
+    UpdatedAt,
+    /// Custom field key, compared according to the field's kind
+    Custom(String),
+}
+
+/// Sort order of a saved view (captures without the sorted date come last)
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub struct ViewSort {
+    pub field: ViewSortField,
+    pub descending: bool,
+}
+
+/// Grouping of a saved view's results
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub enum ViewGrouping {
+    Status,
+    Priority,
+    CaptureType,
+    /// One group per label (captures with several labels appear in each)
+    Label,
+    Sprint,
+}
+
+/// Named capture query saved by a user
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct SavedView {
+    pub id: SavedViewId,
+    pub owner: UserRef,
+    pub name: String,
+    pub filter: CaptureFilter,
+    pub sort: Option<ViewSort>,
+    pub group_by: Option<ViewGrouping>,
+    /// Column keys shown by the UI, in order
+    pub columns: Vec<String>,
+    /// Workspace the view is pinned to (results are limited to it)
+    pub workspace_id: Option<WorkspaceId>,
+    /// Users the view is shared with; they see the owner's matching captures
+    pub shared_with: Vec<UserRef>,
+    pub created_at: Timestamp,
+    pub updated_at: Timestamp,
+}
+
+/// Request to create a saved view
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct CreateSavedViewRequest {
+    pub name: String,
+    pub filter: Option<CaptureFilter>,
+    pub sort: Option<ViewSort>,
+    pub group_by: Option<ViewGrouping>,
+    pub columns: Option<Vec<String>>,
+    pub workspace_id: Option<WorkspaceId>,
+    /// Handles (`@handle`) or principals to share with
+    pub shared_with: Option<Vec<String>>,
+}
+
+/// Request to update a saved view
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct UpdateSavedViewRequest {
+    pub id: SavedViewId,
+    pub name: Option<String>,
+    pub filter: Option<CaptureFilter>,
+    pub sort: Option<ViewSort>,
+    pub group_by: Option<ViewGrouping>,
+    pub columns: Option<Vec<String>>,
+    pub workspace_id: Option<WorkspaceId>,
+    pub shared_with: Option<Vec<String>>,
+}
+
+/// Group of captures in a saved view result
+#[derive(Clone, Debug, CandidType, Serialize)]
+pub struct ViewGroup {
+    /// Group value (e.g. "InProgress", a label or a sprint ID); empty when ungrouped
+    pub key: String,
+    pub captures: Vec<Capture>,
+}
+
+/// Result of running a saved view; pagination applies before grouping
+#[derive(Clone, Debug, CandidType, Serialize)]
+pub struct SavedViewResult {
+    pub view: SavedView,
+    pub groups: Vec<ViewGroup>,
+    pub total: u64,
+    pub offset: u64,
+    pub limit: u64,
+}
+
+// =============================================================================
+// Custom Field Types
+// =============================================================================
+
+pub type CustomFieldId = u64;
+
+/// Maximum custom field definitions per owner or workspace
+pub const MAX_CUSTOM_FIELDS_PER_SCOPE: usize = 50;
+
+/// Maximum length of a custom field value
+pub const MAX_CUSTOM_FIELD_VALUE_LEN: usize = 1000;
+
+/// Type of a custom field. Values are stored as text in
+/// `DynamicFields.custom_fields` in the format noted on each kind.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub enum CustomFieldKind {
+    Text,
+    /// Decimal number, e.g. "3.5"
+    Number,
+    /// `YYYY-MM-DD`
+    Date,
+    /// One of the options
+    SingleSelect(Vec<String>),
+    /// Comma-separated options
+    MultiSelect(Vec<String>),
+    /// Principal in text form
+    Principal,
+    /// `http://` or `https://` URL
+    Url,
+}
+
+/// Custom field definition, scoped to an owner or to one of the owner's workspaces.
+/// Workspace definitions override owner definitions with the same key.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct CustomFieldDefinition {
+    pub id: CustomFieldId,
+    pub owner: UserRef,
+    pub workspace_id: Option<WorkspaceId>,
+    /// Key used in `DynamicFields.custom_fields` (lowercase `a-z`, `0-9`, `_` and `-`)
+    pub key: String,
+    pub name: String,
+    pub kind: CustomFieldKind,
+    pub required: bool,
+    pub created_at: Timestamp,
+    pub updated_at: Timestamp,
+}
+
+/// Request to define a custom field
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct CreateCustomFieldRequest {
+    pub key: String,
+    pub name: String,
+    pub kind: CustomFieldKind,
+    pub required: Option<bool>,
+    pub workspace_id: Option<WorkspaceId>,
+}
+
+/// Request to update a custom field definition (existing values are not re-validated)
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct UpdateCustomFieldRequest {
+    pub id: CustomFieldId,
+    pub name: Option<String>,
+    pub kind: Option<CustomFieldKind>,
+    pub required: Option<bool>,
+}
+
+// =============================================================================
+// Document Version Types
+// =============================================================================
+
+pub type DocumentVersionNumber = u32;
+
+/// Versions kept per document; the oldest are dropped first
+pub const MAX_DOCUMENT_VERSIONS: usize = 500;
+
+/// Every Nth version keeps its full content so rebuilding an old version
+/// never replays more than N deltas
+pub const VERSION_SNAPSHOT_INTERVAL: DocumentVersionNumber = 20;
+
+/// Line edit in a version delta, applied in order to the base text's lines
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub enum DeltaOp {
+    /// Copy the next N base lines
+    Copy(u32),
+    /// Skip the next N base lines
+    Skip(u32),
+    /// Insert text (whole lines)
+    Insert(String),
+}
+
+/// How a version's content is stored
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub enum VersionContent {
+    /// The document's current content (latest version only)
+    Current,
+    Full(String),
+    /// Delta from the next newer version's content
+    Delta(Vec<DeltaOp>),
+}
+
+/// Metadata of a saved document version
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct DocumentVersion {
+    pub document_id: DocumentId,
+    pub version: DocumentVersionNumber,
+    pub author: UserRef,
+    pub title: String,
+    /// Content size in bytes
+    pub size: u64,
+    pub created_at: Timestamp,
+}
+
+/// Document version with its stored (possibly delta-encoded) content
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct StoredDocumentVersion {
+    pub info: DocumentVersion,
+    pub content: VersionContent,
+    /// Ops from the previous version's content; None for the first version
+    /// and versions saved before patches were recorded
+    #[serde(default)]
+    pub patch: Option<Vec<PatchOp>>,
+}
+
+/// Kind of a line in a version diff
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub enum DiffLineKind {
+    Unchanged,
+    Added,
+    Removed,
+}
+
+/// Line in a version diff, with its 1-based line numbers in each version
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct DiffLine {
+    pub kind: DiffLineKind,
+    pub old_line: Option<u32>,
+    pub new_line: Option<u32>,
+    pub text: String,
+}
+
+/// Line-based diff between two versions of a document
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct DocumentDiff {
+    pub document_id: DocumentId,
+    pub from_version: DocumentVersionNumber,
+    pub to_version: DocumentVersionNumber,
+    pub lines: Vec<DiffLine>,
+    pub additions: u32,
+    pub deletions: u32,
+}
+
+// =============================================================================
+// Document Patch Types
+// =============================================================================
+
+/// Maximum ops in one patch
+pub const MAX_PATCH_OPS: usize = 1000;
+
+/// Positional edit in a document patch. Positions and lengths count Unicode
+/// scalar values, and each op applies to the text left by the ops before it.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub enum PatchOp {
+    Insert { position: u32, text: String },
+    Delete { position: u32, length: u32 },
+}
+
+/// Request to patch a document edited from `base_version`
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct ApplyDocumentPatchRequest {
+    pub document_id: DocumentId,
+    pub base_version: DocumentVersionNumber,
+    pub ops: Vec<PatchOp>,
+}
+
+/// Patch that produced a document version, relative to the version before it
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct DocumentPatch {
+    pub document_id: DocumentId,
+    pub version: DocumentVersionNumber,
+    pub author: UserRef,
+    pub ops: Vec<PatchOp>,
+    pub created_at: Timestamp,
+}
+
+// =============================================================================
+// Document Transfer Types
+// =============================================================================
+
+pub type UploadSessionId = u64;
+
+/// Maximum size of a document's content in bytes
+pub const MAX_DOCUMENT_SIZE: usize = 8 * 1024 * 1024;
+
+/// Maximum bytes per upload chunk or ranged read
+pub const MAX_DOCUMENT_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Open upload sessions allowed per user
+pub const MAX_UPLOAD_SESSIONS_PER_USER: usize = 5;
+
+/// Upload sessions expire after an hour (nanoseconds)
+pub const UPLOAD_SESSION_TTL: u64 = 60 * 60 * 1_000_000_000;
+
+/// Chunked upload replacing a document's content
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct UploadSession {
+    pub id: UploadSessionId,
+    pub document_id: DocumentId,
+    pub total_size: u64,
+    /// Bytes received so far; the next chunk must start here
+    pub received: u64,
+    pub expires_at: Timestamp,
+}
+
+/// Upload session with its buffered bytes. Sessions are kept in heap memory
+/// only and do not survive upgrades.
+#[derive(Clone, Debug)]
+pub struct DocumentUpload {
+    pub session: UploadSession,
+    pub owner: Principal,
+    pub buffer: Vec<u8>,
+}
+
+/// Byte range of a document's content
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct DocumentContentChunk {
+    pub document_id: DocumentId,
+    /// Current version, to detect edits between ranged reads
+    pub version: DocumentVersionNumber,
+    pub offset: u64,
+    pub total_size: u64,
+    pub data: Vec<u8>,
+}
+
+// =============================================================================
+// Link Types
+// =============================================================================
+
+/// Maximum links indexed per document or capture
+pub const MAX_LINKS_PER_ENTITY: usize = 200;
+
+/// Maximum length of a `[[...]]` link title
+pub const MAX_LINK_TITLE_LEN: usize = 200;
+
+/// Reference from a document or capture's text: `[[Document Title]]` or
+/// `#capture-123`. The target is resolved on save and then kept by ID, so
+/// renaming the target does not break the link.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub struct EntityLink {
+    /// Link text as written, without brackets
+    pub text: String,
+    /// None while no matching document exists
+    pub target: Option<CommentTarget>,
+}
+
+/// Document or capture that links to an entity
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct Backlink {
+    pub source: CommentTarget,
+    /// Title of the linking document or capture
+    pub title: String,
+    /// Text of the link
+    pub text: String,
+}
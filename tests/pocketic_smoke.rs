@@ -809,6 +809,24 @@ struct CaptureFilter {
     sprint_id: Option<u64>,
     workspace_id: Option<u64>,
     labels: Option<Vec<String>>,
+    dates: Option<Vec<DateCondition>>,
+    custom_fields: Option<Vec<CustomFieldCondition>>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+enum FieldComparison {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct CustomFieldCondition {
+    key: String,
+    comparison: FieldComparison,
+    value: String,
 }
 
 #[derive(CandidType, Serialize, Deserialize, Debug, Default)]
@@ -1120,6 +1138,7 @@ fn test_document_belongs_to_workspace() {
 enum TemplateType {
     Capture,
     Document,
+    Scaffold,
 }
 
 #[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
@@ -4222,3 +4241,3894 @@ fn test_delete_capture_unauthorized() {
     let capture: Option<Capture> = decode_one(&unwrap_wasm_result(get_response)).unwrap();
     assert!(capture.is_some(), "Capture should still exist after unauthorized delete attempt");
 }
+
+// ============================================================================
+// Sprint Schedule Validation Tests
+// Date validation, overlap protection and parallel sprint streams
+// ============================================================================
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct CreateStreamSprintRequest {
+    name: String,
+    goal: Option<String>,
+    start_date: u64,
+    end_date: u64,
+    capacity: Option<u32>,
+    stream: Option<String>,
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct StreamSprint {
+    id: u64,
+    name: String,
+    status: SprintStatus,
+    start_date: u64,
+    end_date: u64,
+    stream: Option<String>,
+}
+
+fn create_stream_sprint(
+    pic: &PocketIc,
+    canister_id: Principal,
+    user: Principal,
+    name: &str,
+    start_date: u64,
+    end_date: u64,
+    stream: Option<&str>,
+) -> Result<StreamSprint, String> {
+    let request = CreateStreamSprintRequest {
+        name: name.to_string(),
+        goal: None,
+        start_date,
+        end_date,
+        capacity: None,
+        stream: stream.map(|s| s.to_string()),
+    };
+
+    let response = pic.update_call(
+        canister_id,
+        user,
+        "create_sprint",
+        encode_one(request).unwrap(),
+    ).unwrap();
+
+    decode_one(&unwrap_wasm_result(response)).unwrap()
+}
+
+fn activate_sprint(pic: &PocketIc, canister_id: Principal, user: Principal, sprint_id: u64) -> Result<StreamSprint, String> {
+    let update_request = UpdateSprintRequest {
+        name: None,
+        goal: None,
+        status: Some(SprintStatus::Active),
+        start_date: None,
+        end_date: None,
+        capacity: None,
+    };
+
+    let response = pic.update_call(
+        canister_id,
+        user,
+        "update_sprint",
+        encode_args((sprint_id, update_request)).unwrap(),
+    ).unwrap();
+
+    decode_one(&unwrap_wasm_result(response)).unwrap()
+}
+
+#[test]
+fn test_create_sprint_rejects_invalid_dates() {
+    let (pic, canister_id, user) = setup();
+
+    let inverted = create_stream_sprint(&pic, canister_id, user, "Inverted", 200, 100, None);
+    assert!(inverted.is_err(), "end_date before start_date should be rejected");
+    assert!(inverted.unwrap_err().contains("before start_date"));
+
+    let zero_length = create_stream_sprint(&pic, canister_id, user, "Zero", 100, 100, None);
+    assert!(zero_length.is_err(), "Zero-length sprint should be rejected");
+    assert!(zero_length.unwrap_err().contains("non-zero duration"));
+}
+
+#[test]
+fn test_sprint_cannot_overlap_active_sprint() {
+    let (pic, canister_id, user) = setup();
+
+    let first = create_stream_sprint(&pic, canister_id, user, "Sprint 1", 0, 100, None).unwrap();
+    activate_sprint(&pic, canister_id, user, first.id).expect("Should activate first sprint");
+
+    // Overlapping sprint in the default stream is rejected
+    let overlapping = create_stream_sprint(&pic, canister_id, user, "Sprint 2", 50, 150, None);
+    assert!(overlapping.is_err(), "Overlap with active sprint should be rejected");
+    assert!(overlapping.unwrap_err().contains("overlaps active sprint"));
+
+    // Back-to-back sprint is fine
+    let next = create_stream_sprint(&pic, canister_id, user, "Sprint 2", 100, 200, None);
+    assert!(next.is_ok(), "Adjacent sprint should be allowed: {:?}", next);
+
+    // Other users are not affected
+    let user_b = Principal::from_slice(&[26, 27, 28, 29, 30, 31, 32, 33, 34, 35]);
+    let other_user = create_stream_sprint(&pic, canister_id, user_b, "B Sprint", 0, 100, None);
+    assert!(other_user.is_ok(), "Different owner should not conflict: {:?}", other_user);
+}
+
+#[test]
+fn test_activating_overlapping_sprint_rejected() {
+    let (pic, canister_id, user) = setup();
+
+    let first = create_stream_sprint(&pic, canister_id, user, "Plan A", 0, 100, None).unwrap();
+    let second = create_stream_sprint(&pic, canister_id, user, "Plan B", 50, 150, None).unwrap();
+
+    activate_sprint(&pic, canister_id, user, first.id).expect("Should activate first sprint");
+    let result = activate_sprint(&pic, canister_id, user, second.id);
+    assert!(result.is_err(), "Second overlapping sprint should not become active");
+}
+
+#[test]
+fn test_parallel_sprint_streams_allowed() {
+    let (pic, canister_id, user) = setup();
+
+    let main = create_stream_sprint(&pic, canister_id, user, "Main", 0, 100, None).unwrap();
+    activate_sprint(&pic, canister_id, user, main.id).expect("Should activate main sprint");
+
+    let design = create_stream_sprint(&pic, canister_id, user, "Design", 0, 100, Some("design")).unwrap();
+    assert_eq!(design.stream, Some("design".to_string()));
+
+    let activated = activate_sprint(&pic, canister_id, user, design.id);
+    assert!(activated.is_ok(), "Sprint in a separate stream should run in parallel: {:?}", activated);
+}
+
+// ============================================================================
+// Backlog Ranking Tests
+// Fractional rank ordering for the backlog and within sprints
+// ============================================================================
+
+fn create_test_capture(
+    pic: &PocketIc,
+    canister_id: Principal,
+    user: Principal,
+    capture_type: CaptureType,
+    title: &str,
+    fields: Option<DynamicFields>,
+) -> Capture {
+    let request = CreateCaptureRequest {
+        capture_type,
+        title: title.to_string(),
+        description: None,
+        content: None,
+        priority: None,
+        fields,
+    };
+
+    let response = pic.update_call(
+        canister_id,
+        user,
+        "create_capture",
+        encode_one(request).unwrap(),
+    ).unwrap();
+
+    let result: Result<Capture, String> = decode_one(&unwrap_wasm_result(response)).unwrap();
+    result.expect("Should create capture")
+}
+
+fn get_backlog_ids(pic: &PocketIc, canister_id: Principal, user: Principal) -> Vec<u64> {
+    let response = pic.query_call(
+        canister_id,
+        user,
+        "get_backlog",
+        encode_one(None::<PaginationParams>).unwrap(),
+    ).unwrap();
+
+    let backlog: PaginatedCaptureResponse = decode_one(&unwrap_wasm_result(response)).unwrap();
+    backlog.items.iter().map(|c| c.id).collect()
+}
+
+fn reorder(
+    pic: &PocketIc,
+    canister_id: Principal,
+    user: Principal,
+    id: u64,
+    before: Option<u64>,
+    after: Option<u64>,
+) -> Result<Capture, String> {
+    let response = pic.update_call(
+        canister_id,
+        user,
+        "reorder_capture",
+        encode_args((id, before, after)).unwrap(),
+    ).unwrap();
+
+    decode_one(&unwrap_wasm_result(response)).unwrap()
+}
+
+#[test]
+fn test_backlog_keeps_creation_order() {
+    let (pic, canister_id, user) = setup();
+
+    let a = create_test_capture(&pic, canister_id, user, CaptureType::Task, "A", None);
+    let b = create_test_capture(&pic, canister_id, user, CaptureType::Task, "B", None);
+    let c = create_test_capture(&pic, canister_id, user, CaptureType::Task, "C", None);
+
+    assert_eq!(get_backlog_ids(&pic, canister_id, user), vec![a.id, b.id, c.id]);
+}
+
+#[test]
+fn test_reorder_capture_in_backlog() {
+    let (pic, canister_id, user) = setup();
+
+    let a = create_test_capture(&pic, canister_id, user, CaptureType::Task, "A", None);
+    let b = create_test_capture(&pic, canister_id, user, CaptureType::Task, "B", None);
+    let c = create_test_capture(&pic, canister_id, user, CaptureType::Task, "C", None);
+
+    // Move C to the top (directly before A)
+    reorder(&pic, canister_id, user, c.id, None, Some(a.id)).expect("Should move C to top");
+    assert_eq!(get_backlog_ids(&pic, canister_id, user), vec![c.id, a.id, b.id]);
+
+    // Move A to the bottom (directly after B)
+    reorder(&pic, canister_id, user, a.id, Some(b.id), None).expect("Should move A to bottom");
+    assert_eq!(get_backlog_ids(&pic, canister_id, user), vec![c.id, b.id, a.id]);
+
+    // Move A between C and B
+    reorder(&pic, canister_id, user, a.id, Some(c.id), Some(b.id)).expect("Should move A to middle");
+    assert_eq!(get_backlog_ids(&pic, canister_id, user), vec![c.id, a.id, b.id]);
+}
+
+#[test]
+fn test_reorder_capture_within_sprint() {
+    let (pic, canister_id, user) = setup();
+
+    let sprint = create_stream_sprint(&pic, canister_id, user, "Sprint", 0, 100, None).unwrap();
+    let a = create_test_capture(&pic, canister_id, user, CaptureType::Task, "A", None);
+    let b = create_test_capture(&pic, canister_id, user, CaptureType::Task, "B", None);
+    let backlog_item = create_test_capture(&pic, canister_id, user, CaptureType::Task, "Backlog", None);
+
+    for id in [a.id, b.id] {
+        pic.update_call(
+            canister_id,
+            user,
+            "add_capture_to_sprint",
+            encode_args((sprint.id, id)).unwrap(),
+        ).unwrap();
+    }
+
+    reorder(&pic, canister_id, user, b.id, None, Some(a.id)).expect("Should reorder within sprint");
+
+    let response = pic.query_call(
+        canister_id,
+        user,
+        "get_sprint_captures",
+        encode_one(sprint.id).unwrap(),
+    ).unwrap();
+    let captures: Vec<Capture> = decode_one(&unwrap_wasm_result(response)).unwrap();
+    let ids: Vec<u64> = captures.iter().map(|c| c.id).collect();
+    assert_eq!(ids, vec![b.id, a.id]);
+
+    // Sprint captures are not in the backlog
+    assert_eq!(get_backlog_ids(&pic, canister_id, user), vec![backlog_item.id]);
+
+    // Cannot order a sprint capture relative to a backlog capture
+    let cross = reorder(&pic, canister_id, user, a.id, Some(backlog_item.id), None);
+    assert!(cross.is_err(), "Cross-list reorder should be rejected");
+}
+
+#[test]
+fn test_get_backlog_pagination() {
+    let (pic, canister_id, user) = setup();
+
+    for i in 0..5 {
+        create_test_capture(&pic, canister_id, user, CaptureType::Idea, &format!("Idea {}", i), None);
+    }
+
+    let response = pic.query_call(
+        canister_id,
+        user,
+        "get_backlog",
+        encode_one(Some(PaginationParams { offset: Some(1), limit: Some(2) })).unwrap(),
+    ).unwrap();
+
+    let page: PaginatedCaptureResponse = decode_one(&unwrap_wasm_result(response)).unwrap();
+    assert_eq!(page.total, 5);
+    assert_eq!(page.items.len(), 2);
+    assert_eq!(page.items[0].title, "Idea 1");
+}
+
+#[test]
+fn test_reorder_capture_unauthorized() {
+    let (pic, canister_id, user) = setup();
+    let user_b = Principal::from_slice(&[36, 37, 38, 39, 40, 41, 42, 43, 44, 45]);
+
+    let a = create_test_capture(&pic, canister_id, user, CaptureType::Task, "A", None);
+    let b = create_test_capture(&pic, canister_id, user, CaptureType::Task, "B", None);
+
+    let result = reorder(&pic, canister_id, user_b, b.id, None, Some(a.id));
+    assert!(result.is_err(), "User B should not reorder User A's captures");
+}
+
+// ============================================================================
+// Board Tests
+// Kanban boards with status/label columns and WIP limits
+// ============================================================================
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+enum BoardScope {
+    Workspace(u64),
+    Sprint(u64),
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct BoardColumn {
+    name: String,
+    statuses: Vec<CaptureStatus>,
+    labels: Vec<String>,
+    wip_limit: Option<u32>,
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct Board {
+    id: u64,
+    owner: Principal,
+    name: String,
+    scope: BoardScope,
+    columns: Vec<BoardColumn>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct CreateBoardRequest {
+    name: String,
+    scope: BoardScope,
+    columns: Vec<BoardColumn>,
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct BoardColumnView {
+    index: u32,
+    name: String,
+    wip_limit: Option<u32>,
+    card_count: u64,
+    over_limit: bool,
+    cards: Vec<Capture>,
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct BoardView {
+    board: Board,
+    columns: Vec<BoardColumnView>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct MoveCardRequest {
+    board_id: u64,
+    capture_id: u64,
+    column: u32,
+    before: Option<u64>,
+    after: Option<u64>,
+}
+
+fn status_column(name: &str, status: CaptureStatus, wip_limit: Option<u32>) -> BoardColumn {
+    BoardColumn {
+        name: name.to_string(),
+        statuses: vec![status],
+        labels: vec![],
+        wip_limit,
+    }
+}
+
+/// Create a workspace and a To Do / Doing (WIP 1) / Done board over it
+fn setup_board(pic: &PocketIc, canister_id: Principal, user: Principal) -> (u64, Board) {
+    let response = pic.update_call(
+        canister_id,
+        user,
+        "create_workspace",
+        encode_one(CreateWorkspaceRequest {
+            name: "Board Workspace".to_string(),
+            description: None,
+            icon: None,
+            parent_id: None,
+        }).unwrap(),
+    ).unwrap();
+    let workspace: Result<Workspace, String> = decode_one(&unwrap_wasm_result(response)).unwrap();
+    let workspace = workspace.expect("Should create workspace");
+
+    let request = CreateBoardRequest {
+        name: "Team Board".to_string(),
+        scope: BoardScope::Workspace(workspace.id),
+        columns: vec![
+            status_column("To Do", CaptureStatus::Draft, None),
+            status_column("Doing", CaptureStatus::InProgress, Some(1)),
+            status_column("Done", CaptureStatus::Completed, None),
+        ],
+    };
+    let response = pic.update_call(
+        canister_id,
+        user,
+        "create_board",
+        encode_one(request).unwrap(),
+    ).unwrap();
+    let board: Result<Board, String> = decode_one(&unwrap_wasm_result(response)).unwrap();
+
+    (workspace.id, board.expect("Should create board"))
+}
+
+fn workspace_fields(workspace_id: u64) -> Option<DynamicFields> {
+    Some(DynamicFields {
+        workspace_id: Some(workspace_id),
+        ..Default::default()
+    })
+}
+
+fn get_board_view(pic: &PocketIc, canister_id: Principal, user: Principal, board_id: u64) -> Option<BoardView> {
+    let response = pic.query_call(
+        canister_id,
+        user,
+        "get_board",
+        encode_one(board_id).unwrap(),
+    ).unwrap();
+
+    decode_one(&unwrap_wasm_result(response)).unwrap()
+}
+
+fn move_card(
+    pic: &PocketIc,
+    canister_id: Principal,
+    user: Principal,
+    request: MoveCardRequest,
+) -> Result<Capture, String> {
+    let response = pic.update_call(
+        canister_id,
+        user,
+        "move_card",
+        encode_one(request).unwrap(),
+    ).unwrap();
+
+    decode_one(&unwrap_wasm_result(response)).unwrap()
+}
+
+#[test]
+fn test_board_view_groups_cards_by_column() {
+    let (pic, canister_id, user) = setup();
+    let (workspace_id, board) = setup_board(&pic, canister_id, user);
+
+    let a = create_test_capture(&pic, canister_id, user, CaptureType::Task, "A", workspace_fields(workspace_id));
+    let b = create_test_capture(&pic, canister_id, user, CaptureType::Task, "B", workspace_fields(workspace_id));
+    // Outside the board's workspace
+    create_test_capture(&pic, canister_id, user, CaptureType::Task, "Elsewhere", None);
+
+    let view = get_board_view(&pic, canister_id, user, board.id).expect("Should get board");
+    assert_eq!(view.columns.len(), 3);
+
+    let todo: Vec<u64> = view.columns[0].cards.iter().map(|c| c.id).collect();
+    assert_eq!(todo, vec![a.id, b.id]);
+    assert_eq!(view.columns[1].card_count, 0);
+    assert_eq!(view.columns[2].card_count, 0);
+}
+
+#[test]
+fn test_move_card_updates_status_and_rank() {
+    let (pic, canister_id, user) = setup();
+    let (workspace_id, board) = setup_board(&pic, canister_id, user);
+
+    let a = create_test_capture(&pic, canister_id, user, CaptureType::Task, "A", workspace_fields(workspace_id));
+    let b = create_test_capture(&pic, canister_id, user, CaptureType::Task, "B", workspace_fields(workspace_id));
+
+    let moved = move_card(&pic, canister_id, user, MoveCardRequest {
+        board_id: board.id,
+        capture_id: a.id,
+        column: 2,
+        before: None,
+        after: None,
+    }).expect("Should move card to Done");
+    assert_eq!(moved.status, CaptureStatus::Completed);
+
+    // Move B into Done above A
+    move_card(&pic, canister_id, user, MoveCardRequest {
+        board_id: board.id,
+        capture_id: b.id,
+        column: 2,
+        before: None,
+        after: Some(a.id),
+    }).expect("Should move card above A");
+
+    let view = get_board_view(&pic, canister_id, user, board.id).unwrap();
+    let done: Vec<u64> = view.columns[2].cards.iter().map(|c| c.id).collect();
+    assert_eq!(done, vec![b.id, a.id]);
+    assert_eq!(view.columns[0].card_count, 0);
+}
+
+#[test]
+fn test_move_card_respects_wip_limit() {
+    let (pic, canister_id, user) = setup();
+    let (workspace_id, board) = setup_board(&pic, canister_id, user);
+
+    let a = create_test_capture(&pic, canister_id, user, CaptureType::Task, "A", workspace_fields(workspace_id));
+    let b = create_test_capture(&pic, canister_id, user, CaptureType::Task, "B", workspace_fields(workspace_id));
+
+    move_card(&pic, canister_id, user, MoveCardRequest {
+        board_id: board.id,
+        capture_id: a.id,
+        column: 1,
+        before: None,
+        after: None,
+    }).expect("First card fits in Doing");
+
+    let result = move_card(&pic, canister_id, user, MoveCardRequest {
+        board_id: board.id,
+        capture_id: b.id,
+        column: 1,
+        before: None,
+        after: None,
+    });
+    assert!(result.is_err(), "Doing is at its WIP limit");
+    assert!(result.unwrap_err().contains("WIP limit"));
+
+    // Reordering within the full column is still allowed
+    let result = move_card(&pic, canister_id, user, MoveCardRequest {
+        board_id: board.id,
+        capture_id: a.id,
+        column: 1,
+        before: None,
+        after: None,
+    });
+    assert!(result.is_ok());
+}
+
+#[test]
+fn test_move_card_across_backlog_and_sprint_cards() {
+    let (pic, canister_id, user) = setup();
+    let (workspace_id, board) = setup_board(&pic, canister_id, user);
+
+    let sprint_request = CreateSprintRequest {
+        name: "Sprint".to_string(),
+        goal: None,
+        start_date: 1700000000000000000,
+        end_date: 1701000000000000000,
+        capacity: None,
+    };
+    let sprint: Result<Sprint, String> = time_call(&pic, canister_id, user, "create_sprint", (sprint_request,));
+    let sprint_id = sprint.unwrap().id;
+
+    // The backlog and the sprint rank their first cards with the same key
+    let backlog = create_test_capture(&pic, canister_id, user, CaptureType::Task, "Backlog", workspace_fields(workspace_id));
+    let in_sprint = DynamicFields { workspace_id: Some(workspace_id), sprint_id: Some(sprint_id), ..Default::default() };
+    let sprinted = create_test_capture(&pic, canister_id, user, CaptureType::Task, "Sprint", Some(in_sprint));
+    let other = create_test_capture(&pic, canister_id, user, CaptureType::Task, "Other", workspace_fields(workspace_id));
+
+    move_card(&pic, canister_id, user, MoveCardRequest {
+        board_id: board.id,
+        capture_id: sprinted.id,
+        column: 0,
+        before: None,
+        after: Some(backlog.id),
+    }).expect("Should rank a sprint card above a backlog card");
+    move_card(&pic, canister_id, user, MoveCardRequest {
+        board_id: board.id,
+        capture_id: other.id,
+        column: 0,
+        before: Some(sprinted.id),
+        after: Some(backlog.id),
+    }).expect("Should rank between cards from different lists");
+
+    let view = get_board_view(&pic, canister_id, user, board.id).unwrap();
+    let todo: Vec<u64> = view.columns[0].cards.iter().map(|c| c.id).collect();
+    assert_eq!(todo, vec![sprinted.id, other.id, backlog.id]);
+}
+
+#[test]
+fn test_deleting_scope_deletes_boards() {
+    let (pic, canister_id, user) = setup();
+    let (workspace_id, board) = setup_board(&pic, canister_id, user);
+
+    let sprint_request = CreateSprintRequest {
+        name: "Sprint".to_string(),
+        goal: None,
+        start_date: 1700000000000000000,
+        end_date: 1701000000000000000,
+        capacity: None,
+    };
+    let sprint: Result<Sprint, String> = time_call(&pic, canister_id, user, "create_sprint", (sprint_request,));
+    let sprint_id = sprint.unwrap().id;
+    let request = CreateBoardRequest {
+        name: "Sprint Board".to_string(),
+        scope: BoardScope::Sprint(sprint_id),
+        columns: vec![status_column("To Do", CaptureStatus::Draft, None)],
+    };
+    let sprint_board: Result<Board, String> = time_call(&pic, canister_id, user, "create_board", (request,));
+    let sprint_board = sprint_board.unwrap();
+
+    let deleted: Result<Sprint, String> = time_call(&pic, canister_id, user, "delete_sprint", (sprint_id,));
+    deleted.unwrap();
+    assert!(get_board_view(&pic, canister_id, user, sprint_board.id).is_none());
+    assert!(get_board_view(&pic, canister_id, user, board.id).is_some());
+
+    let deleted: Result<Workspace, String> = time_call(&pic, canister_id, user, "delete_workspace", (workspace_id,));
+    deleted.unwrap();
+    assert!(get_board_view(&pic, canister_id, user, board.id).is_none());
+}
+
+#[test]
+fn test_board_unauthorized() {
+    let (pic, canister_id, user) = setup();
+    let user_b = Principal::from_slice(&[46, 47, 48, 49, 50, 51, 52, 53, 54, 55]);
+    let (workspace_id, board) = setup_board(&pic, canister_id, user);
+    let a = create_test_capture(&pic, canister_id, user, CaptureType::Task, "A", workspace_fields(workspace_id));
+
+    assert!(get_board_view(&pic, canister_id, user_b, board.id).is_none());
+
+    let result = move_card(&pic, canister_id, user_b, MoveCardRequest {
+        board_id: board.id,
+        capture_id: a.id,
+        column: 2,
+        before: None,
+        after: None,
+    });
+    assert!(result.is_err(), "User B should not move cards on User A's board");
+
+    // User B cannot create a board over User A's workspace
+    let response = pic.update_call(
+        canister_id,
+        user_b,
+        "create_board",
+        encode_one(CreateBoardRequest {
+            name: "Hijack".to_string(),
+            scope: BoardScope::Workspace(workspace_id),
+            columns: vec![status_column("All", CaptureStatus::Draft, None)],
+        }).unwrap(),
+    ).unwrap();
+    let result: Result<Board, String> = decode_one(&unwrap_wasm_result(response)).unwrap();
+    assert!(result.is_err());
+}
+
+// ============================================================================
+// Recurring Capture Tests
+// Completing an occurrence, or the global timer, spawns the next occurrence
+// ============================================================================
+
+const NANOS_PER_DAY: u64 = 86_400 * 1_000_000_000;
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+enum RecurrenceFrequency {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct RecurrenceRule {
+    frequency: RecurrenceFrequency,
+    interval: u32,
+    by_day: Vec<Weekday>,
+    count: Option<u32>,
+    until: Option<u64>,
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct Recurrence {
+    series_id: u64,
+    occurrence: u32,
+    next_occurrence_id: Option<u64>,
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct RecurringCapture {
+    id: u64,
+    title: String,
+    status: CaptureStatus,
+    fields: DynamicFields,
+    recurrence: Option<Recurrence>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct CreateRecurringCaptureRequest {
+    capture_type: CaptureType,
+    title: String,
+    description: Option<String>,
+    content: Option<String>,
+    priority: Option<Priority>,
+    fields: Option<DynamicFields>,
+    recurrence: Option<RecurrenceRule>,
+}
+
+fn pic_now(pic: &PocketIc) -> u64 {
+    pic.get_time()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos() as u64
+}
+
+fn daily_rule(count: Option<u32>) -> RecurrenceRule {
+    RecurrenceRule {
+        frequency: RecurrenceFrequency::Daily,
+        interval: 1,
+        by_day: vec![],
+        count,
+        until: None,
+    }
+}
+
+fn create_recurring_capture(
+    pic: &PocketIc,
+    canister_id: Principal,
+    user: Principal,
+    due_date: Option<u64>,
+    rule: RecurrenceRule,
+) -> Result<RecurringCapture, String> {
+    let request = CreateRecurringCaptureRequest {
+        capture_type: CaptureType::Task,
+        title: "Water the plants".to_string(),
+        description: None,
+        content: None,
+        priority: None,
+        fields: Some(DynamicFields {
+            due_date,
+            labels: vec!["chore".to_string()],
+            ..Default::default()
+        }),
+        recurrence: Some(rule),
+    };
+
+    let response = pic.update_call(
+        canister_id,
+        user,
+        "create_capture",
+        encode_one(request).unwrap(),
+    ).unwrap();
+
+    decode_one(&unwrap_wasm_result(response)).unwrap()
+}
+
+fn get_recurring_capture(pic: &PocketIc, canister_id: Principal, user: Principal, id: u64) -> Option<RecurringCapture> {
+    let response = pic.query_call(
+        canister_id,
+        user,
+        "get_capture",
+        encode_one(id).unwrap(),
+    ).unwrap();
+
+    decode_one(&unwrap_wasm_result(response)).unwrap()
+}
+
+fn complete_capture(pic: &PocketIc, canister_id: Principal, user: Principal, id: u64) {
+    let request = UpdateCaptureRequest {
+        id,
+        title: None,
+        description: None,
+        content: None,
+        priority: None,
+        status: Some(CaptureStatus::Completed),
+        fields: None,
+    };
+
+    let response = pic.update_call(
+        canister_id,
+        user,
+        "update_capture",
+        encode_one(request).unwrap(),
+    ).unwrap();
+    let result: Result<Capture, String> = decode_one(&unwrap_wasm_result(response)).unwrap();
+    result.expect("Should complete capture");
+}
+
+#[test]
+fn test_completing_recurring_capture_spawns_next() {
+    let (pic, canister_id, user) = setup();
+    let due = pic_now(&pic) + NANOS_PER_DAY;
+
+    let first = create_recurring_capture(&pic, canister_id, user, Some(due), daily_rule(None))
+        .expect("Should create recurring capture");
+    complete_capture(&pic, canister_id, user, first.id);
+
+    let first = get_recurring_capture(&pic, canister_id, user, first.id).unwrap();
+    let next_id = first.recurrence.unwrap().next_occurrence_id.expect("Next occurrence should exist");
+
+    let next = get_recurring_capture(&pic, canister_id, user, next_id).unwrap();
+    assert_eq!(next.title, "Water the plants");
+    assert_eq!(next.status, CaptureStatus::Draft);
+    assert_eq!(next.fields.due_date, Some(due + NANOS_PER_DAY));
+    assert_eq!(next.fields.labels, vec!["chore".to_string()]);
+
+    let recurrence = next.recurrence.unwrap();
+    assert_eq!(recurrence.series_id, first.id);
+    assert_eq!(recurrence.occurrence, 2);
+}
+
+#[test]
+fn test_recurrence_count_ends_series() {
+    let (pic, canister_id, user) = setup();
+    let due = pic_now(&pic) + NANOS_PER_DAY;
+
+    let first = create_recurring_capture(&pic, canister_id, user, Some(due), daily_rule(Some(2)))
+        .expect("Should create recurring capture");
+    complete_capture(&pic, canister_id, user, first.id);
+
+    let first = get_recurring_capture(&pic, canister_id, user, first.id).unwrap();
+    let second_id = first.recurrence.unwrap().next_occurrence_id.unwrap();
+    complete_capture(&pic, canister_id, user, second_id);
+
+    let second = get_recurring_capture(&pic, canister_id, user, second_id).unwrap();
+    assert!(second.recurrence.unwrap().next_occurrence_id.is_none(), "Series should end after 2 occurrences");
+}
+
+#[test]
+fn test_timer_spawns_next_occurrence() {
+    let (pic, canister_id, user) = setup();
+    let due = pic_now(&pic) + 60 * 1_000_000_000;
+
+    let first = create_recurring_capture(&pic, canister_id, user, Some(due), daily_rule(None))
+        .expect("Should create recurring capture");
+
+    pic.advance_time(std::time::Duration::from_secs(120));
+    pic.tick();
+    pic.tick();
+
+    let first = get_recurring_capture(&pic, canister_id, user, first.id).unwrap();
+    assert_eq!(first.status, CaptureStatus::Draft, "Timer leaves the current occurrence open");
+    let next_id = first.recurrence.unwrap().next_occurrence_id.expect("Timer should spawn next occurrence");
+
+    let next = get_recurring_capture(&pic, canister_id, user, next_id).unwrap();
+    assert_eq!(next.fields.due_date, Some(due + NANOS_PER_DAY));
+}
+
+#[test]
+fn test_recurring_capture_requires_date() {
+    let (pic, canister_id, user) = setup();
+
+    let result = create_recurring_capture(&pic, canister_id, user, None, daily_rule(None));
+    assert!(result.is_err(), "Recurrence without a date should be rejected");
+}
+
+// ============================================================================
+// Reminder & Notification Tests
+// Timer-driven reminders delivered to the in-canister inbox
+// ============================================================================
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+enum ReminderTrigger {
+    At(u64),
+    BeforeDue(u64),
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct Reminder {
+    id: u64,
+    capture_id: u64,
+    trigger: ReminderTrigger,
+    fire_at: Option<u64>,
+    fired: bool,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct CreateReminderRequest {
+    capture_id: u64,
+    trigger: ReminderTrigger,
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct Notification {
+    id: u64,
+    capture_id: Option<u64>,
+    title: String,
+    body: Option<String>,
+    read: bool,
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct PaginatedNotificationResponse {
+    items: Vec<Notification>,
+    total: u64,
+}
+
+fn create_reminder(
+    pic: &PocketIc,
+    canister_id: Principal,
+    user: Principal,
+    capture_id: u64,
+    trigger: ReminderTrigger,
+) -> Result<Reminder, String> {
+    let response = pic.update_call(
+        canister_id,
+        user,
+        "create_reminder",
+        encode_one(CreateReminderRequest { capture_id, trigger }).unwrap(),
+    ).unwrap();
+
+    decode_one(&unwrap_wasm_result(response)).unwrap()
+}
+
+fn get_notifications(pic: &PocketIc, canister_id: Principal, user: Principal, unread_only: bool) -> PaginatedNotificationResponse {
+    let response = pic.query_call(
+        canister_id,
+        user,
+        "get_my_notifications",
+        encode_args((Some(unread_only), None::<PaginationParams>)).unwrap(),
+    ).unwrap();
+
+    decode_one(&unwrap_wasm_result(response)).unwrap()
+}
+
+fn task_due_at(pic: &PocketIc, canister_id: Principal, user: Principal, due_date: u64) -> Capture {
+    create_test_capture(pic, canister_id, user, CaptureType::Task, "Send invoice", Some(DynamicFields {
+        due_date: Some(due_date),
+        ..Default::default()
+    }))
+}
+
+#[test]
+fn test_reminder_fires_into_inbox() {
+    let (pic, canister_id, user) = setup();
+    let now = pic_now(&pic);
+    let capture = task_due_at(&pic, canister_id, user, now + NANOS_PER_DAY);
+
+    let reminder = create_reminder(&pic, canister_id, user, capture.id, ReminderTrigger::At(now + 60 * 1_000_000_000))
+        .expect("Should create reminder");
+    assert!(!reminder.fired);
+    assert_eq!(get_notifications(&pic, canister_id, user, false).total, 0);
+
+    pic.advance_time(std::time::Duration::from_secs(120));
+    pic.tick();
+    pic.tick();
+
+    let inbox = get_notifications(&pic, canister_id, user, true);
+    assert_eq!(inbox.total, 1);
+    assert_eq!(inbox.items[0].title, "Send invoice");
+    assert_eq!(inbox.items[0].capture_id, Some(capture.id));
+
+    // Mark everything read
+    let response = pic.update_call(
+        canister_id,
+        user,
+        "mark_read",
+        encode_one(Vec::<u64>::new()).unwrap(),
+    ).unwrap();
+    let marked: Result<u64, String> = decode_one(&unwrap_wasm_result(response)).unwrap();
+    assert_eq!(marked, Ok(1));
+    assert_eq!(get_notifications(&pic, canister_id, user, true).total, 0);
+    assert_eq!(get_notifications(&pic, canister_id, user, false).total, 1);
+}
+
+#[test]
+fn test_before_due_reminder_follows_due_date() {
+    let (pic, canister_id, user) = setup();
+    let now = pic_now(&pic);
+    let hour = 3_600 * 1_000_000_000;
+    let capture = task_due_at(&pic, canister_id, user, now + 2 * hour);
+
+    let reminder = create_reminder(&pic, canister_id, user, capture.id, ReminderTrigger::BeforeDue(hour))
+        .expect("Should create reminder");
+    assert_eq!(reminder.fire_at, Some(now + hour));
+
+    // Push the due date out by a day
+    let mut fields = capture.fields.clone();
+    fields.due_date = Some(now + NANOS_PER_DAY + 2 * hour);
+    pic.update_call(
+        canister_id,
+        user,
+        "update_capture",
+        encode_one(UpdateCaptureRequest {
+            id: capture.id,
+            title: None,
+            description: None,
+            content: None,
+            priority: None,
+            status: None,
+            fields: Some(fields),
+        }).unwrap(),
+    ).unwrap();
+
+    let response = pic.query_call(
+        canister_id,
+        user,
+        "get_capture_reminders",
+        encode_one(capture.id).unwrap(),
+    ).unwrap();
+    let reminders: Vec<Reminder> = decode_one(&unwrap_wasm_result(response)).unwrap();
+    assert_eq!(reminders[0].fire_at, Some(now + NANOS_PER_DAY + hour));
+
+    // The original fire time passes without a notification
+    pic.advance_time(std::time::Duration::from_secs(2 * 3_600));
+    pic.tick();
+    assert_eq!(get_notifications(&pic, canister_id, user, false).total, 0);
+}
+
+#[test]
+fn test_reminder_validation_and_authorization() {
+    let (pic, canister_id, user) = setup();
+    let user_b = Principal::from_slice(&[56, 57, 58, 59, 60, 61, 62, 63, 64, 65]);
+    let now = pic_now(&pic);
+
+    let no_due = create_test_capture(&pic, canister_id, user, CaptureType::Task, "No due date", None);
+    let result = create_reminder(&pic, canister_id, user, no_due.id, ReminderTrigger::BeforeDue(1));
+    assert!(result.is_err(), "BeforeDue needs a due_date");
+
+    let result = create_reminder(&pic, canister_id, user, no_due.id, ReminderTrigger::At(now - 1));
+    assert!(result.is_err(), "Reminder in the past should be rejected");
+
+    let result = create_reminder(&pic, canister_id, user_b, no_due.id, ReminderTrigger::At(now + NANOS_PER_DAY));
+    assert!(result.is_err(), "User B should not add reminders to User A's capture");
+}
+
+// ============================================================================
+// Calendar Tests
+// Range queries, recurring projection and ICS feeds over the HTTP gateway
+// ============================================================================
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct CalendarRange {
+    start: u64,
+    end: u64,
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct CalendarEntry {
+    capture: RecurringCapture,
+    start: u64,
+    end: u64,
+    projected: bool,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct HttpRequest {
+    method: String,
+    url: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct HttpResponse {
+    status_code: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+fn get_calendar(pic: &PocketIc, canister_id: Principal, user: Principal, start: u64, end: u64) -> Result<Vec<CalendarEntry>, String> {
+    let response = pic.query_call(
+        canister_id,
+        user,
+        "get_calendar",
+        encode_one(CalendarRange { start, end }).unwrap(),
+    ).unwrap();
+
+    decode_one(&unwrap_wasm_result(response)).unwrap()
+}
+
+fn create_event(pic: &PocketIc, canister_id: Principal, user: Principal, title: &str, start: u64, end: u64) -> Capture {
+    create_test_capture(pic, canister_id, user, CaptureType::Calendar, title, Some(DynamicFields {
+        start_date: Some(start),
+        due_date: Some(end),
+        ..Default::default()
+    }))
+}
+
+fn http_get(pic: &PocketIc, canister_id: Principal, url: &str) -> HttpResponse {
+    let request = HttpRequest {
+        method: "GET".to_string(),
+        url: url.to_string(),
+        headers: vec![],
+        body: vec![],
+    };
+    let response = pic.query_call(
+        canister_id,
+        Principal::anonymous(),
+        "http_request",
+        encode_one(request).unwrap(),
+    ).unwrap();
+
+    decode_one(&unwrap_wasm_result(response)).unwrap()
+}
+
+#[test]
+fn test_calendar_range_query() {
+    let (pic, canister_id, user) = setup();
+    let base = pic_now(&pic);
+
+    let inside = create_event(&pic, canister_id, user, "Inside", base + NANOS_PER_DAY, base + NANOS_PER_DAY + 1);
+    // Starts before the range and ends after it
+    let spanning = create_event(&pic, canister_id, user, "Spanning", base, base + 10 * NANOS_PER_DAY);
+    create_event(&pic, canister_id, user, "Later", base + 20 * NANOS_PER_DAY, base + 21 * NANOS_PER_DAY);
+    create_test_capture(&pic, canister_id, user, CaptureType::Task, "Undated", None);
+
+    let entries = get_calendar(&pic, canister_id, user, base + NANOS_PER_DAY / 2, base + 7 * NANOS_PER_DAY)
+        .expect("Should get calendar");
+    let ids: Vec<u64> = entries.iter().map(|e| e.capture.id).collect();
+    assert_eq!(ids, vec![spanning.id, inside.id]);
+    assert!(entries.iter().all(|e| !e.projected));
+}
+
+#[test]
+fn test_calendar_projects_recurring_occurrences() {
+    let (pic, canister_id, user) = setup();
+    let due = pic_now(&pic) + 60 * 1_000_000_000;
+
+    let first = create_recurring_capture(&pic, canister_id, user, Some(due), daily_rule(Some(3)))
+        .expect("Should create recurring capture");
+
+    let entries = get_calendar(&pic, canister_id, user, due - 1, due + 10 * NANOS_PER_DAY)
+        .expect("Should get calendar");
+
+    // The real capture plus two projected occurrences (count = 3)
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[0].capture.id, first.id);
+    assert!(!entries[0].projected);
+    assert!(entries[1].projected && entries[2].projected);
+    assert_eq!(entries[1].start, due + NANOS_PER_DAY);
+    assert_eq!(entries[2].capture.fields.due_date, Some(due + 2 * NANOS_PER_DAY));
+}
+
+#[test]
+fn test_calendar_rejects_invalid_range() {
+    let (pic, canister_id, user) = setup();
+
+    assert!(get_calendar(&pic, canister_id, user, 100, 100).is_err());
+    assert!(get_calendar(&pic, canister_id, user, 0, 400 * NANOS_PER_DAY).is_err());
+}
+
+#[test]
+fn test_calendar_feed_over_http() {
+    let (pic, canister_id, user) = setup();
+    let base = pic_now(&pic);
+    create_event(&pic, canister_id, user, "Planning", base + NANOS_PER_DAY, base + NANOS_PER_DAY + 1);
+
+    let response = pic.update_call(
+        canister_id,
+        user,
+        "create_calendar_feed",
+        encode_one(()).unwrap(),
+    ).unwrap();
+    let path: Result<String, String> = decode_one(&unwrap_wasm_result(response)).unwrap();
+    let path = path.expect("Should create feed");
+    assert!(path.starts_with("/calendar/") && path.ends_with(".ics"));
+
+    let feed = http_get(&pic, canister_id, &path);
+    assert_eq!(feed.status_code, 200);
+    let body = String::from_utf8(feed.body).unwrap();
+    assert!(body.contains("BEGIN:VEVENT"));
+    assert!(body.contains("SUMMARY:Planning"));
+
+    assert_eq!(http_get(&pic, canister_id, "/calendar/not-a-token.ics").status_code, 404);
+
+    // Revoked feeds stop resolving
+    pic.update_call(
+        canister_id,
+        user,
+        "revoke_calendar_feed",
+        encode_one(()).unwrap(),
+    ).unwrap();
+    assert_eq!(http_get(&pic, canister_id, &path).status_code, 404);
+}
+
+// ============================================================================
+// Dashboard Tests
+// Overdue/upcoming buckets, status sections and active sprint progress
+// ============================================================================
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct PriorityBreakdown {
+    critical: u64,
+    high: u64,
+    medium: u64,
+    low: u64,
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct DashboardSection {
+    count: u64,
+    by_priority: PriorityBreakdown,
+    items: Vec<Capture>,
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct SprintSummary {
+    sprint_id: u64,
+    total_captures: u64,
+    completed_captures: u64,
+    total_estimate: u64,
+    completed_estimate: u64,
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct Dashboard {
+    now: u64,
+    overdue: DashboardSection,
+    due_today: DashboardSection,
+    due_this_week: DashboardSection,
+    in_progress: DashboardSection,
+    blocked: DashboardSection,
+    active_sprints: Vec<SprintSummary>,
+}
+
+fn get_dashboard(pic: &PocketIc, canister_id: Principal, user: Principal, now_hint: Option<u64>) -> Result<Dashboard, String> {
+    let response = pic.query_call(
+        canister_id,
+        user,
+        "get_my_dashboard",
+        encode_one(now_hint).unwrap(),
+    ).unwrap();
+
+    decode_one(&unwrap_wasm_result(response)).unwrap()
+}
+
+fn set_status(pic: &PocketIc, canister_id: Principal, user: Principal, id: u64, status: CaptureStatus) {
+    let request = UpdateCaptureRequest {
+        id,
+        title: None,
+        description: None,
+        content: None,
+        priority: None,
+        status: Some(status),
+        fields: None,
+    };
+    pic.update_call(
+        canister_id,
+        user,
+        "update_capture",
+        encode_one(request).unwrap(),
+    ).unwrap();
+}
+
+#[test]
+fn test_dashboard_due_buckets() {
+    let (pic, canister_id, user) = setup();
+    // Monday 2024-01-01 12:00 UTC
+    let now = 1_704_110_400 * 1_000_000_000;
+    let hour = 3_600 * 1_000_000_000;
+
+    let due = |title: &str, due_date: u64| {
+        create_test_capture(&pic, canister_id, user, CaptureType::Task, title, Some(DynamicFields {
+            due_date: Some(due_date),
+            ..Default::default()
+        }))
+    };
+    let overdue = due("Overdue", now - NANOS_PER_DAY);
+    let today = due("Today", now + 6 * hour);
+    let this_week = due("Wednesday", now + 2 * NANOS_PER_DAY);
+    due("Next week", now + 8 * NANOS_PER_DAY);
+    let done = due("Done and overdue", now - hour);
+    set_status(&pic, canister_id, user, done.id, CaptureStatus::Completed);
+
+    let dashboard = get_dashboard(&pic, canister_id, user, Some(now)).expect("Should get dashboard");
+    assert_eq!(dashboard.now, now);
+    assert_eq!(dashboard.overdue.count, 1);
+    assert_eq!(dashboard.overdue.items[0].id, overdue.id);
+    assert_eq!(dashboard.overdue.by_priority.medium, 1);
+    assert_eq!(dashboard.due_today.items[0].id, today.id);
+    assert_eq!(dashboard.due_this_week.count, 1);
+    assert_eq!(dashboard.due_this_week.items[0].id, this_week.id);
+}
+
+#[test]
+fn test_dashboard_status_sections_and_sprints() {
+    let (pic, canister_id, user) = setup();
+
+    let working = create_test_capture(&pic, canister_id, user, CaptureType::Task, "Working", Some(DynamicFields {
+        estimate: Some(3),
+        ..Default::default()
+    }));
+    let blocked = create_test_capture(&pic, canister_id, user, CaptureType::Task, "Blocked", Some(DynamicFields {
+        estimate: Some(5),
+        ..Default::default()
+    }));
+    set_status(&pic, canister_id, user, working.id, CaptureStatus::InProgress);
+    set_status(&pic, canister_id, user, blocked.id, CaptureStatus::Blocked);
+
+    let sprint = create_stream_sprint(&pic, canister_id, user, "Sprint", 0, u64::MAX / 2, None).unwrap();
+    activate_sprint(&pic, canister_id, user, sprint.id).expect("Should activate sprint");
+    for id in [working.id, blocked.id] {
+        pic.update_call(
+            canister_id,
+            user,
+            "add_capture_to_sprint",
+            encode_args((sprint.id, id)).unwrap(),
+        ).unwrap();
+    }
+    set_status(&pic, canister_id, user, working.id, CaptureStatus::Completed);
+
+    let dashboard = get_dashboard(&pic, canister_id, user, None).expect("Should get dashboard");
+    assert_eq!(dashboard.in_progress.count, 0);
+    assert_eq!(dashboard.blocked.count, 1);
+    assert_eq!(dashboard.active_sprints.len(), 1);
+
+    let summary = &dashboard.active_sprints[0];
+    assert_eq!(summary.sprint_id, sprint.id);
+    assert_eq!(summary.total_captures, 2);
+    assert_eq!(summary.completed_captures, 1);
+    assert_eq!(summary.total_estimate, 8);
+    assert_eq!(summary.completed_estimate, 3);
+}
+
+#[test]
+fn test_dashboard_requires_authentication() {
+    let (pic, canister_id, _user) = setup();
+
+    assert!(get_dashboard(&pic, canister_id, Principal::anonymous(), None).is_err());
+}
+
+// ============================================================================
+// Capture Hierarchy Roll-up Tests
+// ============================================================================
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct Rollup {
+    descendant_count: u64,
+    completed_count: u64,
+    total_estimate: u64,
+    remaining_estimate: u64,
+    percent_complete_by_count: u32,
+    percent_complete_by_points: u32,
+    earliest_start: Option<u64>,
+    latest_due: Option<u64>,
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct CaptureTreeNode {
+    capture: Capture,
+    depth: u32,
+    rollup: Option<Rollup>,
+}
+
+fn get_capture_tree(pic: &PocketIc, canister_id: Principal, user: Principal, root_id: u64) -> Result<Vec<CaptureTreeNode>, String> {
+    let response = pic.query_call(
+        canister_id,
+        user,
+        "get_capture_tree",
+        encode_one(root_id).unwrap(),
+    ).unwrap();
+
+    decode_one(&unwrap_wasm_result(response)).unwrap()
+}
+
+fn create_child(
+    pic: &PocketIc,
+    canister_id: Principal,
+    user: Principal,
+    parent_id: u64,
+    title: &str,
+    estimate: Option<u32>,
+) -> Capture {
+    create_test_capture(pic, canister_id, user, CaptureType::Task, title, Some(DynamicFields {
+        estimate,
+        parent_id: Some(parent_id),
+        ..Default::default()
+    }))
+}
+
+fn set_parent(pic: &PocketIc, canister_id: Principal, user: Principal, capture: &Capture, parent_id: Option<u64>) -> Result<Capture, String> {
+    let request = UpdateCaptureRequest {
+        id: capture.id,
+        title: None,
+        description: None,
+        content: None,
+        priority: None,
+        status: None,
+        fields: Some(DynamicFields {
+            parent_id,
+            ..capture.fields.clone()
+        }),
+    };
+    let response = pic.update_call(
+        canister_id,
+        user,
+        "update_capture",
+        encode_one(request).unwrap(),
+    ).unwrap();
+
+    decode_one(&unwrap_wasm_result(response)).unwrap()
+}
+
+#[test]
+fn test_rollup_through_project_epic_task() {
+    let (pic, canister_id, user) = setup();
+
+    let project = create_test_capture(&pic, canister_id, user, CaptureType::Project, "Launch", None);
+    let epic = create_child(&pic, canister_id, user, project.id, "Website", None);
+    let design = create_test_capture(&pic, canister_id, user, CaptureType::Task, "Design", Some(DynamicFields {
+        estimate: Some(3),
+        parent_id: Some(epic.id),
+        start_date: Some(1_000),
+        due_date: Some(5_000),
+        ..Default::default()
+    }));
+    create_child(&pic, canister_id, user, epic.id, "Build", Some(5));
+    create_child(&pic, canister_id, user, project.id, "Announce", Some(2));
+
+    set_status(&pic, canister_id, user, design.id, CaptureStatus::Completed);
+
+    let tree = get_capture_tree(&pic, canister_id, user, project.id).expect("Should get tree");
+    let titles: Vec<(&str, u32)> = tree.iter().map(|n| (n.capture.title.as_str(), n.depth)).collect();
+    assert_eq!(titles, vec![("Launch", 0), ("Website", 1), ("Design", 2), ("Build", 2), ("Announce", 1)]);
+
+    let rollup = tree[0].rollup.clone().expect("Project should have a roll-up");
+    assert_eq!(rollup.descendant_count, 4);
+    assert_eq!(rollup.completed_count, 1);
+    // The epic has no estimate of its own; points come from its tasks
+    assert_eq!(rollup.total_estimate, 10);
+    assert_eq!(rollup.remaining_estimate, 7);
+    assert_eq!(rollup.percent_complete_by_count, 25);
+    assert_eq!(rollup.percent_complete_by_points, 30);
+    assert_eq!(rollup.earliest_start, Some(1_000));
+    assert_eq!(rollup.latest_due, Some(5_000));
+
+    let epic_rollup = tree[1].rollup.clone().expect("Epic should have a roll-up");
+    assert_eq!(epic_rollup.total_estimate, 8);
+    assert!(tree[2].rollup.is_none());
+}
+
+#[test]
+fn test_rollup_follows_reparenting_and_deletion() {
+    let (pic, canister_id, user) = setup();
+
+    let first = create_test_capture(&pic, canister_id, user, CaptureType::Project, "First", None);
+    let second = create_test_capture(&pic, canister_id, user, CaptureType::Project, "Second", None);
+    let task = create_child(&pic, canister_id, user, first.id, "Task", Some(4));
+    let other = create_child(&pic, canister_id, user, second.id, "Other", Some(1));
+
+    set_parent(&pic, canister_id, user, &task, Some(second.id)).expect("Should move task");
+
+    let first_tree = get_capture_tree(&pic, canister_id, user, first.id).unwrap();
+    assert_eq!(first_tree.len(), 1);
+    assert!(first_tree[0].rollup.is_none());
+    let second_tree = get_capture_tree(&pic, canister_id, user, second.id).unwrap();
+    assert_eq!(second_tree[0].rollup.as_ref().unwrap().total_estimate, 5);
+
+    pic.update_call(
+        canister_id,
+        user,
+        "delete_capture",
+        encode_one(other.id).unwrap(),
+    ).unwrap();
+    let second_tree = get_capture_tree(&pic, canister_id, user, second.id).unwrap();
+    assert_eq!(second_tree[0].rollup.as_ref().unwrap().total_estimate, 4);
+}
+
+#[test]
+fn test_parent_cycles_and_foreign_parents_rejected() {
+    let (pic, canister_id, user) = setup();
+    let user_b = Principal::from_slice(&[66, 67, 68, 69, 70, 71, 72, 73, 74, 75]);
+
+    let project = create_test_capture(&pic, canister_id, user, CaptureType::Project, "Project", None);
+    let epic = create_child(&pic, canister_id, user, project.id, "Epic", None);
+    let task = create_child(&pic, canister_id, user, epic.id, "Task", Some(1));
+
+    assert!(set_parent(&pic, canister_id, user, &project, Some(task.id)).is_err());
+    assert!(set_parent(&pic, canister_id, user, &project, Some(project.id)).is_err());
+
+    let foreign = create_test_capture(&pic, canister_id, user_b, CaptureType::Project, "Foreign", None);
+    assert!(set_parent(&pic, canister_id, user, &task, Some(foreign.id)).is_err());
+    assert!(get_capture_tree(&pic, canister_id, user_b, project.id).is_err());
+}
+
+// ============================================================================
+// Capture Conversion Tests
+// ============================================================================
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug, PartialEq)]
+enum IdeaSubtype {
+    FeatureRequest,
+    Innovation,
+    Improvement,
+    Research,
+    Experiment,
+    Concept,
+    Vision,
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug, PartialEq)]
+enum ProjectSubtype {
+    Feature,
+    Initiative,
+    Epic,
+    Milestone,
+    Release,
+    Campaign,
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug, PartialEq)]
+enum CaptureSubtype {
+    Idea(IdeaSubtype),
+    Project(ProjectSubtype),
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct CaptureConversion {
+    from_type: CaptureType,
+    to_type: CaptureType,
+    remapped_fields: Vec<(String, String)>,
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct ConvertedCapture {
+    id: u64,
+    capture_type: CaptureType,
+    title: String,
+    subtype: Option<CaptureSubtype>,
+    content: Option<String>,
+    fields: DynamicFields,
+    conversions: Option<Vec<CaptureConversion>>,
+    template: Option<TemplateSource>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct ConvertCaptureRequest {
+    id: u64,
+    capture_type: CaptureType,
+    subtype: Option<CaptureSubtype>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct PromoteIdeaRequest {
+    idea_id: u64,
+    subtype: Option<ProjectSubtype>,
+    template_id: Option<u64>,
+    task_titles: Option<Vec<String>>,
+    values: Option<Vec<(String, String)>>,
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct PromoteIdeaResult {
+    project: ConvertedCapture,
+    tasks: Vec<ConvertedCapture>,
+}
+
+fn convert_capture(
+    pic: &PocketIc,
+    canister_id: Principal,
+    user: Principal,
+    request: ConvertCaptureRequest,
+) -> Result<ConvertedCapture, String> {
+    let response = pic.update_call(
+        canister_id,
+        user,
+        "convert_capture",
+        encode_one(request).unwrap(),
+    ).unwrap();
+
+    decode_one(&unwrap_wasm_result(response)).unwrap()
+}
+
+fn promote_idea(
+    pic: &PocketIc,
+    canister_id: Principal,
+    user: Principal,
+    request: PromoteIdeaRequest,
+) -> Result<PromoteIdeaResult, String> {
+    let response = pic.update_call(
+        canister_id,
+        user,
+        "promote_idea",
+        encode_one(request).unwrap(),
+    ).unwrap();
+
+    decode_one(&unwrap_wasm_result(response)).unwrap()
+}
+
+#[test]
+fn test_convert_task_to_idea_remaps_fields() {
+    let (pic, canister_id, user) = setup();
+
+    let task = create_test_capture(&pic, canister_id, user, CaptureType::Task, "Maybe later", Some(DynamicFields {
+        estimate: Some(5),
+        labels: vec!["keep".to_string()],
+        ..Default::default()
+    }));
+    let sprint = create_stream_sprint(&pic, canister_id, user, "Sprint", 0, u64::MAX / 2, None).unwrap();
+    pic.update_call(
+        canister_id,
+        user,
+        "add_capture_to_sprint",
+        encode_args((sprint.id, task.id)).unwrap(),
+    ).unwrap();
+
+    let idea = convert_capture(&pic, canister_id, user, ConvertCaptureRequest {
+        id: task.id,
+        capture_type: CaptureType::Idea,
+        subtype: Some(CaptureSubtype::Idea(IdeaSubtype::Research)),
+    }).expect("Should convert capture");
+
+    assert_eq!(idea.id, task.id);
+    assert_eq!(idea.capture_type, CaptureType::Idea);
+    assert_eq!(idea.subtype, Some(CaptureSubtype::Idea(IdeaSubtype::Research)));
+    assert_eq!(idea.fields.estimate, None);
+    assert_eq!(idea.fields.sprint_id, None);
+    assert_eq!(idea.fields.labels, vec!["keep".to_string()]);
+
+    let conversions = idea.conversions.expect("Conversion should be recorded");
+    assert_eq!(conversions.len(), 1);
+    assert_eq!(conversions[0].from_type, CaptureType::Task);
+    assert!(conversions[0].remapped_fields.contains(&("estimate".to_string(), "5".to_string())));
+}
+
+#[test]
+fn test_convert_rejects_mismatched_subtype() {
+    let (pic, canister_id, user) = setup();
+    let task = create_test_capture(&pic, canister_id, user, CaptureType::Task, "Task", None);
+
+    let result = convert_capture(&pic, canister_id, user, ConvertCaptureRequest {
+        id: task.id,
+        capture_type: CaptureType::Task,
+        subtype: Some(CaptureSubtype::Project(ProjectSubtype::Epic)),
+    });
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_promote_idea_with_template_scaffold() {
+    let (pic, canister_id, user) = setup();
+
+    let template_request = CreateTemplateRequest {
+        template_type: TemplateType::Capture,
+        name: "Launch Plan".to_string(),
+        description: None,
+        content: "## Plan\n- [ ] Write spec\n- Build prototype\nNotes".to_string(),
+        capture_type: Some(CaptureType::Project),
+        default_fields: Some(DynamicFields {
+            labels: vec!["launch".to_string()],
+            ..Default::default()
+        }),
+        is_public: Some(false),
+    };
+    let response = pic.update_call(
+        canister_id,
+        user,
+        "create_template",
+        encode_one(template_request).unwrap(),
+    ).unwrap();
+    let template: Result<Template, String> = decode_one(&unwrap_wasm_result(response)).unwrap();
+    let template = template.expect("Should create template");
+
+    let idea = create_test_capture(&pic, canister_id, user, CaptureType::Idea, "New product", None);
+    let result = promote_idea(&pic, canister_id, user, PromoteIdeaRequest {
+        idea_id: idea.id,
+        subtype: Some(ProjectSubtype::Initiative),
+        template_id: Some(template.id),
+        task_titles: None,
+        values: None,
+    }).expect("Should promote idea");
+
+    assert_eq!(result.project.id, idea.id);
+    assert_eq!(result.project.capture_type, CaptureType::Project);
+    assert_eq!(result.project.fields.labels, vec!["launch".to_string()]);
+    assert!(result.project.content.is_some());
+
+    let titles: Vec<String> = result.tasks.iter()
+        .map(|t| {
+            assert_eq!(t.fields.parent_id, Some(idea.id));
+            t.title.clone()
+        })
+        .collect();
+    assert_eq!(titles, vec!["Write spec".to_string(), "Build prototype".to_string()]);
+
+    let tree = get_capture_tree(&pic, canister_id, user, idea.id).unwrap();
+    assert_eq!(tree[0].rollup.as_ref().unwrap().descendant_count, 2);
+
+    // Only ideas can be promoted
+    assert!(promote_idea(&pic, canister_id, user, PromoteIdeaRequest {
+        idea_id: idea.id,
+        subtype: None,
+        template_id: None,
+        task_titles: None,
+        values: None,
+    }).is_err());
+}
+
+// ============================================================================
+// Checklist Tests
+// ============================================================================
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct ChecklistItem {
+    id: u64,
+    text: String,
+    checked: bool,
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct Checklist {
+    items: Vec<ChecklistItem>,
+    completed_count: u32,
+    total_count: u32,
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct ChecklistCapture {
+    id: u64,
+    title: String,
+    status: CaptureStatus,
+    fields: DynamicFields,
+    checklist: Option<Checklist>,
+}
+
+fn checklist_call<A: candid::utils::ArgumentEncoder>(
+    pic: &PocketIc,
+    canister_id: Principal,
+    user: Principal,
+    method: &str,
+    args: A,
+) -> Result<ChecklistCapture, String> {
+    let response = pic.update_call(
+        canister_id,
+        user,
+        method,
+        encode_args(args).unwrap(),
+    ).unwrap();
+
+    decode_one(&unwrap_wasm_result(response)).unwrap()
+}
+
+fn checklist_texts(capture: &ChecklistCapture) -> Vec<String> {
+    capture.checklist.as_ref()
+        .map(|c| c.items.iter().map(|item| item.text.clone()).collect())
+        .unwrap_or_default()
+}
+
+#[test]
+fn test_checklist_add_check_reorder_remove() {
+    let (pic, canister_id, user) = setup();
+    let task = create_test_capture(&pic, canister_id, user, CaptureType::Task, "Release", None);
+
+    for text in ["Tag", "Build", "Publish"] {
+        checklist_call(&pic, canister_id, user, "add_checklist_item", (task.id, text.to_string(), None::<u32>))
+            .expect("Should add item");
+    }
+    let capture = checklist_call(&pic, canister_id, user, "add_checklist_item", (task.id, "Changelog".to_string(), Some(0u32)))
+        .expect("Should insert item");
+    assert_eq!(checklist_texts(&capture), vec!["Changelog", "Tag", "Build", "Publish"]);
+
+    let ids: Vec<u64> = capture.checklist.unwrap().items.iter().map(|item| item.id).collect();
+    let capture = checklist_call(&pic, canister_id, user, "check_checklist_item", (task.id, ids[1], true))
+        .expect("Should check item");
+    let checklist = capture.checklist.unwrap();
+    assert_eq!(checklist.completed_count, 1);
+    assert_eq!(checklist.total_count, 4);
+
+    let capture = checklist_call(&pic, canister_id, user, "move_checklist_item", (task.id, ids[0], 10u32))
+        .expect("Should move item");
+    assert_eq!(checklist_texts(&capture), vec!["Tag", "Build", "Publish", "Changelog"]);
+
+    let capture = checklist_call(&pic, canister_id, user, "remove_checklist_item", (task.id, ids[1]))
+        .expect("Should remove item");
+    assert_eq!(checklist_texts(&capture), vec!["Build", "Publish", "Changelog"]);
+    assert_eq!(capture.checklist.unwrap().completed_count, 0);
+
+    assert!(checklist_call(&pic, canister_id, user, "remove_checklist_item", (task.id, ids[1])).is_err());
+    assert!(checklist_call(&pic, canister_id, user, "add_checklist_item", (task.id, "  ".to_string(), None::<u32>)).is_err());
+}
+
+#[test]
+fn test_promote_checklist_item_to_child_task() {
+    let (pic, canister_id, user) = setup();
+    let project = create_test_capture(&pic, canister_id, user, CaptureType::Project, "Website", None);
+
+    let capture = checklist_call(&pic, canister_id, user, "add_checklist_item", (project.id, "Write copy".to_string(), None::<u32>))
+        .expect("Should add item");
+    let item_id = capture.checklist.unwrap().items[0].id;
+    checklist_call(&pic, canister_id, user, "check_checklist_item", (project.id, item_id, true)).unwrap();
+
+    let task = checklist_call(&pic, canister_id, user, "promote_checklist_item", (project.id, item_id))
+        .expect("Should promote item");
+    assert_eq!(task.title, "Write copy");
+    assert_eq!(task.fields.parent_id, Some(project.id));
+    assert_eq!(task.status, CaptureStatus::Completed);
+
+    let tree = get_capture_tree(&pic, canister_id, user, project.id).unwrap();
+    assert_eq!(tree.len(), 2);
+    assert_eq!(tree[0].rollup.as_ref().unwrap().completed_count, 1);
+}
+
+#[test]
+fn test_failed_checklist_edit_leaves_capture_unchanged() {
+    let (pic, canister_id, user) = setup();
+    let task = create_test_capture(&pic, canister_id, user, CaptureType::Task, "No list", None);
+
+    assert!(checklist_call(&pic, canister_id, user, "check_checklist_item", (task.id, 1u64, true)).is_err());
+
+    let response = pic.query_call(canister_id, user, "get_capture", encode_one(task.id).unwrap()).unwrap();
+    let capture: Option<ChecklistCapture> = decode_one(&unwrap_wasm_result(response)).unwrap();
+    assert!(capture.unwrap().checklist.is_none());
+}
+
+#[test]
+fn test_checklist_requires_ownership() {
+    let (pic, canister_id, user) = setup();
+    let user_b = Principal::from_slice(&[76, 77, 78, 79, 80, 81, 82, 83, 84, 85]);
+    let task = create_test_capture(&pic, canister_id, user, CaptureType::Task, "Mine", None);
+
+    let result = checklist_call(&pic, canister_id, user_b, "add_checklist_item", (task.id, "Sneaky".to_string(), None::<u32>));
+    assert!(result.is_err());
+}
+
+// ============================================================================
+// Time Tracking Tests
+// ============================================================================
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct TimeEntry {
+    id: u64,
+    capture_id: u64,
+    started_at: u64,
+    ended_at: Option<u64>,
+    manual: bool,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct CreateTimeEntryRequest {
+    capture_id: u64,
+    started_at: u64,
+    ended_at: u64,
+    note: Option<String>,
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct CaptureTime {
+    capture_id: u64,
+    duration: u64,
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct TimeReport {
+    total_duration: u64,
+    by_capture: Vec<CaptureTime>,
+    entries: Vec<TimeEntry>,
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct SprintAccuracy {
+    sprint_id: u64,
+    completed_points: u64,
+    logged_duration: u64,
+    duration_per_point: Option<u64>,
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct EstimateAccuracyReport {
+    sprints: Vec<SprintAccuracy>,
+    completed_points: u64,
+    logged_duration: u64,
+    duration_per_point: Option<u64>,
+}
+
+fn time_call<T: for<'de> Deserialize<'de> + CandidType, A: candid::utils::ArgumentEncoder>(
+    pic: &PocketIc,
+    canister_id: Principal,
+    user: Principal,
+    method: &str,
+    args: A,
+) -> Result<T, String> {
+    let response = pic.update_call(
+        canister_id,
+        user,
+        method,
+        encode_args(args).unwrap(),
+    ).unwrap();
+
+    decode_one(&unwrap_wasm_result(response)).unwrap()
+}
+
+fn log_time(pic: &PocketIc, canister_id: Principal, user: Principal, capture_id: u64, started_at: u64, ended_at: u64) -> Result<TimeEntry, String> {
+    let request = CreateTimeEntryRequest {
+        capture_id,
+        started_at,
+        ended_at,
+        note: None,
+    };
+    time_call(pic, canister_id, user, "add_time_entry", (request,))
+}
+
+#[test]
+fn test_timer_start_stop_and_single_running_guard() {
+    let (pic, canister_id, user) = setup();
+    let first = create_test_capture(&pic, canister_id, user, CaptureType::Task, "First", None);
+    let second = create_test_capture(&pic, canister_id, user, CaptureType::Task, "Second", None);
+
+    let entry: TimeEntry = time_call(&pic, canister_id, user, "start_timer", (first.id, None::<String>))
+        .expect("Should start timer");
+    assert!(entry.ended_at.is_none());
+
+    let second_timer: Result<TimeEntry, String> = time_call(&pic, canister_id, user, "start_timer", (second.id, None::<String>));
+    assert!(second_timer.is_err(), "Only one timer may run at a time");
+
+    pic.advance_time(std::time::Duration::from_secs(90 * 60));
+    pic.tick();
+
+    let stopped: TimeEntry = time_call(&pic, canister_id, user, "stop_timer", ()).expect("Should stop timer");
+    assert_eq!(stopped.id, entry.id);
+    assert!(stopped.ended_at.unwrap() - stopped.started_at >= 90 * 60 * 1_000_000_000);
+
+    let stop_again: Result<TimeEntry, String> = time_call(&pic, canister_id, user, "stop_timer", ());
+    assert!(stop_again.is_err());
+
+    // The second capture can be timed once the first timer stopped
+    let _: TimeEntry = time_call(&pic, canister_id, user, "start_timer", (second.id, None::<String>))
+        .expect("Should start a new timer");
+
+    let report: TimeReport = time_call(&pic, canister_id, user, "get_capture_time", (first.id,)).unwrap();
+    assert_eq!(report.entries.len(), 1);
+    assert_eq!(report.total_duration, stopped.ended_at.unwrap() - stopped.started_at);
+}
+
+#[test]
+fn test_manual_entries_and_range_report() {
+    let (pic, canister_id, user) = setup();
+    let task = create_test_capture(&pic, canister_id, user, CaptureType::Task, "Task", None);
+    let now = pic_now(&pic);
+    let hour = 3_600 * 1_000_000_000;
+
+    log_time(&pic, canister_id, user, task.id, now - 5 * hour, now - 3 * hour).expect("Should log time");
+    log_time(&pic, canister_id, user, task.id, now - 2 * hour, now - hour).expect("Should log time");
+    assert!(log_time(&pic, canister_id, user, task.id, now - hour, now - 2 * hour).is_err());
+    assert!(log_time(&pic, canister_id, user, task.id, now - 30 * hour, now - hour).is_err());
+    assert!(log_time(&pic, canister_id, user, task.id, now, now + hour).is_err());
+
+    // The range cuts the first entry in half
+    let range = CalendarRange { start: now - 4 * hour, end: now };
+    let report: TimeReport = time_call(&pic, canister_id, user, "get_time_report", (range,)).unwrap();
+    assert_eq!(report.total_duration, 2 * hour);
+    assert_eq!(report.by_capture.len(), 1);
+    assert_eq!(report.entries.len(), 2);
+    assert!(report.entries[0].manual);
+}
+
+#[test]
+fn test_estimate_accuracy_over_completed_sprints() {
+    let (pic, canister_id, user) = setup();
+    let now = pic_now(&pic);
+    let hour = 3_600 * 1_000_000_000;
+
+    let sprint = create_stream_sprint(&pic, canister_id, user, "Done", 0, u64::MAX / 2, None).unwrap();
+    let done = create_test_capture(&pic, canister_id, user, CaptureType::Task, "Done", Some(DynamicFields {
+        estimate: Some(4),
+        ..Default::default()
+    }));
+    let open = create_test_capture(&pic, canister_id, user, CaptureType::Task, "Open", Some(DynamicFields {
+        estimate: Some(8),
+        ..Default::default()
+    }));
+    for id in [done.id, open.id] {
+        pic.update_call(
+            canister_id,
+            user,
+            "add_capture_to_sprint",
+            encode_args((sprint.id, id)).unwrap(),
+        ).unwrap();
+    }
+    log_time(&pic, canister_id, user, done.id, now - 9 * hour, now - hour).unwrap();
+    log_time(&pic, canister_id, user, open.id, now - 2 * hour, now - hour).unwrap();
+    set_status(&pic, canister_id, user, done.id, CaptureStatus::Completed);
+
+    let sprint_time: TimeReport = time_call(&pic, canister_id, user, "get_sprint_time", (sprint.id,)).unwrap();
+    assert_eq!(sprint_time.total_duration, 9 * hour);
+
+    activate_sprint(&pic, canister_id, user, sprint.id).expect("Should activate sprint");
+    let complete = UpdateSprintRequest {
+        name: None,
+        goal: None,
+        status: Some(SprintStatus::Completed),
+        start_date: None,
+        end_date: None,
+        capacity: None,
+    };
+    pic.update_call(
+        canister_id,
+        user,
+        "update_sprint",
+        encode_args((sprint.id, complete)).unwrap(),
+    ).unwrap();
+
+    let report: EstimateAccuracyReport = time_call(&pic, canister_id, user, "get_estimate_accuracy", ()).unwrap();
+    assert_eq!(report.sprints.len(), 1);
+    // Only completed captures count: 4 points in 8 hours
+    assert_eq!(report.completed_points, 4);
+    assert_eq!(report.logged_duration, 8 * hour);
+    assert_eq!(report.duration_per_point, Some(2 * hour));
+    assert_eq!(report.sprints[0].sprint_id, sprint.id);
+}
+
+// ============================================================================
+// Comment & Activity Tests
+// ============================================================================
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug, PartialEq)]
+enum CommentTarget {
+    Capture(u64),
+    Document(u64),
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct ThreadComment {
+    id: u64,
+    target: CommentTarget,
+    author_type: AuthorType,
+    content: String,
+    is_retracted: bool,
+    retracted_at: Option<u64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct AddThreadCommentRequest {
+    target: CommentTarget,
+    content: String,
+    author_type: AuthorType,
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug, PartialEq)]
+enum ActivityEvent {
+    Created,
+    FieldChanged {
+        field: String,
+        old_value: Option<String>,
+        new_value: Option<String>,
+    },
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct ActivityRecord {
+    id: u64,
+    event: ActivityEvent,
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+enum ActivityItem {
+    Comment(ThreadComment),
+    Activity(ActivityRecord),
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug)]
+struct PaginatedActivity {
+    items: Vec<ActivityItem>,
+    total: u64,
+}
+
+fn add_thread_comment(
+    pic: &PocketIc,
+    canister_id: Principal,
+    user: Principal,
+    target: CommentTarget,
+    content: &str,
+    author_type: AuthorType,
+) -> Result<ThreadComment, String> {
+    let request = AddThreadCommentRequest {
+        target,
+        content: content.to_string(),
+        author_type,
+    };
+    time_call(pic, canister_id, user, "add_thread_comment", (request,))
+}
+
+fn get_activity_stream(pic: &PocketIc, canister_id: Principal, user: Principal, target: CommentTarget) -> Result<PaginatedActivity, String> {
+    let response = pic.query_call(
+        canister_id,
+        user,
+        "get_activity_stream",
+        encode_args((target, PaginationParams::default())).unwrap(),
+    ).unwrap();
+
+    decode_one(&unwrap_wasm_result(response)).unwrap()
+}
+
+#[test]
+fn test_capture_comments_and_activity_stream() {
+    let (pic, canister_id, user) = setup();
+    let task = create_test_capture(&pic, canister_id, user, CaptureType::Task, "Draft plan", None);
+    let target = CommentTarget::Capture(task.id);
+
+    let human = add_thread_comment(&pic, canister_id, user, target.clone(), "Looks good", AuthorType::Human)
+        .expect("Should add comment");
+    let agent = AuthorType::Agent { agent_id: "planner".to_string() };
+    let tagged = add_thread_comment(&pic, canister_id, user, target.clone(), "Suggest splitting this", agent.clone())
+        .expect("Should add agent comment");
+    assert_eq!(tagged.author_type, agent);
+    assert!(add_thread_comment(&pic, canister_id, user, target.clone(), "   ", AuthorType::Human).is_err());
+
+    let update_request = UpdateCaptureRequest {
+        id: task.id,
+        title: Some("Final plan".to_string()),
+        description: None,
+        content: None,
+        priority: None,
+        status: Some(CaptureStatus::InProgress),
+        fields: None,
+    };
+    let _: Result<Capture, String> = time_call(&pic, canister_id, user, "update_capture", (update_request,));
+
+    let retracted: ThreadComment = time_call(&pic, canister_id, user, "retract_thread_comment", (human.id,))
+        .expect("Should retract comment");
+    assert!(retracted.is_retracted);
+    let again: Result<ThreadComment, String> = time_call(&pic, canister_id, user, "retract_thread_comment", (human.id,));
+    assert!(again.is_err(), "A comment can only be retracted once");
+
+    let stream = get_activity_stream(&pic, canister_id, user, target).expect("Should get stream");
+    // Created, 2 comments, title and status changes
+    assert_eq!(stream.total, 5);
+    let changed: Vec<String> = stream.items.iter().filter_map(|item| match item {
+        ActivityItem::Activity(ActivityRecord { event: ActivityEvent::FieldChanged { field, .. }, .. }) => Some(field.clone()),
+        _ => None,
+    }).collect();
+    assert!(changed.contains(&"title".to_string()));
+    assert!(changed.contains(&"status".to_string()));
+    assert!(matches!(stream.items.last(), Some(ActivityItem::Activity(ActivityRecord { event: ActivityEvent::Created, .. }))));
+    assert!(stream.items.iter().any(|item| matches!(item, ActivityItem::Comment(c) if c.id == human.id && c.is_retracted)));
+}
+
+#[test]
+fn test_document_thread_requires_ownership() {
+    let (pic, canister_id, user) = setup();
+    let other = Principal::from_slice(&[86, 87, 88, 89, 90, 91, 92, 93, 94, 95]);
+
+    let workspace_request = CreateWorkspaceRequest {
+        name: "Notes".to_string(),
+        description: None,
+        icon: None,
+        parent_id: None,
+    };
+    let workspace: Result<Workspace, String> = time_call(&pic, canister_id, user, "create_workspace", (workspace_request,));
+    let doc_request = CreateDocumentRequest {
+        workspace_id: workspace.unwrap().id,
+        title: "Spec".to_string(),
+        content: Some("v1".to_string()),
+        template_id: None,
+        parent_id: None,
+    };
+    let doc: Result<Document, String> = time_call(&pic, canister_id, user, "create_document", (doc_request,));
+    let target = CommentTarget::Document(doc.unwrap().id);
+
+    add_thread_comment(&pic, canister_id, user, target.clone(), "Needs a diagram", AuthorType::Human)
+        .expect("Owner should comment on document");
+    assert!(add_thread_comment(&pic, canister_id, other, target.clone(), "Hi", AuthorType::Human).is_err());
+    assert!(get_activity_stream(&pic, canister_id, other, target.clone()).is_err());
+
+    let stream = get_activity_stream(&pic, canister_id, user, target).expect("Should get stream");
+    assert_eq!(stream.total, 2);
+}
+
+// ============================================================================
+// Mention & Assignment Tests
+// ============================================================================
+
+fn set_handle(pic: &PocketIc, canister_id: Principal, user: Principal, handle: &str) -> Result<String, String> {
+    time_call(pic, canister_id, user, "set_handle", (handle.to_string(),))
+}
+
+fn get_assigned_to_me(pic: &PocketIc, canister_id: Principal, user: Principal) -> PaginatedCaptureResponse {
+    let response = pic.query_call(
+        canister_id,
+        user,
+        "get_assigned_to_me",
+        encode_args((None::<bool>, None::<PaginationParams>)).unwrap(),
+    ).unwrap();
+
+    let result: Result<PaginatedCaptureResponse, String> = decode_one(&unwrap_wasm_result(response)).unwrap();
+    result.unwrap()
+}
+
+#[test]
+fn test_assignees_resolve_and_notify() {
+    let (pic, canister_id, user) = setup();
+    let bob = Principal::from_slice(&[96, 97, 98, 99, 100, 101, 102, 103, 104, 105]);
+
+    assert_eq!(set_handle(&pic, canister_id, bob, "@Bob").unwrap(), "bob");
+    assert!(set_handle(&pic, canister_id, user, "bob").is_err(), "Handles are unique");
+    assert!(set_handle(&pic, canister_id, user, "a b").is_err());
+
+    let unknown = CreateCaptureRequest {
+        capture_type: CaptureType::Task,
+        title: "Unknown".to_string(),
+        description: None,
+        content: None,
+        priority: None,
+        fields: Some(DynamicFields { assignees: vec!["@nobody".to_string()], ..Default::default() }),
+    };
+    let rejected: Result<Capture, String> = time_call(&pic, canister_id, user, "create_capture", (unknown,));
+    assert!(rejected.is_err(), "Unknown assignees are rejected");
+
+    let fields = DynamicFields { assignees: vec!["@bob".to_string()], ..Default::default() };
+    let task = create_test_capture(&pic, canister_id, user, CaptureType::Task, "Review budget", Some(fields));
+
+    let assigned = get_assigned_to_me(&pic, canister_id, bob);
+    assert_eq!(assigned.items.len(), 1);
+    assert_eq!(assigned.items[0].id, task.id);
+    assert!(get_assigned_to_me(&pic, canister_id, user).items.is_empty());
+
+    let inbox = get_notifications(&pic, canister_id, bob, false);
+    assert_eq!(inbox.total, 1);
+    assert_eq!(inbox.items[0].capture_id, Some(task.id));
+
+    // Unassigning removes the capture from the assignee's list
+    let update_request = UpdateCaptureRequest {
+        id: task.id,
+        title: None,
+        description: None,
+        content: None,
+        priority: None,
+        status: None,
+        fields: Some(DynamicFields::default()),
+    };
+    let _: Result<Capture, String> = time_call(&pic, canister_id, user, "update_capture", (update_request,));
+    assert!(get_assigned_to_me(&pic, canister_id, bob).items.is_empty());
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+enum SessionValidationResult {
+    Ok(String),
+    Err(String),
+}
+
+fn leb128(mut value: u32, signed: bool, out: &mut Vec<u8>) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        let done = value == 0 && (!signed || byte & 0x40 == 0);
+        out.push(if done { byte } else { byte | 0x80 });
+        if done {
+            break;
+        }
+    }
+}
+
+fn wasm_section(module: &mut Vec<u8>, id: u8, contents: &[u8]) {
+    module.push(id);
+    leb128(contents.len() as u32, false, module);
+    module.extend_from_slice(contents);
+}
+
+/// A minimal auth-service canister whose `validate_access_token` accepts any
+/// token as the given session user
+fn mock_auth_service_wasm(user_id: &str) -> Vec<u8> {
+    let reply = encode_one(SessionValidationResult::Ok(user_id.to_string())).unwrap();
+    let name = |bytes: &mut Vec<u8>, name: &str| {
+        leb128(name.len() as u32, false, bytes);
+        bytes.extend_from_slice(name.as_bytes());
+    };
+
+    let mut module = b"\0asm\x01\0\0\0".to_vec();
+    // (i32, i32) -> () and () -> ()
+    wasm_section(&mut module, 1, &[2, 0x60, 2, 0x7f, 0x7f, 0, 0x60, 0, 0]);
+    let mut imports = vec![2];
+    for (import, type_index) in [("msg_reply_data_append", 0), ("msg_reply", 1)] {
+        name(&mut imports, "ic0");
+        name(&mut imports, import);
+        imports.extend_from_slice(&[0, type_index]);
+    }
+    wasm_section(&mut module, 2, &imports);
+    wasm_section(&mut module, 3, &[1, 1]);
+    wasm_section(&mut module, 5, &[1, 0, 1]);
+    let mut exports = vec![1];
+    name(&mut exports, "canister_update validate_access_token");
+    exports.extend_from_slice(&[0, 2]);
+    wasm_section(&mut module, 7, &exports);
+
+    let mut body = vec![0, 0x41, 0, 0x41];
+    leb128(reply.len() as u32, true, &mut body);
+    body.extend_from_slice(&[0x10, 0, 0x10, 1, 0x0b]);
+    let mut code = vec![1];
+    leb128(body.len() as u32, false, &mut code);
+    code.extend_from_slice(&body);
+    wasm_section(&mut module, 10, &code);
+
+    let mut data = vec![1, 0, 0x41, 0, 0x0b];
+    leb128(reply.len() as u32, false, &mut data);
+    data.extend_from_slice(&reply);
+    wasm_section(&mut module, 11, &data);
+    module
+}
+
+/// Install a mock auth service so every access token authenticates as `user_id`
+fn setup_session_user(pic: &PocketIc, canister_id: Principal, user_id: &str) {
+    let auth_service = pic.create_canister();
+    pic.add_cycles(auth_service, 2_000_000_000_000);
+    pic.install_canister(auth_service, mock_auth_service_wasm(user_id), vec![], None);
+
+    let controller = Principal::from_text("aaaaa-aa").unwrap();
+    let configured: Result<(), String> = time_call(pic, canister_id, controller, "set_auth_service", (auth_service,));
+    configured.unwrap();
+}
+
+#[test]
+fn test_session_user_ids_resolve_as_assignees() {
+    let (pic, canister_id, user) = setup();
+    setup_session_user(&pic, canister_id, "alice-id");
+
+    // Session user_ids are only accepted once the user owns something
+    let agenda_request = |assignee: &str| CreateCaptureRequest {
+        capture_type: CaptureType::Task,
+        title: "Draft agenda".to_string(),
+        description: None,
+        content: None,
+        priority: None,
+        fields: Some(DynamicFields { assignees: vec![assignee.to_string()], ..Default::default() }),
+    };
+    let create_with_token = |request: CreateCaptureRequest| -> Result<Capture, String> {
+        time_call(&pic, canister_id, Principal::anonymous(), "create_capture_with_token", ("token".to_string(), request))
+    };
+    assert!(create_with_token(agenda_request("user:alice-id")).unwrap_err().contains("Unknown assignee"));
+    let mut notes = agenda_request("user:alice-id");
+    notes.fields = None;
+    create_with_token(notes).unwrap();
+    let agenda = create_with_token(agenda_request("user:alice-id")).unwrap();
+    assert!(create_with_token(agenda_request("user:carol-id")).unwrap_err().contains("Unknown assignee"));
+
+    let fields = DynamicFields { assignees: vec!["alice-id".to_string()], ..Default::default() };
+    let task = create_test_capture(&pic, canister_id, user, CaptureType::Task, "Book venue", Some(fields));
+    let unknown = CreateCaptureRequest {
+        capture_type: CaptureType::Task,
+        title: "Unknown".to_string(),
+        description: None,
+        content: None,
+        priority: None,
+        fields: Some(DynamicFields { assignees: vec!["carol-id".to_string()], ..Default::default() }),
+    };
+    let rejected: Result<Capture, String> = time_call(&pic, canister_id, user, "create_capture", (unknown,));
+    assert!(rejected.unwrap_err().contains("Unknown assignee"));
+
+    let assigned: Result<PaginatedCaptureResponse, String> = time_call(
+        &pic,
+        canister_id,
+        Principal::anonymous(),
+        "get_assigned_to_me_with_token",
+        ("token".to_string(), None::<bool>, None::<PaginationParams>),
+    );
+    let mut ids: Vec<u64> = assigned.unwrap().items.iter().map(|c| c.id).collect();
+    ids.sort();
+    assert_eq!(ids, vec![agenda.id, task.id]);
+}
+
+#[test]
+fn test_mentions_in_content_and_comments_notify_once() {
+    let (pic, canister_id, user) = setup();
+    let bob = Principal::from_slice(&[96, 97, 98, 99, 100, 101, 102, 103, 104, 105]);
+    set_handle(&pic, canister_id, bob, "bob").unwrap();
+
+    let task = create_test_capture(&pic, canister_id, user, CaptureType::Task, "Launch", None);
+    for content in ["Ask @bob (not bob@example.com)", "Ask @bob again"] {
+        let update_request = UpdateCaptureRequest {
+            id: task.id,
+            title: None,
+            description: None,
+            content: Some(content.to_string()),
+            priority: None,
+            status: None,
+            fields: None,
+        };
+        let _: Result<Capture, String> = time_call(&pic, canister_id, user, "update_capture", (update_request,));
+    }
+    // The second edit keeps the existing mention and does not notify again
+    assert_eq!(get_notifications(&pic, canister_id, bob, false).total, 1);
+
+    add_thread_comment(&pic, canister_id, user, CommentTarget::Capture(task.id), "@bob @ghost thoughts?", AuthorType::Human)
+        .expect("Should add comment");
+    let inbox = get_notifications(&pic, canister_id, bob, false);
+    assert_eq!(inbox.total, 2);
+    assert!(inbox.items.iter().all(|n| n.capture_id == Some(task.id)));
+}
+
+// ============================================================================
+// Saved View Tests
+// ============================================================================
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+enum DateField {
+    Due,
+    Start,
+    Created,
+    Updated,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+enum RelativeWindow {
+    Last(u64),
+    Next(u64),
+    Past,
+    Today,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct DateCondition {
+    field: DateField,
+    window: RelativeWindow,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+enum ViewSortField {
+    Rank,
+    DueDate,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct ViewSort {
+    field: ViewSortField,
+    descending: bool,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+enum ViewGrouping {
+    Status,
+    Label,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct SavedView {
+    id: u64,
+    name: String,
+    columns: Vec<String>,
+    workspace_id: Option<u64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct CreateSavedViewRequest {
+    name: String,
+    filter: Option<CaptureFilter>,
+    sort: Option<ViewSort>,
+    group_by: Option<ViewGrouping>,
+    columns: Option<Vec<String>>,
+    workspace_id: Option<u64>,
+    shared_with: Option<Vec<String>>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct ViewGroup {
+    key: String,
+    captures: Vec<Capture>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct SavedViewResult {
+    groups: Vec<ViewGroup>,
+    total: u64,
+}
+
+fn run_saved_view(pic: &PocketIc, canister_id: Principal, user: Principal, id: u64) -> Result<SavedViewResult, String> {
+    let response = pic.query_call(
+        canister_id,
+        user,
+        "run_saved_view",
+        encode_args((id, None::<PaginationParams>)).unwrap(),
+    ).unwrap();
+
+    decode_one(&unwrap_wasm_result(response)).unwrap()
+}
+
+#[test]
+fn test_saved_view_relative_dates_are_evaluated_at_query_time() {
+    let (pic, canister_id, user) = setup();
+    let now = pic_now(&pic);
+    let day = 86_400 * 1_000_000_000;
+
+    for (title, due) in [("Soon", Some(now + 3 * day)), ("Later", Some(now + 10 * day)), ("Undated", None)] {
+        let fields = DynamicFields { due_date: due, ..Default::default() };
+        create_test_capture(&pic, canister_id, user, CaptureType::Task, title, Some(fields));
+    }
+
+    let request = CreateSavedViewRequest {
+        name: "Due this week".to_string(),
+        filter: Some(CaptureFilter {
+            dates: Some(vec![DateCondition { field: DateField::Due, window: RelativeWindow::Next(7 * day) }]),
+            ..Default::default()
+        }),
+        sort: Some(ViewSort { field: ViewSortField::DueDate, descending: false }),
+        group_by: Some(ViewGrouping::Status),
+        columns: Some(vec!["title".to_string(), "due_date".to_string()]),
+        workspace_id: None,
+        shared_with: None,
+    };
+    let view: SavedView = time_call(&pic, canister_id, user, "create_saved_view", (request,))
+        .expect("Should save view");
+
+    let result = run_saved_view(&pic, canister_id, user, view.id).unwrap();
+    assert_eq!(result.total, 1);
+    assert_eq!(result.groups.len(), 1);
+    assert_eq!(result.groups[0].key, "Draft");
+    assert_eq!(result.groups[0].captures[0].title, "Soon");
+
+    // Four days later "Soon" has passed and "Later" is within the window
+    pic.advance_time(std::time::Duration::from_secs(4 * 86_400));
+    pic.tick();
+    let result = run_saved_view(&pic, canister_id, user, view.id).unwrap();
+    assert_eq!(result.total, 1);
+    assert_eq!(result.groups[0].captures[0].title, "Later");
+}
+
+#[test]
+fn test_saved_view_pinned_and_shared() {
+    let (pic, canister_id, user) = setup();
+    let bob = Principal::from_slice(&[96, 97, 98, 99, 100, 101, 102, 103, 104, 105]);
+    let stranger = Principal::from_slice(&[86, 87, 88, 89, 90, 91, 92, 93, 94, 95]);
+    set_handle(&pic, canister_id, bob, "bob").unwrap();
+
+    let workspace_request = CreateWorkspaceRequest {
+        name: "Launch".to_string(),
+        description: None,
+        icon: None,
+        parent_id: None,
+    };
+    let workspace: Result<Workspace, String> = time_call(&pic, canister_id, user, "create_workspace", (workspace_request,));
+    let workspace_id = workspace.unwrap().id;
+
+    let in_workspace = DynamicFields { workspace_id: Some(workspace_id), ..Default::default() };
+    create_test_capture(&pic, canister_id, user, CaptureType::Task, "Pinned", Some(in_workspace));
+    create_test_capture(&pic, canister_id, user, CaptureType::Task, "Elsewhere", None);
+
+    let request = CreateSavedViewRequest {
+        name: "Launch tasks".to_string(),
+        filter: None,
+        sort: None,
+        group_by: None,
+        columns: None,
+        workspace_id: Some(workspace_id),
+        shared_with: Some(vec!["@bob".to_string()]),
+    };
+    let view: SavedView = time_call(&pic, canister_id, user, "create_saved_view", (request,))
+        .expect("Should save view");
+
+    let response = pic.query_call(canister_id, bob, "get_my_saved_views", encode_one(Some(workspace_id)).unwrap()).unwrap();
+    let shared: Result<Vec<SavedView>, String> = decode_one(&unwrap_wasm_result(response)).unwrap();
+    assert_eq!(shared.unwrap().len(), 1);
+
+    let result = run_saved_view(&pic, canister_id, bob, view.id).expect("Shared user should run the view");
+    assert_eq!(result.total, 1);
+    assert_eq!(result.groups[0].captures[0].title, "Pinned");
+
+    assert!(run_saved_view(&pic, canister_id, stranger, view.id).is_err());
+    let deleted: Result<SavedView, String> = time_call(&pic, canister_id, bob, "delete_saved_view", (view.id,));
+    assert!(deleted.is_err(), "Only the owner can delete a view");
+}
+
+#[test]
+fn test_saved_view_shared_with_session_user() {
+    let (pic, canister_id, user) = setup();
+    setup_session_user(&pic, canister_id, "alice-id");
+    create_test_capture(&pic, canister_id, user, CaptureType::Task, "Shared task", None);
+    let alice_capture = CreateCaptureRequest {
+        capture_type: CaptureType::Task,
+        title: "Alice's task".to_string(),
+        description: None,
+        content: None,
+        priority: None,
+        fields: None,
+    };
+    let created: Result<Capture, String> =
+        time_call(&pic, canister_id, Principal::anonymous(), "create_capture_with_token", ("token".to_string(), alice_capture));
+    created.unwrap();
+
+    let request = CreateSavedViewRequest {
+        name: "Team tasks".to_string(),
+        filter: None,
+        sort: None,
+        group_by: None,
+        columns: None,
+        workspace_id: None,
+        shared_with: Some(vec!["user:alice-id".to_string()]),
+    };
+    let view: SavedView = time_call(&pic, canister_id, user, "create_saved_view", (request,))
+        .expect("Should share with a session user");
+
+    let shared: Result<Vec<SavedView>, String> = time_call(
+        &pic,
+        canister_id,
+        Principal::anonymous(),
+        "get_my_saved_views_with_token",
+        ("token".to_string(), None::<u64>),
+    );
+    assert_eq!(shared.unwrap().iter().map(|v| v.id).collect::<Vec<_>>(), vec![view.id]);
+}
+
+// ============================================================================
+// Capture Query Tests
+// ============================================================================
+
+#[derive(CandidType, Serialize, Deserialize, Debug, PartialEq)]
+struct QueryError {
+    position: u32,
+    message: String,
+}
+
+fn query_captures(pic: &PocketIc, canister_id: Principal, user: Principal, query: &str) -> Result<Vec<String>, String> {
+    let response = pic.query_call(
+        canister_id,
+        user,
+        "query_captures",
+        encode_args((query.to_string(), None::<PaginationParams>)).unwrap(),
+    ).unwrap();
+
+    let result: Result<PaginatedCaptureResponse, String> = decode_one(&unwrap_wasm_result(response)).unwrap();
+    result.map(|page| page.items.into_iter().map(|c| c.title).collect())
+}
+
+#[test]
+fn test_query_captures_language() {
+    let (pic, canister_id, user) = setup();
+    let now = pic_now(&pic);
+    let day = 86_400 * 1_000_000_000;
+
+    let backend = |estimate: u32, due: u64| DynamicFields {
+        estimate: Some(estimate),
+        due_date: Some(due),
+        labels: vec!["backend".to_string()],
+        ..Default::default()
+    };
+    create_test_capture(&pic, canister_id, user, CaptureType::Task, "Index rebuild", Some(backend(5, now + 2 * day)));
+    create_test_capture(&pic, canister_id, user, CaptureType::Task, "Small fix", Some(backend(1, now + 2 * day)));
+    create_test_capture(&pic, canister_id, user, CaptureType::Task, "Late migration", Some(backend(8, now + 30 * day)));
+    create_test_capture(&pic, canister_id, user, CaptureType::Idea, "Index ideas", None);
+
+    assert_eq!(
+        query_captures(&pic, canister_id, user, "type:task label:backend due<+7d estimate>=3").unwrap(),
+        vec!["Index rebuild"]
+    );
+    assert_eq!(
+        query_captures(&pic, canister_id, user, "\"index\" -type:idea").unwrap(),
+        vec!["Index rebuild"]
+    );
+    assert_eq!(
+        query_captures(&pic, canister_id, user, "status:(draft|active) type:(idea|task)").unwrap().len(),
+        4
+    );
+
+    let error = query_captures(&pic, canister_id, user, "type:task colour:red").unwrap_err();
+    assert!(error.contains("position 10"), "Unexpected error: {}", error);
+
+    let response = pic.query_call(
+        canister_id,
+        user,
+        "validate_capture_query",
+        encode_one("status:(active|nope)".to_string()).unwrap(),
+    ).unwrap();
+    let validation: Result<(), QueryError> = decode_one(&unwrap_wasm_result(response)).unwrap();
+    assert_eq!(validation.unwrap_err().position, 15);
+}
+
+// ============================================================================
+// Custom Field Tests
+// ============================================================================
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+enum CustomFieldKind {
+    Text,
+    Number,
+    Date,
+    SingleSelect(Vec<String>),
+    MultiSelect(Vec<String>),
+    Principal,
+    Url,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct CustomFieldDefinition {
+    id: u64,
+    key: String,
+    name: String,
+    kind: CustomFieldKind,
+    required: bool,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct CreateCustomFieldRequest {
+    key: String,
+    name: String,
+    kind: CustomFieldKind,
+    required: Option<bool>,
+    workspace_id: Option<u64>,
+}
+
+fn create_custom_field(pic: &PocketIc, canister_id: Principal, user: Principal, key: &str, kind: CustomFieldKind, required: bool) -> Result<CustomFieldDefinition, String> {
+    let request = CreateCustomFieldRequest {
+        key: key.to_string(),
+        name: key.to_string(),
+        kind,
+        required: Some(required),
+        workspace_id: None,
+    };
+    time_call(pic, canister_id, user, "create_custom_field", (request,))
+}
+
+#[test]
+fn test_custom_fields_validate_and_filter() {
+    let (pic, canister_id, user) = setup();
+
+    create_custom_field(&pic, canister_id, user, "points", CustomFieldKind::Number, false).unwrap();
+    let teams = vec!["web".to_string(), "api".to_string()];
+    create_custom_field(&pic, canister_id, user, "team", CustomFieldKind::SingleSelect(teams), true).unwrap();
+    assert!(create_custom_field(&pic, canister_id, user, "Points", CustomFieldKind::Text, false).is_err());
+
+    let create = |title: &str, values: &[(&str, &str)]| -> Result<Capture, String> {
+        let request = CreateCaptureRequest {
+            capture_type: CaptureType::Task,
+            title: title.to_string(),
+            description: None,
+            content: None,
+            priority: None,
+            fields: Some(DynamicFields {
+                custom_fields: values.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+                ..Default::default()
+            }),
+        };
+        time_call(&pic, canister_id, user, "create_capture", (request,))
+    };
+
+    let error = create("Bad points", &[("points", "lots"), ("team", "web")]).unwrap_err();
+    assert!(error.contains("must be a number"), "Unexpected error: {}", error);
+    let error = create("No team", &[("points", "3")]).unwrap_err();
+    assert!(error.contains("required"), "Unexpected error: {}", error);
+    assert!(create("Unknown team", &[("team", "mobile")]).is_err());
+
+    create("Big web task", &[("points", "8"), ("team", "web")]).unwrap();
+    create("Small api task", &[("points", "2"), ("team", "api")]).unwrap();
+    create("Mid api task", &[("points", "3.5"), ("team", "api")]).unwrap();
+
+    let mut titles = query_captures(&pic, canister_id, user, "cf.points>=3").unwrap();
+    titles.sort();
+    assert_eq!(titles, vec!["Big web task", "Mid api task"]);
+    assert_eq!(
+        query_captures(&pic, canister_id, user, "cf.team:api cf.points<3").unwrap(),
+        vec!["Small api task"]
+    );
+
+    let response = pic.query_call(
+        canister_id,
+        user,
+        "get_custom_fields",
+        encode_one(None::<u64>).unwrap(),
+    ).unwrap();
+    let fields: Result<Vec<CustomFieldDefinition>, String> = decode_one(&unwrap_wasm_result(response)).unwrap();
+    assert_eq!(fields.unwrap().len(), 2);
+}
+
+#[test]
+fn test_promote_idea_checks_custom_fields_first() {
+    let (pic, canister_id, user) = setup();
+    let idea = create_test_capture(&pic, canister_id, user, CaptureType::Idea, "New product", None);
+    create_custom_field(&pic, canister_id, user, "team", CustomFieldKind::Text, true).unwrap();
+
+    let error = promote_idea(&pic, canister_id, user, PromoteIdeaRequest {
+        idea_id: idea.id,
+        subtype: None,
+        template_id: None,
+        task_titles: Some(vec!["Write spec".to_string()]),
+        values: None,
+    }).unwrap_err();
+    assert!(error.contains("required"), "Unexpected error: {}", error);
+
+    let tree = get_capture_tree(&pic, canister_id, user, idea.id).unwrap();
+    assert_eq!(tree.len(), 1, "No tasks should be created");
+    assert_eq!(tree[0].capture.capture_type, CaptureType::Idea);
+}
+
+// ============================================================================
+// Document Version Tests
+// ============================================================================
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct DocumentVersion {
+    document_id: u64,
+    version: u32,
+    title: String,
+    size: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug, PartialEq)]
+enum DiffLineKind {
+    Unchanged,
+    Added,
+    Removed,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct DiffLine {
+    kind: DiffLineKind,
+    old_line: Option<u32>,
+    new_line: Option<u32>,
+    text: String,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct DocumentDiff {
+    lines: Vec<DiffLine>,
+    additions: u32,
+    deletions: u32,
+}
+
+fn create_test_document(pic: &PocketIc, canister_id: Principal, user: Principal, title: &str, content: &str) -> Document {
+    let workspace_request = CreateWorkspaceRequest {
+        name: format!("{} workspace", title),
+        description: None,
+        icon: None,
+        parent_id: None,
+    };
+    let workspace: Result<Workspace, String> = time_call(pic, canister_id, user, "create_workspace", (workspace_request,));
+    let doc_request = CreateDocumentRequest {
+        workspace_id: workspace.unwrap().id,
+        title: title.to_string(),
+        content: Some(content.to_string()),
+        template_id: None,
+        parent_id: None,
+    };
+    let doc: Result<Document, String> = time_call(pic, canister_id, user, "create_document", (doc_request,));
+    doc.expect("Should create document")
+}
+
+#[test]
+fn test_document_versions_diff_and_restore() {
+    let (pic, canister_id, user) = setup();
+    let other = Principal::from_slice(&[86, 87, 88, 89, 90, 91, 92, 93, 94, 95]);
+    let doc = create_test_document(&pic, canister_id, user, "Spec", "# Spec\nintro\nbody\n");
+
+    let update = |title: Option<&str>, content: Option<&str>| {
+        let updated: Result<Document, String> = time_call(
+            &pic,
+            canister_id,
+            user,
+            "update_document",
+            (doc.id, title.map(str::to_string), content.map(str::to_string)),
+        );
+        updated.expect("Should update document")
+    };
+    update(None, Some("# Spec\nintro\nnew body\nfooter\n"));
+    update(Some("Spec v3"), None);
+    update(None, Some("# Spec\nintro\nnew body\nfooter\n"));
+
+    let response = pic.query_call(canister_id, user, "get_document_versions", encode_one(doc.id).unwrap()).unwrap();
+    let versions: Result<Vec<DocumentVersion>, String> = decode_one(&unwrap_wasm_result(response)).unwrap();
+    let versions = versions.unwrap();
+    assert_eq!(versions.iter().map(|v| v.version).collect::<Vec<_>>(), vec![3, 2, 1], "Unchanged saves add no version");
+    assert_eq!(versions[0].title, "Spec v3");
+    assert_eq!(versions[2].size, 18);
+
+    let response = pic.query_call(
+        canister_id,
+        user,
+        "diff_document_versions",
+        encode_args((doc.id, 1u32, 3u32, Some(0u32))).unwrap(),
+    ).unwrap();
+    let diff: Result<DocumentDiff, String> = decode_one(&unwrap_wasm_result(response)).unwrap();
+    let diff = diff.unwrap();
+    assert_eq!((diff.additions, diff.deletions), (2, 1));
+    assert!(diff.lines.iter().all(|l| l.kind != DiffLineKind::Unchanged), "Zero context drops unchanged lines");
+    assert_eq!(diff.lines[0].text, "body");
+    assert_eq!(diff.lines[0].old_line, Some(3));
+
+    let response = pic.query_call(canister_id, other, "get_document_versions", encode_one(doc.id).unwrap()).unwrap();
+    let denied: Result<Vec<DocumentVersion>, String> = decode_one(&unwrap_wasm_result(response)).unwrap();
+    assert!(denied.is_err());
+
+    let restored: Result<Document, String> = time_call(&pic, canister_id, user, "restore_document_version", (doc.id, 1u32));
+    let restored = restored.unwrap();
+    assert_eq!(restored.title, "Spec");
+    assert_eq!(restored.content, "# Spec\nintro\nbody\n");
+
+    let response = pic.query_call(canister_id, user, "get_document_versions", encode_one(doc.id).unwrap()).unwrap();
+    let versions: Result<Vec<DocumentVersion>, String> = decode_one(&unwrap_wasm_result(response)).unwrap();
+    assert_eq!(versions.unwrap()[0].version, 4, "Restore is saved as a new version");
+}
+
+// ============================================================================
+// Document Patch Tests
+// ============================================================================
+
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone, PartialEq)]
+enum PatchOp {
+    Insert { position: u32, text: String },
+    Delete { position: u32, length: u32 },
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct ApplyDocumentPatchRequest {
+    document_id: u64,
+    base_version: u32,
+    ops: Vec<PatchOp>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct DocumentPatch {
+    document_id: u64,
+    version: u32,
+    ops: Vec<PatchOp>,
+}
+
+fn apply_patch(pic: &PocketIc, canister_id: Principal, user: Principal, document_id: u64, base_version: u32, ops: Vec<PatchOp>) -> Result<DocumentPatch, String> {
+    let request = ApplyDocumentPatchRequest { document_id, base_version, ops };
+    time_call(pic, canister_id, user, "apply_document_patch", (request,))
+}
+
+#[test]
+fn test_concurrent_document_patches_are_rebased() {
+    let (pic, canister_id, user) = setup();
+    let doc = create_test_document(&pic, canister_id, user, "Notes", "hello world");
+
+    // Two editors start from version 1
+    let first = apply_patch(&pic, canister_id, user, doc.id, 1, vec![PatchOp::Insert { position: 0, text: "Say: ".to_string() }]).unwrap();
+    assert_eq!(first.version, 2);
+    let second = apply_patch(&pic, canister_id, user, doc.id, 1, vec![PatchOp::Delete { position: 5, length: 6 }]).unwrap();
+    assert_eq!(second.version, 3);
+    assert_eq!(second.ops, vec![PatchOp::Delete { position: 10, length: 6 }], "Delete is shifted past the concurrent insert");
+
+    let response = pic.query_call(canister_id, user, "get_document", encode_one(doc.id).unwrap()).unwrap();
+    let current: Option<Document> = decode_one(&unwrap_wasm_result(response)).unwrap();
+    assert_eq!(current.unwrap().content, "Say: hello");
+
+    let response = pic.query_call(
+        canister_id,
+        user,
+        "get_document_patches",
+        encode_args((doc.id, 1u32)).unwrap(),
+    ).unwrap();
+    let patches: Result<Vec<DocumentPatch>, String> = decode_one(&unwrap_wasm_result(response)).unwrap();
+    assert_eq!(patches.unwrap().iter().map(|p| p.version).collect::<Vec<_>>(), vec![2, 3]);
+
+    let error = apply_patch(&pic, canister_id, user, doc.id, 3, vec![PatchOp::Insert { position: 99, text: "x".to_string() }]).unwrap_err();
+    assert!(error.contains("past the end"), "Unexpected error: {}", error);
+}
+
+// ============================================================================
+// Chunked Document Transfer Tests
+// ============================================================================
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct UploadSession {
+    id: u64,
+    document_id: u64,
+    total_size: u64,
+    received: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct DocumentContentChunk {
+    version: u32,
+    offset: u64,
+    total_size: u64,
+    data: Vec<u8>,
+}
+
+#[test]
+fn test_chunked_document_upload_and_ranged_read() {
+    use sha2::{Digest, Sha256};
+
+    let (pic, canister_id, user) = setup();
+    let doc = create_test_document(&pic, canister_id, user, "Big", "");
+    let body: String = (0..40_000).map(|i| format!("line {}\n", i)).collect();
+    let chunks: Vec<&[u8]> = body.as_bytes().chunks(100_000).collect();
+    assert!(chunks.len() > 1);
+
+    let session: Result<UploadSession, String> =
+        time_call(&pic, canister_id, user, "begin_document_upload", (doc.id, body.len() as u64));
+    let session = session.unwrap();
+
+    let mut offset = 0u64;
+    for chunk in &chunks {
+        let progress: Result<UploadSession, String> =
+            time_call(&pic, canister_id, user, "append_document_chunk", (session.id, offset, chunk.to_vec()));
+        offset = progress.unwrap().received;
+    }
+    let out_of_order: Result<UploadSession, String> =
+        time_call(&pic, canister_id, user, "append_document_chunk", (session.id, 0u64, vec![b'x']));
+    assert!(out_of_order.unwrap_err().contains("Expected chunk at offset"));
+
+    let digest = hex::encode(Sha256::digest(body.as_bytes()));
+    let committed: Result<Document, String> =
+        time_call(&pic, canister_id, user, "commit_document_upload", (session.id, digest));
+    assert_eq!(committed.unwrap().content.len(), body.len());
+
+    let response = pic.query_call(
+        canister_id,
+        user,
+        "read_document_content",
+        encode_args((doc.id, 5u64, 10u64)).unwrap(),
+    ).unwrap();
+    let chunk: Result<DocumentContentChunk, String> = decode_one(&unwrap_wasm_result(response)).unwrap();
+    let chunk = chunk.unwrap();
+    assert_eq!(chunk.data, body.as_bytes()[5..15].to_vec());
+    assert_eq!(chunk.total_size, body.len() as u64);
+    assert_eq!(chunk.version, 2);
+
+    // Checksum mismatches are rejected
+    let session: Result<UploadSession, String> = time_call(&pic, canister_id, user, "begin_document_upload", (doc.id, 3u64));
+    let session = session.unwrap();
+    let _: Result<UploadSession, String> =
+        time_call(&pic, canister_id, user, "append_document_chunk", (session.id, 0u64, b"abc".to_vec()));
+    let bad: Result<Document, String> =
+        time_call(&pic, canister_id, user, "commit_document_upload", (session.id, "00".repeat(32)));
+    assert!(bad.unwrap_err().contains("SHA-256"));
+
+    // The size limit is enforced up front
+    let too_big: Result<UploadSession, String> =
+        time_call(&pic, canister_id, user, "begin_document_upload", (doc.id, 9u64 * 1024 * 1024));
+    assert!(too_big.unwrap_err().contains("too large"));
+}
+
+// ============================================================================
+// Link Tests
+// ============================================================================
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct Backlink {
+    source: CommentTarget,
+    title: String,
+    text: String,
+}
+
+fn get_backlinks(pic: &PocketIc, canister_id: Principal, user: Principal, entity: CommentTarget) -> Result<Vec<Backlink>, String> {
+    let response = pic.query_call(canister_id, user, "get_backlinks", encode_one(entity).unwrap()).unwrap();
+    decode_one(&unwrap_wasm_result(response)).unwrap()
+}
+
+#[test]
+fn test_wiki_links_and_backlinks() {
+    let (pic, canister_id, user) = setup();
+    let other = Principal::from_slice(&[86, 87, 88, 89, 90, 91, 92, 93, 94, 95]);
+
+    let roadmap = create_test_document(&pic, canister_id, user, "Roadmap", "Q1 goals");
+    let capture = create_test_capture(&pic, canister_id, user, CaptureType::Task, "Ship search", None);
+    let notes = create_test_document(
+        &pic,
+        canister_id,
+        user,
+        "Notes",
+        &format!("See [[roadmap]], [[Later]] and #capture-{}.", capture.id),
+    );
+
+    let links = get_backlinks(&pic, canister_id, user, CommentTarget::Document(roadmap.id)).unwrap();
+    assert_eq!(links.len(), 1);
+    assert_eq!(links[0].title, "Notes");
+    assert_eq!(links[0].text, "roadmap");
+    let links = get_backlinks(&pic, canister_id, user, CommentTarget::Capture(capture.id)).unwrap();
+    assert!(matches!(links[0].source, CommentTarget::Document(id) if id == notes.id));
+
+    // Renaming keeps the link resolving by ID
+    let renamed: Result<Document, String> =
+        time_call(&pic, canister_id, user, "update_document", (roadmap.id, Some("Plan".to_string()), None::<String>));
+    renamed.unwrap();
+    assert_eq!(get_backlinks(&pic, canister_id, user, CommentTarget::Document(roadmap.id)).unwrap().len(), 1);
+
+    // Unresolved links resolve once the document exists
+    let later = create_test_document(&pic, canister_id, user, "Later", "");
+    assert_eq!(get_backlinks(&pic, canister_id, user, CommentTarget::Document(later.id)).unwrap().len(), 1);
+
+    assert!(get_backlinks(&pic, canister_id, other, CommentTarget::Document(roadmap.id)).is_err());
+}
+
+// ============================================================================
+// Document Tree Tests
+// ============================================================================
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct DocumentTreeEntry {
+    id: u64,
+    parent_id: Option<u64>,
+    title: String,
+    depth: u32,
+    child_count: u32,
+}
+
+fn document_tree(pic: &PocketIc, canister_id: Principal, user: Principal, workspace_id: u64) -> Vec<(String, u32)> {
+    let response = pic.query_call(canister_id, user, "get_document_tree", encode_one(workspace_id).unwrap()).unwrap();
+    let tree: Result<Vec<DocumentTreeEntry>, String> = decode_one(&unwrap_wasm_result(response)).unwrap();
+    tree.unwrap().into_iter().map(|entry| (entry.title, entry.depth)).collect()
+}
+
+#[test]
+fn test_document_tree_move_and_duplicate() {
+    let (pic, canister_id, user) = setup();
+    let guide = create_test_document(&pic, canister_id, user, "Guide", "");
+    let workspace_id = guide.workspace_id;
+    let child = |title: &str, parent_id: u64| {
+        let request = CreateDocumentRequest {
+            workspace_id,
+            title: title.to_string(),
+            content: None,
+            template_id: None,
+            parent_id: Some(parent_id),
+        };
+        let doc: Result<Document, String> = time_call(&pic, canister_id, user, "create_document", (request,));
+        doc.expect("Should create child document")
+    };
+    let intro = child("Intro", guide.id);
+    let setup_doc = child("Setup", guide.id);
+
+    let moved: Result<Document, String> =
+        time_call(&pic, canister_id, user, "move_document", (setup_doc.id, None::<u64>, Some(guide.id), Some(0u32)));
+    moved.unwrap();
+    assert_eq!(
+        document_tree(&pic, canister_id, user, workspace_id),
+        vec![("Guide".to_string(), 0), ("Setup".to_string(), 1), ("Intro".to_string(), 1)]
+    );
+
+    let cycle: Result<Document, String> =
+        time_call(&pic, canister_id, user, "move_document", (guide.id, None::<u64>, Some(intro.id), None::<u32>));
+    assert!(cycle.unwrap_err().contains("cycle"));
+
+    let copy: Result<Document, String> = time_call(&pic, canister_id, user, "duplicate_document", (guide.id, true));
+    assert_eq!(copy.unwrap().title, "Guide (copy)");
+    let tree = document_tree(&pic, canister_id, user, workspace_id);
+    assert_eq!(tree.len(), 6);
+    assert_eq!(tree[3], ("Guide (copy)".to_string(), 0));
+    assert_eq!(tree[4], ("Setup".to_string(), 1));
+
+    // Moving across workspaces takes the subtree along
+    let other = create_test_document(&pic, canister_id, user, "Archive", "");
+    let moved: Result<Document, String> =
+        time_call(&pic, canister_id, user, "move_document", (guide.id, Some(other.workspace_id), None::<u64>, None::<u32>));
+    assert_eq!(moved.unwrap().workspace_id, other.workspace_id);
+    assert_eq!(document_tree(&pic, canister_id, user, other.workspace_id).len(), 4);
+}
+
+#[test]
+fn test_duplicate_document_sends_no_mentions() {
+    let (pic, canister_id, user) = setup();
+    let bob = Principal::from_slice(&[96, 97, 98, 99, 100, 101, 102, 103, 104, 105]);
+    set_handle(&pic, canister_id, bob, "bob").unwrap();
+
+    let doc = create_test_document(&pic, canister_id, user, "Plan", "Review with @bob");
+    assert_eq!(get_notifications(&pic, canister_id, bob, false).total, 1);
+
+    let copy: Result<Document, String> = time_call(&pic, canister_id, user, "duplicate_document", (doc.id, false));
+    assert_eq!(copy.unwrap().content, "Review with @bob");
+    assert_eq!(get_notifications(&pic, canister_id, bob, false).total, 1, "Duplicates do not notify mentions again");
+}
+
+// ============================================================================
+// Template Instantiation Tests
+// ============================================================================
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+enum TemplateVariableKind {
+    Text,
+    Number,
+    Date,
+    Boolean,
+    Select(Vec<String>),
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct TemplateVariable {
+    name: String,
+    kind: TemplateVariableKind,
+    description: Option<String>,
+    default: Option<String>,
+    required: bool,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct CreateVariableTemplateRequest {
+    template_type: TemplateType,
+    name: String,
+    description: Option<String>,
+    content: String,
+    capture_type: Option<CaptureType>,
+    default_fields: Option<DynamicFields>,
+    is_public: Option<bool>,
+    variables: Option<Vec<TemplateVariable>>,
+    scaffold: Option<ScaffoldSpec>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct InstantiateTemplateRequest {
+    template_id: u64,
+    values: Vec<(String, String)>,
+    title: Option<String>,
+    workspace_id: Option<u64>,
+    parent_id: Option<u64>,
+    fields: Option<DynamicFields>,
+    anchor_date: Option<u64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+enum TemplateInstance {
+    Document(Document),
+    Capture(Capture),
+    Scaffold(ScaffoldInstance),
+}
+
+fn variable(name: &str, kind: TemplateVariableKind, default: Option<&str>, required: bool) -> TemplateVariable {
+    TemplateVariable {
+        name: name.to_string(),
+        kind,
+        description: None,
+        default: default.map(str::to_string),
+        required,
+    }
+}
+
+fn instantiate(
+    pic: &PocketIc,
+    canister_id: Principal,
+    user: Principal,
+    template_id: u64,
+    values: &[(&str, &str)],
+    workspace_id: Option<u64>,
+) -> Result<TemplateInstance, String> {
+    let request = InstantiateTemplateRequest {
+        template_id,
+        values: values.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+        title: None,
+        workspace_id,
+        parent_id: None,
+        fields: None,
+        anchor_date: None,
+    };
+    time_call(pic, canister_id, user, "instantiate_template", (request,))
+}
+
+#[test]
+fn test_instantiate_document_template() {
+    let (pic, canister_id, user) = setup();
+    let existing = create_test_document(&pic, canister_id, user, "Notes", "");
+
+    let request = CreateVariableTemplateRequest {
+        template_type: TemplateType::Document,
+        name: "{{project_name}} brief".to_string(),
+        description: None,
+        content: "# {{project_name}}\nBudget: {{budget}}\nStarted {{today}}\n{{unknown}}".to_string(),
+        capture_type: None,
+        default_fields: None,
+        is_public: Some(false),
+        scaffold: None,
+        variables: Some(vec![
+            variable("project_name", TemplateVariableKind::Text, None, true),
+            variable("budget", TemplateVariableKind::Number, Some("1000"), false),
+        ]),
+    };
+    let template: Result<Template, String> = time_call(&pic, canister_id, user, "create_template", (request,));
+    let template = template.expect("Should create template");
+
+    let missing = instantiate(&pic, canister_id, user, template.id, &[], Some(existing.workspace_id));
+    assert_eq!(missing.unwrap_err(), "Missing required template variables: project_name");
+    let invalid = instantiate(&pic, canister_id, user, template.id, &[("project_name", "Apollo"), ("budget", "lots")], Some(existing.workspace_id));
+    assert!(invalid.is_err());
+
+    let instance = instantiate(&pic, canister_id, user, template.id, &[("project_name", "Apollo")], Some(existing.workspace_id));
+    let TemplateInstance::Document(document) = instance.expect("Should instantiate") else {
+        panic!("Expected a document");
+    };
+    assert_eq!(document.title, "Apollo brief");
+    assert!(document.content.starts_with("# Apollo\nBudget: 1000\nStarted 20"));
+    assert!(document.content.ends_with("{{unknown}}"));
+    assert_eq!(document.template_id, Some(template.id));
+
+    // Templates are private to their owner unless public
+    let stranger = Principal::from_slice(&[86, 87, 88, 89, 90, 91, 92, 93, 94, 95]);
+    let denied = instantiate(&pic, canister_id, stranger, template.id, &[("project_name", "X")], Some(existing.workspace_id));
+    assert!(denied.is_err());
+}
+
+#[test]
+fn test_instantiate_capture_template_merges_default_fields() {
+    let (pic, canister_id, user) = setup();
+
+    let request = CreateVariableTemplateRequest {
+        template_type: TemplateType::Capture,
+        name: "Bug: {{component}}".to_string(),
+        description: None,
+        content: "Severity {{severity}} in {{component}}".to_string(),
+        capture_type: Some(CaptureType::Task),
+        default_fields: Some(DynamicFields {
+            estimate: Some(3),
+            labels: vec!["bug".to_string(), "{{component}}".to_string()],
+            ..Default::default()
+        }),
+        is_public: Some(false),
+        scaffold: None,
+        variables: Some(vec![
+            variable("component", TemplateVariableKind::Text, None, true),
+            variable("severity", TemplateVariableKind::Select(vec!["low".into(), "high".into()]), Some("low"), false),
+        ]),
+    };
+    let template: Result<Template, String> = time_call(&pic, canister_id, user, "create_template", (request,));
+    let template = template.expect("Should create template");
+
+    let instance = instantiate(&pic, canister_id, user, template.id, &[("component", "parser"), ("severity", "high")], None);
+    let TemplateInstance::Capture(capture) = instance.expect("Should instantiate") else {
+        panic!("Expected a capture");
+    };
+    assert_eq!(capture.capture_type, CaptureType::Task);
+    assert_eq!(capture.title, "Bug: parser");
+    assert_eq!(capture.content.as_deref(), Some("Severity high in parser"));
+    assert_eq!(capture.fields.estimate, Some(3));
+    assert_eq!(capture.fields.labels, vec!["bug".to_string(), "parser".to_string()]);
+
+    let bad_select = instantiate(&pic, canister_id, user, template.id, &[("component", "ui"), ("severity", "urgent")], None);
+    assert!(bad_select.is_err());
+}
+
+// ============================================================================
+// Scaffold Template Tests
+// ============================================================================
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct ScaffoldWorkspace {
+    name: String,
+    description: Option<String>,
+    icon: Option<String>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct ScaffoldDocument {
+    key: String,
+    title: String,
+    content: String,
+    parent: Option<String>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct ScaffoldSprint {
+    key: String,
+    name: String,
+    goal: Option<String>,
+    start_offset_days: u32,
+    duration_days: u32,
+    capacity: Option<u32>,
+    stream: Option<String>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct ScaffoldCapture {
+    key: String,
+    capture_type: CaptureType,
+    subtype: Option<CaptureSubtype>,
+    title: String,
+    content: Option<String>,
+    priority: Option<Priority>,
+    estimate: Option<u32>,
+    labels: Vec<String>,
+    parent: Option<String>,
+    sprint: Option<String>,
+    start_offset_days: Option<u32>,
+    due_offset_days: Option<u32>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct ScaffoldSpec {
+    workspace: Option<ScaffoldWorkspace>,
+    documents: Vec<ScaffoldDocument>,
+    sprints: Vec<ScaffoldSprint>,
+    captures: Vec<ScaffoldCapture>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct ScaffoldInstance {
+    workspace_id: Option<u64>,
+    documents: Vec<(String, u64)>,
+    sprints: Vec<(String, u64)>,
+    captures: Vec<(String, u64)>,
+}
+
+const DAY_NANOS: u64 = 86_400_000_000_000;
+
+fn scaffold_document(key: &str, title: &str, content: &str, parent: Option<&str>) -> ScaffoldDocument {
+    ScaffoldDocument {
+        key: key.to_string(),
+        title: title.to_string(),
+        content: content.to_string(),
+        parent: parent.map(str::to_string),
+    }
+}
+
+fn scaffold_capture(key: &str, capture_type: CaptureType, title: &str, parent: Option<&str>) -> ScaffoldCapture {
+    ScaffoldCapture {
+        key: key.to_string(),
+        capture_type,
+        subtype: None,
+        title: title.to_string(),
+        content: None,
+        priority: None,
+        estimate: None,
+        labels: vec![],
+        parent: parent.map(str::to_string),
+        sprint: None,
+        start_offset_days: None,
+        due_offset_days: None,
+    }
+}
+
+fn create_scaffold_template(pic: &PocketIc, canister_id: Principal, user: Principal, scaffold: ScaffoldSpec) -> u64 {
+    let request = CreateVariableTemplateRequest {
+        template_type: TemplateType::Scaffold,
+        name: "Project kickoff".to_string(),
+        description: None,
+        content: String::new(),
+        capture_type: None,
+        default_fields: None,
+        is_public: Some(false),
+        variables: Some(vec![variable("project", TemplateVariableKind::Text, None, true)]),
+        scaffold: Some(scaffold),
+    };
+    let template: Result<Template, String> = time_call(pic, canister_id, user, "create_template", (request,));
+    template.expect("Should create scaffold template").id
+}
+
+fn my_workspace_count(pic: &PocketIc, canister_id: Principal, user: Principal) -> usize {
+    let response = pic.query_call(canister_id, user, "get_my_workspaces", encode_one(()).unwrap()).unwrap();
+    let workspaces: Vec<Workspace> = decode_one(&unwrap_wasm_result(response)).unwrap();
+    workspaces.len()
+}
+
+#[test]
+fn test_promote_idea_renders_template_variables() {
+    let (pic, canister_id, user) = setup();
+
+    let request = CreateVariableTemplateRequest {
+        template_type: TemplateType::Capture,
+        name: "Launch".to_string(),
+        description: None,
+        content: "Launch {{product}}\n- Announce {{product}}".to_string(),
+        capture_type: Some(CaptureType::Project),
+        default_fields: Some(DynamicFields {
+            labels: vec!["{{product}}".to_string()],
+            ..Default::default()
+        }),
+        is_public: Some(false),
+        scaffold: None,
+        variables: Some(vec![variable("product", TemplateVariableKind::Text, None, true)]),
+    };
+    let template: Result<Template, String> = time_call(&pic, canister_id, user, "create_template", (request,));
+    let template = template.expect("Should create template");
+    let idea = create_test_capture(&pic, canister_id, user, CaptureType::Idea, "New product", None);
+
+    let promote = |values: Option<Vec<(String, String)>>| promote_idea(&pic, canister_id, user, PromoteIdeaRequest {
+        idea_id: idea.id,
+        subtype: None,
+        template_id: Some(template.id),
+        task_titles: None,
+        values,
+    });
+    assert!(promote(None).is_err(), "Required variables must be given");
+
+    let result = promote(Some(vec![("product".to_string(), "rocket".to_string())])).expect("Should promote idea");
+    assert_eq!(result.project.content.as_deref(), Some("Launch rocket\n- Announce rocket"));
+    assert_eq!(result.project.fields.labels, vec!["rocket".to_string()]);
+    assert_eq!(result.project.template.map(|source| source.template_id), Some(template.id));
+    assert_eq!(result.tasks.iter().map(|t| t.title.as_str()).collect::<Vec<_>>(), vec!["Announce rocket"]);
+}
+
+#[test]
+fn test_instantiate_scaffold_template() {
+    let (pic, canister_id, user) = setup();
+
+    let mut epic = scaffold_capture("epic", CaptureType::Project, "{{project}} MVP", Some("project"));
+    epic.subtype = Some(CaptureSubtype::Project(ProjectSubtype::Epic));
+    let mut task = scaffold_capture("task", CaptureType::Task, "Set up {{project}} repo", Some("epic"));
+    task.sprint = Some("sprint-1".to_string());
+    task.due_offset_days = Some(7);
+    task.labels = vec!["{{project}}".to_string()];
+    let template_id = create_scaffold_template(&pic, canister_id, user, ScaffoldSpec {
+        workspace: Some(ScaffoldWorkspace { name: "{{project}}".to_string(), description: None, icon: None }),
+        documents: vec![
+            scaffold_document("handbook", "{{project}} handbook", "# {{project}}", None),
+            scaffold_document("setup", "Setup", "", Some("handbook")),
+        ],
+        sprints: vec![ScaffoldSprint {
+            key: "sprint-1".to_string(),
+            name: "{{project}} sprint 1".to_string(),
+            goal: None,
+            start_offset_days: 0,
+            duration_days: 14,
+            capacity: None,
+            stream: None,
+        }],
+        captures: vec![scaffold_capture("project", CaptureType::Project, "{{project}}", None), epic, task],
+    });
+
+    let anchor = 1_700_000_000 * 1_000_000_000u64;
+    let request = InstantiateTemplateRequest {
+        template_id,
+        values: vec![("project".to_string(), "Apollo".to_string())],
+        title: None,
+        workspace_id: None,
+        parent_id: None,
+        fields: None,
+        anchor_date: Some(anchor),
+    };
+    let instance: Result<TemplateInstance, String> = time_call(&pic, canister_id, user, "instantiate_template", (request,));
+    let TemplateInstance::Scaffold(instance) = instance.expect("Should instantiate scaffold") else {
+        panic!("Expected a scaffold");
+    };
+    let workspace_id = instance.workspace_id.expect("Should create a workspace");
+    assert_eq!(instance.documents.len(), 2);
+    assert_eq!(instance.captures.iter().map(|(key, _)| key.as_str()).collect::<Vec<_>>(), vec!["project", "epic", "task"]);
+    assert_eq!(
+        document_tree(&pic, canister_id, user, workspace_id),
+        vec![("Apollo handbook".to_string(), 0), ("Setup".to_string(), 1)]
+    );
+
+    let task_id = instance.captures[2].1;
+    let response = pic.query_call(canister_id, user, "get_capture", encode_one(task_id).unwrap()).unwrap();
+    let task: Option<Capture> = decode_one(&unwrap_wasm_result(response)).unwrap();
+    let task = task.unwrap();
+    assert_eq!(task.title, "Set up Apollo repo");
+    assert_eq!(task.fields.parent_id, Some(instance.captures[1].1));
+    assert_eq!(task.fields.sprint_id, Some(instance.sprints[0].1));
+    assert_eq!(task.fields.due_date, Some(anchor + 7 * DAY_NANOS));
+    assert_eq!(task.fields.workspace_id, Some(workspace_id));
+    assert_eq!(task.fields.labels, vec!["Apollo".to_string()]);
+
+    let response = pic.query_call(canister_id, user, "get_sprint", encode_one(instance.sprints[0].1).unwrap()).unwrap();
+    let sprint: Option<Sprint> = decode_one(&unwrap_wasm_result(response)).unwrap();
+    let sprint = sprint.unwrap();
+    assert_eq!(sprint.name, "Apollo sprint 1");
+    assert_eq!(sprint.end_date - sprint.start_date, 14 * DAY_NANOS);
+    assert_eq!(sprint.capture_ids, vec![task_id]);
+}
+
+#[test]
+fn test_scaffold_instantiation_is_all_or_nothing() {
+    let (pic, canister_id, user) = setup();
+
+    // Broken references are rejected when the template is saved
+    let request = CreateVariableTemplateRequest {
+        template_type: TemplateType::Scaffold,
+        name: "Broken".to_string(),
+        description: None,
+        content: String::new(),
+        capture_type: None,
+        default_fields: None,
+        is_public: Some(false),
+        variables: None,
+        scaffold: Some(ScaffoldSpec {
+            workspace: None,
+            documents: vec![],
+            sprints: vec![],
+            captures: vec![scaffold_capture("task", CaptureType::Task, "Task", Some("missing"))],
+        }),
+    };
+    let broken: Result<Template, String> = time_call(&pic, canister_id, user, "create_template", (request,));
+    assert!(broken.unwrap_err().contains("earlier capture"));
+
+    // The last document renders too large, so nothing is created
+    let template_id = create_scaffold_template(&pic, canister_id, user, ScaffoldSpec {
+        workspace: Some(ScaffoldWorkspace { name: "{{project}}".to_string(), description: None, icon: None }),
+        documents: vec![
+            scaffold_document("ok", "Fine", "small", None),
+            scaffold_document("huge", "Huge", &"{{project}}".repeat(1000), None),
+        ],
+        sprints: vec![],
+        captures: vec![scaffold_capture("project", CaptureType::Project, "{{project}}", None)],
+    });
+    let workspaces_before = my_workspace_count(&pic, canister_id, user);
+    let result = instantiate(&pic, canister_id, user, template_id, &[("project", &"x".repeat(10_000))], None);
+    assert!(result.is_err());
+    assert_eq!(my_workspace_count(&pic, canister_id, user), workspaces_before);
+}
+
+// ============================================================================
+// Template Versioning Tests
+// ============================================================================
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug, PartialEq)]
+struct TemplateSource {
+    template_id: u64,
+    version: Option<u32>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct VersionedTemplate {
+    id: u64,
+    owner: Principal,
+    content: String,
+    is_public: bool,
+    latest_version: Option<u32>,
+    forked_from: Option<TemplateSource>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct TemplateVersion {
+    version: u32,
+    content: String,
+    changelog: Option<String>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct VersionedDocument {
+    id: u64,
+    content: String,
+    template_id: Option<u64>,
+    template_version: Option<u32>,
+}
+
+fn template_content(pic: &PocketIc, canister_id: Principal, user: Principal, id: u64) -> String {
+    let response = pic.query_call(canister_id, user, "get_template", encode_one(id).unwrap()).unwrap();
+    let template: Option<VersionedTemplate> = decode_one(&unwrap_wasm_result(response)).unwrap();
+    template.expect("Template should be visible").content
+}
+
+#[test]
+fn test_template_versions_and_forks() {
+    let (pic, canister_id, author) = setup();
+    let reader = Principal::from_slice(&[86, 87, 88, 89, 90, 91, 92, 93, 94, 95]);
+
+    let request = CreateTemplateRequest {
+        template_type: TemplateType::Document,
+        name: "Meeting notes".to_string(),
+        description: None,
+        content: "v1".to_string(),
+        capture_type: None,
+        default_fields: None,
+        is_public: Some(true),
+    };
+    let created: Result<VersionedTemplate, String> = time_call(&pic, canister_id, author, "create_template", (request,));
+    let template = created.unwrap();
+    assert_eq!(template.latest_version, Some(1));
+
+    // Draft edits are invisible to other users until published
+    let update = UpdateTemplateRequest {
+        name: None,
+        description: None,
+        content: Some("v2".to_string()),
+        capture_type: None,
+        default_fields: None,
+        is_public: None,
+    };
+    let updated: Result<VersionedTemplate, String> = time_call(&pic, canister_id, author, "update_template", (template.id, update));
+    updated.unwrap();
+    assert_eq!(template_content(&pic, canister_id, author, template.id), "v2");
+    assert_eq!(template_content(&pic, canister_id, reader, template.id), "v1");
+
+    let published: Result<TemplateVersion, String> =
+        time_call(&pic, canister_id, author, "publish_template", (template.id, Some("Second draft".to_string())));
+    let published = published.unwrap();
+    assert_eq!((published.version, published.changelog.as_deref()), (2, Some("Second draft")));
+    assert_eq!(template_content(&pic, canister_id, reader, template.id), "v2");
+    let unchanged: Result<TemplateVersion, String> = time_call(&pic, canister_id, author, "publish_template", (template.id, None::<String>));
+    assert!(unchanged.unwrap_err().contains("no changes"));
+    let denied: Result<TemplateVersion, String> = time_call(&pic, canister_id, reader, "publish_template", (template.id, None::<String>));
+    assert!(denied.is_err());
+
+    let response = pic.query_call(canister_id, reader, "get_template_versions", encode_one(template.id).unwrap()).unwrap();
+    let versions: Result<Vec<TemplateVersion>, String> = decode_one(&unwrap_wasm_result(response)).unwrap();
+    assert_eq!(versions.unwrap().iter().map(|v| v.content.as_str()).collect::<Vec<_>>(), vec!["v1", "v2"]);
+
+    // Forking copies a published version into a private template
+    let fork: Result<VersionedTemplate, String> = time_call(&pic, canister_id, reader, "fork_template", (template.id, Some(1u32)));
+    let fork = fork.unwrap();
+    assert_eq!(fork.owner, reader);
+    assert_eq!(fork.content, "v1");
+    assert!(!fork.is_public);
+    assert_eq!(fork.forked_from, Some(TemplateSource { template_id: template.id, version: Some(1) }));
+
+    // Documents remember the template version that produced them
+    let workspace_id = create_test_document(&pic, canister_id, reader, "Scratch", "").workspace_id;
+    let request = CreateDocumentRequest {
+        workspace_id,
+        title: "Standup".to_string(),
+        content: None,
+        template_id: Some(template.id),
+        parent_id: None,
+    };
+    let document: Result<VersionedDocument, String> = time_call(&pic, canister_id, reader, "create_document", (request,));
+    let document = document.unwrap();
+    assert_eq!(document.content, "v2");
+    assert_eq!((document.template_id, document.template_version), (Some(template.id), Some(2)));
+}
+
+// ============================================================================
+// Template Gallery Tests
+// ============================================================================
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Debug, PartialEq)]
+enum TemplateCategory {
+    Engineering,
+    Product,
+    Design,
+    Marketing,
+    Operations,
+    Meetings,
+    Personal,
+    Other,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct GalleryTemplateRequest {
+    template_type: TemplateType,
+    name: String,
+    description: Option<String>,
+    content: String,
+    capture_type: Option<CaptureType>,
+    default_fields: Option<DynamicFields>,
+    is_public: Option<bool>,
+    category: Option<TemplateCategory>,
+    tags: Option<Vec<String>>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+enum TemplateSort {
+    Usage,
+    Recent,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug, Default)]
+struct TemplateGalleryQuery {
+    text: Option<String>,
+    category: Option<TemplateCategory>,
+    template_type: Option<TemplateType>,
+    tags: Option<Vec<String>>,
+    featured_only: Option<bool>,
+    sort: Option<TemplateSort>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct GalleryTemplate {
+    id: u64,
+    name: String,
+    is_public: bool,
+    category: Option<TemplateCategory>,
+    tags: Option<Vec<String>>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct TemplateGalleryEntry {
+    template: GalleryTemplate,
+    usage_count: u64,
+    featured: bool,
+    published_at: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct PaginatedTemplateGalleryResponse {
+    items: Vec<TemplateGalleryEntry>,
+    total: u64,
+    offset: u64,
+    limit: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug)]
+struct ModerateTemplateRequest {
+    featured: Option<bool>,
+    hidden: Option<bool>,
+}
+
+fn create_gallery_template(
+    pic: &PocketIc,
+    canister_id: Principal,
+    user: Principal,
+    name: &str,
+    category: TemplateCategory,
+    tags: &[&str],
+) -> GalleryTemplate {
+    let request = GalleryTemplateRequest {
+        template_type: TemplateType::Capture,
+        name: name.to_string(),
+        description: Some(format!("{} template", name)),
+        content: String::new(),
+        capture_type: Some(CaptureType::Task),
+        default_fields: None,
+        is_public: Some(true),
+        category: Some(category),
+        tags: Some(tags.iter().map(|tag| tag.to_string()).collect()),
+    };
+    let created: Result<GalleryTemplate, String> = time_call(pic, canister_id, user, "create_template", (request,));
+    created.unwrap()
+}
+
+fn search_gallery(
+    pic: &PocketIc,
+    canister_id: Principal,
+    query: TemplateGalleryQuery,
+    pagination: Option<PaginationParams>,
+) -> PaginatedTemplateGalleryResponse {
+    let response = pic.query_call(
+        canister_id,
+        Principal::anonymous(),
+        "search_public_templates",
+        encode_args((Some(query), pagination)).unwrap(),
+    ).unwrap();
+    decode_one(&unwrap_wasm_result(response)).unwrap()
+}
+
+fn gallery_names(response: &PaginatedTemplateGalleryResponse) -> Vec<&str> {
+    response.items.iter().map(|entry| entry.template.name.as_str()).collect()
+}
+
+#[test]
+fn test_template_gallery_search_and_sort() {
+    let (pic, canister_id, user) = setup();
+
+    let retro = create_gallery_template(&pic, canister_id, user, "Sprint retro", TemplateCategory::Meetings, &["Agile", "agile "]);
+    assert_eq!(retro.tags, Some(vec!["agile".to_string()]));
+    pic.advance_time(std::time::Duration::from_secs(60));
+    let standup = create_gallery_template(&pic, canister_id, user, "Daily standup", TemplateCategory::Meetings, &["agile", "daily"]);
+    pic.advance_time(std::time::Duration::from_secs(60));
+    create_gallery_template(&pic, canister_id, user, "Bug report", TemplateCategory::Engineering, &["bugs"]);
+
+    for _ in 0..2 {
+        instantiate(&pic, canister_id, user, standup.id, &[], None).unwrap();
+    }
+    instantiate(&pic, canister_id, user, retro.id, &[], None).unwrap();
+
+    // Usage sort puts the most instantiated template first
+    let all = search_gallery(&pic, canister_id, TemplateGalleryQuery::default(), None);
+    assert_eq!(all.total, 3);
+    assert_eq!(gallery_names(&all), vec!["Daily standup", "Sprint retro", "Bug report"]);
+    assert_eq!(all.items.iter().map(|entry| entry.usage_count).collect::<Vec<_>>(), vec![2, 1, 0]);
+
+    let recent = search_gallery(&pic, canister_id, TemplateGalleryQuery { sort: Some(TemplateSort::Recent), ..Default::default() }, None);
+    assert_eq!(gallery_names(&recent), vec!["Bug report", "Daily standup", "Sprint retro"]);
+
+    let text = search_gallery(&pic, canister_id, TemplateGalleryQuery { text: Some("STANDUP template".to_string()), ..Default::default() }, None);
+    assert_eq!(gallery_names(&text), vec!["Daily standup"]);
+
+    let filtered = search_gallery(&pic, canister_id, TemplateGalleryQuery {
+        category: Some(TemplateCategory::Meetings),
+        tags: Some(vec!["Agile".to_string()]),
+        ..Default::default()
+    }, None);
+    assert_eq!(gallery_names(&filtered), vec!["Daily standup", "Sprint retro"]);
+
+    let page = search_gallery(&pic, canister_id, TemplateGalleryQuery::default(), Some(PaginationParams { offset: Some(1), limit: Some(1) }));
+    assert_eq!((page.total, page.offset, page.limit), (3, 1, 1));
+    assert_eq!(gallery_names(&page), vec!["Sprint retro"]);
+}
+
+#[test]
+fn test_template_gallery_moderation_and_unpublish() {
+    let (pic, canister_id, user) = setup();
+    let controller = Principal::from_text("aaaaa-aa").unwrap();
+    let other = Principal::from_slice(&[96, 97, 98, 99, 100, 101, 102, 103, 104, 105]);
+
+    let retro = create_gallery_template(&pic, canister_id, user, "Sprint retro", TemplateCategory::Meetings, &[]);
+    let standup = create_gallery_template(&pic, canister_id, user, "Daily standup", TemplateCategory::Meetings, &[]);
+
+    // Only controllers moderate the gallery
+    let feature = ModerateTemplateRequest { featured: Some(true), hidden: None };
+    let denied: Result<(), String> = time_call(&pic, canister_id, user, "moderate_template", (retro.id, feature));
+    assert!(denied.is_err());
+    let feature = ModerateTemplateRequest { featured: Some(true), hidden: None };
+    let featured: Result<(), String> = time_call(&pic, canister_id, controller, "moderate_template", (retro.id, feature));
+    featured.unwrap();
+
+    let response = search_gallery(&pic, canister_id, TemplateGalleryQuery { featured_only: Some(true), ..Default::default() }, None);
+    assert_eq!(gallery_names(&response), vec!["Sprint retro"]);
+    assert!(response.items[0].featured);
+
+    // Hidden templates leave the gallery but stay visible to their owner
+    let hide = ModerateTemplateRequest { featured: None, hidden: Some(true) };
+    let hidden: Result<(), String> = time_call(&pic, canister_id, controller, "moderate_template", (standup.id, hide));
+    hidden.unwrap();
+    let response = search_gallery(&pic, canister_id, TemplateGalleryQuery::default(), None);
+    assert_eq!(gallery_names(&response), vec!["Sprint retro"]);
+    let response = pic.query_call(canister_id, other, "get_template", encode_one(standup.id).unwrap()).unwrap();
+    let seen: Option<GalleryTemplate> = decode_one(&unwrap_wasm_result(response)).unwrap();
+    assert!(seen.is_none());
+    let response = pic.query_call(canister_id, user, "get_template", encode_one(standup.id).unwrap()).unwrap();
+    let seen: Option<GalleryTemplate> = decode_one(&unwrap_wasm_result(response)).unwrap();
+    assert!(seen.is_some());
+
+    // Only the owner can unpublish
+    let denied: Result<GalleryTemplate, String> = time_call(&pic, canister_id, other, "unpublish_template", (retro.id,));
+    assert!(denied.is_err());
+    let unpublished: Result<GalleryTemplate, String> = time_call(&pic, canister_id, user, "unpublish_template", (retro.id,));
+    assert!(!unpublished.unwrap().is_public);
+    let response = search_gallery(&pic, canister_id, TemplateGalleryQuery::default(), None);
+    assert_eq!(response.total, 0);
+}
+
+#[test]
+fn test_promote_idea_counts_template_use() {
+    let (pic, canister_id, user) = setup();
+
+    let request = GalleryTemplateRequest {
+        template_type: TemplateType::Capture,
+        name: "Project kickoff".to_string(),
+        description: None,
+        content: "- Kickoff meeting".to_string(),
+        capture_type: Some(CaptureType::Project),
+        default_fields: None,
+        is_public: Some(true),
+        category: Some(TemplateCategory::Product),
+        tags: None,
+    };
+    let template: Result<GalleryTemplate, String> = time_call(&pic, canister_id, user, "create_template", (request,));
+    let template = template.unwrap();
+
+    let idea = create_test_capture(&pic, canister_id, user, CaptureType::Idea, "New product", None);
+    promote_idea(&pic, canister_id, user, PromoteIdeaRequest {
+        idea_id: idea.id,
+        subtype: None,
+        template_id: Some(template.id),
+        task_titles: None,
+        values: None,
+    }).expect("Should promote idea");
+
+    let response = search_gallery(&pic, canister_id, TemplateGalleryQuery::default(), None);
+    assert_eq!(response.items[0].usage_count, 1);
+}